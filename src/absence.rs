@@ -0,0 +1,179 @@
+use crate::humanize::humanize_duration;
+use anyhow::{bail, Context, Result};
+use chrono::NaiveTime;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// No-motion alarm settings, read from the `[absence]` config section
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AbsenceConfig {
+    /// Idle time before the alarm, e.g. "12h"; unset disables it
+    pub alert_after: Option<String>,
+    /// How often to repeat the alarm while nothing moves (default: "1h")
+    pub repeat_every: Option<String>,
+    /// `["07:00", "22:00"]`: only idle time inside this daily window counts
+    pub active_hours: Option<[String; 2]>,
+}
+
+/// Parse "90", "45s", "30m", "12h" or "2d"
+pub fn parse_duration(text: &str) -> Result<Duration> {
+    let text = text.trim();
+    let (number, unit) = match text.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(index) => text.split_at(index),
+        None => (text, "s"),
+    };
+    let value: f64 = number
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid duration \"{}\"", text))?;
+    let seconds = match unit {
+        "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        "d" => value * 86400.0,
+        _ => bail!("invalid duration \"{}\" (use s, m, h or d)", text),
+    };
+    if !(seconds > 0.0 && seconds.is_finite()) {
+        bail!("duration \"{}\" must be positive", text);
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AbsenceAlert {
+    /// Nothing moved for `idle`; `count` is 1 for the first alarm, then repeats
+    NoMotion { idle: Duration, count: u32 },
+    /// Motion came back after an alarm
+    Recovered { idle: Duration },
+}
+
+impl AbsenceAlert {
+    pub fn message(&self) -> String {
+        match self {
+            AbsenceAlert::NoMotion { idle, count } => {
+                format!(
+                    "NO MOTION for {} (alert #{})",
+                    humanize_duration(*idle),
+                    count
+                )
+            }
+            AbsenceAlert::Recovered { idle } => {
+                format!(
+                    "MOTION RESUMED after {} without activity",
+                    humanize_duration(*idle)
+                )
+            }
+        }
+    }
+}
+
+/// Countdown shown in the GUI
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AbsenceStatus {
+    /// Counted idle time left before the next alarm (or repeat)
+    pub remaining: Duration,
+    /// An alarm was raised and motion hasn't resumed yet
+    pub alerting: bool,
+    /// Not counting: detector stopped or outside the active hours
+    pub paused: bool,
+}
+
+/// Raises an alarm when no motion event arrives for too long. Only time
+/// that passes while counting (detector running, inside the active hours)
+/// adds to the idle time.
+pub struct AbsenceMonitor {
+    alert_after: Duration,
+    repeat_every: Duration,
+    active_hours: Option<(NaiveTime, NaiveTime)>,
+    idle: Duration,
+    next_alert: Duration,
+    alerts: u32,
+    last_tick: Option<Instant>,
+    paused: bool,
+}
+
+impl AbsenceMonitor {
+    /// `None` when no `alert_after` is configured
+    pub fn from_config(config: &AbsenceConfig) -> Result<Option<Self>> {
+        let Some(alert_after) = &config.alert_after else {
+            return Ok(None);
+        };
+        let alert_after = parse_duration(alert_after)?;
+        let repeat_every = match &config.repeat_every {
+            Some(repeat) => parse_duration(repeat)?,
+            None => Duration::from_secs(3600),
+        };
+        let active_hours = match &config.active_hours {
+            Some([start, end]) => Some((parse_time(start)?, parse_time(end)?)),
+            None => None,
+        };
+        Ok(Some(Self {
+            alert_after,
+            repeat_every,
+            active_hours,
+            idle: Duration::ZERO,
+            next_alert: alert_after,
+            alerts: 0,
+            last_tick: None,
+            paused: false,
+        }))
+    }
+
+    /// Advance the countdown to `now`; `clock` is the local time of day
+    pub fn tick(&mut self, now: Instant, clock: NaiveTime, counting: bool) -> Option<AbsenceAlert> {
+        let elapsed = self
+            .last_tick
+            .map(|last| now.duration_since(last))
+            .unwrap_or_default();
+        self.last_tick = Some(now);
+        self.paused = !counting || !self.in_active_hours(clock);
+        if self.paused {
+            return None;
+        }
+
+        self.idle += elapsed;
+        if self.idle < self.next_alert {
+            return None;
+        }
+        self.alerts += 1;
+        self.next_alert = self.idle + self.repeat_every;
+        Some(AbsenceAlert::NoMotion {
+            idle: self.idle,
+            count: self.alerts,
+        })
+    }
+
+    /// A qualifying motion event restarts the countdown
+    pub fn motion(&mut self) -> Option<AbsenceAlert> {
+        let idle = self.idle;
+        let alerted = self.alerts > 0;
+        self.idle = Duration::ZERO;
+        self.next_alert = self.alert_after;
+        self.alerts = 0;
+        alerted.then_some(AbsenceAlert::Recovered { idle })
+    }
+
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn status(&self) -> AbsenceStatus {
+        AbsenceStatus {
+            remaining: self.next_alert.saturating_sub(self.idle),
+            alerting: self.alerts > 0,
+            paused: self.paused,
+        }
+    }
+
+    fn in_active_hours(&self, clock: NaiveTime) -> bool {
+        match self.active_hours {
+            None => true,
+            Some((start, end)) if start <= end => clock >= start && clock < end,
+            // Window across midnight, e.g. 22:00-06:00
+            Some((start, end)) => clock >= start || clock < end,
+        }
+    }
+}
+
+fn parse_time(text: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(text, "%H:%M")
+        .with_context(|| format!("invalid time \"{}\" in active_hours (use HH:MM)", text))
+}
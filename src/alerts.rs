@@ -0,0 +1,175 @@
+//! Warnings and errors for the GUI's notification center. The detector
+//! thread and background workers raise an `AppAlert` on the state channel;
+//! the GUI folds repeats of the same kind into one notification, so a
+//! failure that has gone on for days shows as one line with a count rather
+//! than scrolling out of the activity log.
+
+// Without the `camera` feature nothing raises alerts yet
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use crate::gui::MotionState;
+use chrono::{DateTime, Local};
+use crossbeam_channel::Sender;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+    /// Detection has stopped or can't see anything; also shown as a toast
+    Critical,
+}
+
+impl Severity {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+            Severity::Critical => "Critical",
+        }
+    }
+}
+
+/// What went wrong; alerts of the same kind share one notification
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlertKind {
+    /// The camera stopped delivering frames and is being reopened
+    CameraLost,
+    SnapshotFailed,
+    UploadFailed,
+    LowDisk,
+    /// The detector thread stopped on an error or crashed
+    DetectorStopped,
+    /// The armed detector saw the empty scene change
+    Intrusion,
+}
+
+impl AlertKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AlertKind::CameraLost => "Camera lost",
+            AlertKind::SnapshotFailed => "Snapshot failed",
+            AlertKind::UploadFailed => "Upload failed",
+            AlertKind::LowDisk => "Low disk space",
+            AlertKind::DetectorStopped => "Detector stopped",
+            AlertKind::Intrusion => "Intrusion",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppAlert {
+    pub kind: AlertKind,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl AppAlert {
+    pub fn new(kind: AlertKind, severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            severity,
+            message: message.into(),
+        }
+    }
+}
+
+/// Raises alerts on the GUI's state channel, from any thread
+#[derive(Clone, Debug)]
+pub struct AlertSender(Sender<MotionState>);
+
+impl AlertSender {
+    pub fn new(sender: Sender<MotionState>) -> Self {
+        Self(sender)
+    }
+
+    /// Dropped rather than blocking when the GUI is behind
+    pub fn raise(&self, alert: AppAlert) {
+        let _ = self.0.try_send(MotionState {
+            alert: Some(alert),
+            ..MotionState::default()
+        });
+    }
+}
+
+/// One kind of alert as the notification center shows it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Notification {
+    pub kind: AlertKind,
+    /// The highest severity seen so far
+    pub severity: Severity,
+    /// The latest occurrence's message
+    pub message: String,
+    pub first: DateTime<Local>,
+    pub last: DateTime<Local>,
+    pub count: u32,
+    pub unread: bool,
+}
+
+/// The session's notifications, newest first. Nothing is written to disk.
+#[derive(Clone, Debug, Default)]
+pub struct NotificationCenter {
+    items: Vec<Notification>,
+}
+
+impl NotificationCenter {
+    /// Add `alert`, merging it into an earlier one of the same kind. True
+    /// when it deserves a toast: a critical alert that isn't already
+    /// waiting unread as critical, so a camera that keeps failing doesn't
+    /// toast on every attempt.
+    pub fn record(&mut self, alert: AppAlert, now: DateTime<Local>) -> bool {
+        let toast;
+        match self.items.iter().position(|item| item.kind == alert.kind) {
+            Some(index) => {
+                let mut item = self.items.remove(index);
+                toast = alert.severity == Severity::Critical
+                    && !(item.unread && item.severity == Severity::Critical);
+                item.severity = item.severity.max(alert.severity);
+                item.message = alert.message;
+                item.last = now;
+                item.count += 1;
+                item.unread = true;
+                self.items.insert(0, item);
+            }
+            None => {
+                toast = alert.severity == Severity::Critical;
+                self.items.insert(
+                    0,
+                    Notification {
+                        kind: alert.kind,
+                        severity: alert.severity,
+                        message: alert.message,
+                        first: now,
+                        last: now,
+                        count: 1,
+                        unread: true,
+                    },
+                );
+            }
+        }
+        toast
+    }
+
+    pub fn items(&self) -> &[Notification] {
+        &self.items
+    }
+
+    /// Shown as the badge on the bell
+    pub fn unread(&self) -> usize {
+        self.items.iter().filter(|item| item.unread).count()
+    }
+
+    pub fn mark_all_read(&mut self) {
+        for item in &mut self.items {
+            item.unread = false;
+        }
+    }
+
+    /// Forget a notification; the next alert of its kind starts over
+    pub fn dismiss(&mut self, kind: AlertKind) {
+        self.items.retain(|item| item.kind != kind);
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+}
@@ -0,0 +1,229 @@
+//! Motion outlines on event snapshots (`--annotation-style`). Snapshots are
+//! saved as captured unless a style is set. Then the motion regions of the
+//! saved frame are drawn onto it: as bounding boxes, as the outline of the
+//! changed pixels, which says more about an odd shape such as a cat along a
+//! fence, or both. Clips and the originals kept with
+//! `--privacy-keep-original` stay unmarked.
+
+// Without the `camera` feature only the config check uses this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use crate::regions::Region;
+use clap::ValueEnum;
+#[cfg(feature = "camera")]
+use opencv::{
+    core::{no_array, Mat, Point, Rect, Scalar, Vector},
+    imgproc,
+};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Thickest line `--box-thickness` accepts, in snapshot pixels
+pub const MAX_THICKNESS: u32 = 20;
+
+/// What `--annotation-style` draws around each motion region
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationStyle {
+    /// The bounding box
+    Rect,
+    /// The outline of the changed pixels
+    Contour,
+    /// Box and outline
+    Both,
+}
+
+impl AnnotationStyle {
+    pub fn draws_rects(&self) -> bool {
+        matches!(self, AnnotationStyle::Rect | AnnotationStyle::Both)
+    }
+
+    /// Whether the detector must keep the contour points
+    pub fn draws_contours(&self) -> bool {
+        matches!(self, AnnotationStyle::Contour | AnnotationStyle::Both)
+    }
+}
+
+/// `--box-color`: `#rrggbb`, or one of `NAMES`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct BoxColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl BoxColor {
+    pub const GREEN: BoxColor = BoxColor::rgb(0, 255, 0);
+
+    /// Colors known by name
+    pub const NAMES: [(&'static str, BoxColor); 8] = [
+        ("red", BoxColor::rgb(255, 0, 0)),
+        ("green", BoxColor::GREEN),
+        ("blue", BoxColor::rgb(0, 0, 255)),
+        ("yellow", BoxColor::rgb(255, 255, 0)),
+        ("orange", BoxColor::rgb(255, 165, 0)),
+        ("magenta", BoxColor::rgb(255, 0, 255)),
+        ("cyan", BoxColor::rgb(0, 255, 255)),
+        ("white", BoxColor::rgb(255, 255, 255)),
+    ];
+
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Self { red, green, blue }
+    }
+
+    /// The color in OpenCV's BGR order
+    #[cfg(feature = "camera")]
+    pub fn scalar(&self) -> Scalar {
+        Scalar::new(self.blue as f64, self.green as f64, self.red as f64, 0.0)
+    }
+}
+
+impl Default for BoxColor {
+    fn default() -> Self {
+        Self::GREEN
+    }
+}
+
+impl FromStr for BoxColor {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if let Some((_, color)) = Self::NAMES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(text))
+        {
+            return Ok(*color);
+        }
+        let invalid = || {
+            format!(
+                "expected #rrggbb or a color name ({}), got \"{}\"",
+                Self::NAMES.map(|(name, _)| name).join(", "),
+                text
+            )
+        };
+        let hex = text.strip_prefix('#').unwrap_or(text);
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16).map_err(|_| invalid());
+        Ok(Self::rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl TryFrom<String> for BoxColor {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        text.parse()
+    }
+}
+
+/// `box_thickness` is a line OpenCV can draw without covering what it marks
+pub fn thickness_problem(thickness: u32) -> Option<String> {
+    if (1..=MAX_THICKNESS).contains(&thickness) {
+        None
+    } else {
+        Some(format!(
+            "box_thickness must be 1 to {} pixels, got {}",
+            MAX_THICKNESS, thickness
+        ))
+    }
+}
+
+/// What is drawn onto a frame's snapshot, in capture pixels
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Marks {
+    pub regions: Vec<Region>,
+    /// Outlines of the contours inside `regions`, kept only for the
+    /// contour styles
+    pub contours: Vec<Vec<(f64, f64)>>,
+}
+
+impl Marks {
+    /// The region boxes on a frame `scale` times the size of the capture
+    /// frame, as x, y, width and height in its whole pixels
+    pub fn rects(&self, scale: f64) -> Vec<(i32, i32, i32, i32)> {
+        let at = |value: f64| (value * scale).round() as i32;
+        self.regions
+            .iter()
+            .map(|region| {
+                (
+                    at(region.x),
+                    at(region.y),
+                    at(region.width),
+                    at(region.height),
+                )
+            })
+            .collect()
+    }
+
+    /// The contour outlines on such a frame
+    pub fn outlines(&self, scale: f64) -> Vec<Vec<(i32, i32)>> {
+        let at = |value: f64| (value * scale).round() as i32;
+        self.contours
+            .iter()
+            .map(|contour| contour.iter().map(|&(x, y)| (at(x), at(y))).collect())
+            .collect()
+    }
+}
+
+/// Whether a contour's first point lies in one of `regions`, so the
+/// outlines of regions that were too small or paused aren't drawn
+pub fn in_regions(contour: &[(f64, f64)], regions: &[Region]) -> bool {
+    let Some(&(x, y)) = contour.first() else {
+        return false;
+    };
+    regions.iter().any(|region| {
+        x >= region.x
+            && x <= region.x + region.width
+            && y >= region.y
+            && y <= region.y + region.height
+    })
+}
+
+/// Draw `marks` onto `frame`, a snapshot `scale` times the capture frame's
+/// size
+#[cfg(feature = "camera")]
+pub fn draw(
+    frame: &mut Mat,
+    style: AnnotationStyle,
+    color: BoxColor,
+    thickness: u32,
+    marks: &Marks,
+    scale: f64,
+) -> opencv::Result<()> {
+    let thickness = thickness as i32;
+    if style.draws_contours() && !marks.contours.is_empty() {
+        let outlines: Vector<Vector<Point>> = marks
+            .outlines(scale)
+            .iter()
+            .map(|contour| contour.iter().map(|&(x, y)| Point::new(x, y)).collect())
+            .collect();
+        imgproc::draw_contours(
+            frame,
+            &outlines,
+            -1,
+            color.scalar(),
+            thickness,
+            imgproc::LINE_8,
+            &no_array(),
+            i32::MAX,
+            Point::new(0, 0),
+        )?;
+    }
+    if style.draws_rects() {
+        for (x, y, width, height) in marks.rects(scale) {
+            imgproc::rectangle(
+                frame,
+                Rect::new(x, y, width, height),
+                color.scalar(),
+                thickness,
+                imgproc::LINE_8,
+                0,
+            )?;
+        }
+    }
+    Ok(())
+}
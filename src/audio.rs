@@ -0,0 +1,195 @@
+// Without the `audio` feature only the config and tests use the WAV helpers
+#![cfg_attr(not(feature = "audio"), allow(dead_code))]
+
+use anyhow::Result;
+#[cfg(feature = "audio")]
+use anyhow::{bail, Context};
+#[cfg(feature = "audio")]
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "audio")]
+use std::sync::{Arc, Mutex};
+
+/// Microphone recording, read from the `[audio]` config section
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Save the microphone audio of each incident (`--record-audio`)
+    pub enabled: bool,
+    /// Longest recording kept per incident; later audio is dropped
+    pub max_secs: u32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_secs: 120,
+        }
+    }
+}
+
+/// File name for an incident's audio, next to its `motion_<id>_key.jpg`
+pub fn file_name(incident_id: u64) -> String {
+    format!("motion_{}.wav", incident_id)
+}
+
+/// 16-bit PCM WAV file contents
+pub fn wav_bytes(sample_rate: u32, channels: u16, samples: &[i16]) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let block_align = channels * 2;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&channels.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
+
+/// `None` unless `[audio] enabled`; an error when the microphone can't be
+/// opened or the build lacks the `audio` feature
+pub fn recorder(config: &AudioConfig) -> Result<Option<AudioRecorder>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    AudioRecorder::open(config).map(Some)
+}
+
+/// Samples collected for the incident being recorded
+#[cfg(feature = "audio")]
+#[derive(Default)]
+struct Recording {
+    incident_id: Option<u64>,
+    samples: Vec<i16>,
+}
+
+/// Keeps the default input device open and buffers its audio while an
+/// incident is in progress
+#[cfg(feature = "audio")]
+pub struct AudioRecorder {
+    _stream: cpal::Stream,
+    recording: Arc<Mutex<Recording>>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+#[cfg(feature = "audio")]
+impl AudioRecorder {
+    fn open(config: &AudioConfig) -> Result<Self> {
+        let device = cpal::default_host()
+            .default_input_device()
+            .context("No microphone found for --record-audio")?;
+        let supported = device
+            .default_input_config()
+            .context("Failed to read the microphone's input format")?;
+        let sample_rate = supported.sample_rate().0;
+        let channels = supported.channels();
+        let max_samples = config.max_secs as usize * sample_rate as usize * channels as usize;
+
+        let recording = Arc::new(Mutex::new(Recording::default()));
+        let buffer = recording.clone();
+        let push = move |samples: &mut dyn Iterator<Item = i16>| {
+            let mut recording = buffer.lock().unwrap();
+            if recording.incident_id.is_some() {
+                let room = max_samples.saturating_sub(recording.samples.len());
+                recording.samples.extend(samples.take(room));
+            }
+        };
+        let on_error = |e: cpal::StreamError| eprintln!("Audio stream error: {}", e);
+        let stream_config = supported.config();
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => device.build_input_stream(
+                &stream_config,
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    push(
+                        &mut data
+                            .iter()
+                            .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16),
+                    )
+                },
+                on_error,
+                None,
+            )?,
+            cpal::SampleFormat::I16 => device.build_input_stream(
+                &stream_config,
+                move |data: &[i16], _: &cpal::InputCallbackInfo| push(&mut data.iter().copied()),
+                on_error,
+                None,
+            )?,
+            cpal::SampleFormat::U16 => device.build_input_stream(
+                &stream_config,
+                move |data: &[u16], _: &cpal::InputCallbackInfo| {
+                    push(&mut data.iter().map(|&s| (s as i32 - 32768) as i16))
+                },
+                on_error,
+                None,
+            )?,
+            other => bail!("Unsupported microphone sample format {:?}", other),
+        };
+        stream.play().context("Failed to start the microphone")?;
+
+        Ok(Self {
+            _stream: stream,
+            recording,
+            sample_rate,
+            channels,
+        })
+    }
+
+    /// Start buffering audio for a new incident, dropping any unsaved audio
+    pub fn start(&self, incident_id: u64) {
+        let mut recording = self.recording.lock().unwrap();
+        recording.incident_id = Some(incident_id);
+        recording.samples.clear();
+    }
+
+    /// Write the audio of a finished incident as `motion_<id>.wav` in `dir`;
+    /// `None` when it wasn't being recorded
+    pub fn finish(&self, incident_id: u64, dir: &Path) -> Result<Option<PathBuf>> {
+        let samples = {
+            let mut recording = self.recording.lock().unwrap();
+            if recording.incident_id != Some(incident_id) {
+                return Ok(None);
+            }
+            recording.incident_id = None;
+            std::mem::take(&mut recording.samples)
+        };
+        let path = dir.join(file_name(incident_id));
+        std::fs::write(&path, wav_bytes(self.sample_rate, self.channels, &samples))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(Some(path))
+    }
+}
+
+/// Stand-in without the `audio` feature; it can never be constructed
+#[cfg(not(feature = "audio"))]
+pub enum AudioRecorder {}
+
+#[cfg(not(feature = "audio"))]
+impl AudioRecorder {
+    fn open(_config: &AudioConfig) -> Result<Self> {
+        Err(anyhow::anyhow!(
+            "--record-audio needs a build with the `audio` feature (cargo build --features audio)"
+        ))
+    }
+
+    pub fn start(&self, _incident_id: u64) {
+        match *self {}
+    }
+
+    pub fn finish(&self, _incident_id: u64, _dir: &Path) -> Result<Option<PathBuf>> {
+        match *self {}
+    }
+}
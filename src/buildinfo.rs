@@ -0,0 +1,105 @@
+//! What this binary is: version, commit, build date, OpenCV and features.
+//! Printed by `--version-full` and shown in the GUI's About window, so bug
+//! reports say which build they came from.
+
+use std::fmt;
+
+/// Cargo features this binary was built with
+const FEATURES: [(&str, bool); 5] = [
+    ("camera", cfg!(feature = "camera")),
+    ("mock-camera", cfg!(feature = "mock-camera")),
+    ("audio", cfg!(feature = "audio")),
+    ("gpio", cfg!(feature = "gpio")),
+    ("s3", cfg!(feature = "s3")),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    pub build_date: &'static str,
+    /// `None` without the `camera` feature
+    pub opencv: Option<String>,
+    pub features: Vec<&'static str>,
+    /// VideoCapture backends OpenCV was built with, e.g. V4L2, GSTREAMER
+    pub backends: Vec<String>,
+}
+
+impl BuildInfo {
+    pub fn collect() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_hash: env!("MOTION_DETECTOR_GIT_HASH"),
+            build_date: env!("MOTION_DETECTOR_BUILD_DATE"),
+            opencv: opencv_version(),
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| *name)
+                .collect(),
+            backends: capture_backends(),
+        }
+    }
+
+    /// Label and value pairs, in the order they are printed
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let list = |items: &[String]| {
+            if items.is_empty() {
+                "none".to_string()
+            } else {
+                items.join(", ")
+            }
+        };
+        let features: Vec<String> = self.features.iter().map(|f| f.to_string()).collect();
+        vec![
+            ("Version", self.version.to_string()),
+            ("Commit", self.git_hash.to_string()),
+            ("Built", self.build_date.to_string()),
+            (
+                "OpenCV",
+                self.opencv
+                    .clone()
+                    .unwrap_or_else(|| "not linked".to_string()),
+            ),
+            ("Features", list(&features)),
+            ("Backends", list(&self.backends)),
+        ]
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "motion_detector {}", self.version)?;
+        for (label, value) in self.fields().into_iter().skip(1) {
+            writeln!(f, "{:<9} {}", format!("{}:", label), value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "camera")]
+fn opencv_version() -> Option<String> {
+    opencv::core::get_version_string().ok()
+}
+
+#[cfg(not(feature = "camera"))]
+fn opencv_version() -> Option<String> {
+    None
+}
+
+#[cfg(feature = "camera")]
+fn capture_backends() -> Vec<String> {
+    use opencv::videoio;
+    videoio::get_backends()
+        .map(|apis| {
+            apis.iter()
+                .filter_map(|api| videoio::get_backend_name(api).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(not(feature = "camera"))]
+fn capture_backends() -> Vec<String> {
+    Vec::new()
+}
@@ -0,0 +1,101 @@
+//! Wall-clock jumps, e.g. when NTP sets the clock of a Pi without a
+//! real-time clock some minutes after boot. Cooldowns, incident gaps and the
+//! no-motion countdown run on `Instant`, which never jumps; the wall clock is
+//! only read for display, file names and the arming schedule, and the
+//! schedule is worked out again after a jump.
+
+use crate::humanize::humanize_duration;
+use chrono::{DateTime, Datelike, Local, TimeDelta};
+use std::time::Instant;
+
+/// Both clocks, so tests can move the wall clock on its own
+pub trait Clock {
+    fn instant(&self) -> Instant;
+    fn local(&self) -> DateTime<Local>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn local(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// The wall clock moved `offset` more than the time that actually passed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClockJump {
+    /// What the wall clock would have read without the jump
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+    /// Negative when the clock went back
+    pub offset: TimeDelta,
+}
+
+impl ClockJump {
+    /// Line for the console and the GUI's activity log
+    pub fn message(&self) -> String {
+        format!(
+            "CLOCK JUMPED {} by {} (from {} to {}), schedule re-evaluated",
+            if self.offset < TimeDelta::zero() {
+                "back"
+            } else {
+                "forward"
+            },
+            humanize_duration(self.offset.abs().to_std().unwrap_or_default()),
+            self.from.format("%Y-%m-%d %H:%M:%S"),
+            self.to.format("%Y-%m-%d %H:%M:%S")
+        )
+    }
+}
+
+/// Compares the wall clock with the monotonic clock between calls
+#[derive(Default)]
+pub struct JumpWatch {
+    last: Option<(Instant, DateTime<Local>)>,
+}
+
+impl JumpWatch {
+    /// Smaller differences are NTP slewing or a slow frame, not a jump
+    const THRESHOLD: TimeDelta = TimeDelta::seconds(60);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn check(&mut self, clock: &impl Clock) -> Option<ClockJump> {
+        let (instant, local) = (clock.instant(), clock.local());
+        let (last_instant, last_local) = self.last.replace((instant, local))?;
+        let passed = TimeDelta::from_std(instant.duration_since(last_instant)).ok()?;
+        let offset = local.signed_duration_since(last_local) - passed;
+        (offset.abs() > Self::THRESHOLD).then(|| ClockJump {
+            from: last_local + passed,
+            to: local,
+            offset,
+        })
+    }
+}
+
+/// Whether `now` can be a real date rather than an unset clock (1970) or
+/// a dead RTC battery
+pub fn looks_set(now: DateTime<Local>) -> bool {
+    const EARLIEST_YEAR: i32 = 2024;
+    const LATEST_YEAR: i32 = 2100;
+
+    (EARLIEST_YEAR..=LATEST_YEAR).contains(&now.year())
+}
+
+/// Warn at startup that snapshot names and the schedule will be off until
+/// the clock is set
+pub fn warn_if_unset(now: DateTime<Local>) {
+    if !looks_set(now) {
+        eprintln!(
+            "Warning: the system clock reads {}; snapshot names and the schedule will be wrong until it is set (e.g. by NTP)",
+            now.format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+}
@@ -0,0 +1,588 @@
+use crate::absence::AbsenceConfig;
+use crate::audio::AudioConfig;
+use crate::gui::GuiConfig;
+use crate::notify::MessageTemplate;
+use crate::overlay::OverlayConfig;
+use crate::recording::RecordingConfig;
+use crate::regions::MinArea;
+use crate::reposition::RepositionConfig;
+use crate::rules::{TransitionRule, Zone};
+use crate::schedule::{ScheduleClock, ScheduleConfig};
+use crate::sun::Location;
+use crate::tracker::TrackingConfig;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// Defaults shared by the CLI, the GUI and `DetectorBuilder`
+pub const DEFAULT_SENSITIVITY: f64 = 0.3;
+pub const DEFAULT_MIN_AREA: u32 = 500;
+pub const DEFAULT_BLUR_KERNEL: i32 = 21;
+
+/// Settings loaded from the optional TOML config file (`--config`)
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub detector: DetectorConfig,
+    pub overlay: OverlayConfig,
+    pub gui: GuiConfig,
+    pub snapshots: SnapshotConfig,
+    pub absence: AbsenceConfig,
+    pub audio: AudioConfig,
+    pub recording: RecordingConfig,
+    pub tracking: TrackingConfig,
+    pub reposition: RepositionConfig,
+    pub zones: Vec<Zone>,
+    pub rules: Vec<TransitionRule>,
+    /// Needed for sunrise/sunset-relative schedules
+    pub location: Option<Location>,
+    pub schedule: ScheduleConfig,
+    /// Fallbacks for every `[camera.<name>]` block
+    pub defaults: CameraConfig,
+    /// Named cameras, selected with `--camera <name>`
+    pub camera: BTreeMap<String, CameraConfig>,
+    /// Message wording per notifier, `[messages.<notifier>]`
+    pub messages: BTreeMap<String, MessageTemplate>,
+}
+
+/// Per-camera overrides, read from `[camera.<name>]` and `[defaults]`;
+/// unset fields fall back to `[defaults]`, then to the global settings
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    pub device: Option<u32>,
+    pub sensitivity: Option<f64>,
+    pub min_area: Option<MinArea>,
+    /// Snapshot directory (default: `<--output-dir>/<name>`)
+    pub output_dir: Option<PathBuf>,
+    pub detector: Option<DetectorConfig>,
+    pub snapshots: Option<SnapshotConfig>,
+    pub absence: Option<AbsenceConfig>,
+    pub tracking: Option<TrackingConfig>,
+    pub zones: Option<Vec<Zone>>,
+    pub rules: Option<Vec<TransitionRule>>,
+}
+
+impl CameraConfig {
+    /// Fields set here win, the rest come from `base`
+    fn or(&self, base: &CameraConfig) -> CameraConfig {
+        CameraConfig {
+            device: self.device.or(base.device),
+            sensitivity: self.sensitivity.or(base.sensitivity),
+            min_area: self.min_area.or(base.min_area),
+            output_dir: self.output_dir.clone().or_else(|| base.output_dir.clone()),
+            detector: self.detector.clone().or_else(|| base.detector.clone()),
+            snapshots: self.snapshots.clone().or_else(|| base.snapshots.clone()),
+            absence: self.absence.clone().or_else(|| base.absence.clone()),
+            tracking: self.tracking.clone().or_else(|| base.tracking.clone()),
+            zones: self.zones.clone().or_else(|| base.zones.clone()),
+            rules: self.rules.clone().or_else(|| base.rules.clone()),
+        }
+    }
+}
+
+/// A `[camera.<name>]` block merged with `[defaults]` and the global settings
+#[derive(Clone, Debug)]
+pub struct NamedCamera {
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub name: String,
+    pub device: u32,
+    pub sensitivity: Option<f64>,
+    pub min_area: Option<MinArea>,
+    pub output_dir: Option<PathBuf>,
+    /// The global config with this camera's sections swapped in
+    pub config: Config,
+}
+
+/// Capture and image-processing settings, read from the `[detector]` config section
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DetectorConfig {
+    /// `[width, height]` to request from the camera instead of probing
+    pub resolution: Option<[u32; 2]>,
+    /// Gaussian blur kernel size; must be odd
+    pub blur_kernel: i32,
+    /// How the sensitivity maps to the pixel difference threshold
+    /// (`--sensitivity-curve`)
+    pub sensitivity_curve: crate::sensitivity::SensitivityCurve,
+    /// Contrast normalization before blurring (`--normalize`)
+    pub normalize: crate::normalize::Normalize,
+    /// CLAHE contrast limit; higher values equalize more aggressively
+    pub clahe_clip_limit: f64,
+    /// CLAHE tiles per side of the frame
+    pub clahe_tile_size: i32,
+    /// Detect on a copy shrunk by this factor (`--process-scale`)
+    pub process_scale: f64,
+    /// Morphological opening of the motion mask before dilation, against
+    /// single-pixel sensor noise; 0 turns it off (`--open-kernel`)
+    pub open_kernel: i32,
+    pub open_iterations: i32,
+    /// Also find motion on the mask halved once or twice, 1 to 3
+    /// (`--pyramid-levels`); 1 is the full size only
+    pub pyramid_levels: u32,
+    /// Lower the processing scale while frames per second stay below this
+    /// (`--min-fps`)
+    pub min_fps: Option<f32>,
+    /// Narrowest and widest contour boxes counted, as width over height
+    /// (`--min-aspect`, `--max-aspect`)
+    pub min_aspect: Option<f64>,
+    pub max_aspect: Option<f64>,
+    /// Time constant in seconds over which the motion score is averaged
+    /// before it is compared with the threshold; 0 is off (`--smoothing`)
+    pub smoothing_secs: f64,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        Self {
+            resolution: None,
+            blur_kernel: DEFAULT_BLUR_KERNEL,
+            sensitivity_curve: crate::sensitivity::SensitivityCurve::Exponential,
+            normalize: crate::normalize::Normalize::Off,
+            clahe_clip_limit: 2.0,
+            clahe_tile_size: 8,
+            process_scale: 1.0,
+            open_kernel: 0,
+            open_iterations: 1,
+            pyramid_levels: 1,
+            min_fps: None,
+            min_aspect: None,
+            max_aspect: None,
+            smoothing_secs: 0.0,
+        }
+    }
+}
+
+/// Snapshot settings, read from the `[snapshots]` config section
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    /// Write an image for each motion event (`--no-snapshot` turns this off)
+    pub enabled: bool,
+    /// Motion frames kept so an event's snapshot shows the frame where the
+    /// motion began; 0 saves the frame that fired the event
+    pub pre_event_frames: usize,
+    /// Event snapshot name without `.jpg`; `{timestamp}`, `{zone}` and
+    /// `{direction}` are filled in from the event
+    pub filename: String,
+    /// Skip a snapshot whose perceptual hash differs from a recent one in
+    /// fewer than this many of 64 bits; 0 keeps every snapshot
+    pub dedup_threshold: u32,
+    /// How long a saved snapshot is compared against, e.g. "10m"
+    pub dedup_window: String,
+    /// Progressive JPEG snapshots (`--jpeg-progressive`)
+    pub jpeg_progressive: bool,
+    /// "444" or "420"; unset leaves it to OpenCV
+    pub jpeg_chroma: Option<crate::jpeg::ChromaSubsampling>,
+    /// Areas obscured in snapshots, clips and the preview, as
+    /// `[x, y, width, height]` in snapshot pixels
+    pub privacy_regions: Vec<crate::privacy::PrivacyRegion>,
+    /// "blur" or "black"
+    pub privacy_style: crate::privacy::PrivacyStyle,
+    /// "off", "faces" or "all-motion" (`--privacy-blur`)
+    pub privacy_blur: crate::privacy::PrivacyBlur,
+    /// Haar cascade for `privacy_blur = "faces"` (default: OpenCV's
+    /// frontal face cascade where it is usually installed)
+    pub face_cascade: Option<PathBuf>,
+    /// Keep an unblurred copy of each event snapshot here
+    /// (`--privacy-keep-original`)
+    pub privacy_keep_original: Option<PathBuf>,
+    /// Save under `YYYY/MM/DD/` subdirectories (`--date-tree`)
+    pub date_tree: bool,
+    /// "rect", "contour" or "both" draws the motion onto event snapshots;
+    /// unset saves them unmarked (`--annotation-style`)
+    pub annotation_style: Option<crate::annotate::AnnotationStyle>,
+    /// `#rrggbb` or a color name (`--box-color`)
+    pub box_color: crate::annotate::BoxColor,
+    /// Line width in snapshot pixels (`--box-thickness`)
+    pub box_thickness: u32,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            pre_event_frames: 8,
+            filename: crate::naming::DEFAULT_TEMPLATE.to_string(),
+            dedup_threshold: 0,
+            dedup_window: "10m".to_string(),
+            jpeg_progressive: false,
+            jpeg_chroma: None,
+            privacy_regions: Vec::new(),
+            privacy_style: crate::privacy::PrivacyStyle::Blur,
+            privacy_blur: crate::privacy::PrivacyBlur::Off,
+            face_cascade: None,
+            privacy_keep_original: None,
+            date_tree: false,
+            annotation_style: None,
+            box_color: crate::annotate::BoxColor::GREEN,
+            box_thickness: 2,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let config: Self = toml::from_str(&text)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+        if let Err(errors) = validate(&config) {
+            bail!(
+                "{} problem(s) in config file {}:\n{}",
+                errors.len(),
+                path.display(),
+                describe_errors(&errors)
+            );
+        }
+        Ok(config)
+    }
+
+    /// Resolve `[camera.<name>]`
+    pub fn named_camera(&self, name: &str) -> Result<NamedCamera> {
+        let Some(block) = self.camera.get(name) else {
+            let known: Vec<&str> = self.camera.keys().map(String::as_str).collect();
+            if known.is_empty() {
+                bail!(
+                    "unknown camera \"{}\" (no [camera.<name>] sections configured)",
+                    name
+                );
+            }
+            bail!(
+                "unknown camera \"{}\" (configured: {})",
+                name,
+                known.join(", ")
+            );
+        };
+        let merged = block.or(&self.defaults);
+        let Some(device) = merged.device else {
+            bail!("[camera.{}] needs a device", name);
+        };
+
+        let mut config = self.clone();
+        if let Some(detector) = merged.detector {
+            config.detector = detector;
+        }
+        if let Some(snapshots) = merged.snapshots {
+            config.snapshots = snapshots;
+        }
+        if let Some(absence) = merged.absence {
+            config.absence = absence;
+        }
+        if let Some(tracking) = merged.tracking {
+            config.tracking = tracking;
+        }
+        if let Some(zones) = merged.zones {
+            config.zones = zones;
+        }
+        if let Some(rules) = merged.rules {
+            config.rules = rules;
+        }
+        Ok(NamedCamera {
+            name: name.to_string(),
+            device,
+            sensitivity: merged.sensitivity,
+            min_area: merged.min_area,
+            output_dir: merged.output_dir,
+            config,
+        })
+    }
+
+    /// Every named camera, in name order
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn named_cameras(&self) -> Vec<NamedCamera> {
+        self.camera
+            .keys()
+            .filter_map(|name| self.named_camera(name).ok())
+            .collect()
+    }
+
+    /// The named camera that owns capture device `device`, if any
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn camera_for_device(&self, device: u32) -> Option<NamedCamera> {
+        self.named_cameras()
+            .into_iter()
+            .find(|camera| camera.device == device)
+    }
+
+    /// Problems with the named cameras. Sections a camera inherits are only
+    /// reported when they differ from the `global` problems already found.
+    fn camera_errors(&self, global: &[ConfigError], errors: &mut Vec<ConfigError>) {
+        let mut names: BTreeMap<String, &str> = BTreeMap::new();
+        let mut devices: BTreeMap<u32, &str> = BTreeMap::new();
+        for name in self.camera.keys() {
+            let section = format!("[camera.{}]", name);
+            // The name becomes a directory under --output-dir
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                errors.push(ConfigError::new(
+                    &section,
+                    "name may only use letters, digits, '_' and '-'",
+                ));
+            }
+            if let Some(other) = names.insert(name.to_ascii_lowercase(), name) {
+                errors.push(ConfigError::new(
+                    &section,
+                    format!("duplicates the name of [camera.{}]", other),
+                ));
+            }
+
+            let camera = match self.named_camera(name) {
+                Ok(camera) => camera,
+                Err(e) => {
+                    errors.push(ConfigError::new(&section, format!("{:#}", e)));
+                    continue;
+                }
+            };
+            if let Some(other) = devices.insert(camera.device, name) {
+                errors.push(ConfigError::new(
+                    &section,
+                    format!(
+                        "device {} is already claimed by [camera.{}]",
+                        camera.device, other
+                    ),
+                ));
+            }
+            if let Some(sensitivity) = camera.sensitivity
+                && !(0.0..=1.0).contains(&sensitivity)
+            {
+                errors.push(ConfigError::new(
+                    &section,
+                    format!(
+                        "sensitivity must be between 0.0 and 1.0, got {}",
+                        sensitivity
+                    ),
+                ));
+            }
+            if camera.min_area.is_some_and(|min_area| min_area.is_zero()) {
+                errors.push(ConfigError::new(
+                    &section,
+                    "min_area must be greater than 0",
+                ));
+            }
+            if let Some(min_area) = camera.min_area
+                && let Some(message) = frame_area_problem(min_area, &camera.config.detector)
+            {
+                errors.push(ConfigError::new(&section, message));
+            }
+            let mut own = Vec::new();
+            camera.config.section_errors(&mut own);
+            errors.extend(
+                own.into_iter()
+                    .filter(|error| !global.contains(error))
+                    .map(|error| ConfigError::new(&section, error.to_string())),
+            );
+        }
+    }
+
+    /// Problems with the sections a camera can override
+    fn section_errors(&self, errors: &mut Vec<ConfigError>) {
+        let kernel = self.detector.blur_kernel;
+        if kernel <= 0 || kernel % 2 == 0 {
+            errors.push(ConfigError::new(
+                "[detector]",
+                format!("blur_kernel must be a positive odd number, got {}", kernel),
+            ));
+        }
+        if self.detector.clahe_clip_limit <= 0.0 || self.detector.clahe_tile_size < 1 {
+            errors.push(ConfigError::new(
+                "[detector]",
+                "clahe_clip_limit and clahe_tile_size must be positive",
+            ));
+        }
+        if let Err(message) =
+            opening_problem(self.detector.open_kernel, self.detector.open_iterations)
+        {
+            errors.push(ConfigError::new("[detector]", message));
+        }
+        if let Some(problem) = crate::pyramid::levels_problem(self.detector.pyramid_levels) {
+            errors.push(ConfigError::new("[detector]", problem));
+        }
+        if let Some(problem) = crate::quality::min_fps_problem(self.detector.min_fps) {
+            errors.push(ConfigError::new("[detector]", problem));
+        }
+        if let Some(problem) =
+            crate::regions::aspect_problem(self.detector.min_aspect, self.detector.max_aspect)
+        {
+            errors.push(ConfigError::new("[detector]", problem));
+        }
+        if let Some(problem) = crate::smoothing::smoothing_problem(self.detector.smoothing_secs) {
+            errors.push(ConfigError::new("[detector]", problem));
+        }
+        let factor = self.detector.process_scale;
+        if !(factor > 0.0 && factor <= 1.0) {
+            errors.push(ConfigError::new(
+                "[detector]",
+                format!(
+                    "process_scale must be above 0 and at most 1, got {}",
+                    factor
+                ),
+            ));
+        }
+        if let Some([width, height]) = self.detector.resolution {
+            if width == 0 || height == 0 {
+                errors.push(ConfigError::new(
+                    "[detector]",
+                    format!("resolution {}x{} must be positive", width, height),
+                ));
+            }
+            for zone in &self.zones {
+                let [x, y, w, h] = zone.rect;
+                if x < 0.0 || y < 0.0 || x + w > width as f64 || y + h > height as f64 {
+                    errors.push(ConfigError::new(
+                        "[[zones]]",
+                        format!(
+                            "zone \"{}\" lies outside the {}x{} frame",
+                            zone.name, width, height
+                        ),
+                    ));
+                }
+            }
+        }
+        if let Err(message) = crate::naming::check(&self.snapshots.filename) {
+            errors.push(ConfigError::new("[snapshots] filename", message));
+        }
+        if let Err(e) = crate::dedup::SnapshotDedup::from_config(&self.snapshots) {
+            errors.push(ConfigError::new("[snapshots]", format!("{:#}", e)));
+        }
+        if let Some(problem) = crate::annotate::thickness_problem(self.snapshots.box_thickness) {
+            errors.push(ConfigError::new("[snapshots]", problem));
+        }
+        for problem in crate::rules::problems(&self.zones, &self.rules) {
+            errors.push(ConfigError::new("[[rules]]", problem));
+        }
+        if let Err(e) = crate::absence::AbsenceMonitor::from_config(&self.absence) {
+            errors.push(ConfigError::new("[absence]", format!("{:#}", e)));
+        }
+    }
+}
+
+/// One problem found by `validate`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigError {
+    /// Where the setting lives, e.g. `[detector]` or `[camera.porch]`
+    pub section: String,
+    pub message: String,
+}
+
+impl ConfigError {
+    pub fn new(section: &str, message: impl Into<String>) -> Self {
+        Self {
+            section: section.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.section, self.message)
+    }
+}
+
+/// `open_kernel` is 0 (off) or odd, and `open_iterations` positive
+pub fn opening_problem(kernel: i32, iterations: i32) -> std::result::Result<(), String> {
+    if kernel != 0 && (kernel < 3 || kernel % 2 == 0) {
+        return Err(format!(
+            "open_kernel must be 0 (off) or an odd number of at least 3, got {}",
+            kernel
+        ));
+    }
+    if iterations < 1 {
+        return Err(format!(
+            "open_iterations must be at least 1, got {}",
+            iterations
+        ));
+    }
+    Ok(())
+}
+
+/// Check all settings and report every problem, not just the first
+pub fn validate(config: &Config) -> std::result::Result<(), Vec<ConfigError>> {
+    let mut errors = Vec::new();
+    config.section_errors(&mut errors);
+    if config.recording.fps <= 0.0 || config.recording.bitrate_kbps == 0 {
+        errors.push(ConfigError::new(
+            "[recording]",
+            "fps and bitrate_kbps must be positive",
+        ));
+    }
+    if config.recording.trim_padding.is_nan() || config.recording.trim_padding < 0.0 {
+        errors.push(ConfigError::new(
+            "[recording]",
+            format!(
+                "trim_padding must be 0 or more seconds, got {}",
+                config.recording.trim_padding
+            ),
+        ));
+    }
+    if let Some(location) = config.location
+        && (!(-90.0..=90.0).contains(&location.latitude)
+            || !(-180.0..=180.0).contains(&location.longitude))
+    {
+        errors.push(ConfigError::new(
+            "[location]",
+            "latitude must be within ±90 and longitude within ±180",
+        ));
+    }
+    if let Err(e) = ScheduleClock::from_config(&config.schedule, config.location) {
+        errors.push(ConfigError::new("[schedule]", format!("{:#}", e)));
+    }
+    let [width, height] = config.gui.preview_max_size;
+    if !(width >= 1.0 && height >= 1.0 && width.is_finite() && height.is_finite()) {
+        errors.push(ConfigError::new(
+            "[gui]",
+            format!(
+                "preview_max_size must be at least 1x1, got {}x{}",
+                width, height
+            ),
+        ));
+    }
+    for problem in config.reposition.problems() {
+        errors.push(ConfigError::new("[reposition]", problem));
+    }
+    for (notifier, template) in &config.messages {
+        if let Err((part, e)) = template.parse() {
+            errors.push(ConfigError::new(
+                &format!("[messages.{}] {}", notifier, part),
+                e.to_string(),
+            ));
+        }
+    }
+    let global = errors.clone();
+    config.camera_errors(&global, &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// One problem per line, indented, for error messages
+pub fn describe_errors(errors: &[ConfigError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("  - {}", error))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `min_area` can never be reached in a frame of the configured resolution.
+/// A percentage of the frame always fits.
+pub fn frame_area_problem(min_area: MinArea, detector: &DetectorConfig) -> Option<String> {
+    let MinArea::Pixels(min_area) = min_area else {
+        return None;
+    };
+    let [width, height] = detector.resolution?;
+    let frame_area = width as u64 * height as u64;
+    (frame_area > 0 && min_area as u64 > frame_area).then(|| {
+        format!(
+            "min_area {} is larger than the whole {}x{} frame",
+            min_area, width, height
+        )
+    })
+}
@@ -0,0 +1,31 @@
+//! Panics in the GUI's detector thread. Without this the thread just ends,
+//! `join()` discards the payload and the GUI stops updating with no word of
+//! why.
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+
+/// Log every panic with the thread's name and a backtrace, which only
+/// exists while the panicking thread is still unwinding
+pub fn install_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let thread = std::thread::current();
+        eprintln!(
+            "Thread '{}' {}\nBacktrace:\n{}",
+            thread.name().unwrap_or("unnamed"),
+            info,
+            Backtrace::force_capture()
+        );
+    }));
+}
+
+/// The message a panic was raised with, from `catch_unwind` or `join`
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
@@ -0,0 +1,223 @@
+//! D-Bus interface for desktop integration (`--dbus`, Linux builds with the
+//! `dbus` feature). `org.motiondetector.Detector1` controls the GUI's
+//! detector through the same command channel as the window, and reads its
+//! state from the channel that feeds the window.
+
+// Without the `dbus` feature only the flag and the status mapping use this,
+// and without the `camera` feature there is no GUI detector to control
+#![cfg_attr(
+    not(all(feature = "camera", feature = "dbus", target_os = "linux")),
+    allow(dead_code)
+)]
+
+use crate::gui::{GuiMessage, MotionState};
+use anyhow::Result;
+use clap::ValueEnum;
+use crossbeam_channel::{Receiver, Sender};
+
+pub const BUS_NAME: &str = "org.motiondetector.Detector1";
+pub const OBJECT_PATH: &str = "/org/motiondetector/Detector1";
+
+/// Which bus `--dbus` registers on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Bus {
+    /// The user's desktop session
+    Session,
+    /// The system bus; needs a policy file allowing the name
+    System,
+}
+
+/// What `GetStatus` returns
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Status {
+    pub motion_detected: bool,
+    pub motion_count: u32,
+    pub fps: f64,
+    /// RFC 3339, empty before the first motion
+    pub last_motion: String,
+}
+
+impl Status {
+    pub fn from_state(state: &MotionState) -> Self {
+        Self {
+            motion_detected: state.motion_detected,
+            motion_count: state.motion_count,
+            fps: state.fps as f64,
+            last_motion: state
+                .last_motion_time
+                .map(|at| at.to_rfc3339())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+mod service {
+    use super::{Bus, Status, BUS_NAME, OBJECT_PATH};
+    use crate::gui::{GuiMessage, MotionState};
+    use anyhow::{Context, Result};
+    use crossbeam_channel::{Receiver, Sender};
+    use std::sync::{Arc, Mutex};
+    use zbus::blocking::connection;
+    use zbus::fdo::{self, RequestNameFlags};
+    use zbus::object_server::SignalEmitter;
+
+    pub struct Detector1 {
+        commands: Sender<GuiMessage>,
+        status: Arc<Mutex<Status>>,
+    }
+
+    impl Detector1 {
+        fn send(&self, message: GuiMessage) -> fdo::Result<()> {
+            self.commands.try_send(message).map_err(|e| {
+                fdo::Error::Failed(format!("detector is not accepting commands: {}", e))
+            })
+        }
+    }
+
+    #[zbus::interface(name = "org.motiondetector.Detector1")]
+    impl Detector1 {
+        fn start(&self) -> fdo::Result<()> {
+            self.send(GuiMessage::StartDetection)
+        }
+
+        fn stop(&self) -> fdo::Result<()> {
+            self.send(GuiMessage::StopDetection)
+        }
+
+        /// Save the current frame, like the GUI's snapshot button
+        fn snapshot(&self) -> fdo::Result<()> {
+            self.send(GuiMessage::SaveSnapshot)
+        }
+
+        fn set_sensitivity(&self, sensitivity: f64) -> fdo::Result<()> {
+            if !(0.0..=1.0).contains(&sensitivity) {
+                return Err(fdo::Error::InvalidArgs(format!(
+                    "sensitivity must be between 0.0 and 1.0, got {}",
+                    sensitivity
+                )));
+            }
+            self.send(GuiMessage::UpdateSensitivity(sensitivity))
+        }
+
+        /// Motion in the last frame, event count, frames per second and the
+        /// time of the last motion
+        fn get_status(&self) -> (bool, u32, f64, String) {
+            let status = self.status.lock().map(|s| s.clone()).unwrap_or_default();
+            (
+                status.motion_detected,
+                status.motion_count,
+                status.fps,
+                status.last_motion,
+            )
+        }
+
+        #[zbus(signal)]
+        async fn motion_detected(
+            emitter: &SignalEmitter<'_>,
+            timestamp: &str,
+            count: u32,
+            snapshot: &str,
+        ) -> zbus::Result<()>;
+    }
+
+    /// Connect to `bus` (or a bus at `address`) and own the name; fails
+    /// when another instance already does
+    pub fn connect(
+        bus: Bus,
+        address: Option<&str>,
+        commands: Sender<GuiMessage>,
+        status: Arc<Mutex<Status>>,
+    ) -> Result<zbus::blocking::Connection> {
+        let builder = match (address, bus) {
+            (Some(address), _) => connection::Builder::address(address)?,
+            (None, Bus::Session) => connection::Builder::session()?,
+            (None, Bus::System) => connection::Builder::system()?,
+        };
+        let interface = Detector1 { commands, status };
+        let connection = builder
+            .serve_at(OBJECT_PATH, interface)?
+            .build()
+            .context("Failed to connect to D-Bus")?;
+        // Without queueing or replacement, so a second instance fails
+        // instead of taking the name over
+        match connection.request_name_with_flags(BUS_NAME, RequestNameFlags::DoNotQueue.into()) {
+            Ok(_) => Ok(connection),
+            Err(zbus::Error::NameTaken) => anyhow::bail!(
+                "{} is already owned on the {:?} bus; is another motion_detector running?",
+                BUS_NAME,
+                bus
+            ),
+            Err(e) => Err(e).with_context(|| format!("Failed to register {} on D-Bus", BUS_NAME)),
+        }
+    }
+
+    /// Forward `states` to the GUI, keeping the status current and
+    /// signalling each motion event
+    pub fn relay(
+        connection: zbus::blocking::Connection,
+        status: Arc<Mutex<Status>>,
+        states: Receiver<MotionState>,
+        gui: Sender<MotionState>,
+    ) {
+        let emitter = connection
+            .object_server()
+            .interface::<_, Detector1>(OBJECT_PATH)
+            .map(|interface| interface.signal_emitter().clone());
+        for state in states {
+            // Replies to commands and errors carry no frame
+            if state.frame_time.is_some()
+                && let Ok(mut status) = status.lock()
+            {
+                *status = Status::from_state(&state);
+            }
+            if let (Some(_), Ok(emitter)) = (state.incident_id, &emitter) {
+                let timestamp = chrono::Local::now().to_rfc3339();
+                let signal = Detector1::motion_detected(
+                    emitter,
+                    &timestamp,
+                    state.motion_count,
+                    state.event_snapshot.as_deref().unwrap_or_default(),
+                );
+                if let Err(e) = zbus::block_on(signal) {
+                    eprintln!("Warning: D-Bus signal failed: {}", e);
+                }
+            }
+            let _ = gui.try_send(state);
+        }
+    }
+}
+
+/// Register on `bus` and relay the detector's `states` to the `gui` channel
+/// from a thread of its own. `address` replaces the bus, for tests.
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+pub fn spawn(
+    bus: Bus,
+    address: Option<&str>,
+    commands: Sender<GuiMessage>,
+    states: Receiver<MotionState>,
+    gui: Sender<MotionState>,
+) -> Result<()> {
+    use std::sync::{Arc, Mutex};
+
+    let status = Arc::new(Mutex::new(Status::default()));
+    let connection = service::connect(bus, address, commands, Arc::clone(&status))?;
+    println!("Registered {} on the {:?} bus", BUS_NAME, bus);
+    std::thread::Builder::new()
+        .name("dbus".to_string())
+        .spawn(move || service::relay(connection, status, states, gui))?;
+    Ok(())
+}
+
+#[cfg(not(all(feature = "dbus", target_os = "linux")))]
+pub fn spawn(
+    _bus: Bus,
+    _address: Option<&str>,
+    _commands: Sender<GuiMessage>,
+    _states: Receiver<MotionState>,
+    _gui: Sender<MotionState>,
+) -> Result<()> {
+    anyhow::bail!(
+        "--dbus needs a Linux build with the `dbus` feature (cargo build --features dbus)"
+    )
+}
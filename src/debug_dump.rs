@@ -0,0 +1,235 @@
+// Without the `camera` feature only `debug-report` and the tests use this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use anyhow::{Context, Result};
+#[cfg(feature = "camera")]
+use opencv::{core::Mat, core::Vector, imgcodecs};
+use serde::{Deserialize, Serialize};
+use std::io::BufRead;
+#[cfg(feature = "camera")]
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// JSON lines file with one `DumpRecord` per dumped frame
+pub const RECORDS_FILE: &str = "dump.jsonl";
+
+/// `--debug-dump`, `--debug-dump-every` and `--debug-dump-max-mb`
+#[derive(Clone, Debug, PartialEq)]
+pub struct DumpOptions {
+    pub dir: PathBuf,
+    pub every: u32,
+    pub max_bytes: u64,
+    /// Sync the records file after each motion frame (`--flush-on-event`)
+    pub sync_motion: bool,
+}
+
+/// What the pipeline saw and decided on one frame
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DumpRecord {
+    pub frame: u32,
+    /// RFC 3339
+    pub time: String,
+    /// Pixel difference above which a pixel counts as changed
+    pub threshold: f64,
+    pub min_area: u32,
+    pub capture_ms: f32,
+    pub blur_ms: f32,
+    pub diff_ms: f32,
+    pub contours_ms: f32,
+    /// Raw contour areas, before merging
+    pub contour_areas: Vec<f64>,
+    /// Width over height of each contour's box, in the same order
+    #[serde(default)]
+    pub contour_aspects: Vec<f64>,
+    /// Contours left out for their aspect ratio (`--min-aspect`, `--max-aspect`)
+    #[serde(default)]
+    pub aspect_rejected: usize,
+    /// Regions left after merging that met `min_area`
+    pub regions: usize,
+    /// How many of those were merged from several contours (`--merge-gap`)
+    #[serde(default)]
+    pub merged_regions: usize,
+    pub motion: bool,
+    /// Mask images written next to the record
+    pub masks: Vec<String>,
+}
+
+impl DumpRecord {
+    pub fn total_ms(&self) -> f32 {
+        self.capture_ms + self.blur_ms + self.diff_ms + self.contours_ms
+    }
+}
+
+/// Writes every Nth frame's masks and record until the size cap is reached
+pub struct DebugDump {
+    options: DumpOptions,
+    records: std::fs::File,
+    written_bytes: u64,
+    full: bool,
+}
+
+impl DebugDump {
+    pub fn open(options: DumpOptions) -> Result<Self> {
+        std::fs::create_dir_all(&options.dir)
+            .with_context(|| format!("Failed to create {}", options.dir.display()))?;
+        let path = options.dir.join(RECORDS_FILE);
+        let records = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let written_bytes = dir_size(&options.dir);
+        Ok(Self {
+            options,
+            records,
+            written_bytes,
+            full: false,
+        })
+    }
+
+    /// Whether frame number `frame` should be dumped; cheap enough to call
+    /// on every frame
+    pub fn due(&self, frame: u32) -> bool {
+        !self.full && frame.is_multiple_of(self.options.every)
+    }
+
+    /// Write the masks as `frame_<n>_<name>.png` and append the record.
+    /// Stops dumping for good once the size cap is hit.
+    #[cfg(feature = "camera")]
+    pub fn write(&mut self, mut record: DumpRecord, masks: &[(&str, &Mat)]) -> Result<()> {
+        for (name, mask) in masks {
+            let file_name = format!("frame_{:06}_{}.png", record.frame, name);
+            let path = self.options.dir.join(&file_name);
+            imgcodecs::imwrite(&path.to_string_lossy(), *mask, &Vector::new())?;
+            self.written_bytes += std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            record.masks.push(file_name);
+        }
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        self.records.write_all(line.as_bytes())?;
+        self.written_bytes += line.len() as u64;
+        if self.options.sync_motion && record.motion {
+            self.records.sync_data()?;
+        }
+
+        if self.written_bytes >= self.options.max_bytes {
+            self.full = true;
+            eprintln!(
+                "Warning: debug dump in {} reached {} MB, no more frames will be dumped",
+                self.options.dir.display(),
+                self.options.max_bytes / (1024 * 1024)
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Bytes already in `dir`, so a reused directory counts towards the cap
+fn dir_size(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.metadata().ok())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Records in a dump directory, in the order they were written
+pub fn load(dir: &Path) -> Result<Vec<DumpRecord>> {
+    let path = dir.join(RECORDS_FILE);
+    let file =
+        std::fs::File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut records = Vec::new();
+    for (number, line) in std::io::BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records.push(
+            serde_json::from_str(&line)
+                .with_context(|| format!("{}:{}", path.display(), number + 1))?,
+        );
+    }
+    Ok(records)
+}
+
+/// Nearest-rank percentile of `values`, which must be sorted
+pub fn percentile(values: &[f32], p: f64) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let rank = (p / 100.0 * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+/// `debug-report`: frame count, decisions and timing percentiles of a dump
+pub fn report(records: &[DumpRecord]) -> String {
+    let motion = records.iter().filter(|record| record.motion).count();
+    let range = match (records.first(), records.last()) {
+        (Some(first), Some(last)) => format!(" (frames {} to {})", first.frame, last.frame),
+        _ => String::new(),
+    };
+    let mut lines = vec![
+        format!("{} frames dumped{}", records.len(), range),
+        format!(
+            "Decisions: {} motion, {} no motion",
+            motion,
+            records.len() - motion
+        ),
+    ];
+    let merged: usize = records.iter().map(|record| record.merged_regions).sum();
+    if merged > 0 {
+        lines.push(format!(
+            "Merged regions: {} in {} frame(s)",
+            merged,
+            records
+                .iter()
+                .filter(|record| record.merged_regions > 0)
+                .count()
+        ));
+    }
+    let mut aspects: Vec<f32> = records
+        .iter()
+        .flat_map(|record| record.contour_aspects.iter().map(|&aspect| aspect as f32))
+        .collect();
+    if !aspects.is_empty() {
+        aspects.sort_by(f32::total_cmp);
+        let rejected: usize = records.iter().map(|record| record.aspect_rejected).sum();
+        lines.push(format!(
+            "Contour aspect (width/height): p1 {:.2}, p50 {:.2}, p99 {:.2} over {} contours; {} rejected",
+            percentile(&aspects, 1.0),
+            percentile(&aspects, 50.0),
+            percentile(&aspects, 99.0),
+            aspects.len(),
+            rejected
+        ));
+    }
+
+    lines.push(format!(
+        "{:<10} {:>8} {:>8} {:>8} {:>8}",
+        "Stage (ms)", "p50", "p90", "p99", "max"
+    ));
+    type Stage = fn(&DumpRecord) -> f32;
+    let stages: [(&str, Stage); 5] = [
+        ("capture", |r| r.capture_ms),
+        ("blur", |r| r.blur_ms),
+        ("diff", |r| r.diff_ms),
+        ("contours", |r| r.contours_ms),
+        ("total", DumpRecord::total_ms),
+    ];
+    for (name, stage) in stages {
+        let mut values: Vec<f32> = records.iter().map(stage).collect();
+        values.sort_by(f32::total_cmp);
+        lines.push(format!(
+            "{:<10} {:>8.2} {:>8.2} {:>8.2} {:>8.2}",
+            name,
+            percentile(&values, 50.0),
+            percentile(&values, 90.0),
+            percentile(&values, 99.0),
+            values.last().copied().unwrap_or(0.0)
+        ));
+    }
+    lines.join("\n")
+}
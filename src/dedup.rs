@@ -0,0 +1,136 @@
+//! Skipping event snapshots that look like one saved shortly before, such
+//! as a tree moving in the wind all afternoon. Frames are compared by a
+//! difference hash (dHash) of a 9x8 thumbnail, which ignores overall
+//! brightness and survives small shifts.
+
+// Without the `camera` feature no snapshots are saved
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use crate::absence::parse_duration;
+use crate::config::SnapshotConfig;
+use anyhow::{bail, Context, Result};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const THUMB_WIDTH: usize = 9;
+const THUMB_HEIGHT: usize = 8;
+
+/// 64-bit dHash of a grayscale image, `width` bytes per row: each bit says
+/// whether a pixel of the 9x8 thumbnail is brighter than its left neighbour
+pub fn dhash(gray: &[u8], width: usize, height: usize) -> u64 {
+    let thumbnail = thumbnail(gray, width, height);
+    let mut hash = 0;
+    for row in thumbnail.chunks_exact(THUMB_WIDTH) {
+        for pair in row.windows(2) {
+            hash = hash << 1 | u64::from(pair[1] > pair[0]);
+        }
+    }
+    hash
+}
+
+/// Number of bits two hashes differ in, 0 to 64
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Box-average `gray` down to 9x8
+fn thumbnail(gray: &[u8], width: usize, height: usize) -> [f64; THUMB_WIDTH * THUMB_HEIGHT] {
+    let mut cells = [0.0; THUMB_WIDTH * THUMB_HEIGHT];
+    if width == 0 || height == 0 || gray.len() < width * height {
+        return cells;
+    }
+    for (cy, row) in cells.chunks_exact_mut(THUMB_WIDTH).enumerate() {
+        let (y0, y1) = span(cy, THUMB_HEIGHT, height);
+        for (cx, cell) in row.iter_mut().enumerate() {
+            let (x0, x1) = span(cx, THUMB_WIDTH, width);
+            let sum: u64 = (y0..y1)
+                .flat_map(|y| &gray[y * width + x0..y * width + x1])
+                .map(|&pixel| u64::from(pixel))
+                .sum();
+            *cell = sum as f64 / ((y1 - y0) * (x1 - x0)) as f64;
+        }
+    }
+    cells
+}
+
+/// Pixels `start..end` of cell `index` out of `cells` along a side of `size`
+fn span(index: usize, cells: usize, size: usize) -> (usize, usize) {
+    let start = index * size / cells;
+    let end = ((index + 1) * size / cells).max(start + 1).min(size);
+    (start.min(size - 1), end)
+}
+
+struct Saved {
+    at: Instant,
+    hash: u64,
+    event: u64,
+}
+
+/// Hashes of the snapshots saved within `window`. Only saved snapshots are
+/// remembered, so a scene that keeps moving still gets one per window.
+pub struct SnapshotDedup {
+    /// Snapshots closer than this many bits count as duplicates
+    threshold: u32,
+    window: Duration,
+    recent: VecDeque<Saved>,
+    deduplicated: u64,
+}
+
+impl SnapshotDedup {
+    /// `None` when `dedup_threshold` is 0 (off)
+    pub fn from_config(config: &SnapshotConfig) -> Result<Option<Self>> {
+        if config.dedup_threshold > 64 {
+            bail!(
+                "dedup_threshold must be at most 64 bits, got {}",
+                config.dedup_threshold
+            );
+        }
+        let window = parse_duration(&config.dedup_window)
+            .with_context(|| format!("invalid dedup_window \"{}\"", config.dedup_window))?;
+        if config.dedup_threshold == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Self::new(config.dedup_threshold, window)))
+    }
+
+    pub fn new(threshold: u32, window: Duration) -> Self {
+        Self {
+            threshold,
+            window,
+            recent: VecDeque::new(),
+            deduplicated: 0,
+        }
+    }
+
+    /// The event whose snapshot the one for `event` duplicates. When there is
+    /// none, `hash` is remembered as saved.
+    pub fn check(&mut self, now: Instant, hash: u64, event: u64) -> Option<u64> {
+        while self
+            .recent
+            .front()
+            .is_some_and(|saved| now.saturating_duration_since(saved.at) > self.window)
+        {
+            self.recent.pop_front();
+        }
+        let duplicate = self
+            .recent
+            .iter()
+            .rev()
+            .find(|saved| distance(saved.hash, hash) < self.threshold)
+            .map(|saved| saved.event);
+        match duplicate {
+            Some(_) => self.deduplicated += 1,
+            None => self.recent.push_back(Saved {
+                at: now,
+                hash,
+                event,
+            }),
+        }
+        duplicate
+    }
+
+    /// Snapshots skipped so far
+    pub fn deduplicated(&self) -> u64 {
+        self.deduplicated
+    }
+}
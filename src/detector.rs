@@ -0,0 +1,2871 @@
+use crate::error::DetectorError;
+use crate::source::EventSnapshot;
+use crate::{
+    alerts, annotate, config, crash, dbus, debug_dump, dedup, events, gui, incident, intrusion,
+    journal, jpeg, mask, mask_editor, monitor, naming, normalize, observe, overlay, phase, privacy,
+    probe, pyramid, quality, reconnect, recording, regions, reposition, rules, run, scale,
+    schedule, selftest, sensitivity, smoothing, source, timestamp, tracker, tuning, upload, Args,
+    BenchArgs,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use opencv::{
+    core::{self, Mat, Vector},
+    imgcodecs, imgproc,
+    prelude::*,
+    videoio::{VideoCapture, CAP_ANY, CAP_PROP_POS_FRAMES, CAP_PROP_POS_MSEC, CAP_V4L2},
+};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How frames are pulled from the camera(s)
+#[derive(Clone, Debug)]
+pub struct CaptureOptions {
+    pub grab_latest: bool,
+    /// Stream retrieved for differencing (0 is the backend's default stream)
+    pub stream_index: i32,
+    /// Stream retrieved for snapshots, when it differs from the detection stream
+    pub snapshot_stream: Option<i32>,
+    /// Separate device used for differencing, e.g. an IR endpoint; `--device`
+    /// then only provides snapshots
+    pub detect_device: Option<u32>,
+    /// Empty first frames to tolerate while the camera warms up
+    pub initial_retries: u32,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self {
+            grab_latest: false,
+            stream_index: 0,
+            snapshot_stream: None,
+            detect_device: None,
+            initial_retries: probe::INITIAL_FRAME_RETRIES,
+        }
+    }
+}
+
+impl CaptureOptions {
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            grab_latest: args.grab_latest,
+            stream_index: args.stream_index,
+            snapshot_stream: args.snapshot_stream,
+            detect_device: args.detect_device,
+            initial_retries: args.initial_frame_retries,
+        }
+    }
+}
+
+/// Builds a `MotionDetector`. Unset options fall back to the defaults in
+/// `config`, so every caller opens the camera the same way.
+#[derive(Clone, Debug)]
+pub struct DetectorBuilder {
+    device: u32,
+    sensitivity: f64,
+    /// `--sensitivity-curve`
+    sensitivity_curve: sensitivity::SensitivityCurve,
+    min_area: regions::MinArea,
+    resolution: scale::CaptureResolution,
+    /// Detect on a copy shrunk by this factor, from `[detector] process_scale`
+    process_scale: f64,
+    blur_kernel: i32,
+    /// `--open-kernel` and `--open-iterations`
+    opening: (i32, i32),
+    merge_gap: regions::MergeGap,
+    diff_lag: u32,
+    /// `--mask-file`
+    mask_file: Option<PathBuf>,
+    background_interval: Option<Duration>,
+    /// Read this video file instead of opening `device`
+    input_file: Option<PathBuf>,
+    loop_file: bool,
+    /// Generate frames of this size instead of opening `device` (`bench`)
+    synthetic: Option<(u32, u32)>,
+    debug_dump: Option<debug_dump::DumpOptions>,
+    output_dir: PathBuf,
+    capture: CaptureOptions,
+    config: config::Config,
+    /// `--s3-bucket`
+    uploader: Option<upload::Uploader>,
+    /// `--min-cooldown` and `--max-cooldown`
+    cooldowns: (Duration, Duration),
+    /// `--intrusion-secs`
+    intrusion_sustain: Duration,
+    /// Keep the frames around the last event for the GUI's tuning window
+    tuning: bool,
+}
+
+impl Default for DetectorBuilder {
+    fn default() -> Self {
+        Self {
+            device: 0,
+            sensitivity: config::DEFAULT_SENSITIVITY,
+            sensitivity_curve: sensitivity::SensitivityCurve::default(),
+            min_area: regions::MinArea::default(),
+            resolution: scale::CaptureResolution::Auto,
+            process_scale: 1.0,
+            blur_kernel: config::DEFAULT_BLUR_KERNEL,
+            opening: (0, 1),
+            merge_gap: regions::MergeGap::default(),
+            diff_lag: 1,
+            mask_file: None,
+            background_interval: None,
+            input_file: None,
+            loop_file: false,
+            synthetic: None,
+            debug_dump: None,
+            output_dir: PathBuf::from("pics"),
+            capture: CaptureOptions::default(),
+            config: config::Config::default(),
+            uploader: None,
+            cooldowns: (Duration::from_secs(2), Duration::from_secs(2)),
+            intrusion_sustain: intrusion::DEFAULT_SUSTAIN,
+            tuning: false,
+        }
+    }
+}
+
+impl DetectorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything the CLI flags and config file specify
+    pub fn from_args(args: &Args, config: &config::Config) -> Self {
+        Self::new()
+            .config(config)
+            .device(args.device)
+            .sensitivity(args.sensitivity)
+            .min_area(args.min_area)
+            .merge_gap(args.merge_gap)
+            .diff_lag(args.diff_lag)
+            .background_interval(
+                args.background_snapshot_interval
+                    .map(|minutes| Duration::from_secs(minutes * 60)),
+            )
+            .debug_dump(args.debug_dump.clone().map(|dir| debug_dump::DumpOptions {
+                dir,
+                every: args.debug_dump_every,
+                max_bytes: args.debug_dump_max_mb * 1024 * 1024,
+                sync_motion: args.flush_on_event,
+            }))
+            .output_dir(args.output_dir.clone())
+            .capture(CaptureOptions::from_args(args))
+            .capture_resolution(args.capture_resolution)
+            .mask_file(args.mask_file.clone())
+            .intrusion_sustain(Duration::from_secs(args.intrusion_secs))
+    }
+
+    pub fn device(mut self, device: u32) -> Self {
+        self.device = device;
+        self
+    }
+
+    pub fn sensitivity(mut self, sensitivity: f64) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    pub fn min_area(mut self, min_area: regions::MinArea) -> Self {
+        self.min_area = min_area;
+        self
+    }
+
+    /// Request this resolution instead of probing the usual C920 modes
+    pub fn resolution(mut self, width: u32, height: u32) -> Self {
+        self.resolution = scale::CaptureResolution::Size(width, height);
+        self
+    }
+
+    /// `--capture-resolution`; `None` keeps the configured resolution
+    pub fn capture_resolution(mut self, mode: Option<scale::CaptureResolution>) -> Self {
+        if let Some(mode) = mode {
+            self.resolution = mode;
+        }
+        self
+    }
+
+    pub fn blur_kernel(mut self, size: i32) -> Self {
+        self.blur_kernel = size;
+        self
+    }
+
+    /// How `sensitivity` maps to the pixel difference threshold
+    pub fn sensitivity_curve(mut self, curve: sensitivity::SensitivityCurve) -> Self {
+        self.sensitivity_curve = curve;
+        self
+    }
+
+    /// Open the motion mask with a `kernel`-sized square before dilating
+    /// it; a kernel of 0 skips the opening
+    pub fn opening(mut self, kernel: i32, iterations: i32) -> Self {
+        self.opening = (kernel, iterations);
+        self
+    }
+
+    /// Merge contours whose bounding boxes are within this gap
+    pub fn merge_gap(mut self, gap: regions::MergeGap) -> Self {
+        self.merge_gap = gap;
+        self
+    }
+
+    /// Compare each frame with the one this many captures earlier
+    pub fn diff_lag(mut self, frames: u32) -> Self {
+        self.diff_lag = frames;
+        self
+    }
+
+    /// Never count motion under the black pixels of this image
+    pub fn mask_file(mut self, path: Option<PathBuf>) -> Self {
+        self.mask_file = path;
+        self
+    }
+
+    /// Save the reference frame into `background/` this often
+    pub fn background_interval(mut self, interval: Option<Duration>) -> Self {
+        self.background_interval = interval;
+        self
+    }
+
+    /// How long a difference from the empty scene lasts before the
+    /// intrusion alarm goes off
+    pub fn intrusion_sustain(mut self, sustain: Duration) -> Self {
+        self.intrusion_sustain = sustain;
+        self
+    }
+
+    /// Keep the frames around the last event, to be replayed with other
+    /// settings
+    pub fn tuning(mut self, enabled: bool) -> Self {
+        self.tuning = enabled;
+        self
+    }
+
+    /// Process a recorded video instead of the camera
+    pub fn input_file(mut self, path: Option<PathBuf>) -> Self {
+        self.input_file = path;
+        self
+    }
+
+    /// Start the input file over when it ends, instead of stopping
+    pub fn loop_file(mut self, enabled: bool) -> Self {
+        self.loop_file = enabled;
+        self
+    }
+
+    /// Dump intermediate masks and per-frame records for debugging
+    pub fn debug_dump(mut self, options: Option<debug_dump::DumpOptions>) -> Self {
+        self.debug_dump = options;
+        self
+    }
+
+    /// Run on generated frames of `width`x`height` instead of the camera
+    pub fn synthetic(mut self, width: u32, height: u32) -> Self {
+        self.synthetic = Some((width, height));
+        self
+    }
+
+    pub fn output_dir(mut self, dir: PathBuf) -> Self {
+        self.output_dir = dir;
+        self
+    }
+
+    pub fn capture(mut self, options: CaptureOptions) -> Self {
+        self.capture = options;
+        self
+    }
+
+    /// Shortest and longest time between two motion events
+    pub fn cooldowns(mut self, cooldowns: (Duration, Duration)) -> Self {
+        self.cooldowns = cooldowns;
+        self
+    }
+
+    /// Also upload event snapshots, whether or not they're saved locally
+    pub fn uploader(mut self, uploader: Option<upload::Uploader>) -> Self {
+        self.uploader = uploader;
+        self
+    }
+
+    /// Tracking, rules, snapshot and `[detector]` settings from a config file
+    pub fn config(mut self, config: &config::Config) -> Self {
+        if let Some([width, height]) = config.detector.resolution {
+            self = self.resolution(width, height);
+        }
+        self.config = config.clone();
+        self.process_scale = config.detector.process_scale;
+        self.opening = (config.detector.open_kernel, config.detector.open_iterations);
+        self.sensitivity_curve = config.detector.sensitivity_curve;
+        self.blur_kernel(config.detector.blur_kernel)
+    }
+
+    pub fn build(&self) -> Result<MotionDetector> {
+        if !(0.0..=1.0).contains(&self.sensitivity) {
+            return Err(DetectorError::InvalidArguments(format!(
+                "sensitivity must be between 0.0 and 1.0, got {}",
+                self.sensitivity
+            ))
+            .into());
+        }
+        if self.min_area.is_zero() {
+            return Err(DetectorError::InvalidArguments(
+                "min-area must be greater than 0".to_string(),
+            )
+            .into());
+        }
+        if self.diff_lag == 0 {
+            return Err(
+                DetectorError::InvalidArguments("diff-lag must be at least 1".to_string()).into(),
+            );
+        }
+        if self.blur_kernel <= 0 || self.blur_kernel % 2 == 0 {
+            return Err(DetectorError::InvalidArguments(format!(
+                "blur kernel must be a positive odd number, got {}",
+                self.blur_kernel
+            ))
+            .into());
+        }
+
+        let (kernel, iterations) = self.opening;
+        config::opening_problem(kernel, iterations).map_err(DetectorError::InvalidArguments)?;
+
+        let mut detector = MotionDetector::open(self)?;
+        detector.output_dir = self.output_dir.clone();
+        detector.background_interval = self.background_interval;
+        detector.guard = intrusion::IntrusionGuard::new(self.intrusion_sustain);
+        detector.recorder = self.tuning.then(tuning::EventRecorder::new);
+        detector.snapshots = self.config.snapshots.clone();
+        detector.dedup = dedup::SnapshotDedup::from_config(&self.config.snapshots)?;
+        detector.redactor = privacy::Redactor::from_config(&self.config.snapshots);
+        detector.privacy_filter = privacy::PrivacyFilter::from_config(&self.config.snapshots)?;
+        detector.quality = self
+            .config
+            .detector
+            .min_fps
+            .map(|fps| quality::QualityController::new(fps, self.process_scale));
+        jpeg::check_support(&self.config.snapshots);
+        detector.jpeg_params = jpeg::params(&self.config.snapshots);
+        detector.uploader = self.uploader.clone();
+        if detector.snapshots.enabled || detector.uploader.is_some() {
+            detector.pre_event = PreEventBuffer::new(detector.snapshots.pre_event_frames);
+        }
+        detector.set_tracking(&self.config);
+        detector.normalizer = normalize::Normalizer::new(&self.config.detector)?;
+        detector.mask = self
+            .mask_file
+            .as_deref()
+            .map(mask::IgnoreMask::load)
+            .transpose()?;
+        detector.repositioning = reposition::Repositioning::new(self.config.reposition.clone());
+        detector.debug_dump = self
+            .debug_dump
+            .clone()
+            .map(debug_dump::DebugDump::open)
+            .transpose()?;
+        // Stream and secondary device options only make sense for cameras
+        if self.input_file.is_none() && self.synthetic.is_none() {
+            detector.set_capture_options(self.capture.clone())?;
+        }
+        Ok(detector)
+    }
+}
+
+/// A square crossing a flat background, rendered once and replayed in a loop
+struct SyntheticFrames {
+    frames: Vec<Mat>,
+    next: usize,
+}
+
+impl SyntheticFrames {
+    /// Pixels the square moves per frame
+    const STEP: i32 = 8;
+
+    fn new(width: i32, height: i32) -> Result<Self> {
+        let side = (height / 4).max(1);
+        let travel = (width - side).max(1);
+        let mut frames = Vec::new();
+        for x in (0..travel).step_by(Self::STEP as usize) {
+            let mut frame = Mat::new_rows_cols_with_default(
+                height,
+                width,
+                core::CV_8UC3,
+                core::Scalar::all(64.0),
+            )?;
+            imgproc::rectangle(
+                &mut frame,
+                core::Rect::new(x, (height - side) / 2, side, side),
+                core::Scalar::all(224.0),
+                imgproc::FILLED,
+                imgproc::LINE_8,
+                0,
+            )?;
+            frames.push(frame);
+        }
+        Ok(Self { frames, next: 0 })
+    }
+
+    /// A copy of the next frame, as a camera would hand over a new buffer
+    fn next(&mut self) -> Result<Mat> {
+        let frame = self.frames[self.next].try_clone()?;
+        self.next = (self.next + 1) % self.frames.len();
+        Ok(frame)
+    }
+}
+
+/// Grab on `secondary` until it has caught up with the main camera's
+/// timestamp. Backends that report no timestamps (0) are taken as-is.
+fn grab_in_sync(secondary: &mut VideoCapture, target_ms: f64) -> Result<()> {
+    const SYNC_TOLERANCE_MS: f64 = 40.0;
+    const MAX_GRABS: u32 = 5;
+
+    for _ in 0..MAX_GRABS {
+        if !secondary.grab()? {
+            return Err(anyhow::anyhow!(
+                "Failed to capture frame from detection device"
+            ));
+        }
+        let timestamp = secondary.get(CAP_PROP_POS_MSEC)?;
+        if timestamp <= 0.0 || target_ms <= 0.0 || timestamp >= target_ms - SYNC_TOLERANCE_MS {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Color frames of the motion run in progress, so an event's snapshot can
+/// show where the motion first crossed the threshold rather than the frame
+/// that fired the event. Quiet frames end the run; in a run longer than
+/// `capacity` the oldest frame kept is the closest to its start.
+pub struct PreEventBuffer {
+    /// With `--privacy-keep-original`, each frame's unblurred copy; with
+    /// `--annotation-style`, what to draw onto it
+    frames: VecDeque<(Instant, Mat, Option<Mat>, annotate::Marks)>,
+    capacity: usize,
+}
+
+impl PreEventBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(
+        &mut self,
+        at: Instant,
+        frame: &Mat,
+        original: Option<&Mat>,
+        marks: &annotate::Marks,
+        motion: bool,
+    ) -> Result<()> {
+        if !motion {
+            self.frames.clear();
+            return Ok(());
+        }
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        let original = original.map(|original| original.try_clone()).transpose()?;
+        self.frames
+            .push_back((at, frame.try_clone()?, original, marks.clone()));
+        Ok(())
+    }
+
+    /// The earliest kept frame of the current run, when it was captured,
+    /// its unblurred copy and its marks
+    pub fn trigger(&self) -> Option<(Instant, &Mat, Option<&Mat>, &annotate::Marks)> {
+        self.frames
+            .front()
+            .map(|(at, frame, original, marks)| (*at, frame, original.as_ref(), marks))
+    }
+
+    /// Start over after an event, so the next one indexes from here
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+/// Pixel difference (0-255) above which a pixel counts as changed, at the
+/// default sensitivity
+const DIFF_THRESHOLD: f64 = 25.0;
+
+/// Recent blurred grayscale frames, so each frame can be compared with the
+/// one `lag` captures earlier. A lag above 1 adds up slow changes (a door
+/// opening slowly) that barely differ from one frame to the next.
+///
+/// The difference images are written into buffers kept between frames, and
+/// the frame that drops out of the history is handed back by `take_buffer`
+/// for the next one, so a steady stream allocates nothing here.
+pub struct FrameHistory {
+    frames: VecDeque<Mat>,
+    /// The frame last dropped from `frames`
+    spare: Option<Mat>,
+    /// The absolute difference of the last comparison
+    diff: Mat,
+    /// A thresholded mask handed back by `recycle`
+    binary: Option<Mat>,
+    lag: usize,
+    /// Pixel difference above which a pixel counts as changed
+    threshold: f64,
+    /// The empty scene in intrusion mode, compared with instead of `frames`
+    fixed: Option<Mat>,
+    /// The next frame becomes `fixed`
+    fix_next: bool,
+}
+
+impl FrameHistory {
+    pub fn new(lag: usize) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(lag + 1),
+            spare: None,
+            diff: Mat::default(),
+            binary: None,
+            lag: lag.max(1),
+            threshold: DIFF_THRESHOLD,
+            fixed: None,
+            fix_next: false,
+        }
+    }
+
+    /// Compare every frame from the next one on with that frame, until
+    /// `release_reference`
+    pub fn fix_next(&mut self) {
+        self.fix_next = true;
+        self.fixed = None;
+    }
+
+    /// Back to comparing with the frame `lag` captures earlier
+    pub fn release_reference(&mut self) {
+        self.fixed = None;
+        self.fix_next = false;
+    }
+
+    /// A fixed reference is in use
+    pub fn is_fixed(&self) -> bool {
+        self.fixed.is_some()
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Frames between the two compared
+    pub fn lag(&self) -> usize {
+        self.lag
+    }
+
+    /// Frames still to come before one can be compared
+    pub fn frames_left(&self) -> usize {
+        if self.fixed.is_some() || self.fix_next {
+            0
+        } else {
+            self.lag.saturating_sub(self.frames.len())
+        }
+    }
+
+    pub fn set_threshold(&mut self, threshold: f64) {
+        self.threshold = threshold;
+    }
+
+    /// Binary mask of the pixels that changed since `lag` frames ago, or
+    /// `None` while the history fills up (again, after a frame size change)
+    pub fn diff(&mut self, frame: Mat) -> Result<Option<Mat>> {
+        self.compare(frame, None)
+    }
+
+    /// A buffer to write the next frame into: the last frame dropped from
+    /// the history, or an empty one
+    pub fn take_buffer(&mut self) -> Mat {
+        self.spare.take().unwrap_or_default()
+    }
+
+    /// Hand back a mask `compare` returned, to be written over next time
+    pub fn recycle(&mut self, binary: Mat) {
+        self.binary = Some(binary);
+    }
+
+    /// `diff`, also copying the raw difference image to `raw`
+    pub fn compare(&mut self, frame: Mat, raw: Option<&mut Mat>) -> Result<Option<Mat>> {
+        if let Some(last) = self.frames.back() {
+            if last.size()? != frame.size()? {
+                self.frames.clear();
+            }
+        }
+
+        if std::mem::take(&mut self.fix_next) {
+            self.fixed = Some(frame.try_clone()?);
+        }
+        // Nothing to compare with while the frame size differs from the
+        // fixed reference's; the rolling history refills meanwhile
+        let fixed = match &self.fixed {
+            Some(fixed) if fixed.size()? == frame.size()? => Some(fixed),
+            _ => None,
+        };
+
+        let mut thresh = None;
+        if fixed.is_some() || (self.fixed.is_none() && self.frames.len() >= self.lag) {
+            let reference = fixed.unwrap_or(&self.frames[self.frames.len() - self.lag]);
+            core::absdiff(&frame, reference, &mut self.diff)?;
+            let mut binary = self.binary.take().unwrap_or_default();
+            imgproc::threshold(
+                &self.diff,
+                &mut binary,
+                self.threshold,
+                255.0,
+                imgproc::THRESH_BINARY,
+            )?;
+            thresh = Some(binary);
+            if let Some(raw) = raw {
+                self.diff.copy_to(raw)?;
+            }
+        }
+
+        self.frames.push_back(frame);
+        while self.frames.len() > self.lag {
+            self.spare = self.frames.pop_front();
+        }
+        Ok(thresh)
+    }
+
+    /// The frame the next capture will be compared with
+    pub fn reference(&self) -> Option<&Mat> {
+        self.fixed.as_ref().or(self.frames.front())
+    }
+
+    /// Start over, e.g. when a looped video jumps back to its beginning
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+pub struct MotionDetector {
+    camera: VideoCapture,
+    /// Reused by every frame's grayscale conversion and mask clean-up
+    buffers: FrameBuffers,
+    sensitivity: f64,
+    /// Maps `sensitivity` to the threshold `history` applies
+    sensitivity_curve: sensitivity::SensitivityCurve,
+    /// `--min-area`, resolved against each frame's size
+    min_area: regions::MinArea,
+    blur_kernel: i32,
+    open_kernel: i32,
+    open_iterations: i32,
+    merge_gap: regions::MergeGap,
+    /// Contours above the noise floor in the last frame, and the regions they merged into
+    contours_found: usize,
+    regions_found: usize,
+    /// Regions in the last frame that met min_area, boxed on the preview
+    motion_regions: Vec<regions::Region>,
+    /// What `--annotation-style` draws onto the last frame's snapshot
+    marks: annotate::Marks,
+    /// Total area of the motion regions in the last frame, scores key frames
+    motion_area: f64,
+    /// Largest region's share of the frame, against min_area's share
+    score: regions::MotionScore,
+    output_dir: PathBuf,
+    snapshots: config::SnapshotConfig,
+    dedup: Option<dedup::SnapshotDedup>,
+    /// Privacy regions, obscured in every frame handed out
+    redactor: privacy::Redactor,
+    /// `--privacy-blur`, applied after the privacy regions
+    privacy_filter: Option<privacy::PrivacyFilter>,
+    /// The last frame handed out before `privacy_filter`, kept only with
+    /// `--privacy-keep-original`
+    original: Option<Mat>,
+    /// `imencode` parameters for every image written
+    jpeg_params: Vector<i32>,
+    uploader: Option<upload::Uploader>,
+    /// `--observe`: which side effects are skipped, and the notes on them
+    pub(crate) observer: observe::Observer,
+    history: FrameHistory,
+    /// Intrusion mode; its empty scene is the fixed reference in `history`
+    guard: intrusion::IntrusionGuard,
+    /// Set on the frame the intrusion alarm went off
+    intrusion_alarm: bool,
+    /// The blurred frames around the last event, with `DetectorBuilder::tuning`
+    recorder: Option<tuning::EventRecorder<Mat>>,
+    /// A finished recording, until `take_recording`
+    recording: Option<tuning::Recording<Mat>>,
+    background_interval: Option<Duration>,
+    last_background: Instant,
+    frame_count: u32,
+    motion_count: u32,
+    last_motion_time: Option<Instant>,
+    last_fps_update: Instant,
+    fps_frames: u32,
+    current_fps: f32,
+    /// `--min-fps`: lowers `process_scale` while frames come too slowly
+    quality: Option<quality::QualityController>,
+    quality_change: Option<quality::Adjustment>,
+    tracker: Option<tracker::CentroidTracker>,
+    /// Count one event per new track instead of debouncing motion frames
+    track_events: bool,
+    new_tracks: Vec<u64>,
+    events: events::EventSource,
+    /// What the last `poll` saw
+    motion_detected: bool,
+    frame: Mat,
+    rules: Option<rules::RuleEngine>,
+    rule_hits: Vec<rules::RuleHit>,
+    timings: monitor::StageTimings,
+    capture: CaptureOptions,
+    secondary: Option<VideoCapture>,
+    frames_skipped: u32,
+    total_frames_skipped: u64,
+    capture_cycles: u64,
+    /// Set when reading a video file rather than a camera
+    input_file: Option<PathBuf>,
+    loop_file: bool,
+    /// The input file ended and isn't looped
+    file_finished: bool,
+    /// Set when running on generated frames (`bench`)
+    synthetic: Option<SyntheticFrames>,
+    /// Time spent per stage over the whole run
+    stage_totals: monitor::StageTotals,
+    debug_dump: Option<debug_dump::DebugDump>,
+    normalizer: Option<normalize::Normalizer>,
+    mask: Option<mask::IgnoreMask>,
+    repositioning: reposition::Repositioning,
+    /// Set on the frame repositioning started or ended
+    reposition_transition: Option<reposition::Transition>,
+    /// Frames skipped after a reconnect
+    settle: reconnect::Settle,
+    pre_event: PreEventBuffer,
+    /// Where the largest motion region has been, for `{direction}`
+    trail: naming::MotionTrail,
+    /// `[[zones]]`, for `{zone}`
+    zones: Vec<rules::Zone>,
+    process_scale: f64,
+    /// Mask levels contours are found at, from `[detector] pyramid_levels`
+    pyramid_levels: u32,
+    /// `--min-aspect` and `--max-aspect`
+    aspect: regions::AspectRange,
+    /// Contours in the last frame dropped for their aspect ratio
+    aspect_rejected: usize,
+    /// `--smoothing`: the score that decides motion, averaged over time
+    smoother: smoothing::MotionSmoother,
+    /// Sizes of the last captured frame and of its processed copy
+    frame_scale: scale::FrameScale,
+    /// When the last frame was captured
+    frame_time: timestamp::FrameTime,
+}
+
+impl MotionDetector {
+    fn open(options: &DetectorBuilder) -> Result<Self> {
+        if let Some(path) = &options.input_file {
+            return Self::open_file(path, options);
+        }
+        if let Some((width, height)) = options.synthetic {
+            return Self::open_synthetic(width, height, options);
+        }
+
+        let (camera, frame) = Self::open_camera(options)?;
+        Self::with_first_frame(camera, &frame, options)
+    }
+
+    /// Open and set up the camera device, returning it with a first frame
+    /// that has the size it reports
+    fn open_camera(options: &DetectorBuilder) -> Result<(VideoCapture, Mat)> {
+        let device = options.device;
+        // Try V4L2 first (better for Logitech on Linux)
+        let mut camera = match VideoCapture::new(device as i32, CAP_V4L2) {
+            Ok(cam) => cam,
+            Err(_) => {
+                println!("V4L2 failed, falling back to default backend");
+                VideoCapture::new(device as i32, CAP_ANY)
+                    .map_err(|e| DetectorError::CameraOpen(e.to_string()))?
+            }
+        };
+
+        if !camera.is_opened()? {
+            return Err(DetectorError::CameraOpen(format!(
+                "Failed to open camera device {} - check if device exists and user has permissions",
+                device
+            ))
+            .into());
+        }
+
+        // Enhanced Logitech C920 initialization
+        println!("Initializing Logitech C920 camera...");
+
+        // First, set the backend and basic properties
+        camera.set(opencv::videoio::CAP_PROP_FOURCC, 1196444237.0)?; // MJPG
+        camera.set(opencv::videoio::CAP_PROP_FPS, 30.0)?;
+
+        // Try multiple resolution settings optimized for Logitech C920
+        let mut resolutions = vec![
+            (1920, 1080, "1080p HD"),
+            (1280, 720, "720p HD"),
+            (960, 540, "540p"),
+            (640, 480, "480p SD"),
+        ];
+        match options.resolution {
+            scale::CaptureResolution::Auto => {}
+            // Drivers clamp an oversized request to their largest mode; the
+            // usual modes remain as a fallback
+            scale::CaptureResolution::Native => resolutions.insert(0, (7680, 4320, "native")),
+            scale::CaptureResolution::Size(width, height) => {
+                resolutions = vec![(width, height, "requested")]
+            }
+        }
+
+        let mut actual_resolution = (640, 480);
+        let mut camera_working = false;
+
+        for (width, height, desc) in resolutions {
+            println!("Trying Logitech C920 at {}x{} ({})...", width, height, desc);
+
+            // Set resolution
+            camera.set(opencv::videoio::CAP_PROP_FRAME_WIDTH, width as f64)?;
+            camera.set(opencv::videoio::CAP_PROP_FRAME_HEIGHT, height as f64)?;
+
+            // Reset format after resolution change
+            camera.set(opencv::videoio::CAP_PROP_FOURCC, 1196444237.0)?; // MJPG
+
+            // Give camera time to adjust
+            std::thread::sleep(Duration::from_millis(1000));
+
+            // Test frame capture multiple times
+            let mut success_count = 0;
+            for _ in 0..5 {
+                let mut test_frame = Mat::default();
+                if camera.read(&mut test_frame)? && !test_frame.empty() {
+                    success_count += 1;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+
+            if success_count >= 3 {
+                let actual_width = camera.get(opencv::videoio::CAP_PROP_FRAME_WIDTH)? as i32;
+                let actual_height = camera.get(opencv::videoio::CAP_PROP_FRAME_HEIGHT)? as i32;
+
+                println!(
+                    "✓ Logitech C920 working at {}x{} (success rate: {}/5)",
+                    actual_width, actual_height, success_count
+                );
+
+                // Update actual resolution based on what the camera reports
+                actual_resolution = (actual_width as u32, actual_height as u32);
+                camera_working = true;
+                break;
+            } else {
+                println!(
+                    "✗ {}x{} failed (success rate: {}/5)",
+                    width, height, success_count
+                );
+            }
+        }
+
+        if !camera_working {
+            return Err(DetectorError::CameraOpen(
+                "Failed to get stable frame capture from Logitech C920".to_string(),
+            )
+            .into());
+        }
+
+        // Final camera parameters
+        let final_width = camera.get(opencv::videoio::CAP_PROP_FRAME_WIDTH)? as i32;
+        let final_height = camera.get(opencv::videoio::CAP_PROP_FRAME_HEIGHT)? as i32;
+        let final_fps = camera.get(opencv::videoio::CAP_PROP_FPS)?;
+
+        println!("Logitech C920 initialized successfully:");
+        println!("  Resolution: {}x{}", final_width, final_height);
+        println!("  Target FPS: {}", final_fps);
+        println!("  Format: MJPG");
+
+        // Wait for camera to stabilize completely
+        std::thread::sleep(Duration::from_millis(2000));
+
+        // Capture and validate initial frame; some cameras need a few reads
+        // before the first one has an image
+        let retries = options.capture.initial_retries;
+        let frame = probe::retry_read(
+            retries,
+            Duration::from_millis(200),
+            || -> Result<Option<Mat>> {
+                let mut frame = Mat::default();
+                let valid = camera.read(&mut frame)?
+                    && !frame.empty()
+                    && frame.cols() == final_width
+                    && frame.rows() == final_height;
+                Ok(valid.then_some(frame))
+            },
+            |retry| {
+                println!(
+                    "Initial frame empty or incomplete, retrying ({}/{})...",
+                    retry, retries
+                )
+            },
+        )?
+        .ok_or_else(|| {
+            DetectorError::CameraOpen(format!(
+                "Failed to capture valid initial frame after {} attempts (see --initial-frame-retries)",
+                retries + 1
+            ))
+        })?;
+
+        Ok((camera, frame))
+    }
+
+    /// Read a recorded video; none of the camera setup applies
+    fn open_file(path: &std::path::Path, options: &DetectorBuilder) -> Result<Self> {
+        let mut file = VideoCapture::from_file(&path.to_string_lossy(), CAP_ANY)
+            .map_err(|e| DetectorError::CameraOpen(format!("{}: {}", path.display(), e)))?;
+        let mut frame = Mat::default();
+        if !file.is_opened()? || !file.read(&mut frame)? || frame.empty() {
+            return Err(DetectorError::CameraOpen(format!(
+                "Failed to read video file {}",
+                path.display()
+            ))
+            .into());
+        }
+        println!("Reading video file {}", path.display());
+
+        let mut detector = Self::with_first_frame(file, &frame, options)?;
+        detector.input_file = Some(path.to_path_buf());
+        detector.loop_file = options.loop_file;
+        Ok(detector)
+    }
+
+    /// Generated frames held in memory, so no device or file I/O is involved
+    fn open_synthetic(width: u32, height: u32, options: &DetectorBuilder) -> Result<Self> {
+        let mut frames = SyntheticFrames::new(width as i32, height as i32)?;
+        let frame = frames.next()?;
+        let mut detector = Self::with_first_frame(VideoCapture::default()?, &frame, options)?;
+        detector.synthetic = Some(frames);
+        Ok(detector)
+    }
+
+    fn with_first_frame(
+        camera: VideoCapture,
+        frame: &Mat,
+        options: &DetectorBuilder,
+    ) -> Result<Self> {
+        // Convert to grayscale and blur for initial frame to match detection format
+        let frame_scale =
+            scale::FrameScale::new((frame.cols(), frame.rows()), options.process_scale);
+        let mut buffers = FrameBuffers::default();
+        let gray = buffers.grayscale(frame, &frame_scale)?;
+        let mut blurred = Mat::default();
+        imgproc::gaussian_blur(
+            gray,
+            &mut blurred,
+            opencv::core::Size::new(options.blur_kernel, options.blur_kernel),
+            0.0,
+            0.0,
+            opencv::core::BORDER_DEFAULT,
+        )?;
+
+        let sensitivity_curve = options.sensitivity_curve;
+        let mut history = FrameHistory::new(options.diff_lag as usize);
+        history.set_threshold(sensitivity_curve.threshold(options.sensitivity));
+        history.diff(blurred)?;
+
+        Ok(Self {
+            camera,
+            buffers,
+            sensitivity: options.sensitivity,
+            sensitivity_curve,
+            min_area: options.min_area,
+            blur_kernel: options.blur_kernel,
+            open_kernel: options.opening.0,
+            open_iterations: options.opening.1,
+            merge_gap: options.merge_gap,
+            contours_found: 0,
+            regions_found: 0,
+            motion_regions: Vec::new(),
+            marks: annotate::Marks::default(),
+            motion_area: 0.0,
+            score: regions::MotionScore::default(),
+            output_dir: PathBuf::from("pics"),
+            snapshots: config::SnapshotConfig::default(),
+            dedup: None,
+            redactor: privacy::Redactor::default(),
+            privacy_filter: None,
+            original: None,
+            jpeg_params: Vector::new(),
+            uploader: None,
+            observer: observe::Observer::default(),
+            history,
+            guard: intrusion::IntrusionGuard::default(),
+            intrusion_alarm: false,
+            recorder: None,
+            recording: None,
+            background_interval: None,
+            last_background: Instant::now(),
+            frame_count: 0,
+            motion_count: 0,
+            last_motion_time: None,
+            last_fps_update: Instant::now(),
+            fps_frames: 0,
+            current_fps: 0.0,
+            quality: None,
+            quality_change: None,
+            tracker: None,
+            track_events: false,
+            new_tracks: Vec::new(),
+            events: events::EventSource::new(events::Debouncer::adaptive(
+                options.cooldowns.0,
+                options.cooldowns.1,
+                Instant::now(),
+            )),
+            motion_detected: false,
+            frame: Mat::default(),
+            rules: None,
+            rule_hits: Vec::new(),
+            timings: monitor::StageTimings::default(),
+            capture: CaptureOptions::default(),
+            secondary: None,
+            frames_skipped: 0,
+            total_frames_skipped: 0,
+            capture_cycles: 0,
+            input_file: None,
+            loop_file: false,
+            file_finished: false,
+            synthetic: None,
+            stage_totals: monitor::StageTotals::default(),
+            debug_dump: None,
+            normalizer: None,
+            mask: None,
+            repositioning: reposition::Repositioning::new(Default::default()),
+            reposition_transition: None,
+            settle: reconnect::Settle::default(),
+            pre_event: PreEventBuffer::new(0),
+            trail: naming::MotionTrail::new(),
+            zones: Vec::new(),
+            process_scale: options.process_scale,
+            pyramid_levels: options.config.detector.pyramid_levels,
+            smoother: smoothing::MotionSmoother::new(Duration::from_secs_f64(
+                options.config.detector.smoothing_secs,
+            )),
+            aspect: regions::AspectRange {
+                min: options.config.detector.min_aspect,
+                max: options.config.detector.max_aspect,
+            },
+            aspect_rejected: 0,
+            frame_scale,
+            frame_time: timestamp::FrameTime::processed(&timestamp::Clocks::now()),
+        })
+    }
+
+    /// Returns whether there was motion and the color frame it was measured
+    /// on. Event snapshots must save this frame, not a later read.
+    fn detect_motion(&mut self) -> Result<(bool, Mat)> {
+        self.reposition_transition = None;
+        self.quality_change = None;
+        let capture_start = Instant::now();
+        let (current_frame, snapshot_frame) = self.capture_frames()?;
+
+        if current_frame.empty() {
+            return Ok((false, Mat::default()));
+        }
+
+        // A reconnected camera's first frames are shown but neither compared
+        // nor kept, the next one becomes the new background
+        if self.settle.skip() {
+            self.marks = annotate::Marks::default();
+            let frame = self.redact(snapshot_frame.unwrap_or(current_frame), false)?;
+            return Ok((false, frame));
+        }
+
+        // Convert to grayscale, shrunk with --process-scale
+        let blur_start = Instant::now();
+        self.frame_scale = scale::FrameScale::new(
+            (current_frame.cols(), current_frame.rows()),
+            self.process_scale,
+        );
+        let gray = self.buffers.grayscale(&current_frame, &self.frame_scale)?;
+        let normalized = match self.normalizer.as_mut() {
+            Some(normalizer) => Some(normalizer.apply(gray)?),
+            None => None,
+        };
+        let gray = normalized.as_ref().unwrap_or(gray);
+
+        // Apply Gaussian blur to reduce noise, into the frame the history
+        // dropped last
+        let mut blurred = self.history.take_buffer();
+        imgproc::gaussian_blur(
+            gray,
+            &mut blurred,
+            opencv::core::Size::new(self.blur_kernel, self.blur_kernel),
+            0.0,
+            0.0,
+            opencv::core::BORDER_DEFAULT,
+        )?;
+
+        // Compare against the frame --diff-lag captures ago. Nothing to compare
+        // until the history fills, e.g. when the detection stream differs in
+        // size from the frame used at startup
+        let diff_start = Instant::now();
+        // Only dumped frames keep the raw difference image
+        let dump_due = self
+            .debug_dump
+            .as_ref()
+            .is_some_and(|dump| dump.due(self.frame_count + 1));
+        let mut raw_diff = dump_due.then(Mat::default);
+        self.intrusion_alarm = false;
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Some(recording) = recorder.push(Instant::now(), blurred.try_clone()?) {
+                self.recording = Some(recording);
+            }
+        }
+        let compared = self.history.compare(blurred, raw_diff.as_mut())?;
+        if self.history.is_fixed() {
+            self.guard.reference_taken();
+        }
+        let Some(mut thresh) = compared else {
+            self.marks = annotate::Marks::default();
+            let frame = self.redact(snapshot_frame.unwrap_or(current_frame), false)?;
+            return Ok((false, frame));
+        };
+        if let Some(mask) = self.mask.as_mut() {
+            mask.apply_to(&mut thresh)?;
+        }
+
+        // While the camera is being moved nothing counts as motion
+        let changed = core::count_non_zero(&thresh)? as f64 / thresh.total().max(1) as f64;
+        self.reposition_transition = self.repositioning.update(Instant::now(), changed);
+        if let Some(reposition::Transition::Ended(_)) = self.reposition_transition {
+            self.reset_reference();
+        }
+        let paused = self.repositioning.is_active() || self.reposition_transition.is_some();
+
+        let is_opened = clean_mask_into(
+            &thresh,
+            self.open_kernel,
+            self.open_iterations,
+            &mut self.buffers.opened,
+            &mut self.buffers.dilated,
+        )?;
+        let opened = is_opened.then_some(&self.buffers.opened);
+        let dilated = &self.buffers.dilated;
+
+        // Find contours
+        let contours_start = Instant::now();
+        let found = contour_regions(
+            &thresh,
+            dilated,
+            self.pyramid_levels,
+            self.open_kernel,
+            self.open_iterations,
+        )?;
+
+        // Merge contours that split one object, then keep the regions that
+        // meet the minimum area requirement. Regions are in capture pixels
+        // from here on, like min_area and the zones.
+        let found: Vec<regions::Region> = found
+            .iter()
+            .map(|region| self.frame_scale.region_to_capture(region))
+            .collect();
+        // Dumps list every contour, so the aspect bounds can be chosen
+        // from what was rejected too
+        let (contour_areas, contour_aspects): (Vec<f64>, Vec<f64>) = if dump_due {
+            found
+                .iter()
+                .map(|region| (region.area, region.aspect()))
+                .unzip()
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let before = found.len();
+        let found: Vec<regions::Region> = found
+            .into_iter()
+            .filter(|region| self.aspect.contains(region.aspect()))
+            .collect();
+        self.aspect_rejected = before - found.len();
+        self.contours_found = found.len();
+        let (width, height) = self.frame_scale.capture;
+        let merged = regions::merge_regions(found, self.merge_gap.pixels(width as u32));
+        let min_area = self.min_area_pixels();
+        self.score = regions::MotionScore::new(&merged, min_area, width as f64 * height as f64);
+        // Smoothed by real time, so a flicker weighs the same at any frame rate
+        let raw_score = if paused { 0.0 } else { self.score.score };
+        self.score.score = self.smoother.update(Instant::now(), raw_score);
+        self.motion_regions = merged
+            .into_iter()
+            .filter(|region| !paused && region.area > min_area as f64)
+            .collect();
+        self.marks = self.marks_of(dilated)?;
+        let detections: Vec<tracker::Detection> = self
+            .motion_regions
+            .iter()
+            .map(|region| region.detection())
+            .collect();
+        self.regions_found = detections.len();
+        self.motion_area = detections.iter().map(|detection| detection.area).sum();
+        let motion_detected = !paused && self.score.triggered();
+        self.intrusion_alarm = self.guard.update(Instant::now(), motion_detected);
+        if let Some(largest) = detections.iter().max_by(|a, b| a.area.total_cmp(&b.area)) {
+            self.trail.push(Instant::now(), largest.centroid);
+        }
+
+        // Associate contours with tracked objects and check zone transitions
+        self.new_tracks.clear();
+        self.rule_hits.clear();
+        if let Some(ref mut tracker) = self.tracker {
+            self.new_tracks = tracker.update(&detections);
+            if let Some(ref mut rules) = self.rules {
+                self.rule_hits =
+                    rules.evaluate(Instant::now(), &tracker.visible(), &tracker.active_ids());
+            }
+        }
+
+        let stages = (
+            blur_start - capture_start,
+            diff_start - blur_start,
+            contours_start - diff_start,
+            contours_start.elapsed(),
+        );
+        self.timings.update(stages.0, stages.1, stages.2, stages.3);
+        self.stage_totals
+            .add(stages.0, stages.1, stages.2, stages.3);
+
+        if let Some(dump) = self.debug_dump.as_mut().filter(|_| dump_due) {
+            let ms = |stage: Duration| stage.as_secs_f32() * 1000.0;
+            let record = debug_dump::DumpRecord {
+                frame: self.frame_count + 1,
+                time: self.frame_time.wall.to_rfc3339(),
+                threshold: self.history.threshold(),
+                min_area: self.min_area_pixels(),
+                capture_ms: ms(stages.0),
+                blur_ms: ms(stages.1),
+                diff_ms: ms(stages.2),
+                contours_ms: ms(stages.3),
+                contour_areas,
+                contour_aspects,
+                aspect_rejected: self.aspect_rejected,
+                regions: self.regions_found,
+                merged_regions: self.motion_regions.iter().filter(|r| r.is_merged()).count(),
+                motion: motion_detected,
+                masks: Vec::new(),
+            };
+            let mut masks = vec![("thresh", &thresh)];
+            if let Some(opened) = opened {
+                masks.push(("opened", opened));
+            }
+            masks.push(("dilated", dilated));
+            if let Some(diff) = &raw_diff {
+                masks.insert(0, ("diff", diff));
+            }
+            if let Err(e) = dump.write(record, &masks) {
+                eprintln!("Warning: debug dump failed: {:#}", e);
+            }
+        }
+        self.history.recycle(thresh);
+
+        self.frame_count += 1;
+
+        // Update FPS calculation
+        self.fps_frames += 1;
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_fps_update);
+        if elapsed >= Duration::from_secs(1) {
+            self.current_fps = self.fps_frames as f32 / elapsed.as_secs_f32();
+            self.fps_frames = 0;
+            self.last_fps_update = now;
+            // Takes effect from the next frame; the history refills at the new size
+            if let Some(change) = self
+                .quality
+                .as_mut()
+                .and_then(|quality| quality.update(now, self.current_fps))
+            {
+                self.process_scale = change.to;
+                self.quality_change = Some(change);
+            }
+        }
+
+        // Update motion count and time, as of the frame's capture
+        let captured = self.frame_time.captured;
+        if motion_detected {
+            self.motion_count += 1;
+            self.last_motion_time = Some(captured);
+        }
+
+        let frame = self.redact(snapshot_frame.unwrap_or(current_frame), true)?;
+        self.pre_event.push(
+            captured,
+            &frame,
+            self.original.as_ref(),
+            &self.marks,
+            motion_detected,
+        )?;
+        Ok((motion_detected, frame))
+    }
+
+    /// Grab the next frame set and retrieve the detection frame, plus the
+    /// snapshot frame when it comes from another stream or device
+    fn capture_frames(&mut self) -> Result<(Mat, Option<Mat>)> {
+        if let Some(frames) = self.synthetic.as_mut() {
+            self.frame_time = timestamp::FrameTime::processed(&timestamp::Clocks::now());
+            return Ok((frames.next()?, None));
+        }
+        if !self.grab_primary()? {
+            let Some(path) = self.input_file.clone() else {
+                return Err(anyhow::anyhow!("Failed to capture frame"));
+            };
+            if !self.loop_file {
+                self.file_finished = true;
+                return Err(anyhow::anyhow!("Reached the end of {}", path.display()));
+            }
+            self.rewind()?;
+            if !self.grab_primary()? {
+                return Err(anyhow::anyhow!("Failed to rewind {}", path.display()));
+            }
+        }
+
+        // The driver's timestamp for cameras, the position for files
+        let reported_ms = self.camera.get(CAP_PROP_POS_MSEC)?;
+        let clocks = timestamp::Clocks::now();
+        self.frame_time = match self.input_file {
+            Some(_) => timestamp::FrameTime::in_file(reported_ms, &clocks),
+            None => timestamp::FrameTime::from_device(reported_ms, &clocks),
+        };
+
+        let mut detection = Mat::default();
+        let mut snapshot = None;
+        match self.secondary {
+            Some(ref mut secondary) => {
+                grab_in_sync(secondary, reported_ms)?;
+                secondary.retrieve(&mut detection, self.capture.stream_index)?;
+
+                let mut color = Mat::default();
+                self.camera
+                    .retrieve(&mut color, self.capture.snapshot_stream.unwrap_or(0))?;
+                snapshot = Some(color);
+            }
+            None => {
+                self.camera
+                    .retrieve(&mut detection, self.capture.stream_index)?;
+                if let Some(stream) = self.capture.snapshot_stream {
+                    let mut color = Mat::default();
+                    self.camera.retrieve(&mut color, stream)?;
+                    snapshot = Some(color);
+                }
+            }
+        }
+        Ok((detection, snapshot))
+    }
+
+    /// Go back to the start of the input file
+    fn rewind(&mut self) -> Result<()> {
+        self.camera.set(CAP_PROP_POS_FRAMES, 0.0)?;
+        // The jump back to the first frame isn't motion
+        self.history.clear();
+        self.file_finished = false;
+        Ok(())
+    }
+
+    /// Grab on the main camera. With `grab_latest`, frames already buffered
+    /// by the driver are grabbed and discarded so only the freshest is decoded.
+    fn grab_primary(&mut self) -> Result<bool> {
+        if !self.capture.grab_latest {
+            return Ok(self.camera.grab()?);
+        }
+
+        // Buffered frames come back almost instantly; a grab that has to wait
+        // for the sensor is the newest frame available
+        const FRESH_GRAB: Duration = Duration::from_millis(5);
+        const MAX_SKIPPED: u32 = 30;
+
+        let mut skipped = 0;
+        loop {
+            let grab_start = Instant::now();
+            if !self.camera.grab()? {
+                return Ok(false);
+            }
+            if grab_start.elapsed() >= FRESH_GRAB || skipped >= MAX_SKIPPED {
+                break;
+            }
+            skipped += 1;
+        }
+
+        self.frames_skipped = skipped;
+        self.total_frames_skipped += skipped as u64;
+        self.capture_cycles += 1;
+        Ok(true)
+    }
+
+    /// Apply capture options, opening the separate detection device if one is set
+    fn set_capture_options(&mut self, options: CaptureOptions) -> Result<()> {
+        self.secondary = match options.detect_device {
+            Some(device) => {
+                let camera = VideoCapture::new(device as i32, CAP_ANY)?;
+                if !camera.is_opened()? {
+                    return Err(DetectorError::CameraOpen(format!(
+                        "Failed to open detection device {}",
+                        device
+                    ))
+                    .into());
+                }
+                Some(camera)
+            }
+            None => None,
+        };
+        self.capture = options;
+        Ok(())
+    }
+
+    /// Average number of stale frames dropped per capture with `grab_latest`
+    fn average_frames_skipped(&self) -> f64 {
+        if self.capture_cycles == 0 {
+            return 0.0;
+        }
+        self.total_frames_skipped as f64 / self.capture_cycles as f64
+    }
+
+    /// Set up tracking and zone rules. Rules need tracking, so the tracker
+    /// also runs when only rules are configured.
+    fn set_tracking(&mut self, config: &config::Config) {
+        let has_rules = !config.rules.is_empty();
+        self.track_events = config.tracking.enabled;
+        self.tracker = (self.track_events || has_rules)
+            .then(|| tracker::CentroidTracker::new(&config.tracking));
+        self.rules =
+            has_rules.then(|| rules::RuleEngine::new(config.zones.clone(), config.rules.clone()));
+        self.zones = config.zones.clone();
+    }
+
+    /// Read and process the next frame and return the motion events it
+    /// started. The frame itself is left for `take_frame`.
+    pub(crate) fn poll(&mut self) -> Result<Vec<events::MotionEvent>> {
+        let (motion_detected, frame) = self.detect_motion()?;
+        self.motion_detected = motion_detected;
+        self.frame = frame;
+        let new_tracks = self.track_events.then_some(self.new_tracks.as_slice());
+        let events = self
+            .events
+            .frame(self.frame_time.captured, motion_detected, new_tracks);
+        if let Some(recorder) = self.recorder.as_mut().filter(|_| !events.is_empty()) {
+            recorder.mark_event(Local::now());
+        }
+        Ok(events)
+    }
+
+    /// The frames around the last event, once recorded, with the settings
+    /// to replay them
+    fn take_recording(
+        &mut self,
+    ) -> Result<Option<(tuning::Recording<Mat>, tuning::ReplaySettings)>> {
+        let Some(recording) = self.recording.take() else {
+            return Ok(None);
+        };
+        let settings = tuning::ReplaySettings {
+            curve: self.sensitivity_curve,
+            mask: self
+                .mask
+                .as_ref()
+                .map(|mask| mask.try_clone())
+                .transpose()?,
+            open_kernel: self.open_kernel,
+            open_iterations: self.open_iterations,
+            pyramid_levels: self.pyramid_levels,
+            aspect: self.aspect,
+            merge_gap: self.merge_gap,
+            diff_lag: self.history.lag(),
+            smoothing: self.smoother.time_constant(),
+            frame_scale: self.frame_scale,
+        };
+        Ok(Some((recording, settings)))
+    }
+
+    /// The color frame the last `poll` measured. Event snapshots must save
+    /// this frame, not a later read.
+    pub(crate) fn take_frame(&mut self) -> Mat {
+        std::mem::take(&mut self.frame)
+    }
+
+    /// Save the snapshot for a motion event, unless snapshots are disabled,
+    /// and queue it for upload with `--s3-bucket`. `frame` fired the event;
+    /// the frame where its motion began is saved instead when it is still
+    /// buffered. Snapshots that look like a recent one are skipped with
+    /// `dedup_threshold`.
+    pub(crate) fn save_event_snapshot(&mut self, frame: &Mat, event: u64) -> Result<EventSnapshot> {
+        if !self.snapshots.enabled && self.uploader.is_none() {
+            return Ok(EventSnapshot::Stored(None));
+        }
+        // Named after the moment the saved frame was captured
+        let (captured, frame, original, marks) = match self.pre_event.trigger() {
+            Some((at, trigger, original, marks)) => (
+                Local::now() - chrono::TimeDelta::from_std(at.elapsed()).unwrap_or_default(),
+                trigger,
+                original,
+                marks,
+            ),
+            None => (Local::now(), frame, self.original.as_ref(), &self.marks),
+        };
+        if let Some(dedup) = &mut self.dedup {
+            let gray = to_grayscale(frame)?;
+            let hash = dedup::dhash(
+                gray.data_bytes()?,
+                gray.cols() as usize,
+                gray.rows() as usize,
+            );
+            if let Some(original) = dedup.check(Instant::now(), hash, event) {
+                self.pre_event.clear();
+                return Ok(EventSnapshot::Duplicate(original));
+            }
+        }
+        let name = naming::render(&self.snapshots.filename, captured, &self.event_context());
+        let name = self.dated(format!("{}.jpg", name), captured);
+        let saved = match self.annotated(frame, marks)? {
+            Some(annotated) => self.store_image(&annotated, &name),
+            None => self.store_image(frame, &name),
+        };
+        if let Some(original) = original {
+            if let Err(e) = self.keep_original(original, &name) {
+                eprintln!("Warning: {:#}", e);
+            }
+        }
+        self.pre_event.clear();
+        saved.map(EventSnapshot::Stored)
+    }
+
+    /// The motion regions of the frame just compared, with their contours
+    /// in capture pixels for the contour styles of `--annotation-style`
+    fn marks_of(&self, dilated: &Mat) -> Result<annotate::Marks> {
+        let Some(style) = self.snapshots.annotation_style else {
+            return Ok(annotate::Marks::default());
+        };
+        let mut contours = Vec::new();
+        if style.draws_contours() && !self.motion_regions.is_empty() {
+            for contour in motion_outlines(dilated)? {
+                let contour: Vec<(f64, f64)> = contour
+                    .into_iter()
+                    .map(|point| self.frame_scale.point_to_capture(point))
+                    .collect();
+                if annotate::in_regions(&contour, &self.motion_regions) {
+                    contours.push(contour);
+                }
+            }
+        }
+        Ok(annotate::Marks {
+            regions: self.motion_regions.clone(),
+            contours,
+        })
+    }
+
+    /// A copy of `frame` with its motion drawn on, if `--annotation-style`
+    /// is set
+    fn annotated(&self, frame: &Mat, marks: &annotate::Marks) -> Result<Option<Mat>> {
+        let Some(style) = self.snapshots.annotation_style else {
+            return Ok(None);
+        };
+        let mut annotated = frame.try_clone()?;
+        // Marks are in capture pixels; the snapshot stream may be larger
+        let scale = frame.cols() as f64 / self.frame_scale.capture.0.max(1) as f64;
+        annotate::draw(
+            &mut annotated,
+            style,
+            self.snapshots.box_color,
+            self.snapshots.box_thickness,
+            marks,
+            scale,
+        )
+        .context("Failed to annotate the snapshot")?;
+        Ok(Some(annotated))
+    }
+
+    /// Event snapshots skipped as duplicates
+    fn snapshots_deduplicated(&self) -> u64 {
+        self.dedup.as_ref().map_or(0, |dedup| dedup.deduplicated())
+    }
+
+    /// Apply a mask painted in the GUI and save it to `path`. A mask that
+    /// ignores nothing turns masking off, removing `path` only when the GUI
+    /// created it.
+    fn set_painted_mask(
+        &mut self,
+        image: &mask_editor::MaskImage,
+        path: &Path,
+        created: bool,
+    ) -> Result<()> {
+        if image.is_clear() {
+            self.mask = None;
+            if created {
+                match std::fs::remove_file(path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                        return Err(e)
+                            .with_context(|| format!("Failed to remove {}", path.display()));
+                    }
+                    _ => println!("Mask cleared"),
+                }
+            } else {
+                println!(
+                    "Mask cleared for this session, {} is unchanged",
+                    path.display()
+                );
+            }
+            return Ok(());
+        }
+        let mask = mask::IgnoreMask::from_image(path.to_path_buf(), image)?;
+        mask.save()?;
+        println!("Mask saved to {}", path.display());
+        self.mask = Some(mask);
+        Ok(())
+    }
+
+    /// The file the current mask was loaded from or saved to
+    fn mask_file(&self) -> Option<PathBuf> {
+        self.mask.as_ref().map(|mask| mask.path().to_path_buf())
+    }
+
+    /// Take the next frame as the empty scene and compare every frame with
+    /// it, raising the intrusion alarm on a lasting difference
+    fn arm(&mut self) {
+        self.history.fix_next();
+        self.guard.arm();
+    }
+
+    /// Back to comparing with the previous frames
+    fn disarm(&mut self) {
+        self.history.release_reference();
+        self.guard.disarm();
+    }
+
+    /// What the detector is doing, as of the last frame
+    fn phase(&self) -> phase::DetectorPhase {
+        phase::DetectorPhase::of_running(
+            self.settle.frames_left() + self.history.frames_left(),
+            self.repositioning.is_active(),
+            self.events.armed,
+        )
+    }
+
+    /// Start or end repositioning by hand
+    fn toggle_repositioning(&mut self) -> reposition::Transition {
+        let transition = self.repositioning.toggle(Instant::now());
+        if let reposition::Transition::Ended(_) = transition {
+            self.reset_reference();
+        }
+        transition
+    }
+
+    /// Forget the frames of the old view, so the first frames of the new
+    /// one become the reference
+    fn reset_reference(&mut self) {
+        self.history.clear();
+        // In intrusion mode the new view becomes the empty scene
+        if self.history.is_fixed() {
+            self.history.fix_next();
+        }
+        self.pre_event.clear();
+        self.trail = naming::MotionTrail::new();
+        self.smoother.reset();
+    }
+
+    /// Zone and direction of the motion that fired the current event
+    fn event_context(&self) -> naming::EventContext {
+        let position = self.trail.latest();
+        naming::EventContext {
+            zone: position.and_then(|position| {
+                self.zones
+                    .iter()
+                    .find(|zone| zone.contains(position))
+                    .map(|zone| zone.name.clone())
+            }),
+            direction: self.trail.direction(),
+        }
+    }
+
+    /// Save the frame that set off the intrusion alarm as
+    /// `intrusion_<timestamp>.jpg`, even with `--no-snapshot`
+    fn save_intrusion_snapshot(&self, frame: &Mat) -> Result<String> {
+        let now = Local::now();
+        let name = format!("intrusion_{}.jpg", now.format("%Y%m%d_%H%M%S"));
+        self.write_image(frame, &self.dated(name, now))
+    }
+
+    fn save_snapshot(&self, frame: &Mat) -> Result<String> {
+        let now = Local::now();
+        let name = format!("motion_{}.jpg", now.format("%Y%m%d_%H%M%S"));
+        self.write_image(frame, &self.dated(name, now))
+    }
+
+    /// `name` in its `YYYY/MM/DD/` directory with `--date-tree`
+    fn dated(&self, name: String, captured: DateTime<Local>) -> String {
+        if self.snapshots.date_tree {
+            naming::in_date_tree(&name, captured)
+        } else {
+            name
+        }
+    }
+
+    /// Read a frame outside the detection loop, dropping `warmup` frames
+    /// first. Video files aren't warmed up, that would skip footage.
+    fn read_fresh_frame(&mut self, warmup: usize) -> Option<Mat> {
+        let warmup = if self.input_file.is_some() { 0 } else { warmup };
+        let mut frame = Mat::default();
+        for _ in 0..=warmup {
+            if !self.camera.read(&mut frame).unwrap_or(false) {
+                return None;
+            }
+        }
+        if frame.empty() {
+            return None;
+        }
+        // Never hand out a frame the privacy regions weren't applied to
+        self.redact(frame, false)
+            .map_err(|e| eprintln!("Warning: {:#}", e))
+            .ok()
+    }
+
+    /// `frame` with the privacy regions obscured and `--privacy-blur`
+    /// applied, before it is saved, recorded or shown. `motion` blurs the
+    /// last frame's motion boxes with `all-motion`; they belong to this
+    /// frame only when it was just compared.
+    fn redact(&mut self, mut frame: Mat, motion: bool) -> Result<Mat> {
+        self.redactor
+            .apply(&mut frame, 1.0)
+            .context("Failed to apply the privacy regions")?;
+        self.original = None;
+        let Some(filter) = self.privacy_filter.as_mut() else {
+            return Ok(frame);
+        };
+        if self.snapshots.privacy_keep_original.is_some() {
+            self.original = Some(frame.try_clone()?);
+        }
+        // Motion boxes are in detection-frame pixels; the snapshot stream
+        // may be larger
+        let scale = frame.cols() as f64 / self.frame_scale.capture.0.max(1) as f64;
+        let boxes = if motion {
+            &self.motion_regions[..]
+        } else {
+            &[]
+        };
+        filter
+            .apply(&mut frame, boxes, scale)
+            .context("Failed to apply --privacy-blur")?;
+        Ok(frame)
+    }
+
+    /// Write the unblurred copy of event snapshot `name` under
+    /// `--privacy-keep-original`
+    fn keep_original(&self, frame: &Mat, name: &str) -> Result<()> {
+        let Some(dir) = &self.snapshots.privacy_keep_original else {
+            return Ok(());
+        };
+        if !self.observer.allows(observe::Effect::Image, || {
+            format!("kept the original in {}", dir.join(name).display())
+        }) {
+            return Ok(());
+        }
+        let jpeg = encode_jpeg(frame, &self.jpeg_params)?;
+        privacy::write_private(dir, name, &jpeg)
+            .with_context(|| format!("Failed to keep the original in {}", dir.display()))?;
+        Ok(())
+    }
+
+    /// Save the best frame of a finished incident as `motion_<id>_key.jpg`
+    fn save_key_frame(
+        &self,
+        incident_id: u64,
+        key_frames: &mut incident::KeyFrame<Mat>,
+    ) -> Result<Option<String>> {
+        let Some(frame) = key_frames.take(incident_id) else {
+            return Ok(None);
+        };
+        let name = self.dated(format!("motion_{}_key.jpg", incident_id), Local::now());
+        self.store_image(&frame, &name)
+    }
+
+    /// Save the frame motion is measured against as
+    /// `background/background_<timestamp>.jpg`, once per
+    /// `--background-snapshot-interval`
+    pub(crate) fn save_background_if_due(&mut self) -> Result<Option<String>> {
+        let Some(interval) = self.background_interval else {
+            return Ok(None);
+        };
+        if self.last_background.elapsed() < interval {
+            return Ok(None);
+        }
+        self.last_background = Instant::now();
+        let Some(reference) = self.history.reference() else {
+            return Ok(None);
+        };
+        // The reference is at the processing scale
+        let mut reference = reference.try_clone()?;
+        let scale = self.frame_scale.process.0 as f64 / self.frame_scale.capture.0.max(1) as f64;
+        self.redactor.apply(&mut reference, scale)?;
+        let timestamp = Local::now().format("%Y%m%d_%H%M%S");
+        let name = format!("background/background_{}.jpg", timestamp);
+        if !self.observer.allows(observe::Effect::Image, || {
+            format!("saved {}", self.output_dir.join(&name).display())
+        }) {
+            return Ok(None);
+        }
+        std::fs::create_dir_all(self.output_dir.join("background"))?;
+        self.write_image(&reference, &name).map(Some)
+    }
+
+    /// Write an event image if snapshots are enabled and upload it if
+    /// `--s3-bucket` is set, encoding it once for both. `--observe` does
+    /// neither.
+    fn store_image(&self, frame: &Mat, name: &str) -> Result<Option<String>> {
+        let write = self.snapshots.enabled
+            && self.observer.allows(observe::Effect::Image, || {
+                format!("saved {}", self.output_dir.join(name).display())
+            });
+        let upload = self.uploader.is_some()
+            && self
+                .observer
+                .allows(observe::Effect::Upload, || format!("uploaded {}", name));
+        if !write && !upload {
+            return Ok(None);
+        }
+        let jpeg = encode_jpeg(frame, &self.jpeg_params)?;
+        let saved = if write {
+            Some(self.write_bytes(&jpeg, name)?)
+        } else {
+            None
+        };
+        if let (true, Some(uploader)) = (upload, &self.uploader) {
+            uploader.upload_bytes(name, jpeg);
+        }
+        Ok(saved)
+    }
+
+    fn write_image(&self, frame: &Mat, name: &str) -> Result<String> {
+        self.write_bytes(&encode_jpeg(frame, &self.jpeg_params)?, name)
+    }
+
+    fn write_bytes(&self, bytes: &[u8], name: &str) -> Result<String> {
+        let path = self.output_dir.join(name);
+        if !self.observer.allows(observe::Effect::Image, || {
+            format!("saved {}", path.display())
+        }) {
+            anyhow::bail!("{} not saved in observe mode", name);
+        }
+        // Create the output directory, or the `--date-tree` one, if needed
+        std::fs::create_dir_all(path.parent().unwrap_or(&self.output_dir))?;
+
+        let filename = path.to_string_lossy().into_owned();
+        std::fs::write(&filename, bytes)?;
+        Ok(filename)
+    }
+
+    fn release(&mut self) {
+        let _ = self.camera.release();
+        if let Some(secondary) = self.secondary.as_mut() {
+            let _ = secondary.release();
+        }
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: f64) {
+        self.sensitivity = sensitivity;
+        self.history
+            .set_threshold(self.sensitivity_curve.threshold(sensitivity));
+    }
+
+    pub fn set_sensitivity_curve(&mut self, curve: sensitivity::SensitivityCurve) {
+        self.sensitivity_curve = curve;
+        self.set_sensitivity(self.sensitivity);
+    }
+
+    /// Reopen the capture devices, keeping the counters and everything
+    /// else learnt so far except the background: it is rebuilt from the
+    /// first frames once the camera has settled. Generated frames have no
+    /// device to reopen, only the background is rebuilt.
+    fn reconnect(&mut self, builder: &DetectorBuilder) -> Result<()> {
+        self.release();
+        if self.synthetic.is_none() {
+            self.camera = Self::open_camera(builder)?.0;
+            self.set_capture_options(self.capture.clone())?;
+        }
+        self.reset_reference();
+        self.settle.start();
+        Ok(())
+    }
+
+    /// Size of the captured frames, which snapshots and clips keep
+    fn get_resolution(&self) -> (i32, i32) {
+        self.frame_scale.capture
+    }
+
+    /// Size of the copy detection runs on
+    fn process_resolution(&self) -> (i32, i32) {
+        self.frame_scale.process
+    }
+
+    /// `--min-area` in capture pixels for the current frame size, so a
+    /// percentage follows a change of resolution
+    fn min_area_pixels(&self) -> u32 {
+        let (width, height) = self.frame_scale.capture;
+        self.min_area.pixels(width as f64 * height as f64)
+    }
+
+    /// e.g. "1920x1080, detecting at 634x356"
+    fn describe_resolution(&self) -> String {
+        let (width, height) = self.get_resolution();
+        if self.frame_scale.is_identity() {
+            return format!("{}x{}", width, height);
+        }
+        let (process_width, process_height) = self.process_resolution();
+        format!(
+            "{}x{}, detecting at {}x{}",
+            width, height, process_width, process_height
+        )
+    }
+
+    /// Cameras 0-3 and the size of their first frame, all probed at once so
+    /// a device that is slow to fail holds up nothing but its own line.
+    /// `in_use` is the detector's own camera, which is listed without
+    /// being opened a second time.
+    pub fn list_cameras(in_use: Option<u32>) -> Result<Vec<String>> {
+        let devices: Vec<u32> = (0..4).filter(|&i| Some(i) != in_use).collect();
+        let results = probe::probe_all(devices.clone(), probe::PROBE_TIMEOUT, |i| {
+            let mut cam = VideoCapture::new(i as i32, CAP_V4L2)
+                .or_else(|_| VideoCapture::new(i as i32, CAP_ANY))
+                .ok()?;
+            let mut frame = Mat::default();
+            let size = (cam.is_opened().ok()? && cam.read(&mut frame).ok()? && !frame.empty())
+                .then(|| (frame.cols(), frame.rows()));
+            let _ = cam.release();
+            size
+        });
+
+        let mut cameras = Vec::new();
+        if let Some(device) = in_use.filter(|&device| device < 4) {
+            cameras.push(format!("Camera {} - in use by the detector", device));
+        }
+        for (device, result) in devices.into_iter().zip(results) {
+            match result {
+                Some(Some((width, height))) => {
+                    cameras.push(format!("Camera {} - {}x{}", device, width, height))
+                }
+                Some(None) => {}
+                None => cameras.push(format!(
+                    "Camera {} - no answer within {} s",
+                    device,
+                    probe::PROBE_TIMEOUT.as_secs()
+                )),
+            }
+        }
+        cameras.sort();
+        Ok(cameras)
+    }
+}
+
+impl selftest::FrameSource for MotionDetector {
+    fn grab_frame(&mut self) -> Result<bool> {
+        let mut frame = Mat::default();
+        Ok(self.camera.read(&mut frame)? && !frame.empty())
+    }
+}
+
+impl source::Frame for Mat {
+    fn is_empty(&self) -> bool {
+        self.empty()
+    }
+
+    fn try_clone(&self) -> Result<Self> {
+        Ok(MatTraitConst::try_clone(self)?)
+    }
+}
+
+impl source::FrameSource for MotionDetector {
+    type Frame = Mat;
+    type Builder = DetectorBuilder;
+    type Clips = recording::ClipRecorder;
+    type Tuner = tuning::TuneWorker;
+
+    fn poll(&mut self) -> Result<Vec<events::MotionEvent>> {
+        MotionDetector::poll(self)
+    }
+
+    fn take_frame(&mut self) -> Mat {
+        MotionDetector::take_frame(self)
+    }
+
+    fn stats(&self) -> source::FrameStats {
+        source::FrameStats {
+            motion_detected: self.motion_detected,
+            frame_time: self.frame_time,
+            frame_count: self.frame_count,
+            motion_count: self.motion_count,
+            last_motion_time: self.last_motion_time,
+            current_fps: self.current_fps,
+            timings: self.timings,
+            score: self.score,
+            contours_found: self.contours_found,
+            regions_found: self.regions_found,
+            motion_regions: self.motion_regions.clone(),
+            motion_area: self.motion_area,
+            aspect_rejected: self.aspect_rejected,
+            frames_skipped: self.frames_skipped,
+            total_frames_skipped: self.total_frames_skipped,
+            average_frames_skipped: self.average_frames_skipped(),
+            grab_latest: self.capture.grab_latest,
+            rule_hits: self.rule_hits.clone(),
+            new_tracks: self.new_tracks.clone(),
+            active_tracks: self
+                .tracker
+                .as_ref()
+                .map(|tracker| tracker.active_ids())
+                .unwrap_or_default(),
+            quality_change: self.quality_change,
+            intrusion_alarm: self.intrusion_alarm,
+            intrusion: self.guard.state(),
+            reposition_transition: self.reposition_transition,
+            repositioning: self.repositioning.is_active(),
+            snapshots_deduplicated: self.dedup.as_ref().map(|_| self.snapshots_deduplicated()),
+            resolution: self.get_resolution(),
+            process_resolution: self.process_resolution(),
+        }
+    }
+
+    fn release(&mut self) {
+        MotionDetector::release(self)
+    }
+
+    fn reconnect(&mut self, builder: &DetectorBuilder) -> Result<()> {
+        MotionDetector::reconnect(self, builder)
+    }
+
+    fn is_live(&self) -> bool {
+        self.input_file.is_none() && self.synthetic.is_none()
+    }
+
+    fn file_finished(&self) -> bool {
+        self.file_finished
+    }
+
+    fn rewind(&mut self) -> Result<()> {
+        MotionDetector::rewind(self)
+    }
+
+    fn set_loop_file(&mut self, enabled: bool) {
+        self.loop_file = enabled;
+    }
+
+    fn events(&self) -> &events::EventSource {
+        &self.events
+    }
+
+    fn events_mut(&mut self) -> &mut events::EventSource {
+        &mut self.events
+    }
+
+    fn observer(&self) -> &observe::Observer {
+        &self.observer
+    }
+
+    fn observer_mut(&mut self) -> &mut observe::Observer {
+        &mut self.observer
+    }
+
+    fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    fn set_output_dir(&mut self, dir: PathBuf) {
+        self.output_dir = dir;
+    }
+
+    fn uploader(&self) -> Option<&upload::Uploader> {
+        self.uploader.as_ref()
+    }
+
+    fn phase(&self) -> phase::DetectorPhase {
+        MotionDetector::phase(self)
+    }
+
+    fn event_context(&self) -> naming::EventContext {
+        MotionDetector::event_context(self)
+    }
+
+    fn describe_resolution(&self) -> String {
+        MotionDetector::describe_resolution(self)
+    }
+
+    fn min_area(&self) -> regions::MinArea {
+        self.min_area
+    }
+
+    fn live_settings(&self) -> source::LiveSettings {
+        source::LiveSettings {
+            sensitivity: self.sensitivity,
+            sensitivity_curve: self.sensitivity_curve,
+            min_area: self.min_area,
+            opening: (self.open_kernel, self.open_iterations),
+            output_dir: self.output_dir.clone(),
+            observe: self.observer.is_active(),
+            armed: self.guard.state().is_armed(),
+        }
+    }
+
+    fn set_sensitivity(&mut self, sensitivity: f64) {
+        MotionDetector::set_sensitivity(self, sensitivity)
+    }
+
+    fn set_sensitivity_curve(&mut self, curve: sensitivity::SensitivityCurve) {
+        MotionDetector::set_sensitivity_curve(self, curve)
+    }
+
+    fn set_min_area(&mut self, min_area: regions::MinArea) {
+        self.min_area = min_area;
+    }
+
+    fn set_opening(&mut self, kernel: i32, iterations: i32) {
+        self.open_kernel = kernel;
+        self.open_iterations = iterations;
+    }
+
+    fn arm(&mut self) {
+        MotionDetector::arm(self)
+    }
+
+    fn disarm(&mut self) {
+        MotionDetector::disarm(self)
+    }
+
+    fn toggle_repositioning(&mut self) -> reposition::Transition {
+        MotionDetector::toggle_repositioning(self)
+    }
+
+    fn mask_image(&self) -> Option<mask_editor::MaskImage> {
+        self.mask.as_ref().and_then(|mask| mask.image().ok())
+    }
+
+    fn set_painted_mask(
+        &mut self,
+        image: &mask_editor::MaskImage,
+        path: &Path,
+        created: bool,
+    ) -> Result<()> {
+        MotionDetector::set_painted_mask(self, image, path, created)
+    }
+
+    fn mask_file(&self) -> Option<PathBuf> {
+        MotionDetector::mask_file(self)
+    }
+
+    fn save_event_snapshot(&mut self, frame: &Mat, event: u64) -> Result<EventSnapshot> {
+        MotionDetector::save_event_snapshot(self, frame, event)
+    }
+
+    fn save_intrusion_snapshot(&self, frame: &Mat) -> Result<String> {
+        MotionDetector::save_intrusion_snapshot(self, frame)
+    }
+
+    fn save_key_frame(
+        &self,
+        incident_id: u64,
+        key_frames: &mut incident::KeyFrame<Mat>,
+    ) -> Result<Option<String>> {
+        MotionDetector::save_key_frame(self, incident_id, key_frames)
+    }
+
+    fn save_background_if_due(&mut self) -> Result<Option<String>> {
+        MotionDetector::save_background_if_due(self)
+    }
+
+    fn manual_snapshot(&mut self, running: bool, last_frame: Option<&Mat>) -> Result<String> {
+        let fresh = match (running, last_frame) {
+            (true, Some(_)) => None,
+            (true, None) => self.read_fresh_frame(0),
+            (false, _) => self.read_fresh_frame(SNAPSHOT_WARMUP_FRAMES),
+        };
+        let Some(frame) = fresh.as_ref().or(last_frame) else {
+            anyhow::bail!("Failed to capture a frame from the camera");
+        };
+        self.save_snapshot(frame)
+    }
+
+    fn preview_frame(
+        frame: &Mat,
+        overlay_config: &overlay::OverlayConfig,
+        info: &overlay::OverlayInfo,
+        max_size: [f32; 2],
+    ) -> Result<gui::PreviewFrame> {
+        build_preview_frame(frame, overlay_config, info, max_size)
+    }
+
+    fn clips(config: &recording::RecordingConfig) -> Option<recording::ClipRecorder> {
+        config.enabled.then(|| recording::ClipRecorder::new(config))
+    }
+
+    fn take_recording(
+        &mut self,
+    ) -> Result<Option<(tuning::Recording<Mat>, tuning::ReplaySettings)>> {
+        MotionDetector::take_recording(self)
+    }
+}
+
+impl source::ClipSink<Mat> for recording::ClipRecorder {
+    fn prepare(&mut self, frame: &Mat) -> Result<recording::Selection> {
+        recording::ClipRecorder::prepare(self, frame)
+    }
+
+    fn selection(&self) -> Option<recording::Selection> {
+        recording::ClipRecorder::selection(self)
+    }
+
+    fn encode_fps(&self) -> Option<f64> {
+        recording::ClipRecorder::encode_fps(self)
+    }
+
+    fn start(&mut self, incident_id: u64, dir: &Path, frame: &Mat) -> Result<()> {
+        recording::ClipRecorder::start(self, incident_id, dir, frame)
+    }
+
+    fn write(&mut self, frame: &Mat, motion: bool) -> Result<()> {
+        recording::ClipRecorder::write(self, frame, motion)
+    }
+
+    fn finish(&mut self, incident_id: u64) -> Result<Option<PathBuf>> {
+        recording::ClipRecorder::finish(self, incident_id)
+    }
+
+    fn trims(&self) -> bool {
+        recording::ClipRecorder::trims(self)
+    }
+
+    fn take_trimmed(&self) -> Vec<recording::Trimmed> {
+        recording::ClipRecorder::take_trimmed(self)
+    }
+}
+
+impl source::Tuner for tuning::TuneWorker {
+    type Recording = (tuning::Recording<Mat>, tuning::ReplaySettings);
+
+    fn spawn(states: crossbeam_channel::Sender<gui::MotionState>) -> Self {
+        tuning::TuneWorker::spawn(states)
+    }
+
+    fn is_loaded(&self) -> bool {
+        tuning::TuneWorker::is_loaded(self)
+    }
+
+    fn load(&mut self, (recording, settings): Self::Recording, params: tuning::TuneParams) {
+        tuning::TuneWorker::load(self, recording, settings, params)
+    }
+
+    fn run(&self, params: tuning::TuneParams) {
+        tuning::TuneWorker::run(self, params)
+    }
+}
+
+impl source::SourceBuilder for DetectorBuilder {
+    type Source = MotionDetector;
+
+    fn build(&self) -> Result<MotionDetector> {
+        DetectorBuilder::build(self)
+    }
+
+    fn device_index(&self) -> u32 {
+        self.device
+    }
+
+    fn device(self, device: u32) -> Self {
+        DetectorBuilder::device(self, device)
+    }
+
+    fn camera_config(self, config: &config::Config) -> Self {
+        self.config(config)
+    }
+
+    fn input_file(self, path: Option<PathBuf>, loop_file: bool) -> Self {
+        DetectorBuilder::input_file(self, path).loop_file(loop_file)
+    }
+
+    fn mask_file(self, path: Option<PathBuf>) -> Self {
+        DetectorBuilder::mask_file(self, path)
+    }
+
+    fn live_settings(self, live: &source::LiveSettings) -> Self {
+        self.sensitivity(live.sensitivity)
+            .sensitivity_curve(live.sensitivity_curve)
+            .min_area(live.min_area)
+            .opening(live.opening.0, live.opening.1)
+            .output_dir(live.output_dir.clone())
+    }
+}
+
+/// `self-test`: open the configured camera and check everything else a
+/// deployment needs, printing a pass/fail table
+pub fn run_self_test(config: config::Config, args: Args) -> Result<()> {
+    println!("Running self-test...");
+    let camera = match DetectorBuilder::from_args(&args, &config).build() {
+        Ok(mut detector) => {
+            let result = selftest::check_camera(&mut detector, selftest::CAMERA_FRAMES);
+            detector.release();
+            result
+        }
+        Err(e) => selftest::CheckResult::new(
+            "Camera",
+            selftest::CheckStatus::Fail,
+            format!("{:#}; check --device and camera permissions", e),
+        ),
+    };
+
+    let mut results = vec![camera];
+    results.extend(selftest::common_checks(&args.output_dir));
+    selftest::print_report("Self-test", &results);
+    selftest::finish("Self-test", &results)
+}
+
+/// `--dry-run`: open and release the camera and check the rest of the
+/// setup, without entering the detection loop
+/// `bench`: run the detection pipeline flat out over a fixed number of frames
+/// and report throughput, per-stage timings and peak memory. Nothing is
+/// written to disk and nothing sleeps.
+pub fn run_bench(config: config::Config, args: Args, bench: BenchArgs) -> Result<()> {
+    let builder = DetectorBuilder::from_args(&args, &config);
+    let builder = match &bench.input {
+        Some(path) => builder.input_file(Some(path.clone())).loop_file(true),
+        None => builder.synthetic(bench.width, bench.height),
+    };
+    let mut detector = builder.build()?;
+    let source = match &bench.input {
+        Some(path) => path.display().to_string(),
+        None => "synthetic frames".to_string(),
+    };
+    println!(
+        "Benchmarking {} frames of {} at {}...",
+        bench.frames,
+        source,
+        detector.describe_resolution()
+    );
+
+    let mut motion_frames = 0;
+    let started = Instant::now();
+    for _ in 0..bench.frames {
+        let (motion_detected, _) = detector.detect_motion()?;
+        if motion_detected {
+            motion_frames += 1;
+        }
+    }
+    let elapsed = started.elapsed();
+    detector.release();
+
+    let stages = detector.stage_totals.average();
+    println!(
+        "Processed {} frames in {:.2} s: {:.1} FPS ({} with motion)",
+        bench.frames,
+        elapsed.as_secs_f64(),
+        bench.frames as f64 / elapsed.as_secs_f64(),
+        motion_frames
+    );
+    println!(
+        "Per frame: capture {:.2} ms, blur {:.2} ms, diff {:.2} ms, contours {:.2} ms (total {:.2} ms)",
+        stages.capture_ms,
+        stages.blur_ms,
+        stages.diff_ms,
+        stages.contours_ms,
+        stages.total_ms()
+    );
+    match monitor::read_peak_rss_bytes() {
+        Some(bytes) => println!("Peak memory: {:.1} MB", bytes as f64 / (1024.0 * 1024.0)),
+        None => println!("Peak memory: n/a"),
+    }
+    Ok(())
+}
+
+pub fn run_dry_run(config: config::Config, args: Args) -> Result<()> {
+    let camera = match DetectorBuilder::from_args(&args, &config).build() {
+        Ok(mut detector) => {
+            detector.release();
+            selftest::CheckResult::new(
+                "Camera",
+                selftest::CheckStatus::Pass,
+                format!("device {} opened", args.device),
+            )
+        }
+        Err(e) => selftest::CheckResult::new(
+            "Camera",
+            selftest::CheckStatus::Fail,
+            format!("{:#}; check --device and camera permissions", e),
+        ),
+    };
+
+    let results = vec![
+        selftest::check_config(args.config.as_deref()),
+        camera,
+        selftest::check_output_dir(&args.output_dir),
+        selftest::check_microphone(&config.audio),
+        selftest::check_notifiers(),
+    ];
+    selftest::print_report("Dry run", &results);
+    selftest::finish("Dry run", &results)
+}
+
+/// `--check`: read a few frames and test the output directory, exiting with
+/// the code of whatever failed first
+pub fn run_check(config: config::Config, args: Args) -> Result<()> {
+    let camera = match DetectorBuilder::from_args(&args, &config).build() {
+        Ok(mut detector) => {
+            let result = selftest::check_camera(&mut detector, selftest::CHECK_FRAMES);
+            detector.release();
+            result
+        }
+        Err(e) => selftest::CheckResult::new(
+            "Camera",
+            selftest::CheckStatus::Fail,
+            format!("{:#}; check --device and camera permissions", e),
+        ),
+    };
+    selftest::finish_check(
+        selftest::check_config(args.config.as_deref()),
+        camera,
+        selftest::check_output_dir(&args.output_dir),
+    )
+}
+
+pub fn run_cli_mode(config: config::Config, args: Args) -> Result<()> {
+    selftest::require_output_dir(&args.output_dir)?;
+    let uploader = args
+        .s3()?
+        .as_ref()
+        .map(|s3| upload::open(s3, None))
+        .transpose()?;
+    let builder = DetectorBuilder::from_args(&args, &config);
+    let detector = builder
+        .clone()
+        .cooldowns(args.cooldowns()?)
+        .uploader(uploader)
+        .build()?;
+    run::detect_cli(&config, &args, builder, detector)
+}
+
+pub fn run_gui_mode(config: config::Config, args: Args) -> Result<()> {
+    use crossbeam_channel::bounded;
+    use gui::{GuiMessage, MotionDetectorGui, MotionState, PreviewFrame};
+
+    let (gui_sender, detector_receiver) = bounded::<GuiMessage>(100);
+    let (mut detector_sender, gui_state_receiver) = bounded::<MotionState>(100);
+    // Preview frames are large, so only keep the latest couple in flight
+    let (preview_sender, gui_preview_receiver) = bounded::<PreviewFrame>(2);
+
+    // Start detector thread
+    let detector_config = config.clone();
+    let output_dir = args.output_dir.clone();
+    let flush_on_event = args.flush_on_event;
+    let target_fps = args.target_fps;
+    let observe = args.observe;
+    let options = run::ThreadOptions {
+        incident_gap: Duration::from_secs(args.incident_gap),
+        state_file: args.state_file.clone(),
+        prefer_device: args.prefer_device.clone(),
+        gpio: args.gpio()?,
+        flush_on_event,
+        // Masks painted in the GUI are saved over --mask-file, or here
+        mask_path: args
+            .mask_file
+            .clone()
+            .unwrap_or_else(|| output_dir.join(GUI_MASK_FILE)),
+        default_mask: args.mask_file.is_none(),
+        target_fps,
+        arm: args.arm,
+        re_alert: args.re_alert()?,
+        observe: args.observe,
+    };
+    // The GUI starts on device 0 with default settings and adjusts them live,
+    // unless --camera picked a named camera
+    let mut builder = DetectorBuilder::new()
+        .config(&config)
+        .cooldowns(args.cooldowns()?)
+        .merge_gap(args.merge_gap)
+        .diff_lag(args.diff_lag)
+        .background_interval(
+            args.background_snapshot_interval
+                .map(|minutes| Duration::from_secs(minutes * 60)),
+        )
+        .output_dir(output_dir.clone())
+        .capture(CaptureOptions::from_args(&args))
+        .tuning(true)
+        .mask_file(
+            args.mask_file
+                .clone()
+                .or_else(|| Some(output_dir.join(GUI_MASK_FILE)).filter(|path| path.exists())),
+        );
+    if args.camera.is_some() {
+        builder = builder
+            .device(args.device)
+            .sensitivity(args.sensitivity)
+            .min_area(args.min_area);
+    }
+    let start = (builder.device, builder.sensitivity, builder.min_area);
+    let cameras: Vec<gui::CameraPreset> = config
+        .named_cameras()
+        .into_iter()
+        .map(|camera| gui::CameraPreset {
+            device: camera.device,
+            sensitivity: camera.sensitivity.unwrap_or(config::DEFAULT_SENSITIVITY),
+            min_area: camera.min_area.unwrap_or_default(),
+            output_dir: camera
+                .output_dir
+                .unwrap_or_else(|| args.output_dir.join(&camera.name)),
+            name: camera.name,
+        })
+        .collect();
+    let selected_camera = args.camera.clone();
+    let journal_path = journal::path(&args.output_dir);
+    let location = config.location;
+    // A panic is logged and shown in the GUI instead of silently stopping
+    // the updates
+    crash::install_hook();
+    // With --dbus the detector's states pass through the D-Bus service on
+    // their way to the window
+    if let Some(bus) = args.dbus {
+        let (bridge_sender, bridge_receiver) = bounded::<MotionState>(100);
+        dbus::spawn(
+            bus,
+            None,
+            gui_sender.clone(),
+            bridge_receiver,
+            detector_sender,
+        )?;
+        detector_sender = bridge_sender;
+    }
+    // Failed uploads show in the GUI's notification center
+    let alerts = alerts::AlertSender::new(detector_sender.clone());
+    builder = builder.uploader(
+        args.s3()?
+            .as_ref()
+            .map(|s3| upload::open(s3, Some(alerts)))
+            .transpose()?,
+    );
+    let crash_sender = detector_sender.clone();
+    let detector_handle = thread::Builder::new()
+        .name("detector".to_string())
+        .spawn(move || {
+            let run = std::panic::AssertUnwindSafe(move || {
+                run::detector_thread(
+                    detector_receiver,
+                    detector_sender,
+                    preview_sender,
+                    detector_config,
+                    builder,
+                    options,
+                )
+            });
+            let error = match std::panic::catch_unwind(run) {
+                Ok(Ok(())) => return,
+                Ok(Err(e)) => format!("Detector thread stopped: {:#}", e),
+                Err(panic) => format!("Detector thread crashed: {}", crash::panic_message(&*panic)),
+            };
+            eprintln!("{}", error);
+            let _ = crash_sender.send(MotionState {
+                error: Some(error),
+                ..MotionState::default()
+            });
+        })
+        .context("Failed to start the detector thread")?;
+
+    // Start the GUI in the main thread
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([800.0, 600.0])
+            .with_title("Motion Detector"),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "Motion Detector",
+        options,
+        Box::new(move |cc| {
+            let mut gui = MotionDetectorGui::new_with_sender(cc, gui_sender.clone());
+            gui.state_receiver = Some(gui_state_receiver.clone());
+            gui.preview_receiver = Some(gui_preview_receiver.clone());
+            gui.overlay_config = config.overlay.clone();
+            gui.activity_log.set_capacity(config.gui.log_capacity);
+            gui.preview_max_size = config.gui.preview_max_size;
+            gui.minimize_on_close = config.gui.minimize_on_close;
+            gui.notify_when_hidden = config.gui.notify_when_hidden;
+            gui.sensitivity_curve = config.detector.sensitivity_curve;
+            gui.opening = (config.detector.open_kernel, config.detector.open_iterations);
+            gui.flush_on_event = flush_on_event;
+            gui.observe = observe;
+            gui.target_fps = target_fps;
+            gui.output_dir = output_dir.clone();
+            gui.event_snapshots = config.snapshots.enabled;
+            gui.set_settings(start.0, start.1, start.2);
+            gui.set_cameras(cameras.clone(), selected_camera.as_deref());
+            gui.open_journal(journal_path.clone());
+            gui.location = location;
+            // Validated when the config was loaded
+            gui.set_schedule(
+                schedule::ScheduleClock::from_config(&config.schedule, location)
+                    .ok()
+                    .flatten(),
+            );
+            Box::new(gui)
+        }),
+    )
+    .map_err(|e| anyhow::anyhow!("GUI error: {}", e))?;
+
+    // Wait for detector thread to finish
+    let _ = detector_handle.join();
+
+    Ok(())
+}
+
+/// The GUI's painted mask, in the output directory, when there is no `--mask-file`
+const GUI_MASK_FILE: &str = "mask.png";
+
+/// Frames read and dropped before a manual snapshot from an idle camera, so
+/// auto exposure has caught up
+const SNAPSHOT_WARMUP_FRAMES: usize = 3;
+
+/// Copy a color frame, draw the OSD on the copy and convert it to RGB for egui.
+/// Frames larger than the preview can show are shrunk first, so a 4K feed
+/// doesn't send 4K textures; twice `max_size` keeps HiDPI screens sharp.
+fn build_preview_frame(
+    frame: &Mat,
+    overlay_config: &overlay::OverlayConfig,
+    info: &overlay::OverlayInfo,
+    max_size: [f32; 2],
+) -> Result<gui::PreviewFrame> {
+    let mut annotated = to_bgr(frame)?;
+    overlay::render_overlay(&mut annotated, overlay_config, info)?;
+
+    let (cols, rows) = (annotated.cols() as f32, annotated.rows() as f32);
+    let [width, height] = gui::fit_within([cols, rows], [max_size[0] * 2.0, max_size[1] * 2.0]);
+    if width < cols {
+        let mut shrunk = Mat::default();
+        let size = core::Size::new((width as i32).max(1), (height as i32).max(1));
+        imgproc::resize(&annotated, &mut shrunk, size, 0.0, 0.0, imgproc::INTER_AREA)?;
+        annotated = shrunk;
+    }
+
+    let mut rgb = Mat::default();
+    imgproc::cvt_color(&annotated, &mut rgb, imgproc::COLOR_BGR2RGB, 0)?;
+
+    Ok(gui::PreviewFrame {
+        width: rgb.cols() as usize,
+        height: rgb.rows() as usize,
+        rgb: rgb.data_bytes()?.to_vec(),
+    })
+}
+
+/// Bounding boxes of the contours in the cleaned mask `dilated`, plus what
+/// the coarser levels of `--pyramid-levels` find in `thresh`, in pixels of
+/// the processed frame
+pub fn contour_regions(
+    thresh: &Mat,
+    dilated: &Mat,
+    pyramid_levels: u32,
+    open_kernel: i32,
+    open_iterations: i32,
+) -> Result<Vec<regions::Region>> {
+    let mut contours = Vector::<Vector<opencv::core::Point>>::new();
+    imgproc::find_contours(
+        dilated,
+        &mut contours,
+        imgproc::RETR_EXTERNAL,
+        imgproc::CHAIN_APPROX_SIMPLE,
+        opencv::core::Point::new(-1, -1),
+    )?;
+    let mut found = Vec::new();
+    for contour in &contours {
+        let rect = imgproc::bounding_rect(&contour)?;
+        found.push(regions::Region {
+            x: rect.x as f64,
+            y: rect.y as f64,
+            width: rect.width as f64,
+            height: rect.height as f64,
+            area: imgproc::contour_area(&contour, false)?,
+            contours: 1,
+        });
+    }
+    if pyramid_levels > 1 {
+        let mut levels = vec![found];
+        levels.extend(pyramid::coarse_regions(
+            thresh,
+            pyramid_levels,
+            open_kernel,
+            open_iterations,
+        )?);
+        found = pyramid::combine(levels);
+    }
+    Ok(found)
+}
+
+/// The outlines of the contours in the cleaned mask `dilated`, in pixels
+/// of the processed frame
+pub fn motion_outlines(dilated: &Mat) -> Result<Vec<Vec<(f64, f64)>>> {
+    let mut contours = Vector::<Vector<opencv::core::Point>>::new();
+    imgproc::find_contours(
+        dilated,
+        &mut contours,
+        imgproc::RETR_EXTERNAL,
+        imgproc::CHAIN_APPROX_SIMPLE,
+        opencv::core::Point::new(-1, -1),
+    )?;
+    Ok(contours
+        .iter()
+        .map(|contour| {
+            contour
+                .iter()
+                .map(|point| (point.x as f64, point.y as f64))
+                .collect()
+        })
+        .collect())
+}
+
+/// Remove isolated noise from the thresholded difference with an opening
+/// (skipped when `open_kernel` is 0), then dilate what's left to fill holes
+/// in moving objects. Returns the opened mask too, for the debug dump.
+pub fn clean_mask(
+    thresh: &Mat,
+    open_kernel: i32,
+    open_iterations: i32,
+) -> Result<(Option<Mat>, Mat)> {
+    let (mut opened, mut dilated) = (Mat::default(), Mat::default());
+    let is_opened = clean_mask_into(
+        thresh,
+        open_kernel,
+        open_iterations,
+        &mut opened,
+        &mut dilated,
+    )?;
+    Ok((is_opened.then_some(opened), dilated))
+}
+
+/// `clean_mask` into buffers the caller keeps between frames. Returns
+/// whether `opened` was written.
+pub fn clean_mask_into(
+    thresh: &Mat,
+    open_kernel: i32,
+    open_iterations: i32,
+    opened: &mut Mat,
+    dilated: &mut Mat,
+) -> Result<bool> {
+    let is_opened = open_kernel > 0;
+    if is_opened {
+        let kernel = Mat::ones(open_kernel, open_kernel, core::CV_8UC1)?;
+        imgproc::morphology_ex(
+            thresh,
+            opened,
+            imgproc::MORPH_OPEN,
+            &kernel,
+            core::Point::new(-1, -1),
+            open_iterations,
+            core::BORDER_CONSTANT,
+            imgproc::morphology_default_border_value()?,
+        )?;
+    }
+
+    // Dilate to fill in holes
+    let kernel = Mat::ones(3, 3, core::CV_8UC1)?;
+    imgproc::dilate(
+        if is_opened { &*opened } else { thresh },
+        dilated,
+        &kernel,
+        core::Point::new(-1, -1),
+        2,
+        core::BORDER_DEFAULT,
+        core::Scalar::all(0.0),
+    )?;
+    Ok(is_opened)
+}
+
+/// Frame-sized buffers the detector converts each capture into, kept
+/// between frames. OpenCV reallocates a destination only when its size or
+/// type changes, so at a steady resolution the grayscale, resize and mask
+/// clean-up stages run on the same continuous memory every frame.
+#[derive(Default)]
+struct FrameBuffers {
+    gray: Mat,
+    small: Mat,
+    opened: Mat,
+    dilated: Mat,
+}
+
+impl FrameBuffers {
+    /// `frame` as 8-bit grayscale at the size detection runs at. A frame
+    /// that already is one is used as it is, unless it isn't continuous,
+    /// e.g. a region of a larger capture.
+    fn grayscale<'a>(
+        &'a mut self,
+        frame: &'a Mat,
+        frame_scale: &scale::FrameScale,
+    ) -> Result<&'a Mat> {
+        let gray = match gray_conversion(frame)? {
+            None if frame.is_continuous() => frame,
+            None => {
+                frame.copy_to(&mut self.gray)?;
+                &self.gray
+            }
+            Some(code) => {
+                imgproc::cvt_color(frame, &mut self.gray, code, 0)?;
+                &self.gray
+            }
+        };
+        if frame_scale.is_identity() {
+            return Ok(gray);
+        }
+        let (width, height) = frame_scale.process;
+        imgproc::resize(
+            gray,
+            &mut self.small,
+            core::Size::new(width, height),
+            0.0,
+            0.0,
+            imgproc::INTER_AREA,
+        )?;
+        Ok(&self.small)
+    }
+}
+
+/// The `cvt_color` code that turns `frame` into 8-bit grayscale, `None`
+/// when it already is
+fn gray_conversion(frame: &Mat) -> Result<Option<i32>> {
+    match (frame.depth(), frame.channels()) {
+        // IR/thermal sources are already single channel
+        (core::CV_8U, 1) => Ok(None),
+        (core::CV_8U, 2) => Ok(Some(imgproc::COLOR_YUV2GRAY_YUYV)),
+        (core::CV_8U, 3) => Ok(Some(imgproc::COLOR_BGR2GRAY)),
+        (core::CV_8U, 4) => Ok(Some(imgproc::COLOR_BGRA2GRAY)),
+        _ => Err(DetectorError::UnsupportedFormat(pixel_format(frame)).into()),
+    }
+}
+
+/// Convert any supported capture format to 8-bit grayscale for detection
+pub fn to_grayscale(frame: &Mat) -> Result<Mat> {
+    let Some(code) = gray_conversion(frame)? else {
+        return Ok(frame.try_clone()?);
+    };
+    let mut gray = Mat::default();
+    imgproc::cvt_color(frame, &mut gray, code, 0)?;
+    Ok(gray)
+}
+
+/// Convert any supported capture format to 3-channel BGR for snapshots and preview
+pub fn to_bgr(frame: &Mat) -> Result<Mat> {
+    let code = match (frame.depth(), frame.channels()) {
+        (core::CV_8U, 1) => imgproc::COLOR_GRAY2BGR,
+        (core::CV_8U, 2) => imgproc::COLOR_YUV2BGR_YUYV,
+        (core::CV_8U, 3) => return Ok(frame.try_clone()?),
+        (core::CV_8U, 4) => imgproc::COLOR_BGRA2BGR,
+        _ => return Err(DetectorError::UnsupportedFormat(pixel_format(frame)).into()),
+    };
+    let mut bgr = Mat::default();
+    imgproc::cvt_color(frame, &mut bgr, code, 0)?;
+    Ok(bgr)
+}
+
+/// A snapshot as JPEG bytes. Grayscale frames are encoded as-is, packed
+/// YUV needs converting first.
+fn encode_jpeg(frame: &Mat, params: &Vector<i32>) -> Result<Vec<u8>> {
+    let frame = if frame.channels() == 2 {
+        to_bgr(frame)?
+    } else {
+        frame.try_clone()?
+    };
+    let mut jpeg = Vector::<u8>::new();
+    imgcodecs::imencode(".jpg", &frame, &mut jpeg, params)?;
+    Ok(jpeg.to_vec())
+}
+
+/// OpenCV's name for the frame type, e.g. "CV_16UC1"
+fn pixel_format(frame: &Mat) -> String {
+    core::type_to_string(frame.typ()).unwrap_or_else(|_| format!("type {}", frame.typ()))
+}
@@ -0,0 +1,80 @@
+//! Native open/save dialogs for the GUI. rfd's dialogs block until the user
+//! answers, so each one runs on its own thread and the GUI polls for the
+//! answer once per frame.
+
+// Without the `camera` feature there is no GUI to use this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use crossbeam_channel::{Receiver, TryRecvError};
+use std::path::PathBuf;
+use std::thread;
+
+/// What the chosen path will be used for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Purpose {
+    OutputDir,
+    VideoFile,
+    ExportLog,
+    ExportGraph,
+}
+
+/// A dialog the user hasn't answered yet
+pub struct PendingDialog {
+    pub purpose: Purpose,
+    answer: Receiver<Option<PathBuf>>,
+}
+
+impl PendingDialog {
+    /// `None` while the dialog is open, then `Some(None)` if it was cancelled
+    pub fn poll(&self) -> Option<Option<PathBuf>> {
+        match self.answer.try_recv() {
+            Ok(path) => Some(path),
+            Err(TryRecvError::Empty) => None,
+            // The dialog thread died without answering
+            Err(TryRecvError::Disconnected) => Some(None),
+        }
+    }
+}
+
+fn spawn(
+    purpose: Purpose,
+    show: impl FnOnce() -> Option<PathBuf> + Send + 'static,
+) -> PendingDialog {
+    let (sender, answer) = crossbeam_channel::bounded(1);
+    thread::spawn(move || {
+        let _ = sender.send(show());
+    });
+    PendingDialog { purpose, answer }
+}
+
+pub fn pick_folder(purpose: Purpose, start: PathBuf) -> PendingDialog {
+    spawn(purpose, move || {
+        rfd::FileDialog::new().set_directory(start).pick_folder()
+    })
+}
+
+pub fn pick_file(
+    purpose: Purpose,
+    filter: &'static str,
+    extensions: &'static [&'static str],
+) -> PendingDialog {
+    spawn(purpose, move || {
+        rfd::FileDialog::new()
+            .add_filter(filter, extensions)
+            .pick_file()
+    })
+}
+
+pub fn save_file(
+    purpose: Purpose,
+    file_name: String,
+    filter: &'static str,
+    extensions: &'static [&'static str],
+) -> PendingDialog {
+    spawn(purpose, move || {
+        rfd::FileDialog::new()
+            .set_file_name(file_name)
+            .add_filter(filter, extensions)
+            .save_file()
+    })
+}
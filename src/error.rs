@@ -0,0 +1,86 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Failures that map to a dedicated process exit code.
+///
+/// Exit codes:
+/// - 0: clean shutdown
+/// - 1: any other error
+/// - 2: camera could not be opened or initialized, or its pixel format is unsupported
+/// - 3: invalid command-line arguments or config
+/// - 4: `--timeout` elapsed without any motion
+/// - 5: `self-test` or `--dry-run` found a failing check
+/// - 6: the camera stopped delivering frames and `--reconnect-attempts` ran out
+/// - 7: the output directory can't be created or written to
+#[derive(Debug)]
+pub enum DetectorError {
+    CameraOpen(String),
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    UnsupportedFormat(String),
+    InvalidArguments(String),
+    NoMotionTimeout(Duration),
+    /// Number of failed checks
+    SelfTestFailed(usize),
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    CameraLost(String),
+    OutputDir(String),
+}
+
+impl DetectorError {
+    pub const EXIT_OTHER: i32 = 1;
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DetectorError::CameraOpen(_) | DetectorError::UnsupportedFormat(_) => 2,
+            DetectorError::InvalidArguments(_) => 3,
+            DetectorError::NoMotionTimeout(_) => 4,
+            DetectorError::SelfTestFailed(_) => 5,
+            DetectorError::CameraLost(_) => 6,
+            DetectorError::OutputDir(_) => 7,
+        }
+    }
+}
+
+impl fmt::Display for DetectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetectorError::CameraOpen(msg) => write!(f, "Camera error: {}", msg),
+            DetectorError::UnsupportedFormat(format) => write!(
+                f,
+                "Unsupported camera pixel format {} (expected 8-bit gray, YUYV, BGR or BGRA)",
+                format
+            ),
+            DetectorError::InvalidArguments(msg) => write!(f, "Invalid arguments: {}", msg),
+            DetectorError::NoMotionTimeout(timeout) => {
+                write!(f, "No motion detected within {}s", timeout.as_secs())
+            }
+            DetectorError::SelfTestFailed(count) => {
+                write!(f, "{} check(s) failed", count)
+            }
+            DetectorError::CameraLost(msg) => write!(f, "Camera lost: {}", msg),
+            DetectorError::OutputDir(msg) => write!(f, "Output directory unusable: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for DetectorError {}
+
+/// The exit codes as `--help` lists them, the same as on `DetectorError`
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  Clean shutdown
+  1  Any other error
+  2  Camera could not be opened, or its pixel format is unsupported
+  3  Invalid arguments or config
+  4  --timeout elapsed without any motion
+  5  self-test or --dry-run found a failing check
+  6  Camera stopped delivering frames and --reconnect-attempts ran out
+  7  Output directory can't be created or written to";
+
+/// Exit code for an error returned from `main`'s run functions
+pub fn exit_code_for(error: &anyhow::Error) -> i32 {
+    error
+        .downcast_ref::<DetectorError>()
+        .map(DetectorError::exit_code)
+        .unwrap_or(DetectorError::EXIT_OTHER)
+}
@@ -0,0 +1,129 @@
+use std::time::{Duration, Instant};
+
+/// How far back the activity estimate looks, roughly
+const ACTIVITY_WINDOW: Duration = Duration::from_secs(60);
+/// Motion frames closer together than this count as continuous motion
+const CONTINUOUS_MOTION: Duration = Duration::from_secs(1);
+
+/// Turns a stream of motion frames into events, at most one per cooldown.
+/// The cooldown moves between `min` and `max` with the share of recent time
+/// that had motion, so a scene that never stops moving produces occasional
+/// summary events instead of one every few seconds.
+pub struct Debouncer {
+    min: Duration,
+    max: Duration,
+    last_event: Instant,
+    last_motion: Option<Instant>,
+    /// 0.0 (no recent motion) to 1.0 (motion the whole time)
+    activity: f64,
+}
+
+impl Debouncer {
+    /// A cooldown between `min` and `max` that follows the recent activity;
+    /// equal bounds give a fixed cooldown. The first window starts at `start`,
+    /// so motion right after the camera opens (exposure settling) doesn't count
+    pub fn adaptive(min: Duration, max: Duration, start: Instant) -> Self {
+        Self {
+            min,
+            max: max.max(min),
+            last_event: start,
+            last_motion: None,
+            activity: 0.0,
+        }
+    }
+
+    /// Whether motion seen at `now` is a new event
+    pub fn ready(&mut self, now: Instant) -> bool {
+        self.activity = self.activity_at(now);
+        self.last_motion = Some(now);
+        if now.duration_since(self.last_event) > self.cooldown(now) {
+            self.last_event = now;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Share of recent time with motion, as of `now`
+    fn activity_at(&self, now: Instant) -> f64 {
+        let Some(last) = self.last_motion else {
+            return self.activity;
+        };
+        let gap = now.saturating_duration_since(last);
+        let keep = (-gap.as_secs_f64() / ACTIVITY_WINDOW.as_secs_f64()).exp();
+        let moving = if gap <= CONTINUOUS_MOTION { 1.0 } else { 0.0 };
+        self.activity * keep + moving * (1.0 - keep)
+    }
+
+    /// The cooldown in effect at `now`
+    pub fn cooldown(&self, now: Instant) -> Duration {
+        self.min + (self.max - self.min).mul_f64(self.activity_at(now).clamp(0.0, 1.0))
+    }
+}
+
+/// A new motion event, as returned by `MotionDetector::poll`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MotionEvent {
+    /// Events so far, counting this one
+    pub number: u64,
+    /// The object that started it, when events follow tracks (`--track`)
+    pub track: Option<u64>,
+}
+
+/// Turns what each frame saw into numbered events: one per new track when
+/// events follow tracks, otherwise one per motion frame the debouncer lets
+/// through. Nothing is an event while disarmed.
+pub struct EventSource {
+    debouncer: Debouncer,
+    pub armed: bool,
+    count: u64,
+}
+
+impl EventSource {
+    pub fn new(debouncer: Debouncer) -> Self {
+        Self {
+            debouncer,
+            armed: true,
+            count: 0,
+        }
+    }
+
+    /// The events in a frame captured at `now`. `new_tracks` is `Some` when
+    /// events follow tracks.
+    pub fn frame(
+        &mut self,
+        now: Instant,
+        motion: bool,
+        new_tracks: Option<&[u64]>,
+    ) -> Vec<MotionEvent> {
+        if !self.armed {
+            return Vec::new();
+        }
+        let tracks: Vec<Option<u64>> = match new_tracks {
+            Some(tracks) => tracks.iter().map(|&id| Some(id)).collect(),
+            None if motion && self.debouncer.ready(now) => vec![None],
+            None => Vec::new(),
+        };
+        tracks
+            .into_iter()
+            .map(|track| {
+                self.count += 1;
+                MotionEvent {
+                    number: self.count,
+                    track,
+                }
+            })
+            .collect()
+    }
+
+    /// Events so far
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The cooldown in effect at `now`
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn cooldown(&self, now: Instant) -> Duration {
+        self.debouncer.cooldown(now)
+    }
+}
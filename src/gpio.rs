@@ -0,0 +1,145 @@
+//! Relay or siren on a Raspberry Pi GPIO pin (`--gpio-pin`). The pin goes
+//! active when an event fires and is released `--gpio-hold` after the last
+//! motion, or as soon as the detector is disarmed.
+
+// Without the `gpio` feature only the sequencing and the tests use this
+#![cfg_attr(not(feature = "gpio"), allow(dead_code))]
+
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+/// `--gpio-pin`, `--gpio-active-high` and `--gpio-hold`
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpioOptions {
+    /// BCM pin number
+    pub pin: u8,
+    /// Drive the pin high while active; most relay boards switch on low
+    pub active_high: bool,
+    pub hold: Duration,
+}
+
+/// The output the relay drives, so tests can swap in a fake pin
+pub trait RelayPin: Send {
+    fn set(&mut self, active: bool) -> Result<()>;
+}
+
+pub struct Relay {
+    pin: Box<dyn RelayPin>,
+    hold: Duration,
+    active: bool,
+    last_motion: Option<Instant>,
+}
+
+impl Relay {
+    pub fn new(pin: Box<dyn RelayPin>, hold: Duration) -> Self {
+        Self {
+            pin,
+            hold,
+            active: false,
+            last_motion: None,
+        }
+    }
+
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Feed one frame: `event` when it raised a motion event, `motion` when
+    /// it had any motion at all. Returns the new state when the pin changed.
+    pub fn update(&mut self, now: Instant, event: bool, motion: bool, armed: bool) -> Option<bool> {
+        if !armed {
+            self.last_motion = None;
+            return self.switch(false);
+        }
+        if event || (self.active && motion) {
+            self.last_motion = Some(now);
+        }
+        if event {
+            return self.switch(true);
+        }
+        let expired = self
+            .last_motion
+            .is_none_or(|last| now.duration_since(last) >= self.hold);
+        if self.active && expired {
+            return self.switch(false);
+        }
+        None
+    }
+
+    fn switch(&mut self, active: bool) -> Option<bool> {
+        if self.active == active {
+            return None;
+        }
+        // A failed write is retried on the next frame
+        if let Err(e) = self.pin.set(active) {
+            eprintln!("Warning: failed to switch GPIO relay: {:#}", e);
+            return None;
+        }
+        self.active = active;
+        Some(active)
+    }
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        // Never leave a siren sounding after the detector exits
+        if self.active {
+            let _ = self.pin.set(false);
+        }
+    }
+}
+
+#[cfg(feature = "gpio")]
+struct RppalPin {
+    pin: rppal::gpio::OutputPin,
+    active_high: bool,
+}
+
+#[cfg(feature = "gpio")]
+impl RelayPin for RppalPin {
+    fn set(&mut self, active: bool) -> Result<()> {
+        if active == self.active_high {
+            self.pin.set_high();
+        } else {
+            self.pin.set_low();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "gpio")]
+fn open_pin(options: &GpioOptions) -> Result<Box<dyn RelayPin>> {
+    use anyhow::Context;
+
+    let gpio = rppal::gpio::Gpio::new().context("GPIO is not available")?;
+    let pin = gpio
+        .get(options.pin)
+        .with_context(|| format!("Failed to claim GPIO pin {}", options.pin))?;
+    let mut pin = RppalPin {
+        pin: pin.into_output(),
+        active_high: options.active_high,
+    };
+    pin.set(false)?;
+    Ok(Box::new(pin))
+}
+
+#[cfg(not(feature = "gpio"))]
+fn open_pin(_options: &GpioOptions) -> Result<Box<dyn RelayPin>> {
+    anyhow::bail!("built without the `gpio` feature")
+}
+
+/// The relay on `--gpio-pin`. Detection carries on without it when the pin
+/// can't be set up, e.g. off a Pi or without access to /dev/gpiomem.
+pub fn open(options: &GpioOptions) -> Option<Relay> {
+    match open_pin(options) {
+        Ok(pin) => Some(Relay::new(pin, options.hold)),
+        Err(e) => {
+            eprintln!(
+                "Warning: GPIO relay on pin {} disabled: {:#}",
+                options.pin, e
+            );
+            None
+        }
+    }
+}
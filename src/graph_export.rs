@@ -0,0 +1,480 @@
+//! Exporting the GUI's motion graph as a PNG, e.g. to attach a night's
+//! activity to a message. The graph panel only shows the last 100 frames,
+//! so the GUI also keeps a `Timeline` of the whole session at one sample
+//! per second. An export draws a chosen range of it offscreen, with axes,
+//! the time range and the camera name, on a worker thread.
+
+// Without the `camera` feature there is no GUI to use this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local};
+use crossbeam_channel::{Receiver, TryRecvError};
+use image::{ImageFormat, Rgb, RgbImage};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::thread;
+
+/// Seconds of history the timeline keeps, two days
+pub const TIMELINE_SECONDS: usize = 2 * 24 * 3600;
+
+/// Size of the exported image in pixels
+pub const IMAGE_SIZE: (u32, u32) = (1200, 500);
+
+/// One second of the session
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    pub at: DateTime<Local>,
+    /// Highest score of the second's frames
+    pub score: f32,
+    /// Any frame of the second had motion
+    pub motion: bool,
+    /// Motion events fired during the second
+    pub events: u32,
+}
+
+/// The session's scores, one sample per second, oldest first
+#[derive(Clone, Debug, Default)]
+pub struct Timeline {
+    samples: VecDeque<Sample>,
+}
+
+impl Timeline {
+    /// A frame scored `score` at `at`
+    pub fn record(&mut self, at: DateTime<Local>, score: f32, motion: bool) {
+        let sample = self.sample_at(at);
+        sample.score = sample.score.max(score);
+        sample.motion |= motion;
+    }
+
+    /// A motion event fired at `at`
+    pub fn record_event(&mut self, at: DateTime<Local>) {
+        self.sample_at(at).events += 1;
+    }
+
+    /// The sample of `at`'s second, started if it is a new one
+    fn sample_at(&mut self, at: DateTime<Local>) -> &mut Sample {
+        let same_second = self
+            .samples
+            .back()
+            .is_some_and(|last| last.at.timestamp() == at.timestamp());
+        if !same_second {
+            if self.samples.len() == TIMELINE_SECONDS {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(Sample {
+                at,
+                score: 0.0,
+                motion: false,
+                events: 0,
+            });
+        }
+        self.samples.back_mut().expect("a sample was just pushed")
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// When the oldest sample kept was taken
+    pub fn start(&self) -> Option<DateTime<Local>> {
+        self.samples.front().map(|sample| sample.at)
+    }
+
+    /// The samples from `from` up to `to`
+    pub fn range(&self, from: DateTime<Local>, to: DateTime<Local>) -> Vec<Sample> {
+        self.samples
+            .iter()
+            .filter(|sample| sample.at >= from && sample.at <= to)
+            .copied()
+            .collect()
+    }
+}
+
+/// How much of the timeline an export covers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphRange {
+    LastHour,
+    Last8Hours,
+    Last24Hours,
+    /// Everything the timeline kept
+    Session,
+}
+
+impl GraphRange {
+    pub const ALL: [GraphRange; 4] = [
+        GraphRange::LastHour,
+        GraphRange::Last8Hours,
+        GraphRange::Last24Hours,
+        GraphRange::Session,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GraphRange::LastHour => "Last hour",
+            GraphRange::Last8Hours => "Last 8 hours",
+            GraphRange::Last24Hours => "Last 24 hours",
+            GraphRange::Session => "Whole session",
+        }
+    }
+
+    /// Where the range starts when it ends at `now`
+    pub fn start(&self, timeline: &Timeline, now: DateTime<Local>) -> DateTime<Local> {
+        match self {
+            GraphRange::LastHour => now - Duration::hours(1),
+            GraphRange::Last8Hours => now - Duration::hours(8),
+            GraphRange::Last24Hours => now - Duration::hours(24),
+            GraphRange::Session => timeline.start().unwrap_or(now).min(now),
+        }
+    }
+}
+
+/// Everything an export draws, copied out of the GUI for the worker thread
+#[derive(Clone, Debug)]
+pub struct GraphRequest {
+    pub samples: Vec<Sample>,
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+    /// Score above which a frame has motion
+    pub threshold: f32,
+    pub camera: String,
+}
+
+impl GraphRequest {
+    pub fn new(
+        timeline: &Timeline,
+        range: GraphRange,
+        now: DateTime<Local>,
+        threshold: f32,
+        camera: String,
+    ) -> Self {
+        let from = range.start(timeline, now);
+        Self {
+            samples: timeline.range(from, now),
+            from,
+            to: now,
+            threshold,
+            camera,
+        }
+    }
+}
+
+/// An export still being drawn and written
+pub struct PendingExport {
+    pub path: PathBuf,
+    done: Receiver<Result<(), String>>,
+}
+
+impl PendingExport {
+    /// `None` while the worker is busy, then whether the file was written
+    pub fn poll(&self) -> Option<Result<(), String>> {
+        match self.done.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("the export thread died".to_string())),
+        }
+    }
+}
+
+/// Draw `request` and save it to `path` as a PNG on a worker thread
+pub fn export(request: GraphRequest, path: PathBuf) -> PendingExport {
+    let (sender, done) = crossbeam_channel::bounded(1);
+    let target = path.clone();
+    thread::spawn(move || {
+        let result = render(&request)
+            .and_then(|image| save_png(&image, &target))
+            .map_err(|e| format!("{:#}", e));
+        let _ = sender.send(result);
+    });
+    PendingExport { path, done }
+}
+
+pub fn save_png(image: &RgbImage, path: &std::path::Path) -> Result<()> {
+    image
+        .save_with_format(path, ImageFormat::Png)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+const BACKGROUND: Rgb<u8> = Rgb([20, 20, 25]);
+const GRID: Rgb<u8> = Rgb([40, 40, 45]);
+const AXIS: Rgb<u8> = Rgb([120, 120, 130]);
+const TEXT: Rgb<u8> = Rgb([220, 220, 220]);
+const MUTED: Rgb<u8> = Rgb([150, 150, 160]);
+const QUIET: Rgb<u8> = Rgb([200, 60, 60]);
+const MOTION: Rgb<u8> = Rgb([0, 220, 0]);
+const THRESHOLD: Rgb<u8> = Rgb([255, 255, 0]);
+const EVENT: Rgb<u8> = Rgb([255, 160, 0]);
+
+/// Left, top, right and bottom margins around the plot
+const MARGINS: (u32, u32, u32, u32) = (80, 70, 30, 50);
+
+/// Minutes between time labels, the first that gives at most 8 labels
+fn time_step(span: Duration) -> i64 {
+    const STEPS: [i64; 12] = [1, 2, 5, 10, 15, 30, 60, 120, 180, 360, 720, 1440];
+    let minutes = span.num_minutes().max(1);
+    STEPS
+        .into_iter()
+        .find(|step| minutes / step <= 8)
+        .unwrap_or(2880)
+}
+
+/// Draw the motion score of `request.samples` over the requested range:
+/// one bar per pixel column with the highest score it covers, green where
+/// there was motion, with the threshold, event marks and labelled axes
+pub fn render(request: &GraphRequest) -> Result<RgbImage> {
+    let (width, height) = IMAGE_SIZE;
+    let mut canvas = Canvas::new(width, height)?;
+    let (left, top, right, bottom) = MARGINS;
+    let plot = (left, top, width - right, height - bottom);
+    let plot_width = plot.2 - plot.0;
+    let plot_height = plot.3 - plot.1;
+
+    let span = (request.to - request.from).max(Duration::seconds(1));
+    let column_of = |at: DateTime<Local>| {
+        let offset = (at - request.from).num_milliseconds() as f64;
+        let column = offset / span.num_milliseconds() as f64 * plot_width as f64;
+        (column.max(0.0) as u32).min(plot_width - 1)
+    };
+
+    // Scale so the threshold sits at most halfway up and peaks fit, as in
+    // the graph panel
+    let peak = request
+        .samples
+        .iter()
+        .map(|sample| sample.score)
+        .fold(request.threshold * 2.0, f32::max)
+        .max(f32::EPSILON);
+    let y_of = |score: f32| plot.3 - ((score / peak).min(1.0) * plot_height as f32) as u32;
+
+    canvas.fill(plot.0, plot.1, plot.2, plot.3, BACKGROUND);
+
+    // Score axis: grid and percentages
+    for i in 0..=5 {
+        let score = peak * i as f32 / 5.0;
+        let y = y_of(score);
+        canvas.fill(plot.0, y, plot.2, y + 1, GRID);
+        canvas.text(
+            plot.0 as f32 - 8.0,
+            y as f32,
+            &format!("{:.1}%", score * 100.0),
+            13.0,
+            MUTED,
+            Anchor::Right,
+        );
+    }
+
+    // Time axis: labels on whole steps of local time
+    let step = time_step(span) * 60;
+    let offset = request.from.offset().local_minus_utc() as i64;
+    let first = ((request.from.timestamp() + offset).div_euclid(step) + 1) * step - offset;
+    let multi_day = request.from.date_naive() != request.to.date_naive();
+    let mut tick = first;
+    while tick < request.to.timestamp() {
+        if let Some(at) = DateTime::from_timestamp(tick, 0) {
+            let at = at.with_timezone(&Local);
+            let x = plot.0 + column_of(at);
+            canvas.fill(x, plot.1, x + 1, plot.3, GRID);
+            canvas.fill(x, plot.3, x + 1, plot.3 + 5, AXIS);
+            let label = if multi_day {
+                at.format("%a %H:%M")
+            } else {
+                at.format("%H:%M")
+            };
+            canvas.text(
+                x as f32,
+                plot.3 as f32 + 20.0,
+                &label.to_string(),
+                13.0,
+                MUTED,
+                Anchor::Center,
+            );
+        }
+        tick += step;
+    }
+
+    // The highest score and any motion of each column
+    let mut columns: Vec<Option<(f32, bool)>> = vec![None; plot_width as usize];
+    for sample in &request.samples {
+        let column = &mut columns[column_of(sample.at) as usize];
+        let (score, motion) = column.get_or_insert((0.0, false));
+        *score = score.max(sample.score);
+        *motion |= sample.motion;
+    }
+    for (x, column) in columns.iter().enumerate() {
+        if let Some((score, motion)) = column {
+            let x = plot.0 + x as u32;
+            let color = if *motion { MOTION } else { QUIET };
+            canvas.fill(x, y_of(*score).min(plot.3 - 1), x + 1, plot.3, color);
+        }
+    }
+
+    let threshold = y_of(request.threshold);
+    canvas.fill(
+        plot.0,
+        threshold.saturating_sub(1),
+        plot.2,
+        threshold + 1,
+        THRESHOLD,
+    );
+
+    // Events as marks along the top of the plot
+    for sample in request.samples.iter().filter(|sample| sample.events > 0) {
+        let x = plot.0 + column_of(sample.at);
+        canvas.fill(x.saturating_sub(1), plot.1, x + 2, plot.1 + 10, EVENT);
+    }
+
+    // Axes, titles and the key
+    canvas.fill(plot.0, plot.1, plot.0 + 1, plot.3, AXIS);
+    canvas.fill(plot.0, plot.3, plot.2, plot.3 + 1, AXIS);
+    canvas.text(
+        left as f32,
+        28.0,
+        &format!("Motion score: {}", request.camera),
+        20.0,
+        TEXT,
+        Anchor::Left,
+    );
+    let events: u32 = request.samples.iter().map(|sample| sample.events).sum();
+    canvas.text(
+        left as f32,
+        52.0,
+        &format!(
+            "{} to {}, {} event(s)",
+            request.from.format("%Y-%m-%d %H:%M"),
+            request.to.format("%Y-%m-%d %H:%M"),
+            events
+        ),
+        14.0,
+        MUTED,
+        Anchor::Left,
+    );
+    let key = [
+        (MOTION, "motion"),
+        (QUIET, "no motion"),
+        (THRESHOLD, "threshold"),
+        (EVENT, "event"),
+    ];
+    let mut x = plot.2 as f32;
+    for (color, label) in key.iter().rev() {
+        x = canvas.text(x, 52.0, label, 13.0, MUTED, Anchor::Right) - 8.0;
+        canvas.fill(x as u32 - 10, 46, x as u32, 56, *color);
+        x -= 22.0;
+    }
+    canvas.text(
+        plot.0 as f32 - 8.0,
+        plot.1 as f32 - 14.0,
+        "Score",
+        13.0,
+        MUTED,
+        Anchor::Right,
+    );
+    canvas.text(
+        plot.2 as f32,
+        height as f32 - 8.0,
+        "Time",
+        13.0,
+        MUTED,
+        Anchor::Right,
+    );
+    if request.samples.is_empty() {
+        canvas.text(
+            (plot.0 + plot.2) as f32 / 2.0,
+            (plot.1 + plot.3) as f32 / 2.0,
+            "No motion data in this range",
+            16.0,
+            MUTED,
+            Anchor::Center,
+        );
+    }
+    Ok(canvas.image)
+}
+
+/// Which end of the text its x coordinate is
+#[derive(Clone, Copy)]
+enum Anchor {
+    Left,
+    Center,
+    Right,
+}
+
+/// An image to draw rectangles and text onto
+struct Canvas {
+    image: RgbImage,
+    font: FontVec,
+}
+
+impl Canvas {
+    /// Text uses egui's built-in font, so nothing is loaded from disk
+    fn new(width: u32, height: u32) -> Result<Self> {
+        let data = egui::FontDefinitions::default()
+            .font_data
+            .remove("Ubuntu-Light")
+            .context("egui's default font is missing")?;
+        let font = FontVec::try_from_vec(data.font.into_owned())
+            .context("Failed to load egui's default font")?;
+        Ok(Self {
+            image: RgbImage::from_pixel(width, height, Rgb([12, 12, 15])),
+            font,
+        })
+    }
+
+    /// Fill the pixels from (`x0`, `y0`) up to, not including, (`x1`, `y1`)
+    fn fill(&mut self, x0: u32, y0: u32, x1: u32, y1: u32, color: Rgb<u8>) {
+        for y in y0..y1.min(self.image.height()) {
+            for x in x0..x1.min(self.image.width()) {
+                self.image.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    /// Write `text` with its baseline at `y`; returns the x of its other end
+    fn text(
+        &mut self,
+        x: f32,
+        y: f32,
+        text: &str,
+        size: f32,
+        color: Rgb<u8>,
+        anchor: Anchor,
+    ) -> f32 {
+        let scale = PxScale::from(size);
+        let scaled = self.font.as_scaled(scale);
+        let width: f32 = text
+            .chars()
+            .map(|c| scaled.h_advance(scaled.glyph_id(c)))
+            .sum();
+        let start = match anchor {
+            Anchor::Left => x,
+            Anchor::Center => x - width / 2.0,
+            Anchor::Right => x - width,
+        };
+        let mut caret = start;
+        for c in text.chars() {
+            let id = scaled.glyph_id(c);
+            let glyph = id.with_scale_and_position(scale, point(caret, y));
+            caret += scaled.h_advance(id);
+            let Some(outline) = self.font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            let image = &mut self.image;
+            outline.draw(|gx, gy, coverage| {
+                let px = bounds.min.x as i64 + gx as i64;
+                let py = bounds.min.y as i64 + gy as i64;
+                if px < 0 || py < 0 || px >= image.width() as i64 || py >= image.height() as i64 {
+                    return;
+                }
+                let pixel = image.get_pixel_mut(px as u32, py as u32);
+                for (channel, target) in pixel.0.iter_mut().zip(color.0) {
+                    let blended = *channel as f32 + (target as f32 - *channel as f32) * coverage;
+                    *channel = blended.round().clamp(0.0, 255.0) as u8;
+                }
+            });
+        }
+        match anchor {
+            Anchor::Right => start,
+            Anchor::Left | Anchor::Center => start + width,
+        }
+    }
+}
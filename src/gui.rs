@@ -1,164 +1,1523 @@
-use chrono::{DateTime, Local};
-use crossbeam_channel::{Receiver, Sender};
+use crate::absence::{AbsenceAlert, AbsenceStatus};
+use crate::alerts::{AlertKind, AppAlert, NotificationCenter, Severity};
+use crate::buildinfo::BuildInfo;
+use crate::dialogs::{self, PendingDialog, Purpose};
+use crate::graph_export::{self, GraphRange, GraphRequest, PendingExport, Timeline};
+use crate::hotplug::{self, CameraInfo};
+use crate::humanize::{humanize_ago, humanize_duration};
+use crate::incident::{self, Acknowledgement, IncidentFilter};
+use crate::intrusion::IntrusionState;
+use crate::journal::{Journal, Session};
+use crate::mask_editor::{Brush, MaskEditor, MaskImage};
+use crate::monitor::{ResourceUsage, StageTimings};
+use crate::overlay::{OverlayConfig, OverlayPosition};
+use crate::phase::DetectorPhase;
+use crate::regions::MinArea;
+use crate::rules::RuleHit;
+use crate::schedule::{Schedule, ScheduleClock};
+use crate::selftest::{self, CheckResult, CheckStatus};
+use crate::sensitivity::SensitivityCurve;
+use crate::sun::{self, Location, SunTimes};
+use crate::switch::DeviceChange;
+use crate::timestamp::{format_position, FrameTime};
+use crate::tuning::{TuneParams, TuneResult};
+use chrono::{DateTime, Datelike, Local, Timelike};
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
 use eframe;
 use eframe::egui::*;
+use serde::Deserialize;
 use std::collections::VecDeque;
+use std::path::PathBuf;
+
+/// GUI settings, read from the `[gui]` config section
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct GuiConfig {
+    /// Activity log entries kept before the oldest are dropped
+    pub log_capacity: usize,
+    /// Largest `[width, height]` the preview is drawn at, in points
+    pub preview_max_size: [f32; 2],
+    /// The close button minimizes the window and detection keeps running;
+    /// File ▸ Quit still quits
+    pub minimize_on_close: bool,
+    /// Flash the taskbar entry on motion events while minimized
+    pub notify_when_hidden: bool,
+}
+
+impl Default for GuiConfig {
+    fn default() -> Self {
+        Self {
+            log_capacity: 1000,
+            preview_max_size: [640.0, 480.0],
+            minimize_on_close: false,
+            notify_when_hidden: true,
+        }
+    }
+}
+
+/// Largest size with the aspect ratio of `size` that fits in `bounds`
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn fit_within(size: [f32; 2], bounds: [f32; 2]) -> [f32; 2] {
+    let [width, height] = size;
+    if width <= 0.0 || height <= 0.0 {
+        return [0.0, 0.0];
+    }
+    let scale = (bounds[0] / width).min(bounds[1] / height).max(0.0);
+    [width * scale, height * scale]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Event,
+    Error,
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl LogLevel {
+    pub const ALL: [LogLevel; 4] = [
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Event,
+        LogLevel::Error,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "Debug",
+            LogLevel::Info => "Info",
+            LogLevel::Event => "Event",
+            LogLevel::Error => "Error",
+        }
+    }
+
+    fn color(&self) -> Color32 {
+        match self {
+            LogLevel::Debug => Color32::GRAY,
+            LogLevel::Info => Color32::LIGHT_GRAY,
+            LogLevel::Event => Color32::YELLOW,
+            LogLevel::Error => Color32::RED,
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct LogEntry {
+    pub level: LogLevel,
+    pub time: DateTime<Local>,
+    pub message: String,
+}
+
+/// Activity log with a retention cap, oldest entries dropped first
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct ActivityLog {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl ActivityLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn push(&mut self, level: LogLevel, message: impl Into<String>) {
+        self.entries.push_back(LogEntry {
+            level,
+            time: Local::now(),
+            message: message.into(),
+        });
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.level == LogLevel::Error)
+            .count()
+    }
+
+    /// All entries as CSV with a `time,level,message` header
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("time,level,message\n");
+        for entry in &self.entries {
+            csv.push_str(&format!(
+                "{},{},\"{}\"\n",
+                entry.time.to_rfc3339(),
+                entry.level.label(),
+                entry.message.replace('"', "\"\"")
+            ));
+        }
+        csv
+    }
+
+    /// Entries at one of `levels` whose message contains `search` (case-insensitive)
+    pub fn filtered<'a>(
+        &'a self,
+        levels: &'a [LogLevel],
+        search: &'a str,
+    ) -> impl Iterator<Item = &'a LogEntry> {
+        let search = search.to_lowercase();
+        self.entries.iter().filter(move |entry| {
+            levels.contains(&entry.level)
+                && (search.is_empty() || entry.message.to_lowercase().contains(&search))
+        })
+    }
+}
+
+/// Motion events of the session counted by hour of the day
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct HourlyCounts {
+    counts: [u32; 24],
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl HourlyCounts {
+    pub fn record(&mut self, time: DateTime<Local>) {
+        self.counts[time.hour() as usize] += 1;
+    }
+
+    /// Events per hour, midnight first
+    pub fn counts(&self) -> &[u32; 24] {
+        &self.counts
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    /// Hour with the most events, the earliest on a tie; `None` before any
+    pub fn busiest(&self) -> Option<usize> {
+        let max = *self.counts.iter().max()?;
+        if max == 0 {
+            return None;
+        }
+        self.counts.iter().position(|&count| count == max)
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
 pub enum GuiMessage {
     UpdateSensitivity(f64),
-    UpdateMinArea(u32),
+    UpdateSensitivityCurve(SensitivityCurve),
+    UpdateMinArea(MinArea),
+    /// Opening kernel size (0 for off) and iterations
+    UpdateOpening(i32, i32),
+    /// Frames per second to process at most; 0 follows the camera
+    UpdateTargetFps(f64),
     UpdateDevice(u32),
     StartDetection,
     StopDetection,
     SaveSnapshot,
+    UpdateOverlay(OverlayConfig),
+    SetPreviewEnabled(bool),
+    UpdateOutputDir(PathBuf),
+    /// New arming schedule; `None` arms detection all day
+    UpdateSchedule(Option<Schedule>),
+    /// Run detection on a recorded video instead of the camera
+    OpenFile(PathBuf),
+    /// Start the video file over when it ends
+    SetLoopFile(bool),
+    RunSelfTest,
+    /// Pause detection while the camera is moved, or resume it
+    ToggleRepositioning,
+    /// Take the next frame as the empty scene and raise an alarm on any
+    /// lasting difference from it (intrusion mode)
+    Arm,
+    /// Back to comparing with the previous frames; silences the alarm
+    Disarm,
+    /// Turn `--observe` on, or go live
+    SetObserve(bool),
+    /// Replay the last event with these trial settings while the tuning
+    /// window is open; `None` when it closes
+    Tune(Option<TuneParams>),
+    /// Mask painted in the editor: pixels row by row, width and height, at
+    /// the processing resolution. A mask that ignores nothing turns it off.
+    UpdateMask(Vec<u8>, usize, usize),
+    /// The window is closing; the detector thread releases the camera and exits
+    Shutdown,
 }
 
-#[derive(Clone, Debug)]
+/// Messages waiting for the detector thread, or `None` once it should stop:
+/// on `Shutdown`, or when the GUI side of the channel has been dropped
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn pending_messages(receiver: &Receiver<GuiMessage>) -> Option<Vec<GuiMessage>> {
+    let mut messages = Vec::new();
+    loop {
+        match receiver.try_recv() {
+            Ok(GuiMessage::Shutdown) | Err(TryRecvError::Disconnected) => return None,
+            Ok(message) => messages.push(message),
+            Err(TryRecvError::Empty) => return Some(messages),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
 pub struct MotionState {
     pub motion_detected: bool,
+    /// Set only on frames that produced a (debounced) motion event
+    pub incident_id: Option<u64>,
+    /// The frame's event was a `--re-alert` follow-up of the open incident
+    pub followup: bool,
+    /// Tracks opened by this frame (empty unless tracking is enabled)
+    pub new_tracks: Vec<u64>,
+    pub active_tracks: Vec<u64>,
+    /// Zone transition rules matched in this frame
+    pub rule_hits: Vec<RuleHit>,
     pub motion_count: u32,
+    /// `None` until the first sample, or where the platform can't report it
+    pub resources: Option<ResourceUsage>,
+    pub timings: StageTimings,
+    /// Stale frames dropped before this one (`--grab-latest`)
+    pub frames_skipped: u32,
+    /// Event snapshots skipped as look-alikes of a recent one
+    pub snapshots_deduplicated: u64,
+    /// Raw contours in this frame and the motion regions left after merging
+    pub contours: usize,
+    pub regions: usize,
+    /// Largest motion region's share of the frame (0-1)
+    pub score: f32,
+    /// The score above which a frame counts as motion: min_area's share
+    pub score_threshold: f32,
     pub last_motion_time: Option<DateTime<Local>>,
     pub fps: f32,
+    /// Size of the captured frames, which snapshots and clips keep
     pub resolution: (i32, i32),
+    /// Size detection runs at, smaller than `resolution` with `--process-scale`
+    pub process_resolution: (i32, i32),
+    /// No-motion countdown, `None` when the alarm isn't configured
+    pub absence: Option<AbsenceStatus>,
+    pub absence_alert: Option<AbsenceAlert>,
+    /// Set when detection stopped on a problem retrying won't fix
+    pub error: Option<String>,
+    /// Set only on states that raise a notification, from the detector
+    /// thread or a background worker
+    pub alert: Option<AppAlert>,
+    /// Set only on the reply to `GuiMessage::UpdateDevice`: the camera
+    /// asked for and the one now in use
+    pub device_change: Option<DeviceChange>,
+    /// Set only on the reply to `GuiMessage::OpenFile`: the video now playing
+    pub active_file: Option<PathBuf>,
+    /// Set when the connected cameras changed, and once at startup
+    pub cameras: Option<Vec<CameraInfo>>,
+    /// The video file ended and detection stopped
+    pub file_finished: bool,
+    /// Set only on the reply to `GuiMessage::RunSelfTest`
+    pub self_test: Option<Vec<CheckResult>>,
+    /// Encoder picked for `--record`, `None` when not recording
+    pub recording: Option<String>,
+    /// `--target-fps` or the GUI's limit, against `fps` achieved
+    pub target_fps: Option<f64>,
+    /// Frames per second the clip encoder keeps up with
+    pub encode_fps: Option<f64>,
+    /// Snapshot saved for the event on this frame, if any
+    pub event_snapshot: Option<String>,
+    /// Clips `--trim-clips` finished cutting: incident, full and trimmed length
+    pub clip_lengths: Vec<(u64, std::time::Duration, std::time::Duration)>,
+    /// Time until motion counts as a new event, grows with activity
+    pub cooldown: Option<std::time::Duration>,
+    /// Whether the `--gpio-pin` relay is switched on, `None` without a relay
+    pub relay: Option<bool>,
+    /// Set only on the reply to `GuiMessage::SaveSnapshot`: the file written,
+    /// or why nothing was
+    pub snapshot: Option<Result<String, String>>,
+    /// Set only on a replay for the tuning window: how the last event
+    /// looks with its trial settings, or why the replay failed
+    pub tuning: Option<Result<TuneResult, String>>,
+    /// Set on every detection frame: what the detector is doing
+    pub phase: Option<DetectorPhase>,
+    /// Observe mode: events are reported but nothing is saved or switched
+    pub observing: bool,
+    /// What observe mode skipped, once per kind of side effect
+    pub observed: Vec<String>,
+    /// Detection is paused while the camera is moved
+    pub repositioning: bool,
+    /// Set on the frame repositioning started or ended
+    pub reposition: Option<String>,
+    /// Intrusion mode (`GuiMessage::Arm`)
+    pub intrusion: IntrusionState,
+    /// Set on the frame a wall-clock jump was noticed
+    pub clock_jump: Option<String>,
+    /// Set on the first frame after the camera was reopened
+    pub reconnected: bool,
+    /// When the frame was captured, and how
+    pub frame_time: Option<FrameTime>,
+    /// Set once at startup when a mask was loaded, for the mask editor
+    pub mask: Option<MaskImage>,
 }
 
+/// RGB8 frame for the live preview, already carrying the overlay
+#[derive(Clone)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct PreviewFrame {
+    pub width: usize,
+    pub height: usize,
+    pub rgb: Vec<u8>,
+}
+
+/// A `[camera.<name>]` config block as edited in the settings panel.
+/// Edits last for this session; they aren't written back to the file.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct CameraPreset {
+    pub name: String,
+    pub device: u32,
+    pub sensitivity: f64,
+    pub min_area: MinArea,
+    pub output_dir: PathBuf,
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
 pub struct MotionDetectorGui {
     pub sender: Sender<GuiMessage>,
     pub state_receiver: Option<Receiver<MotionState>>,
+    pub preview_receiver: Option<Receiver<PreviewFrame>>,
+    pub overlay_config: OverlayConfig,
+    pub output_dir: PathBuf,
+    /// False with --no-snapshot: motion events don't write images
+    pub event_snapshots: bool,
 
     // Settings
     sensitivity: f64,
-    min_area: u32,
+    min_area: MinArea,
     device: u32,
 
     // Status
-    detector_status: DetectorStatus,
+    detector_status: DetectorPhase,
+    started: std::time::Instant,
     is_detecting: bool,
     motion_state: MotionState,
 
-    // Camera info
-    available_cameras: Vec<String>,
+    // Camera info: device index and label
+    available_cameras: Vec<(u32, String)>,
+    /// Connected cameras as last reported by the detector
+    scanned_cameras: Option<Vec<CameraInfo>>,
+    /// Named cameras from the config; when present they replace the device list
+    cameras: Vec<CameraPreset>,
+    /// Index into `cameras` whose settings the panel edits
+    selected_camera: Option<usize>,
+
+    // Arming schedule
+    pub location: Option<Location>,
+    schedule: Option<ScheduleClock>,
+    schedule_text: String,
+    schedule_error: Option<String>,
+
+    // Recorded video replacing the camera, for tuning against footage
+    playing_file: Option<PathBuf>,
+    loop_file: bool,
+
+    // Crash recovery: the journal of this session, and one a crashed
+    // session left behind until it is resumed or dismissed
+    journal: Option<Journal>,
+    /// `--sensitivity-curve`, adjustable under Advanced
+    pub sensitivity_curve: SensitivityCurve,
+    /// `--open-kernel` and `--open-iterations`, adjustable under Advanced
+    pub opening: (i32, i32),
+    /// `--target-fps`, adjustable under Advanced; `None` follows the camera
+    pub target_fps: Option<f64>,
+    /// Write the journal to disk on each event (`--flush-on-event`)
+    pub flush_on_event: bool,
+    /// `--observe`, until "Go live" is clicked
+    pub observe: bool,
+    /// Why the last camera switch didn't give the camera asked for
+    device_problem: Option<String>,
+    /// An event arrived since the journal was last written
+    event_unsaved: bool,
+    session_started: DateTime<Local>,
+    recovered: Option<Session>,
+    /// Asking whether to quit while detection runs
+    confirm_exit: bool,
+    /// Quitting was confirmed, let the next close request through
+    allow_close: bool,
+    /// `[gui] minimize_on_close` and `notify_when_hidden`, also in the View
+    /// menu
+    pub minimize_on_close: bool,
+    pub notify_when_hidden: bool,
+    /// File ▸ Quit was chosen, so the close request isn't a minimize
+    quit_requested: bool,
+    /// Logged that there is no tray to hide the window in
+    minimize_noted: bool,
+
+    /// Open file dialog, answered on another thread
+    pending_dialog: Option<PendingDialog>,
 
     // UI state
     show_about: bool,
-    status_log: Vec<String>,
+    /// Collected the first time the About window opens
+    build_info: Option<BuildInfo>,
+    show_self_test: bool,
+    show_hourly: bool,
+    show_tuning: bool,
+    /// The tuning window's trial settings, applied only on request
+    tune: TuneParams,
+    /// The latest replay for the tuning window
+    tuning: Option<Result<TuneResult, String>>,
+    show_notifications: bool,
+    /// Warnings and errors of the session, behind the bell in the menu bar
+    pub notifications: NotificationCenter,
+    /// The latest critical notification and when it arrived, shown for a
+    /// few seconds
+    toast: Option<(String, std::time::Instant)>,
+    /// `None` while a self-test is running
+    self_test_results: Option<Vec<CheckResult>>,
+    pub activity_log: ActivityLog,
+    log_levels: Vec<LogLevel>,
+    log_search: String,
     auto_scroll: bool,
 
     // Motion graph data
-    motion_history: VecDeque<bool>,
+    /// Score and motion flag of recent frames
+    motion_history: VecDeque<(f32, bool)>,
     max_history_points: usize,
+    /// The whole session at one sample per second, for graph exports
+    timeline: Timeline,
+    /// What "Export graph as image..." covers
+    graph_range: GraphRange,
+    /// Graph image being drawn and saved on a worker thread
+    graph_export: Option<PendingExport>,
+    /// Events by hour of the day, for the "Events by hour" window
+    hourly_counts: HourlyCounts,
 
     // Animation state
     motion_animation_time: f32,
+
+    // Live preview
+    show_preview: bool,
+    preview_texture: Option<TextureHandle>,
+    pub preview_max_size: [f32; 2],
+    /// Painting the ignore mask on the preview
+    editing_mask: bool,
+    mask_editor: MaskEditor,
+    /// Tinted mask drawn over the preview while editing, rebuilt when `None`
+    mask_texture: Option<TextureHandle>,
+
+    // Motion events grouped by incident, newest last
+    incidents: VecDeque<IncidentRow>,
+    incident_filter: IncidentFilter,
+    /// "From" and "to" dates as typed, YYYY-MM-DD
+    incident_dates: (String, String),
+    /// Page of the filtered incident list, newest first
+    incident_page: usize,
+}
+
+/// The replayed scores over time, with the threshold and the moment the
+/// recorded event fired
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+fn render_tuning_chart(ui: &mut Ui, result: &TuneResult) {
+    let (rect, response) = ui.allocate_exact_size(vec2(360.0, 120.0), Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, Color32::from_rgb(20, 20, 25));
+    let (Some(&(start, _)), Some(&(end, _))) = (result.scores.first(), result.scores.last()) else {
+        return;
+    };
+    let span = (end - start).max(1e-3);
+    let top = result
+        .scores
+        .iter()
+        .map(|&(_, score)| score)
+        .fold(result.threshold, f64::max)
+        * 1.2;
+    let point = |secs: f64, score: f64| {
+        pos2(
+            rect.min.x + ((secs - start) / span) as f32 * rect.width(),
+            rect.max.y - (score / top.max(1e-9)) as f32 * rect.height(),
+        )
+    };
+
+    let event_x = point(0.0, 0.0).x;
+    painter.line_segment(
+        [pos2(event_x, rect.min.y), pos2(event_x, rect.max.y)],
+        Stroke::new(1.0, Color32::GRAY),
+    );
+    let threshold_y = point(start, result.threshold).y;
+    painter.line_segment(
+        [pos2(rect.min.x, threshold_y), pos2(rect.max.x, threshold_y)],
+        Stroke::new(1.0, Color32::RED),
+    );
+    for pair in result.scores.windows(2) {
+        let color = if pair[1].1 > result.threshold {
+            Color32::from_rgb(255, 120, 120)
+        } else {
+            Color32::from_rgb(80, 160, 255)
+        };
+        painter.line_segment(
+            [point(pair[0].0, pair[0].1), point(pair[1].0, pair[1].1)],
+            Stroke::new(1.5, color),
+        );
+    }
+
+    // Hovering shows the frame under the pointer
+    if let Some(pointer) = response.hover_pos() {
+        let secs = start + ((pointer.x - rect.min.x) / rect.width()) as f64 * span;
+        if let Some(&(at, score)) = result
+            .scores
+            .iter()
+            .min_by(|a, b| (a.0 - secs).abs().total_cmp(&(b.0 - secs).abs()))
+        {
+            response.on_hover_text(format!(
+                "{:+.2} s: score {:.2}% (threshold {:.2}%)",
+                at,
+                score * 100.0,
+                result.threshold * 100.0
+            ));
+        }
+    }
 }
 
+/// Tag chips of an incident not yet acknowledged, with a box to add more
+/// that suggests tags used before
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+fn render_tag_editor(ui: &mut Ui, row: &mut IncidentRow, used: &[(String, usize)]) {
+    ui.horizontal_wrapped(|ui| {
+        row.tags.retain(|tag| {
+            !ui.small_button(format!("🏷 {} ✖", tag))
+                .on_hover_text("Remove")
+                .clicked()
+        });
+        let input = ui.add(
+            TextEdit::singleline(&mut row.tag_input)
+                .hint_text("Add tag, e.g. dog")
+                .desired_width(100.0),
+        );
+        let entered = input.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+        let mut add = entered.then(|| row.tag_input.clone());
+        if input.has_focus() || !row.tag_input.is_empty() {
+            for tag in incident::suggest_tags(used, &row.tag_input, &row.tags) {
+                if ui.small_button(&tag).clicked() {
+                    add = Some(tag);
+                }
+            }
+        }
+        if let Some(tag) = add.as_deref().and_then(incident::normalize_tag) {
+            if !row.tags.contains(&tag) {
+                row.tags.push(tag);
+            }
+            row.tag_input.clear();
+        }
+    });
+}
+
+/// One incident in the GUI list, with the times of its events
 #[derive(Clone, Debug)]
-pub enum DetectorStatus {
-    Stopped,
-    #[allow(dead_code)]
-    Starting,
-    Running,
-    #[allow(dead_code)]
-    Error(String),
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+struct IncidentRow {
+    id: u64,
+    events: Vec<DateTime<Local>>,
+    acknowledged: Option<Acknowledgement>,
+    /// Note being typed before "Acknowledge" is pressed
+    note: String,
+    /// Tags chosen before "Acknowledge" is pressed
+    tags: Vec<String>,
+    /// Tag being typed
+    tag_input: String,
+    /// Full and trimmed length of the incident's clip, with `--trim-clips`
+    clip: Option<(std::time::Duration, std::time::Duration)>,
+    /// `--re-alert` follow-ups while it lasted
+    followups: u32,
 }
 
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
 impl MotionDetectorGui {
     pub fn new_with_sender(_cc: &eframe::CreationContext<'_>, sender: Sender<GuiMessage>) -> Self {
         Self {
             sender,
             state_receiver: None,
-            sensitivity: 0.3,
-            min_area: 500,
+            preview_receiver: None,
+            overlay_config: OverlayConfig::default(),
+            output_dir: PathBuf::from("pics"),
+            event_snapshots: true,
+            sensitivity: crate::config::DEFAULT_SENSITIVITY,
+            min_area: MinArea::default(),
             device: 0,
-            detector_status: DetectorStatus::Stopped,
+            detector_status: DetectorPhase::Stopped,
+            started: std::time::Instant::now(),
             is_detecting: false,
             motion_state: MotionState {
                 motion_detected: false,
+                incident_id: None,
+                followup: false,
+                new_tracks: Vec::new(),
+                active_tracks: Vec::new(),
+                rule_hits: Vec::new(),
                 motion_count: 0,
+                resources: None,
+                timings: StageTimings::default(),
+                frames_skipped: 0,
+                snapshots_deduplicated: 0,
+                contours: 0,
+                regions: 0,
+                score: 0.0,
+                score_threshold: 0.0,
                 last_motion_time: None,
                 fps: 0.0,
                 resolution: (640, 480), // Will be detected at runtime
+                process_resolution: (640, 480),
+                absence: None,
+                absence_alert: None,
+                error: None,
+                alert: None,
+                device_change: None,
+                active_file: None,
+                cameras: None,
+                file_finished: false,
+                self_test: None,
+                recording: None,
+                target_fps: None,
+                encode_fps: None,
+                event_snapshot: None,
+                clip_lengths: Vec::new(),
+                cooldown: None,
+                relay: None,
+                snapshot: None,
+                tuning: None,
+                phase: None,
+                observing: false,
+                observed: Vec::new(),
+                repositioning: false,
+                reposition: None,
+                intrusion: IntrusionState::Disarmed,
+                clock_jump: None,
+                reconnected: false,
+                frame_time: None,
+                mask: None,
             },
-            available_cameras: vec!["Camera 0 - Detecting resolution...".to_string()],
+            available_cameras: vec![(0, "Camera 0 - Detecting resolution...".to_string())],
+            scanned_cameras: None,
+            cameras: Vec::new(),
+            selected_camera: None,
+            location: None,
+            schedule: None,
+            schedule_text: String::new(),
+            schedule_error: None,
+            playing_file: None,
+            loop_file: false,
+            journal: None,
+            sensitivity_curve: SensitivityCurve::default(),
+            opening: (0, 1),
+            target_fps: None,
+            flush_on_event: false,
+            observe: false,
+            device_problem: None,
+            event_unsaved: false,
+            session_started: Local::now(),
+            recovered: None,
+            confirm_exit: false,
+            allow_close: false,
+            minimize_on_close: false,
+            notify_when_hidden: true,
+            quit_requested: false,
+            minimize_noted: false,
+            pending_dialog: None,
             show_about: false,
-            status_log: vec!["GUI Control Panel Started".to_string()],
+            build_info: None,
+            show_self_test: false,
+            show_hourly: false,
+            show_tuning: false,
+            tune: TuneParams {
+                sensitivity: 0.5,
+                min_area: MinArea::default(),
+            },
+            tuning: None,
+            show_notifications: false,
+            notifications: NotificationCenter::default(),
+            toast: None,
+            self_test_results: None,
+            activity_log: {
+                let mut log = ActivityLog::new(GuiConfig::default().log_capacity);
+                log.push(LogLevel::Info, "GUI Control Panel Started");
+                log
+            },
+            log_levels: vec![LogLevel::Info, LogLevel::Event, LogLevel::Error],
+            log_search: String::new(),
             auto_scroll: true,
             motion_history: VecDeque::new(),
             max_history_points: 100,
+            timeline: Timeline::default(),
+            graph_range: GraphRange::Last8Hours,
+            graph_export: None,
+            hourly_counts: HourlyCounts::default(),
             motion_animation_time: 0.0,
+            show_preview: true,
+            preview_texture: None,
+            editing_mask: false,
+            mask_editor: MaskEditor::default(),
+            mask_texture: None,
+            preview_max_size: GuiConfig::default().preview_max_size,
+            incidents: VecDeque::new(),
+            incident_filter: IncidentFilter::default(),
+            incident_dates: (String::new(), String::new()),
+            incident_page: 0,
+        }
+    }
+
+    /// Settings the detector thread starts with
+    pub fn set_settings(&mut self, device: u32, sensitivity: f64, min_area: MinArea) {
+        self.device = device;
+        self.sensitivity = sensitivity;
+        self.min_area = min_area;
+    }
+
+    /// Keep a session journal at `path`, and offer to resume the session an
+    /// earlier crash left there
+    pub fn open_journal(&mut self, path: PathBuf) {
+        match Journal::open(path) {
+            Ok((journal, previous)) => {
+                self.journal = Some(journal);
+                if let Some(previous) = previous {
+                    self.activity_log.push(LogLevel::Error, previous.describe());
+                    self.recovered = Some(previous);
+                }
+            }
+            Err(e) => self.activity_log.push(
+                LogLevel::Error,
+                format!("Session journal disabled: {:#}", e),
+            ),
+        }
+    }
+
+    fn update_journal(&mut self) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+        let session = Session {
+            started: self.session_started.to_rfc3339(),
+            updated: Local::now().to_rfc3339(),
+            events: self.motion_state.motion_count as u64,
+            device: self.device,
+            sensitivity: self.sensitivity,
+            min_area: self.min_area,
+            detecting: self.is_detecting,
+        };
+        let saved = if std::mem::take(&mut self.event_unsaved) && self.flush_on_event {
+            journal.sync(&session)
+        } else {
+            journal.update(&session)
+        };
+        if let Err(e) = saved {
+            self.journal = None;
+            self.activity_log.push(
+                LogLevel::Error,
+                format!("Session journal disabled: {:#}", e),
+            );
+        }
+    }
+
+    /// Restore the settings and arm state of a crashed session
+    fn resume_session(&mut self, session: Session) {
+        self.sensitivity = session.sensitivity;
+        self.min_area = session.min_area;
+        self.store_camera_settings();
+        let _ = self
+            .sender
+            .send(GuiMessage::UpdateSensitivity(session.sensitivity));
+        let _ = self
+            .sender
+            .send(GuiMessage::UpdateMinArea(session.min_area));
+        if session.device != self.device {
+            self.device = session.device;
+            let _ = self.sender.send(GuiMessage::UpdateDevice(session.device));
+        }
+        if session.detecting {
+            self.is_detecting = true;
+            self.detector_status = DetectorPhase::Initializing;
+            let _ = self.sender.send(GuiMessage::StartDetection);
+        }
+        self.activity_log.push(
+            LogLevel::Info,
+            format!(
+                "Resumed previous session (camera {}, sensitivity {:.2}, min area {}{})",
+                session.device,
+                session.sensitivity,
+                session.min_area.describe(None),
+                if session.detecting { ", detecting" } else { "" }
+            ),
+        );
+    }
+
+    /// Named cameras from the config; `selected` is the `--camera` in use
+    pub fn set_cameras(&mut self, cameras: Vec<CameraPreset>, selected: Option<&str>) {
+        self.selected_camera =
+            selected.and_then(|name| cameras.iter().position(|c| c.name == name));
+        self.cameras = cameras;
+    }
+
+    /// Arming schedule from the config
+    pub fn set_schedule(&mut self, schedule: Option<ScheduleClock>) {
+        self.schedule_text = schedule
+            .as_ref()
+            .map(|clock| clock.schedule().text().to_string())
+            .unwrap_or_default();
+        self.schedule = schedule;
+    }
+
+    /// Switch to a named camera and load its settings into the panel
+    fn select_camera(&mut self, index: usize) {
+        let Some(camera) = self.cameras.get(index).cloned() else {
+            return;
+        };
+        self.selected_camera = Some(index);
+        self.sensitivity = camera.sensitivity;
+        self.min_area = camera.min_area;
+        self.output_dir = camera.output_dir.clone();
+        let _ = self
+            .sender
+            .send(GuiMessage::UpdateSensitivity(camera.sensitivity));
+        let _ = self.sender.send(GuiMessage::UpdateMinArea(camera.min_area));
+        let _ = self
+            .sender
+            .send(GuiMessage::UpdateOutputDir(camera.output_dir));
+        if camera.device != self.device {
+            self.device = camera.device;
+            // The detector thread stops detection while it switches
+            self.is_detecting = false;
+            self.detector_status = DetectorPhase::Initializing;
+            self.activity_log.push(
+                LogLevel::Info,
+                format!("Switching to {} (camera {})...", camera.name, camera.device),
+            );
+            let _ = self.sender.send(GuiMessage::UpdateDevice(camera.device));
+        }
+    }
+
+    /// Copy the detection settings of `cameras[from]` into the selected camera
+    fn copy_camera_settings(&mut self, from: usize) {
+        let Some(source) = self.cameras.get(from).cloned() else {
+            return;
+        };
+        self.sensitivity = source.sensitivity;
+        self.min_area = source.min_area;
+        self.store_camera_settings();
+        let _ = self
+            .sender
+            .send(GuiMessage::UpdateSensitivity(source.sensitivity));
+        let _ = self.sender.send(GuiMessage::UpdateMinArea(source.min_area));
+        self.activity_log.push(
+            LogLevel::Info,
+            format!("Copied detection settings from {}", source.name),
+        );
+    }
+
+    /// Keep the panel's values in the selected camera's block
+    fn store_camera_settings(&mut self) {
+        if let Some(camera) = self
+            .selected_camera
+            .and_then(|index| self.cameras.get_mut(index))
+        {
+            camera.sensitivity = self.sensitivity;
+            camera.min_area = self.min_area;
+            camera.output_dir = self.output_dir.clone();
+        }
+    }
+
+    fn update_preview_from_receiver(&mut self, ctx: &egui::Context) {
+        let Some(ref receiver) = self.preview_receiver else {
+            return;
+        };
+
+        // Only the newest frame matters, drop anything older
+        let mut latest = None;
+        while let Ok(frame) = receiver.try_recv() {
+            latest = Some(frame);
+        }
+
+        if let Some(frame) = latest {
+            let image = ColorImage::from_rgb([frame.width, frame.height], &frame.rgb);
+            match self.preview_texture {
+                Some(ref mut texture) => texture.set(image, TextureOptions::LINEAR),
+                None => {
+                    self.preview_texture =
+                        Some(ctx.load_texture("preview", image, TextureOptions::LINEAR));
+                }
+            }
+        }
+    }
+
+    /// Log an alert and add it to the notification center
+    fn raise_alert(&mut self, alert: AppAlert) {
+        let level = match alert.severity {
+            Severity::Warning => LogLevel::Info,
+            Severity::Error | Severity::Critical => LogLevel::Error,
+        };
+        self.activity_log.push(
+            level,
+            format!("⚠ {}: {}", alert.kind.label(), alert.message),
+        );
+        let message = format!("{}: {}", alert.kind.label(), alert.message);
+        if self.notifications.record(alert, Local::now()) {
+            self.toast = Some((message, std::time::Instant::now()));
+        }
+    }
+
+    /// Show the camera the detector thread actually opened. A failed switch
+    /// leaves a banner under the camera list until the next switch.
+    fn device_changed(&mut self, change: DeviceChange) {
+        let message = change.message();
+        match change.actual {
+            Some(actual) => {
+                self.device = actual;
+                self.detector_status = DetectorPhase::Stopped;
+                self.playing_file = None;
+            }
+            None => self.detector_status = DetectorPhase::Error(message.clone()),
+        }
+        if change.succeeded() {
+            self.activity_log.push(LogLevel::Info, message);
+            self.device_problem = None;
+        } else {
+            self.raise_alert(AppAlert::new(
+                AlertKind::DetectorStopped,
+                if change.actual.is_some() {
+                    Severity::Error
+                } else {
+                    Severity::Critical
+                },
+                message.clone(),
+            ));
+            self.device_problem = Some(message);
         }
     }
 
     fn update_settings_from_receiver(&mut self) {
         // Update state from detector thread
+        let mut incident_events = Vec::new();
+        let mut alerts = Vec::new();
+        let mut device_changes = Vec::new();
         if let Some(ref receiver) = self.state_receiver {
             while let Ok(state) = receiver.try_recv() {
+                if let Some(change) = state.device_change {
+                    device_changes.push(change);
+                    continue;
+                }
+                if let Some(mask) = state.mask {
+                    self.mask_editor.mask = Some(mask);
+                    self.mask_texture = None;
+                    continue;
+                }
+                if let Some(alert) = state.alert {
+                    alerts.push(alert);
+                    continue;
+                }
+                if let Some(error) = state.error {
+                    self.detector_status = DetectorPhase::Error(error.clone());
+                    self.is_detecting = false;
+                    self.activity_log
+                        .push(LogLevel::Error, format!("❌ {}", error));
+                    alerts.push(AppAlert::new(
+                        AlertKind::DetectorStopped,
+                        Severity::Critical,
+                        error,
+                    ));
+                    continue;
+                }
+                if let Some(path) = state.active_file {
+                    self.detector_status = DetectorPhase::Stopped;
+                    self.activity_log
+                        .push(LogLevel::Info, format!("Opened {}", path.display()));
+                    self.playing_file = Some(path);
+                    continue;
+                }
+                if let Some(cameras) = state.cameras {
+                    if let Some(previous) = &self.scanned_cameras {
+                        let (added, removed) = hotplug::diff(previous, &cameras);
+                        for camera in added {
+                            self.activity_log.push(
+                                LogLevel::Info,
+                                format!("📷 Camera connected: {}", camera.label()),
+                            );
+                        }
+                        for camera in removed {
+                            self.activity_log.push(
+                                LogLevel::Info,
+                                format!("📷 Camera disconnected: {}", camera.label()),
+                            );
+                        }
+                    }
+                    self.available_cameras = cameras
+                        .iter()
+                        .map(|camera| (camera.index, camera.label()))
+                        .collect();
+                    self.scanned_cameras = Some(cameras);
+                    continue;
+                }
+                if state.file_finished {
+                    self.detector_status = DetectorPhase::Stopped;
+                    self.is_detecting = false;
+                    self.activity_log
+                        .push(LogLevel::Info, "Video file ended, detection stopped");
+                    continue;
+                }
+                if let Some(saved) = state.snapshot {
+                    match saved {
+                        Ok(filename) => self
+                            .activity_log
+                            .push(LogLevel::Info, format!("📸 Snapshot saved: {}", filename)),
+                        Err(e) => alerts.push(AppAlert::new(
+                            AlertKind::SnapshotFailed,
+                            Severity::Error,
+                            e,
+                        )),
+                    }
+                    continue;
+                }
+                if let Some(tuning) = state.tuning {
+                    self.tuning = Some(tuning);
+                    continue;
+                }
+                if let Some(results) = state.self_test {
+                    let failed = results
+                        .iter()
+                        .filter(|r| r.status == CheckStatus::Fail)
+                        .count();
+                    match failed {
+                        0 => self.activity_log.push(LogLevel::Info, "Self-test passed"),
+                        n => self.activity_log.push(
+                            LogLevel::Error,
+                            format!("❌ {} self-test check(s) failed", n),
+                        ),
+                    }
+                    self.self_test_results = Some(results);
+                    continue;
+                }
+
                 let was_motion_detected = self.motion_state.motion_detected;
                 self.motion_state = state.clone();
 
+                for &(id, original, trimmed) in &state.clip_lengths {
+                    if let Some(row) = self.incidents.iter_mut().find(|row| row.id == id) {
+                        row.clip = Some((original, trimmed));
+                    }
+                }
+
                 // Add to motion history for graph
-                self.motion_history.push_back(state.motion_detected);
+                self.motion_history
+                    .push_back((state.score, state.motion_detected));
                 if self.motion_history.len() > self.max_history_points {
                     self.motion_history.pop_front();
                 }
+                self.timeline
+                    .record(Local::now(), state.score, state.motion_detected);
 
-                // Update detector status based on detection state
+                // The detector thread knows whether it is warming up or paused
                 if self.is_detecting {
-                    self.detector_status = DetectorStatus::Running;
+                    self.detector_status = state.phase.clone().unwrap_or(DetectorPhase::Running);
                 }
 
                 // Update camera resolution info on first status update
-                if self.available_cameras[0] == "Camera 0 - Detecting..." {
-                    self.available_cameras[0] =
-                        format!("Camera 0 - {}x{}", state.resolution.0, state.resolution.1);
+                if let Some((0, label)) = self.available_cameras.first_mut()
+                    && label == "Camera 0 - Detecting..."
+                {
+                    *label = format!("Camera 0 - {}x{}", state.resolution.0, state.resolution.1);
+                }
+
+                // Group events under their incident
+                if let Some(id) = state.incident_id {
+                    incident_events.push((id, state.followup));
+                    self.event_unsaved = true;
+                }
+
+                for hit in &state.rule_hits {
+                    self.activity_log.push(
+                        LogLevel::Event,
+                        format!(
+                            "🚩 Rule \"{}\" matched by object #{}",
+                            hit.rule, hit.track_id
+                        ),
+                    );
+                }
+
+                if let Some(message) = &state.reposition {
+                    self.activity_log
+                        .push(LogLevel::Event, format!("📐 {}", message));
+                }
+
+                if state.reconnected {
+                    self.activity_log.push(
+                        LogLevel::Info,
+                        "🔌 Camera reconnected, rebuilding the background".to_string(),
+                    );
+                }
+
+                if let Some(message) = &state.clock_jump {
+                    self.activity_log
+                        .push(LogLevel::Error, format!("🕒 {}", message));
+                    if let Some(schedule) = self.schedule.as_mut() {
+                        schedule.reset();
+                    }
+                }
+
+                match &state.absence_alert {
+                    Some(alert @ AbsenceAlert::NoMotion { .. }) => self
+                        .activity_log
+                        .push(LogLevel::Error, format!("⚠️ {}", alert.message())),
+                    Some(alert @ AbsenceAlert::Recovered { .. }) => {
+                        self.activity_log.push(LogLevel::Event, alert.message())
+                    }
+                    None => {}
+                }
+
+                for track in &state.new_tracks {
+                    self.activity_log
+                        .push(LogLevel::Event, format!("New object track #{}", track));
+                }
+
+                for note in &state.observed {
+                    self.activity_log
+                        .push(LogLevel::Info, format!("👁 {}", note));
                 }
 
                 // Log motion detection events
                 if state.motion_detected && !was_motion_detected {
-                    self.status_log.push(format!(
-                        "Motion detected! (#{}) FPS: {:.1}",
-                        state.motion_count, state.fps
-                    ));
-                    if self.status_log.len() > 100 {
-                        self.status_log.remove(0);
-                    }
+                    self.activity_log.push(
+                        LogLevel::Event,
+                        format!(
+                            "{}Motion detected! (#{}) FPS: {:.1}",
+                            if state.observing { "[OBSERVE] " } else { "" },
+                            state.motion_count,
+                            state.fps
+                        ),
+                    );
                 }
             }
         }
+
+        for (id, followup) in incident_events {
+            self.record_incident_event(id, followup);
+        }
+        for change in device_changes {
+            self.device_changed(change);
+        }
+        for alert in alerts {
+            self.raise_alert(alert);
+        }
     }
 
-    fn render_control_panel(&mut self, ui: &mut Ui) {
-        ui.heading("⚙️ Motion Detector Controls");
-        ui.separator();
+    fn record_incident_event(&mut self, id: u64, followup: bool) {
+        let now = Local::now();
+        self.hourly_counts.record(now);
+        self.timeline.record_event(now);
+        match self.incidents.back_mut() {
+            Some(row) if row.id == id => {
+                row.events.push(now);
+                if followup {
+                    row.followups += 1;
+                    let ongoing = (now - row.events[0]).to_std().unwrap_or_default();
+                    self.activity_log.push(
+                        LogLevel::Event,
+                        format!(
+                            "🔁 Incident #{} still going after {} (follow-up {})",
+                            id,
+                            humanize_duration(ongoing),
+                            row.followups
+                        ),
+                    );
+                }
+            }
+            _ => {
+                self.incidents.push_back(IncidentRow {
+                    id,
+                    events: vec![now],
+                    acknowledged: None,
+                    note: String::new(),
+                    tags: Vec::new(),
+                    tag_input: String::new(),
+                    clip: None,
+                    followups: 0,
+                });
+                self.activity_log
+                    .push(LogLevel::Event, format!("New incident #{}", id));
+                if self.incidents.len() > 50 {
+                    self.incidents.pop_front();
+                }
+            }
+        }
+    }
 
-        // Camera selection
+    fn render_device_list(&mut self, ui: &mut Ui) {
         ui.horizontal(|ui| {
             ui.label("Camera:");
-            let mut selected_index = self.device as usize;
-            let camera_names: Vec<&str> =
-                self.available_cameras.iter().map(|s| s.as_str()).collect();
+            let cameras = self.available_cameras.clone();
 
             ComboBox::from_label("")
                 .selected_text(
-                    self.available_cameras
-                        .get(selected_index)
-                        .cloned()
-                        .unwrap_or_else(|| "Unknown".to_string()),
+                    cameras
+                        .iter()
+                        .find(|(index, _)| *index == self.device)
+                        .map(|(_, label)| label.clone())
+                        .unwrap_or_else(|| format!("Camera {} (disconnected)", self.device)),
                 )
                 .show_ui(ui, |ui| {
-                    for (i, camera_name) in camera_names.iter().enumerate() {
+                    for (i, camera_name) in &cameras {
                         if ui
-                            .selectable_label(selected_index == i, *camera_name)
+                            .selectable_label(self.device == *i, camera_name)
                             .clicked()
                         {
-                            selected_index = i;
-                            self.device = i as u32;
-                            let _ = self.sender.send(GuiMessage::UpdateDevice(i as u32));
+                            self.device = *i;
+                            // The detector thread stops detection while it switches
+                            self.is_detecting = false;
+                            self.detector_status = DetectorPhase::Initializing;
+                            self.activity_log
+                                .push(LogLevel::Info, format!("Switching to camera {}...", i));
+                            let _ = self.sender.send(GuiMessage::UpdateDevice(*i));
+                        }
+                    }
+                });
+        });
+    }
+
+    /// Named cameras from the config, labelled with their devices
+    fn render_named_cameras(&mut self, ui: &mut Ui) {
+        let label = |camera: &CameraPreset| format!("{} - Camera {}", camera.name, camera.device);
+        let mut chosen = None;
+        ui.horizontal(|ui| {
+            ui.label("Camera:");
+            let selected_text = self
+                .selected_camera
+                .and_then(|index| self.cameras.get(index))
+                .map(label)
+                .unwrap_or_else(|| format!("Camera {}", self.device));
+            ComboBox::from_id_source("named_camera")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    for (i, camera) in self.cameras.iter().enumerate() {
+                        if ui
+                            .selectable_label(self.selected_camera == Some(i), label(camera))
+                            .clicked()
+                        {
+                            chosen = Some(i);
+                        }
+                    }
+                });
+        });
+        if let Some(index) = chosen.filter(|index| Some(*index) != self.selected_camera) {
+            self.select_camera(index);
+        }
+
+        let Some(selected) = self.selected_camera else {
+            return;
+        };
+        let mut copy_from = None;
+        ui.horizontal(|ui| {
+            ui.label("Copy settings from:");
+            ComboBox::from_id_source("copy_camera")
+                .selected_text("Choose...")
+                .show_ui(ui, |ui| {
+                    for (i, camera) in self.cameras.iter().enumerate() {
+                        if i != selected && ui.selectable_label(false, &camera.name).clicked() {
+                            copy_from = Some(i);
                         }
                     }
                 });
         });
+        if let Some(index) = copy_from {
+            self.copy_camera_settings(index);
+        }
+    }
+
+    /// Replace the camera with a recorded video; detection stops until
+    /// it is started again
+    fn open_file(&mut self, path: PathBuf) {
+        self.is_detecting = false;
+        self.detector_status = DetectorPhase::Initializing;
+        self.activity_log
+            .push(LogLevel::Info, format!("Opening {}...", path.display()));
+        let _ = self.sender.send(GuiMessage::OpenFile(path));
+    }
+
+    /// Apply the answer of a finished file dialog, checking the path first
+    fn poll_dialog(&mut self) {
+        let Some(answer) = self
+            .pending_dialog
+            .as_ref()
+            .and_then(|dialog| dialog.poll())
+        else {
+            return;
+        };
+        let purpose = self.pending_dialog.take().map(|dialog| dialog.purpose);
+        let (Some(purpose), Some(path)) = (purpose, answer) else {
+            return;
+        };
+        match purpose {
+            Purpose::OutputDir => {
+                let check = selftest::check_output_dir(&path);
+                if check.status == CheckStatus::Fail {
+                    self.activity_log.push(
+                        LogLevel::Error,
+                        format!("Output directory not changed: {}", check.message),
+                    );
+                    return;
+                }
+                self.activity_log.push(
+                    LogLevel::Info,
+                    format!("Output directory changed to {}", path.display()),
+                );
+                self.output_dir = path.clone();
+                self.store_camera_settings();
+                let _ = self.sender.send(GuiMessage::UpdateOutputDir(path));
+            }
+            Purpose::VideoFile => {
+                if path.is_file() {
+                    self.open_file(path);
+                } else {
+                    self.activity_log.push(
+                        LogLevel::Error,
+                        format!("{} is not a readable file", path.display()),
+                    );
+                }
+            }
+            Purpose::ExportLog => match std::fs::write(&path, self.activity_log.to_csv()) {
+                Ok(()) => self.activity_log.push(
+                    LogLevel::Info,
+                    format!("Activity log exported to {}", path.display()),
+                ),
+                Err(e) => self.activity_log.push(
+                    LogLevel::Error,
+                    format!("Failed to export the log to {}: {}", path.display(), e),
+                ),
+            },
+            Purpose::ExportGraph => {
+                let request = GraphRequest::new(
+                    &self.timeline,
+                    self.graph_range,
+                    Local::now(),
+                    self.motion_state.score_threshold,
+                    self.camera_name(),
+                );
+                self.activity_log.push(
+                    LogLevel::Info,
+                    format!(
+                        "Exporting the motion graph ({}) to {}...",
+                        self.graph_range.label().to_lowercase(),
+                        path.display()
+                    ),
+                );
+                self.graph_export = Some(graph_export::export(request, path));
+            }
+        }
+    }
+
+    /// Log the graph export once its worker is done
+    fn poll_graph_export(&mut self) {
+        let Some(result) = self.graph_export.as_ref().and_then(|export| export.poll()) else {
+            return;
+        };
+        let Some(export) = self.graph_export.take() else {
+            return;
+        };
+        match result {
+            Ok(()) => self.activity_log.push(
+                LogLevel::Info,
+                format!("Motion graph saved to {}", export.path.display()),
+            ),
+            Err(e) => self.activity_log.push(
+                LogLevel::Error,
+                format!("Failed to export the motion graph: {}", e),
+            ),
+        }
+    }
+
+    /// Ask where to save the motion graph as a PNG
+    fn start_graph_export(&mut self) {
+        self.pending_dialog = Some(dialogs::save_file(
+            Purpose::ExportGraph,
+            format!("motion_{}.png", Local::now().format("%Y%m%d_%H%M%S")),
+            "PNG image",
+            &["png"],
+        ));
+    }
+
+    /// The playing video, the named camera or the device, for exports
+    fn camera_name(&self) -> String {
+        if let Some(path) = &self.playing_file {
+            return path
+                .file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .into_owned();
+        }
+        match self
+            .selected_camera
+            .and_then(|index| self.cameras.get(index))
+        {
+            Some(camera) => format!("{} (camera {})", camera.name, self.device),
+            None => format!("Camera {}", self.device),
+        }
+    }
+
+    fn render_video_file(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Video file:");
+            match &self.playing_file {
+                Some(path) => {
+                    ui.monospace(
+                        path.file_name()
+                            .unwrap_or(path.as_os_str())
+                            .to_string_lossy(),
+                    )
+                    .on_hover_text(path.display().to_string());
+                }
+                None => {
+                    ui.colored_label(Color32::GRAY, "none, drop one on the window");
+                }
+            }
+            if ui
+                .add_enabled(self.pending_dialog.is_none(), Button::new("🎞️ Open..."))
+                .clicked()
+            {
+                self.pending_dialog = Some(dialogs::pick_file(
+                    Purpose::VideoFile,
+                    "Video",
+                    &["mp4", "avi", "mkv", "mov", "webm"],
+                ));
+            }
+            if ui.checkbox(&mut self.loop_file, "Loop").changed() {
+                let _ = self.sender.send(GuiMessage::SetLoopFile(self.loop_file));
+            }
+        });
+    }
+
+    fn render_control_panel(&mut self, ui: &mut Ui) {
+        ui.heading("⚙️ Motion Detector Controls");
+        ui.separator();
+
+        // Camera selection
+        if !self.cameras.is_empty() {
+            self.render_named_cameras(ui);
+        } else {
+            self.render_device_list(ui);
+        }
+        // A failed switch, until the next one
+        if let Some(problem) = self.device_problem.clone() {
+            ui.horizontal(|ui| {
+                ui.colored_label(Color32::from_rgb(255, 165, 0), format!("⚠ {}", problem));
+                if ui.small_button("✖").on_hover_text("Dismiss").clicked() {
+                    self.device_problem = None;
+                }
+            });
+        }
+        self.render_video_file(ui);
 
         ui.add_space(10.0);
 
@@ -166,30 +1525,105 @@ impl MotionDetectorGui {
         ui.horizontal(|ui| {
             ui.label("Sensitivity:");
             let mut sensitivity = self.sensitivity;
-            if ui
-                .add(Slider::new(&mut sensitivity, 0.0..=1.0).text(""))
-                .changed()
-            {
+            let response = ui.add(Slider::new(&mut sensitivity, 0.0..=1.0).text(""));
+            if response.changed() {
                 self.sensitivity = sensitivity;
+                self.store_camera_settings();
                 let _ = self.sender.send(GuiMessage::UpdateSensitivity(sensitivity));
             }
-            ui.label(format!("{:.2}", self.sensitivity));
+            // Log once when a drag ends rather than on every tick
+            if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                self.activity_log.push(
+                    LogLevel::Debug,
+                    format!("Sensitivity → {:.2} (final)", self.sensitivity),
+                );
+            }
+            ui.label(format!("{:.2}", self.sensitivity))
+                .on_hover_text(format!(
+                    "Pixels changing by more than {:.0} grey levels count as motion",
+                    self.sensitivity_curve.threshold(self.sensitivity)
+                ));
         });
 
-        // Min area slider
+        // Min area slider, in pixels or as a share of the frame
         ui.horizontal(|ui| {
             ui.label("Min Area:");
+            let (width, height) = self.motion_state.resolution;
+            let frame_area = (width as f64 * height as f64).max(1.0);
             let mut min_area = self.min_area;
-            if ui
-                .add(Slider::new(&mut min_area, 50..=5000).text(""))
-                .changed()
-            {
+            let response = match &mut min_area {
+                MinArea::Pixels(pixels) => ui.add(Slider::new(pixels, 50..=5000).text("")),
+                MinArea::Fraction(fraction) => {
+                    let mut percent = *fraction * 100.0;
+                    let response = ui.add(
+                        Slider::new(&mut percent, 0.01..=10.0)
+                            .logarithmic(true)
+                            .max_decimals(2)
+                            .text(""),
+                    );
+                    *fraction = percent / 100.0;
+                    response
+                }
+            };
+            // Switching units keeps the same area for the current frame size
+            let was_percent = matches!(min_area, MinArea::Fraction(_));
+            let mut percent = was_percent;
+            ui.selectable_value(&mut percent, false, "px")
+                .on_hover_text("Pixels of the captured frame");
+            ui.selectable_value(&mut percent, true, "%")
+                .on_hover_text("Share of the frame, the same at any resolution");
+            let toggled = percent != was_percent;
+            if toggled {
+                let pixels = min_area.pixels(frame_area);
+                min_area = if percent {
+                    MinArea::Fraction((pixels as f64 / frame_area).clamp(0.0001, 0.1))
+                } else {
+                    MinArea::Pixels(pixels.clamp(50, 5000))
+                };
+            }
+            if min_area != self.min_area {
                 self.min_area = min_area;
+                self.store_camera_settings();
                 let _ = self.sender.send(GuiMessage::UpdateMinArea(min_area));
             }
-            ui.label(format!("{} px", self.min_area));
+            if response.drag_stopped() || (response.changed() && !response.dragged()) || toggled {
+                self.activity_log.push(
+                    LogLevel::Debug,
+                    format!(
+                        "Min area → {} (final)",
+                        self.min_area.describe(Some(frame_area))
+                    ),
+                );
+            }
+            ui.label(match self.min_area {
+                MinArea::Pixels(pixels) => {
+                    format!("{} px ({:.2}%)", pixels, pixels as f64 / frame_area * 100.0)
+                }
+                MinArea::Fraction(_) => self.min_area.describe(Some(frame_area)),
+            });
         });
 
+        // Snapshot output directory
+        ui.horizontal(|ui| {
+            ui.label("Output:");
+            ui.monospace(self.output_dir.display().to_string());
+            if ui
+                .add_enabled(self.pending_dialog.is_none(), Button::new("📁 Change..."))
+                .clicked()
+            {
+                self.pending_dialog = Some(dialogs::pick_folder(
+                    Purpose::OutputDir,
+                    self.output_dir.clone(),
+                ));
+            }
+        });
+        if !self.event_snapshots {
+            ui.colored_label(
+                Color32::from_rgb(255, 165, 0),
+                "Event snapshots disabled (--no-snapshot)",
+            );
+        }
+
         ui.add_space(10.0);
 
         // Detection toggle
@@ -200,11 +1634,9 @@ impl MotionDetectorGui {
                     .clicked()
                 {
                     self.is_detecting = false;
-                    self.detector_status = DetectorStatus::Stopped;
-                    self.status_log.push("Motion detection stopped".to_string());
-                    if self.status_log.len() > 100 {
-                        self.status_log.remove(0);
-                    }
+                    self.detector_status = DetectorPhase::Stopped;
+                    self.activity_log
+                        .push(LogLevel::Info, "Motion detection stopped".to_string());
                     let _ = self.sender.send(GuiMessage::StopDetection);
                 }
             } else {
@@ -213,23 +1645,354 @@ impl MotionDetectorGui {
                     .clicked()
                 {
                     self.is_detecting = true;
-                    self.detector_status = DetectorStatus::Running;
-                    self.status_log.push("Motion detection started".to_string());
-                    if self.status_log.len() > 100 {
-                        self.status_log.remove(0);
-                    }
+                    self.detector_status = DetectorPhase::Initializing;
+                    self.activity_log
+                        .push(LogLevel::Info, "Motion detection started".to_string());
                     let _ = self.sender.send(GuiMessage::StartDetection);
                 }
             }
 
-            if ui.add(Button::new("📸 Save Snapshot")).clicked() {
-                self.status_log.push("Manual snapshot saved".to_string());
-                if self.status_log.len() > 100 {
-                    self.status_log.remove(0);
-                }
+            // Manual snapshots are always written, even with --no-snapshot
+            if ui
+                .add(Button::new("📸 Save Snapshot"))
+                .on_hover_text("Saved even when event snapshots are disabled")
+                .clicked()
+            {
+                // Logged once the detector thread reports back
                 let _ = self.sender.send(GuiMessage::SaveSnapshot);
             }
+
+            let label = if self.motion_state.repositioning {
+                "▶️ Resume"
+            } else {
+                "📐 Reposition"
+            };
+            if ui
+                .add_enabled(self.is_detecting, Button::new(label))
+                .on_hover_text("Pause detection while the camera is moved")
+                .clicked()
+            {
+                let _ = self.sender.send(GuiMessage::ToggleRepositioning);
+            }
+
+            if self.motion_state.intrusion.is_armed() {
+                if ui
+                    .add_enabled(self.is_detecting, Button::new("🔓 Disarm"))
+                    .on_hover_text("Back to comparing with the previous frames")
+                    .clicked()
+                {
+                    let _ = self.sender.send(GuiMessage::Disarm);
+                }
+            } else if ui
+                .add_enabled(self.is_detecting, Button::new("🔒 Arm"))
+                .on_hover_text(
+                    "Take the current view as the empty scene; anything that stays \
+                     different from it is an intrusion",
+                )
+                .clicked()
+            {
+                let _ = self.sender.send(GuiMessage::Arm);
+            }
+        });
+
+        ui.add_space(10.0);
+        self.render_overlay_settings(ui);
+        self.render_schedule_settings(ui);
+    }
+
+    fn render_schedule_settings(&mut self, ui: &mut Ui) {
+        CollapsingHeader::new("🌅 Schedule").show(ui, |ui| {
+            let now = Local::now();
+            match self.location {
+                Some(location) => {
+                    let today = match sun::sun_times(now.date_naive(), location) {
+                        SunTimes::Normal { sunrise, sunset } => format!(
+                            "sunrise {}, sunset {}",
+                            sunrise.with_timezone(&Local).format("%H:%M"),
+                            sunset.with_timezone(&Local).format("%H:%M")
+                        ),
+                        SunTimes::PolarDay => "the sun doesn't set".to_string(),
+                        SunTimes::PolarNight => "the sun doesn't rise".to_string(),
+                    };
+                    ui.label(format!(
+                        "{:.3}, {:.3}: {} today",
+                        location.latitude, location.longitude, today
+                    ));
+                }
+                None => {
+                    ui.label("No [location] configured, so only clock times work");
+                }
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Active:");
+                ui.add(
+                    TextEdit::singleline(&mut self.schedule_text)
+                        .hint_text("sunset-30m..sunrise+30m"),
+                );
+                if ui.button("Apply").clicked() {
+                    self.apply_schedule();
+                }
+            });
+            if let Some(error) = &self.schedule_error {
+                ui.colored_label(Color32::RED, error);
+            }
+
+            match self.schedule.as_mut() {
+                Some(clock) => {
+                    let active = clock.is_active(now);
+                    let plan = clock.plan(now);
+                    ui.label(plan.describe());
+                    if let Some(warning) = &plan.warning {
+                        ui.colored_label(Color32::from_rgb(255, 165, 0), warning);
+                    }
+                    if active {
+                        ui.colored_label(Color32::GREEN, "Armed now");
+                    } else {
+                        ui.colored_label(Color32::GRAY, "Outside the schedule: events are ignored");
+                    }
+                }
+                None => {
+                    ui.label("Armed all day");
+                }
+            }
+        });
+    }
+
+    /// Parse the schedule field and hand it to the detector thread
+    fn apply_schedule(&mut self) {
+        let text = self.schedule_text.trim();
+        let schedule = if text.is_empty() {
+            None
+        } else {
+            match Schedule::parse(text) {
+                Ok(schedule) if schedule.uses_sun() && self.location.is_none() => {
+                    self.schedule_error =
+                        Some("sunrise/sunset need a [location] in the config".to_string());
+                    return;
+                }
+                Ok(schedule) => Some(schedule),
+                Err(e) => {
+                    self.schedule_error = Some(format!("{:#}", e));
+                    return;
+                }
+            }
+        };
+        self.schedule_error = None;
+        self.activity_log.push(
+            LogLevel::Info,
+            match &schedule {
+                Some(schedule) => format!("Schedule set to {}", schedule.text()),
+                None => "Schedule cleared, armed all day".to_string(),
+            },
+        );
+        self.schedule = schedule
+            .clone()
+            .map(|schedule| ScheduleClock::new(schedule, self.location));
+        let _ = self.sender.send(GuiMessage::UpdateSchedule(schedule));
+    }
+
+    fn render_overlay_settings(&mut self, ui: &mut Ui) {
+        CollapsingHeader::new("🖼️ Overlay").show(ui, |ui| {
+            let mut config = self.overlay_config.clone();
+
+            ui.checkbox(&mut config.enabled, "Show overlay");
+            ui.add_enabled_ui(config.enabled, |ui| {
+                ComboBox::from_label("Position")
+                    .selected_text(config.position.label())
+                    .show_ui(ui, |ui| {
+                        for position in OverlayPosition::ALL {
+                            ui.selectable_value(&mut config.position, position, position.label());
+                        }
+                    });
+                ui.add(Slider::new(&mut config.font_scale, 0.2..=2.0).text("Font scale"));
+                ui.checkbox(&mut config.show_time, "Time");
+                ui.checkbox(&mut config.show_camera, "Camera name");
+                ui.checkbox(&mut config.show_fps, "FPS");
+                ui.checkbox(&mut config.show_armed, "Armed state");
+                ui.checkbox(&mut config.show_count, "Event count");
+                ui.checkbox(&mut config.show_motion_banner, "Flashing MOTION banner");
+                ui.checkbox(&mut config.show_regions, "Motion boxes")
+                    .on_hover_text("Regions merged by --merge-gap are orange");
+            });
+
+            if config != self.overlay_config {
+                self.overlay_config = config.clone();
+                let _ = self.sender.send(GuiMessage::UpdateOverlay(config));
+            }
+        });
+    }
+
+    fn render_preview(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.heading("📷 Preview");
+            if ui.checkbox(&mut self.show_preview, "Show").changed() {
+                let _ = self
+                    .sender
+                    .send(GuiMessage::SetPreviewEnabled(self.show_preview));
+            }
+            if self.show_preview {
+                ui.toggle_value(&mut self.editing_mask, "🖌 Edit mask");
+            }
+        });
+        ui.separator();
+
+        if !self.show_preview {
+            return;
+        }
+        if self.editing_mask {
+            self.render_mask_tools(ui);
+        }
+
+        let Some((texture, [w, h])) = self
+            .preview_texture
+            .as_ref()
+            .map(|texture| (texture.id(), texture.size()))
+        else {
+            ui.label("Waiting for frames...");
+            return;
+        };
+        // Keep the camera's aspect ratio and center the image, with
+        // black bars where the panel is wider than the feed
+        let [max_width, max_height] = self.preview_max_size;
+        let bounds = [
+            ui.available_width().min(max_width),
+            ui.available_height().max(120.0).min(max_height),
+        ];
+        let [width, height] = fit_within([w as f32, h as f32], bounds);
+        let sense = if self.editing_mask {
+            Sense::click_and_drag()
+        } else {
+            Sense::hover()
+        };
+        let (panel, response) = ui.allocate_exact_size(vec2(bounds[0], height), sense);
+        ui.painter().rect_filled(panel, 0.0, Color32::BLACK);
+        let image = Rect::from_center_size(panel.center(), vec2(width, height));
+        Image::new((texture, image.size())).paint_at(ui, image);
+        if self.editing_mask {
+            self.paint_mask(ui, &response, image);
+        }
+    }
+
+    /// Brush, whole-mask edits and tint for the mask editor
+    fn render_mask_tools(&mut self, ui: &mut Ui) {
+        let editor = &mut self.mask_editor;
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut editor.brush, Brush::Exclude, "Ignore");
+            ui.radio_value(&mut editor.brush, Brush::Include, "Detect (erase)");
+            ui.add(Slider::new(&mut editor.radius, 1.0..=100.0).text("Brush"));
+        });
+        ui.horizontal(|ui| {
+            if ui
+                .button("Fill")
+                .on_hover_text("Paint the whole frame with the brush")
+                .clicked()
+            {
+                let value = editor.brush.value();
+                editor.edit(|mask| mask.fill(value));
+                changed = true;
+            }
+            if ui.button("Invert").clicked() {
+                editor.edit(|mask| mask.invert());
+                changed = true;
+            }
+            if ui
+                .add_enabled(editor.can_undo(), Button::new("↶ Undo"))
+                .clicked()
+            {
+                changed = editor.undo();
+            }
+            if ui
+                .button("Clear")
+                .on_hover_text("Detect motion everywhere")
+                .clicked()
+            {
+                editor.edit(|mask| mask.fill(255));
+                changed = true;
+            }
+            if ui
+                .add(Slider::new(&mut editor.opacity, 0.0..=1.0).text("Tint"))
+                .changed()
+            {
+                self.mask_texture = None;
+            }
         });
+        if changed {
+            self.send_mask();
+        }
+    }
+
+    /// Paint with the pointer over the preview `image`, and draw the mask on it
+    fn paint_mask(&mut self, ui: &mut Ui, response: &Response, image: Rect) {
+        let (process_width, process_height) = self.motion_state.process_resolution;
+        let (width, height) = (
+            process_width.max(1) as usize,
+            process_height.max(1) as usize,
+        );
+        if self
+            .mask_editor
+            .mask
+            .as_ref()
+            .is_none_or(|mask| (mask.width, mask.height) != (width, height))
+        {
+            self.mask_texture = None;
+        }
+        self.mask_editor.mask_for(width, height);
+
+        // Pointer position in mask pixels
+        let point = response.interact_pointer_pos().map(|pos| {
+            (
+                (pos.x - image.min.x) / image.width() * width as f32,
+                (pos.y - image.min.y) / image.height() * height as f32,
+            )
+        });
+        if let Some(point) = point {
+            if response.clicked() || response.drag_started() {
+                self.mask_editor.press(point);
+                self.mask_texture = None;
+            } else if response.dragged() {
+                self.mask_editor.drag(point);
+                self.mask_texture = None;
+            }
+        }
+        if response.clicked() || response.drag_stopped() {
+            self.mask_editor.release();
+            self.send_mask();
+        }
+
+        let Some(mask) = &self.mask_editor.mask else {
+            return;
+        };
+        let overlay = self.mask_texture.get_or_insert_with(|| {
+            let rgba = ColorImage::from_rgba_unmultiplied(
+                [mask.width, mask.height],
+                &mask.overlay(self.mask_editor.opacity),
+            );
+            ui.ctx().load_texture("mask", rgba, TextureOptions::NEAREST)
+        });
+        Image::new((overlay.id(), image.size())).paint_at(ui, image);
+        // Brush outline under the pointer
+        if let Some(pos) = response.hover_pos() {
+            let scale = image.width() / width as f32;
+            ui.painter().circle_stroke(
+                pos,
+                self.mask_editor.radius * scale,
+                Stroke::new(1.0, Color32::WHITE),
+            );
+        }
+    }
+
+    /// Hand the edited mask to the detector, which applies and saves it
+    fn send_mask(&mut self) {
+        if let Some(mask) = &self.mask_editor.mask {
+            let _ = self.sender.send(GuiMessage::UpdateMask(
+                mask.pixels.clone(),
+                mask.width,
+                mask.height,
+            ));
+            self.mask_texture = None;
+        }
     }
 
     fn render_status_panel(&mut self, ui: &mut Ui) {
@@ -264,12 +2027,7 @@ impl MotionDetectorGui {
 
                     // Time since last motion
                     if let Some(last_time) = self.motion_state.last_motion_time {
-                        let duration = Local::now().signed_duration_since(last_time);
-                        if duration.num_seconds() < 60 {
-                            ui.label(format!("{} seconds ago", duration.num_seconds()));
-                        } else {
-                            ui.label(format!("{} minutes ago", duration.num_minutes()));
-                        }
+                        ui.label(humanize_ago(last_time, Local::now()));
                     }
                 });
             } else {
@@ -292,6 +2050,21 @@ impl MotionDetectorGui {
                 });
             }
         });
+        // Held until disarmed, so it can't be missed between glances
+        if self.motion_state.intrusion == IntrusionState::Alarm {
+            Frame::none()
+                .fill(Color32::from_rgb(160, 0, 0))
+                .inner_margin(8.0)
+                .show(ui, |ui| {
+                    ui.vertical_centered(|ui| {
+                        ui.colored_label(
+                            Color32::WHITE,
+                            RichText::new("🚨 INTRUSION: the empty scene changed").size(22.0),
+                        );
+                        ui.colored_label(Color32::WHITE, "Disarm to clear the alarm");
+                    });
+                });
+        }
         ui.separator();
 
         // Enhanced real-time status grid
@@ -302,14 +2075,20 @@ impl MotionDetectorGui {
 
             // Detector status with icon
             columns[0].horizontal(|ui| {
-                let (icon, status_text, color) = match &self.detector_status {
-                    DetectorStatus::Stopped => ("⏹️", "Stopped", Color32::GRAY),
-                    DetectorStatus::Running => ("▶️", "Running", Color32::GREEN),
-                    DetectorStatus::Starting => ("⏳", "Starting...", Color32::YELLOW),
-                    DetectorStatus::Error(e) => ("❌", "Error", Color32::RED),
+                let (icon, color) = match &self.detector_status {
+                    DetectorPhase::Stopped => ("⏹️", Color32::GRAY),
+                    DetectorPhase::Running => ("▶️", Color32::GREEN),
+                    DetectorPhase::Initializing | DetectorPhase::WarmingUp { .. } => {
+                        ("⏳", Color32::YELLOW)
+                    }
+                    DetectorPhase::Paused(_) => ("⏸️", Color32::YELLOW),
+                    DetectorPhase::Error(_) => ("❌", Color32::RED),
                 };
                 ui.label(icon);
-                ui.colored_label(color, status_text);
+                match &self.detector_status {
+                    DetectorPhase::Error(e) => ui.colored_label(color, e),
+                    phase => ui.colored_label(color, phase.to_string()),
+                };
             });
 
             // FPS with color coding
@@ -323,6 +2102,10 @@ impl MotionDetectorGui {
             columns[0].horizontal(|ui| {
                 ui.label("📹 FPS:");
                 ui.colored_label(fps_color, format!("{:.1}", self.motion_state.fps));
+                if let Some(target) = self.motion_state.target_fps {
+                    ui.label(format!("/ {:.0} target", target))
+                        .on_hover_text("Limited under Advanced or with --target-fps");
+                }
             });
 
             // Resolution
@@ -331,8 +2114,104 @@ impl MotionDetectorGui {
                 ui.label(format!(
                     "{}x{}",
                     self.motion_state.resolution.0, self.motion_state.resolution.1
-                ));
+                ))
+                .on_hover_text("Captured frames; snapshots and clips are saved at this size");
             });
+            let (process_width, process_height) = self.motion_state.process_resolution;
+            if self.motion_state.process_resolution != self.motion_state.resolution {
+                columns[0].horizontal(|ui| {
+                    ui.label("🔍 Detecting at:");
+                    ui.label(format!("{}x{}", process_width, process_height))
+                        .on_hover_text("Motion is measured on this smaller copy (--process-scale)");
+                });
+            }
+
+            if let Some(cooldown) = self.motion_state.cooldown {
+                columns[0].horizontal(|ui| {
+                    ui.label("⏳ Cooldown:");
+                    ui.label(format!("{:.1} s", cooldown.as_secs_f64()));
+                });
+            }
+
+            if self.is_detecting && self.motion_state.repositioning {
+                columns[0].horizontal(|ui| {
+                    ui.label("📐");
+                    ui.colored_label(
+                        Color32::from_rgb(255, 165, 0),
+                        "Repositioning, detection paused",
+                    );
+                });
+            }
+
+            if self.is_detecting && self.motion_state.intrusion.is_armed() {
+                columns[0].horizontal(|ui| {
+                    ui.label("🔒 Intrusion:");
+                    let state = self.motion_state.intrusion;
+                    if state == IntrusionState::Alarm {
+                        ui.colored_label(Color32::RED, RichText::new(state.label()).strong());
+                    } else {
+                        ui.colored_label(Color32::from_rgb(255, 165, 0), state.label());
+                    }
+                });
+            }
+
+            // The relay is released whenever detection stops
+            if let (true, Some(active)) = (self.is_detecting, self.motion_state.relay) {
+                columns[0].horizontal(|ui| {
+                    ui.label("🔌 Relay:");
+                    if active {
+                        ui.colored_label(Color32::RED, "ON");
+                    } else {
+                        ui.colored_label(Color32::GRAY, "off");
+                    }
+                });
+            }
+
+            // Clip encoder, red when it can't keep up with the camera
+            if let Some(recording) = &self.motion_state.recording {
+                columns[0].horizontal(|ui| {
+                    ui.label("🎬 Recording:");
+                    ui.label(recording);
+                });
+                if let Some(encode_fps) = self.motion_state.encode_fps {
+                    let color = if encode_fps >= self.motion_state.fps as f64 {
+                        Color32::GREEN
+                    } else {
+                        Color32::RED
+                    };
+                    columns[0].horizontal(|ui| {
+                        ui.label("🎞️ Encode FPS:");
+                        ui.colored_label(color, format!("{:.1}", encode_fps));
+                    });
+                }
+            }
+
+            // Own CPU and memory usage, warn when it gets heavy
+            if let Some(usage) = self.motion_state.resources {
+                let cpu_color = if usage.cpu_percent >= 80.0 {
+                    Color32::RED
+                } else if usage.cpu_percent >= 50.0 {
+                    Color32::YELLOW
+                } else {
+                    Color32::GREEN
+                };
+                let rss_mb = usage.rss_mb();
+                let rss_color = if rss_mb >= 1024.0 {
+                    Color32::RED
+                } else if rss_mb >= 512.0 {
+                    Color32::YELLOW
+                } else {
+                    Color32::GREEN
+                };
+                columns[0].horizontal(|ui| {
+                    ui.label("🖥️ CPU:");
+                    ui.colored_label(cpu_color, format!("{:.0}%", usage.cpu_percent));
+                });
+                columns[0].horizontal(|ui| {
+                    ui.label("💾 Memory:");
+                    ui.colored_label(rss_color, format!("{:.1} MB", rss_mb));
+                });
+            }
 
             // Right column - Motion status
             columns[1].heading("🎯 Motion");
@@ -364,29 +2243,229 @@ impl MotionDetectorGui {
 
             // Time since last motion
             if let Some(last_time) = self.motion_state.last_motion_time {
-                let duration = Local::now().signed_duration_since(last_time);
-                let time_text = if duration.num_seconds() < 60 {
-                    format!("{}s ago", duration.num_seconds())
-                } else if duration.num_minutes() < 60 {
-                    format!("{}m ago", duration.num_minutes())
-                } else {
-                    format!("{}h ago", duration.num_hours())
-                };
+                let time_text = humanize_ago(last_time, Local::now());
                 columns[1].horizontal(|ui| {
                     ui.label("⏰ Last:");
                     ui.colored_label(Color32::from_rgb(200, 200, 255), time_text);
                 });
-            } else {
-                columns[1].horizontal(|ui| {
-                    ui.label("⏰ Last:");
-                    ui.label("Never");
+            } else {
+                columns[1].horizontal(|ui| {
+                    ui.label("⏰ Last:");
+                    ui.label("Never");
+                });
+            }
+
+            columns[1].horizontal(|ui| {
+                ui.label("⏱️ Uptime:");
+                ui.label(humanize_duration(self.started.elapsed()));
+            });
+
+            // Time left before the no-motion alarm
+            if let Some(status) = self.motion_state.absence {
+                columns[1].horizontal(|ui| {
+                    ui.label("😴 Idle alarm:");
+                    if status.paused || !self.is_detecting {
+                        ui.colored_label(Color32::GRAY, "paused");
+                    } else if status.alerting {
+                        ui.colored_label(
+                            Color32::RED,
+                            format!("ALERT (repeats in {})", humanize_duration(status.remaining)),
+                        );
+                    } else {
+                        ui.label(format!("in {}", humanize_duration(status.remaining)));
+                    }
+                });
+            }
+
+            // Objects currently tracked
+            if !self.motion_state.active_tracks.is_empty() {
+                let ids: Vec<String> = self
+                    .motion_state
+                    .active_tracks
+                    .iter()
+                    .map(|id| format!("#{}", id))
+                    .collect();
+                columns[1].horizontal(|ui| {
+                    ui.label("🏃 Tracks:");
+                    ui.label(ids.join(", "));
+                });
+            }
+        });
+    }
+
+    fn render_advanced_status(&mut self, ui: &mut Ui) {
+        CollapsingHeader::new("🔬 Advanced").show(ui, |ui| {
+            let timings = self.motion_state.timings;
+            Grid::new("stage_timings").num_columns(2).show(ui, |ui| {
+                for (stage, ms) in [
+                    ("Capture", timings.capture_ms),
+                    ("Blur", timings.blur_ms),
+                    ("Diff", timings.diff_ms),
+                    ("Contours", timings.contours_ms),
+                    ("Total", timings.total_ms()),
+                ] {
+                    ui.label(stage);
+                    ui.label(format!("{:.2} ms", ms));
+                    ui.end_row();
+                }
+                ui.label("Skipped frames");
+                ui.label(format!("{}", self.motion_state.frames_skipped));
+                ui.end_row();
+                if self.motion_state.snapshots_deduplicated > 0 {
+                    ui.label("Deduplicated snapshots");
+                    ui.label(format!("{}", self.motion_state.snapshots_deduplicated));
+                    ui.end_row();
+                }
+                if let Some(frame_time) = &self.motion_state.frame_time {
+                    ui.label("Capture latency");
+                    ui.label(format!(
+                        "{:.0} ms ({})",
+                        frame_time.latency.as_secs_f64() * 1000.0,
+                        frame_time.source.describe()
+                    ));
+                    ui.end_row();
+                    if let Some(position) = frame_time.position {
+                        ui.label("File position");
+                        ui.label(format_position(position));
+                        ui.end_row();
+                    }
+                }
+                ui.label("Contours → regions");
+                ui.label(format!(
+                    "{} → {}",
+                    self.motion_state.contours, self.motion_state.regions
+                ));
+                ui.end_row();
+            });
+
+            // How the Sensitivity slider maps to the pixel difference
+            ui.separator();
+            let mut curve = self.sensitivity_curve;
+            ui.horizontal(|ui| {
+                ui.label("Sensitivity curve:");
+                ComboBox::from_id_source("sensitivity_curve")
+                    .selected_text(curve.label())
+                    .show_ui(ui, |ui| {
+                        for option in SensitivityCurve::ALL {
+                            ui.selectable_value(&mut curve, option, option.label());
+                        }
+                    });
+                ui.label(format!(
+                    "threshold {:.0}",
+                    curve.threshold(self.sensitivity)
+                ));
+            })
+            .response
+            .on_hover_text(
+                "Exponential spreads the useful thresholds over the whole slider; \
+                 linear lowers the threshold by the same number of grey levels per step",
+            );
+            if curve != self.sensitivity_curve {
+                self.sensitivity_curve = curve;
+                let _ = self.sender.send(GuiMessage::UpdateSensitivityCurve(curve));
+                self.activity_log.push(
+                    LogLevel::Debug,
+                    format!("Sensitivity curve → {}", curve.label()),
+                );
+            }
+
+            // Opening before dilation, against single-pixel sensor noise
+            let (mut kernel, mut iterations) = self.opening;
+            let describe = |size: i32| match size {
+                0 => "Off".to_string(),
+                size => format!("{}×{}", size, size),
+            };
+            let mut changed = false;
+            ui.horizontal(|ui| {
+                ui.label("Noise opening:");
+                ComboBox::from_id_source("open_kernel")
+                    .selected_text(describe(kernel))
+                    .show_ui(ui, |ui| {
+                        for size in [0, 3, 5, 7] {
+                            changed |= ui
+                                .selectable_value(&mut kernel, size, describe(size))
+                                .changed();
+                        }
+                    });
+                ui.add_enabled_ui(kernel > 0, |ui| {
+                    changed |= ui
+                        .add(Slider::new(&mut iterations, 1..=5).text("passes"))
+                        .changed();
+                });
+            })
+            .response
+            .on_hover_text(
+                "Erode then dilate the motion mask, so isolated noise pixels can't grow into blobs",
+            );
+            if changed {
+                self.opening = (kernel, iterations);
+                let _ = self
+                    .sender
+                    .send(GuiMessage::UpdateOpening(kernel, iterations));
+                self.activity_log.push(
+                    LogLevel::Debug,
+                    format!(
+                        "Noise opening → {}, {} pass(es)",
+                        describe(kernel),
+                        iterations
+                    ),
+                );
+            }
+
+            // Throttle processing below the camera's rate, to save CPU
+            let mut limited = self.target_fps.is_some();
+            let mut fps = self.target_fps.unwrap_or(15.0);
+            let mut changed = false;
+            let (mut dragged, mut drag_stopped) = (false, false);
+            ui.horizontal(|ui| {
+                changed |= ui.checkbox(&mut limited, "Limit FPS:").changed();
+                ui.add_enabled_ui(limited, |ui| {
+                    let response = ui.add(Slider::new(&mut fps, 1.0..=60.0).integer());
+                    changed |= response.changed();
+                    dragged = response.dragged();
+                    drag_stopped = response.drag_stopped();
                 });
+            })
+            .response
+            .on_hover_text("Process at most this many frames per second; off follows the camera");
+            if changed {
+                self.target_fps = limited.then_some(fps);
+                let _ = self
+                    .sender
+                    .send(GuiMessage::UpdateTargetFps(self.target_fps.unwrap_or(0.0)));
+            }
+            // Log once when a drag ends rather than on every tick
+            if drag_stopped || (changed && !dragged) {
+                self.activity_log.push(
+                    LogLevel::Debug,
+                    match self.target_fps {
+                        Some(fps) => format!("Target FPS → {:.0} (final)", fps),
+                        None => "Target FPS → camera rate".to_string(),
+                    },
+                );
             }
         });
     }
 
     fn render_motion_graph(&mut self, ui: &mut Ui) {
-        ui.heading("📈 Motion Graph");
+        ui.horizontal(|ui| {
+            ui.heading("📈 Motion Graph");
+            ComboBox::from_id_source("graph_range")
+                .selected_text(self.graph_range.label())
+                .show_ui(ui, |ui| {
+                    for range in GraphRange::ALL {
+                        ui.selectable_value(&mut self.graph_range, range, range.label());
+                    }
+                });
+            let idle = self.pending_dialog.is_none() && self.graph_export.is_none();
+            if ui
+                .add_enabled(idle, Button::new("🖼 Export graph as image..."))
+                .on_hover_text("Save the score over the chosen range as a PNG")
+                .clicked()
+            {
+                self.start_graph_export();
+            }
+        });
         ui.separator();
 
         // Current motion status
@@ -398,10 +2477,21 @@ impl MotionDetectorGui {
                 ui.colored_label(Color32::RED, RichText::new("🔴 NO MOTION"));
             }
 
-            ui.label(format!("History: {} points", self.motion_history.len()));
             ui.label(format!(
-                "Events detected: {}",
-                self.motion_history.iter().filter(|&x| *x).count()
+                "Score: {:.2}% (threshold {:.2}%)",
+                self.motion_state.score * 100.0,
+                self.motion_state.score_threshold * 100.0
+            ))
+            .on_hover_text(
+                "Largest motion region as a share of the frame; min area sets the threshold",
+            );
+            ui.label(format!(
+                "Motion frames: {}/{}",
+                self.motion_history
+                    .iter()
+                    .filter(|(_, motion)| *motion)
+                    .count(),
+                self.motion_history.len()
             ));
         });
 
@@ -426,9 +2516,20 @@ impl MotionDetectorGui {
                 );
             }
 
-            // Draw motion line
+            // Scale so the threshold sits at most halfway up and peaks fit
+            let threshold = self.motion_state.score_threshold;
+            let top = self
+                .motion_history
+                .iter()
+                .map(|(score, _)| *score)
+                .fold(threshold * 2.0, f32::max)
+                .max(f32::EPSILON);
+            let score_y =
+                |score: f32| graph_rect.min.y + graph_height * (1.0 - (score / top).min(1.0));
+
+            // Draw score line
             let mut last_point = None;
-            for (i, motion) in self
+            for (i, (score, motion)) in self
                 .motion_history
                 .iter()
                 .rev()
@@ -437,11 +2538,7 @@ impl MotionDetectorGui {
             {
                 let x = graph_rect.min.x
                     + (i as f32 / self.max_history_points as f32) * graph_rect.width();
-                let y = if *motion {
-                    graph_rect.min.y + graph_height * 0.2
-                } else {
-                    graph_rect.min.y + graph_height * 0.8
-                };
+                let y = score_y(*score);
 
                 let current_point = pos2(x, y);
 
@@ -476,8 +2573,8 @@ impl MotionDetectorGui {
                 last_point = Some(current_point);
             }
 
-            // Draw threshold line
-            let threshold_y = graph_rect.min.y + graph_height * 0.5;
+            // Draw threshold line where min area puts it
+            let threshold_y = score_y(threshold);
             painter.line_segment(
                 [
                     pos2(graph_rect.min.x, threshold_y),
@@ -490,25 +2587,309 @@ impl MotionDetectorGui {
         }
     }
 
+    /// Incidents nobody has marked as reviewed yet
+    fn unacknowledged(&self) -> usize {
+        self.incidents
+            .iter()
+            .filter(|row| row.acknowledged.is_none())
+            .count()
+    }
+
+    /// Incidents shown per page of the list
+    const INCIDENTS_PER_PAGE: usize = 10;
+
+    /// Search box, acknowledged toggle and date range above the incident list
+    fn render_incident_filter(&mut self, ui: &mut Ui) {
+        let before = self.incident_filter.clone();
+        ui.horizontal(|ui| {
+            ui.label("🔍");
+            ui.add(
+                TextEdit::singleline(&mut self.incident_filter.text)
+                    .hint_text("Search notes")
+                    .desired_width(120.0),
+            );
+            ui.checkbox(&mut self.incident_filter.unacknowledged, "Unacknowledged");
+            if let Some(tag) = &self.incident_filter.tag
+                && ui
+                    .button(format!("🏷 {} ✖", tag))
+                    .on_hover_text("Show all tags")
+                    .clicked()
+            {
+                self.incident_filter.tag = None;
+            }
+        });
+        let mut invalid = false;
+        ui.horizontal(|ui| {
+            for (label, text, date) in [
+                (
+                    "From",
+                    &mut self.incident_dates.0,
+                    &mut self.incident_filter.since,
+                ),
+                (
+                    "to",
+                    &mut self.incident_dates.1,
+                    &mut self.incident_filter.until,
+                ),
+            ] {
+                ui.label(label);
+                ui.add(
+                    TextEdit::singleline(text)
+                        .hint_text("YYYY-MM-DD")
+                        .desired_width(80.0),
+                );
+                // A date that doesn't parse (yet) leaves that end open
+                *date = chrono::NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok();
+                invalid |= !text.trim().is_empty() && date.is_none();
+            }
+            if self.incident_filter.is_active() && ui.button("Clear").clicked() {
+                self.incident_filter = IncidentFilter::default();
+                self.incident_dates = (String::new(), String::new());
+            }
+        });
+        if invalid {
+            ui.colored_label(Color32::RED, "Dates are YYYY-MM-DD");
+        }
+        if self.incident_filter != before {
+            self.incident_page = 0;
+        }
+    }
+
+    fn render_incidents(&mut self, ui: &mut Ui) {
+        let mut header = format!("🚨 Incidents ({})", self.incidents.len());
+        let unacknowledged = self.unacknowledged();
+        if unacknowledged > 0 {
+            header.push_str(&format!(" - {} unacknowledged", unacknowledged));
+        }
+        let mut acknowledged = Vec::new();
+        CollapsingHeader::new(header)
+            .id_source("incidents_header")
+            .default_open(true)
+            .show(ui, |ui| {
+                if self.incidents.is_empty() {
+                    ui.label("No incidents yet");
+                    return;
+                }
+                self.render_incident_filter(ui);
+                let acknowledgements = || {
+                    self.incidents
+                        .iter()
+                        .filter_map(|row| row.acknowledged.as_ref())
+                };
+                let used_tags = incident::top_tags(acknowledgements(), None, usize::MAX);
+                let month_start = Local::now()
+                    .date_naive()
+                    .with_day(1)
+                    .and_then(|day| day.and_hms_opt(0, 0, 0))
+                    .and_then(|time| time.and_local_timezone(Local).earliest());
+                let top = incident::top_tags(acknowledgements(), month_start, 5);
+                if !top.is_empty() {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.label("Top tags this month:");
+                        for (tag, count) in top {
+                            if ui.small_button(format!("{} ({})", tag, count)).clicked() {
+                                self.incident_filter.tag = Some(tag);
+                                self.incident_page = 0;
+                            }
+                        }
+                    });
+                }
+                let mut clicked_tag = None;
+                // Newest first
+                let matching: Vec<usize> = (0..self.incidents.len())
+                    .rev()
+                    .filter(|&index| {
+                        let row = &self.incidents[index];
+                        self.incident_filter
+                            .matches(&row.events, row.acknowledged.as_ref())
+                    })
+                    .collect();
+                if matching.is_empty() {
+                    ui.label("No incidents match");
+                    return;
+                }
+                let pages = matching.len().div_ceil(Self::INCIDENTS_PER_PAGE);
+                self.incident_page = self.incident_page.min(pages - 1);
+                if pages > 1 {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(self.incident_page > 0, Button::new("◀"))
+                            .clicked()
+                        {
+                            self.incident_page -= 1;
+                        }
+                        ui.label(format!(
+                            "Page {} of {} ({} incidents)",
+                            self.incident_page + 1,
+                            pages,
+                            matching.len()
+                        ));
+                        if ui
+                            .add_enabled(self.incident_page + 1 < pages, Button::new("▶"))
+                            .clicked()
+                        {
+                            self.incident_page += 1;
+                        }
+                    });
+                }
+                let shown = incident::page(&matching, self.incident_page, Self::INCIDENTS_PER_PAGE);
+                ScrollArea::vertical()
+                    .id_source("incidents")
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        for &index in shown {
+                            let row = &mut self.incidents[index];
+                            let (Some(first), Some(last)) = (row.events.first(), row.events.last())
+                            else {
+                                continue;
+                            };
+                            let span = (*last - *first).to_std().unwrap_or_default();
+                            let mut title = format!(
+                                "{}Incident #{} - {} events over {}, {} to {}",
+                                if row.acknowledged.is_some() {
+                                    "✅ "
+                                } else {
+                                    ""
+                                },
+                                row.id,
+                                row.events.len(),
+                                humanize_duration(span),
+                                first.format("%H:%M:%S"),
+                                last.format("%H:%M:%S")
+                            );
+                            if row.followups > 0 {
+                                title.push_str(&format!(", {} follow-up(s)", row.followups));
+                            }
+                            if let Some((original, trimmed)) = row.clip {
+                                title.push_str(&format!(
+                                    ", clip {} (trimmed from {})",
+                                    humanize_duration(trimmed),
+                                    humanize_duration(original)
+                                ));
+                            }
+                            CollapsingHeader::new(title)
+                                .id_source(("incident", row.id))
+                                .show(ui, |ui| {
+                                    let now = Local::now();
+                                    for event in &row.events {
+                                        ui.label(format!(
+                                            "Motion at {} ({})",
+                                            event.format("%H:%M:%S"),
+                                            humanize_ago(*event, now)
+                                        ));
+                                    }
+                                    match &row.acknowledged {
+                                        Some(ack) => {
+                                            ui.colored_label(Color32::GRAY, ack.describe());
+                                            ui.horizontal_wrapped(|ui| {
+                                                for tag in &ack.tags {
+                                                    if ui
+                                                        .small_button(format!("🏷 {}", tag))
+                                                        .on_hover_text(
+                                                            "Show incidents with this tag",
+                                                        )
+                                                        .clicked()
+                                                    {
+                                                        clicked_tag = Some(tag.clone());
+                                                    }
+                                                }
+                                            });
+                                        }
+                                        None => {
+                                            ui.horizontal(|ui| {
+                                                ui.add(
+                                                    TextEdit::singleline(&mut row.note)
+                                                        .hint_text("Note, e.g. cat")
+                                                        .desired_width(150.0),
+                                                );
+                                                if ui.button("✅ Acknowledge").clicked() {
+                                                    let ack =
+                                                        Acknowledgement::now(&row.note, &row.tags);
+                                                    acknowledged.push((row.id, ack.clone()));
+                                                    row.acknowledged = Some(ack);
+                                                }
+                                            });
+                                            render_tag_editor(ui, row, &used_tags);
+                                        }
+                                    }
+                                });
+                        }
+                    });
+                if let Some(tag) = clicked_tag {
+                    self.incident_filter.tag = Some(tag);
+                    self.incident_page = 0;
+                }
+            });
+        for (id, ack) in acknowledged {
+            self.activity_log.push(
+                LogLevel::Info,
+                format!("Incident #{}: {}", id, ack.describe()),
+            );
+        }
+    }
+
     fn render_log_panel(&mut self, ui: &mut Ui) {
         ui.horizontal(|ui| {
             ui.heading("📝 Activity Log");
+            // Error badge, visible even when errors are scrolled away or filtered out
+            let errors = self.activity_log.error_count();
+            if errors > 0 {
+                ui.label(
+                    RichText::new(format!(" {} ", errors))
+                        .color(Color32::WHITE)
+                        .background_color(Color32::RED)
+                        .strong(),
+                );
+            }
             ui.checkbox(&mut self.auto_scroll, "Auto-scroll");
             if ui.button("Clear").clicked() {
-                self.status_log.clear();
+                self.activity_log.clear();
+            }
+            if ui
+                .add_enabled(self.pending_dialog.is_none(), Button::new("💾 Export..."))
+                .clicked()
+            {
+                self.pending_dialog = Some(dialogs::save_file(
+                    Purpose::ExportLog,
+                    format!("activity_{}.csv", Local::now().format("%Y%m%d_%H%M%S")),
+                    "CSV",
+                    &["csv"],
+                ));
             }
         });
+
+        // Level filters and search
+        ui.horizontal(|ui| {
+            for level in LogLevel::ALL {
+                let mut shown = self.log_levels.contains(&level);
+                if ui.checkbox(&mut shown, level.label()).changed() {
+                    if shown {
+                        self.log_levels.push(level);
+                    } else {
+                        self.log_levels.retain(|l| *l != level);
+                    }
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("🔍");
+            ui.text_edit_singleline(&mut self.log_search);
+        });
         ui.separator();
 
         ScrollArea::vertical()
             .auto_shrink([false, false])
             .stick_to_bottom(self.auto_scroll)
             .show(ui, |ui| {
-                for (i, entry) in self.status_log.iter().enumerate() {
-                    ui.label(entry);
-                    if i < self.status_log.len() - 1 {
-                        ui.separator();
-                    }
+                for entry in self
+                    .activity_log
+                    .filtered(&self.log_levels, &self.log_search)
+                {
+                    ui.colored_label(
+                        entry.level.color(),
+                        format!("[{}] {}", entry.time.format("%H:%M:%S"), entry.message),
+                    );
+                    ui.separator();
                 }
             });
     }
@@ -517,40 +2898,553 @@ impl MotionDetectorGui {
         TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
+                    let idle = self.pending_dialog.is_none() && self.graph_export.is_none();
+                    if ui
+                        .add_enabled(idle, Button::new("Export graph as image..."))
+                        .clicked()
+                    {
+                        self.start_graph_export();
+                        ui.close_menu();
+                    }
                     if ui.button("Quit").clicked() {
+                        self.quit_requested = true;
                         ctx.send_viewport_cmd(ViewportCommand::Close);
                     }
                 });
 
                 ui.menu_button("View", |ui| {
                     ui.checkbox(&mut self.show_about, "About");
+                    if ui
+                        .checkbox(&mut self.observe, "Observe only (save nothing)")
+                        .changed()
+                    {
+                        self.set_observe(self.observe);
+                    }
+                    ui.checkbox(&mut self.minimize_on_close, "Minimize instead of closing");
+                    ui.add_enabled(
+                        self.minimize_on_close,
+                        egui::Checkbox::new(
+                            &mut self.notify_when_hidden,
+                            "Flash the taskbar on motion while minimized",
+                        ),
+                    );
+                    ui.checkbox(&mut self.show_hourly, "Events by hour");
+                    let mut tuning = self.show_tuning;
+                    if ui
+                        .checkbox(&mut tuning, "Tune against last event")
+                        .changed()
+                    {
+                        self.set_tuning(tuning);
+                    }
                 });
 
                 ui.menu_button("Camera", |ui| {
+                    if ui.button("Run self-test").clicked() {
+                        self.show_self_test = true;
+                        self.self_test_results = None;
+                        self.activity_log
+                            .push(LogLevel::Info, "Running self-test...".to_string());
+                        let _ = self.sender.send(GuiMessage::RunSelfTest);
+                        ui.close_menu();
+                    }
+
                     if ui.button("Toggle Detection").clicked() {
                         if self.is_detecting {
                             self.is_detecting = false;
-                            self.detector_status = DetectorStatus::Stopped;
-                            self.status_log.push("Motion detection stopped".to_string());
-                            if self.status_log.len() > 100 {
-                                self.status_log.remove(0);
-                            }
+                            self.detector_status = DetectorPhase::Stopped;
+                            self.activity_log
+                                .push(LogLevel::Info, "Motion detection stopped".to_string());
                             let _ = self.sender.send(GuiMessage::StopDetection);
                         } else {
                             self.is_detecting = true;
-                            self.detector_status = DetectorStatus::Running;
-                            self.status_log.push("Motion detection started".to_string());
-                            if self.status_log.len() > 100 {
-                                self.status_log.remove(0);
-                            }
+                            self.detector_status = DetectorPhase::Initializing;
+                            self.activity_log
+                                .push(LogLevel::Info, "Motion detection started".to_string());
                             let _ = self.sender.send(GuiMessage::StartDetection);
                         }
                     }
                 });
+
+                // Bell with the number of unread notifications
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    let unread = self.notifications.unread();
+                    let bell = match unread {
+                        0 => RichText::new("🔔"),
+                        n => RichText::new(format!("🔔 {}", n)).color(Color32::RED),
+                    };
+                    if ui
+                        .selectable_label(self.show_notifications, bell)
+                        .on_hover_text("Notifications")
+                        .clicked()
+                    {
+                        self.show_notifications = !self.show_notifications;
+                    }
+                });
             });
         });
     }
 
+    /// Warnings and errors of the session, one row per kind
+    fn render_notifications_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut dismissed = None;
+        let mut cleared = false;
+        Window::new("Notifications")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if self.notifications.items().is_empty() {
+                    ui.label("No warnings or errors this session");
+                    return;
+                }
+                let now = Local::now();
+                Grid::new("notifications")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for item in self.notifications.items() {
+                            let color = match item.severity {
+                                Severity::Warning => Color32::YELLOW,
+                                Severity::Error => Color32::LIGHT_RED,
+                                Severity::Critical => Color32::RED,
+                            };
+                            ui.colored_label(color, item.severity.label());
+                            ui.vertical(|ui| {
+                                let title = match item.count {
+                                    1 => item.kind.label().to_string(),
+                                    n => format!("{} (×{})", item.kind.label(), n),
+                                };
+                                ui.strong(title);
+                                ui.label(&item.message);
+                            });
+                            ui.label(match item.count {
+                                1 => humanize_ago(item.last, now),
+                                _ => format!(
+                                    "{}\nfirst {}",
+                                    humanize_ago(item.last, now),
+                                    humanize_ago(item.first, now)
+                                ),
+                            });
+                            if ui.small_button("Dismiss").clicked() {
+                                dismissed = Some(item.kind);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+                cleared = ui.button("Dismiss all").clicked();
+            });
+        if let Some(kind) = dismissed {
+            self.notifications.dismiss(kind);
+        }
+        if cleared {
+            self.notifications.clear();
+        }
+        // Whatever was on screen has been seen
+        self.notifications.mark_all_read();
+        self.show_notifications = open;
+    }
+
+    /// The latest critical notification, in the corner for a few seconds
+    fn render_toast(&mut self, ctx: &egui::Context) {
+        const SHOWN_FOR: std::time::Duration = std::time::Duration::from_secs(8);
+        let Some((message, since)) = &self.toast else {
+            return;
+        };
+        if since.elapsed() >= SHOWN_FOR {
+            self.toast = None;
+            return;
+        }
+        let mut open_center = false;
+        Area::new(Id::new("toast"))
+            .anchor(Align2::RIGHT_BOTTOM, vec2(-12.0, -12.0))
+            .show(ctx, |ui| {
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.colored_label(Color32::RED, format!("⚠ {}", message));
+                    open_center = ui.small_button("Show notifications").clicked();
+                });
+            });
+        if open_center {
+            self.show_notifications = true;
+            self.toast = None;
+        }
+        ctx.request_repaint_after(std::time::Duration::from_millis(500));
+    }
+
+    fn render_self_test_window(&mut self, ctx: &egui::Context) {
+        Window::new("Self-test")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                match &self.self_test_results {
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Checking camera, output directory, disk and clock...");
+                        });
+                    }
+                    Some(results) => {
+                        Grid::new("self_test_results")
+                            .num_columns(3)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for result in results {
+                                    let color = match result.status {
+                                        CheckStatus::Pass => Color32::GREEN,
+                                        CheckStatus::Warn => Color32::YELLOW,
+                                        CheckStatus::Fail => Color32::RED,
+                                        CheckStatus::Skip => Color32::GRAY,
+                                    };
+                                    ui.colored_label(color, result.status.label());
+                                    ui.label(result.name);
+                                    ui.label(&result.message);
+                                    ui.end_row();
+                                }
+                            });
+                    }
+                }
+                ui.separator();
+                if ui.button("Close").clicked() {
+                    self.show_self_test = false;
+                }
+            });
+    }
+
+    /// Bar chart of the session's motion events by hour of the day
+    fn render_hourly_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        Window::new("Events by hour")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let counts = self.hourly_counts.counts();
+                let max = counts.iter().copied().max().unwrap_or(0).max(1);
+                let (rect, response) =
+                    ui.allocate_exact_size(vec2(24.0 * 14.0, 120.0), Sense::hover());
+                let painter = ui.painter_at(rect);
+                painter.rect_filled(rect, 0.0, Color32::from_rgb(20, 20, 25));
+
+                let chart = Rect::from_min_max(rect.min, pos2(rect.max.x, rect.max.y - 14.0));
+                let bar_width = chart.width() / 24.0;
+                for (hour, &count) in counts.iter().enumerate() {
+                    let x = chart.min.x + hour as f32 * bar_width;
+                    let height = chart.height() * count as f32 / max as f32;
+                    if count > 0 {
+                        painter.rect_filled(
+                            Rect::from_min_max(
+                                pos2(x + 1.0, chart.max.y - height),
+                                pos2(x + bar_width - 1.0, chart.max.y),
+                            ),
+                            1.0,
+                            Color32::from_rgb(80, 160, 255),
+                        );
+                    }
+                    if hour % 6 == 0 {
+                        painter.text(
+                            pos2(x, rect.max.y),
+                            Align2::LEFT_BOTTOM,
+                            format!("{:02}", hour),
+                            FontId::proportional(10.0),
+                            Color32::GRAY,
+                        );
+                    }
+                }
+
+                // Hovering a bar shows its hour and count
+                if let Some(pointer) = response.hover_pos() {
+                    let hour = (((pointer.x - chart.min.x) / bar_width) as usize).min(23);
+                    response.on_hover_text(format!(
+                        "{:02}:00–{:02}:00: {} event(s)",
+                        hour,
+                        (hour + 1) % 24,
+                        counts[hour]
+                    ));
+                }
+
+                ui.label(match self.hourly_counts.busiest() {
+                    Some(hour) => format!(
+                        "{} event(s) this session, most around {:02}:00",
+                        self.hourly_counts.total(),
+                        hour
+                    ),
+                    None => "No motion events yet this session".to_string(),
+                });
+            });
+        self.show_hourly = open;
+    }
+
+    /// Open or close the tuning window. It opens on the live settings.
+    fn set_tuning(&mut self, open: bool) {
+        self.show_tuning = open;
+        if open {
+            self.tune = TuneParams {
+                sensitivity: self.sensitivity,
+                min_area: self.min_area,
+            };
+            let _ = self.sender.send(GuiMessage::Tune(Some(self.tune)));
+        } else {
+            let _ = self.sender.send(GuiMessage::Tune(None));
+        }
+    }
+
+    fn render_tuning_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        Window::new("🎚 Tune against last event")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let (width, height) = self.motion_state.resolution;
+                let frame_area = (width as f64 * height as f64).max(1.0);
+                let mut tune = self.tune;
+                ui.horizontal(|ui| {
+                    ui.label("Sensitivity:");
+                    ui.add(Slider::new(&mut tune.sensitivity, 0.0..=1.0));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Min Area:");
+                    match &mut tune.min_area {
+                        MinArea::Pixels(pixels) => ui.add(Slider::new(pixels, 50..=5000)),
+                        MinArea::Fraction(fraction) => {
+                            let mut percent = *fraction * 100.0;
+                            let response = ui.add(
+                                Slider::new(&mut percent, 0.01..=10.0)
+                                    .logarithmic(true)
+                                    .max_decimals(2),
+                            );
+                            *fraction = percent / 100.0;
+                            response
+                        }
+                    };
+                    ui.label(tune.min_area.describe(Some(frame_area)));
+                });
+                if tune != self.tune {
+                    self.tune = tune;
+                    let _ = self.sender.send(GuiMessage::Tune(Some(tune)));
+                }
+                ui.separator();
+
+                match &self.tuning {
+                    None => {
+                        ui.label("No event recorded yet; the next one shows up here.");
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(Color32::RED, e);
+                    }
+                    Some(Ok(result)) => {
+                        ui.label(format!(
+                            "Event at {}, {} frames",
+                            result.recorded.format("%H:%M:%S"),
+                            result.scores.len()
+                        ));
+                        render_tuning_chart(ui, result);
+                        let color = if result.would_trigger() {
+                            Color32::from_rgb(255, 120, 120)
+                        } else {
+                            Color32::GRAY
+                        };
+                        ui.colored_label(color, result.summary());
+                        if result.params != self.tune {
+                            ui.colored_label(Color32::GRAY, "Replaying...");
+                        }
+                    }
+                }
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    let live = TuneParams {
+                        sensitivity: self.sensitivity,
+                        min_area: self.min_area,
+                    };
+                    if ui
+                        .add_enabled(self.tune != live, Button::new("✔ Apply these settings"))
+                        .clicked()
+                    {
+                        self.sensitivity = self.tune.sensitivity;
+                        self.min_area = self.tune.min_area;
+                        self.store_camera_settings();
+                        let _ = self
+                            .sender
+                            .send(GuiMessage::UpdateSensitivity(self.sensitivity));
+                        let _ = self.sender.send(GuiMessage::UpdateMinArea(self.min_area));
+                        self.activity_log.push(
+                            LogLevel::Info,
+                            format!(
+                                "Tuned: sensitivity {:.2}, min area {}",
+                                self.sensitivity,
+                                self.min_area.describe(Some(frame_area))
+                            ),
+                        );
+                    }
+                    if ui
+                        .add_enabled(self.tune != live, Button::new("↺ Reset"))
+                        .on_hover_text("Back to the live settings")
+                        .clicked()
+                    {
+                        self.tune = live;
+                        let _ = self.sender.send(GuiMessage::Tune(Some(live)));
+                    }
+                    if ui
+                        .button("⟳ Latest event")
+                        .on_hover_text("The window keeps its event while open; load a newer one")
+                        .clicked()
+                    {
+                        let _ = self.sender.send(GuiMessage::Tune(None));
+                        let _ = self.sender.send(GuiMessage::Tune(Some(self.tune)));
+                    }
+                });
+            });
+        if !open {
+            self.set_tuning(false);
+        }
+    }
+
+    fn render_recovery_window(&mut self, ctx: &egui::Context) {
+        let Some(session) = &self.recovered else {
+            return;
+        };
+        let mut resume = false;
+        let mut dismiss = false;
+        Window::new("Previous session")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(session.describe());
+                ui.label(format!(
+                    "Camera {}, sensitivity {:.2}, min area {}, detection {}",
+                    session.device,
+                    session.sensitivity,
+                    session.min_area.describe(None),
+                    if session.detecting {
+                        "running"
+                    } else {
+                        "stopped"
+                    }
+                ));
+                ui.separator();
+                ui.horizontal(|ui| {
+                    resume = ui.button("▶️ Resume").clicked();
+                    dismiss = ui.button("Dismiss").clicked();
+                });
+            });
+        if resume {
+            if let Some(session) = self.recovered.take() {
+                self.resume_session(session);
+            }
+        } else if dismiss {
+            self.recovered = None;
+        }
+    }
+
+    /// Closing the window stops monitoring, so ask first while detecting
+    fn render_about_window(&mut self, ctx: &egui::Context) {
+        let info = self.build_info.get_or_insert_with(BuildInfo::collect);
+        let mut open = true;
+        Window::new("About Motion Detector")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.heading(format!("Motion Detector {}", info.version));
+                ui.label("A Rust-based motion detection application");
+                ui.separator();
+                Grid::new("build_info").num_columns(2).show(ui, |ui| {
+                    for (label, value) in info.fields().into_iter().skip(1) {
+                        ui.label(format!("{}:", label));
+                        ui.label(value);
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("📋 Copy to clipboard")
+                        .on_hover_text("For bug reports")
+                        .clicked()
+                    {
+                        ui.output_mut(|o| o.copied_text = info.to_string());
+                    }
+                    if ui.button("Close").clicked() {
+                        open = false;
+                    }
+                });
+            });
+        self.show_about = open;
+    }
+
+    fn render_exit_dialog(&mut self, ctx: &egui::Context) {
+        Window::new("Quit Motion Detector?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label("Detection is running. Quitting stops monitoring.");
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add(Button::new("⏹️ Stop and quit").fill(Color32::RED))
+                        .clicked()
+                    {
+                        self.confirm_exit = false;
+                        self.allow_close = true;
+                        self.is_detecting = false;
+                        let _ = self.sender.send(GuiMessage::StopDetection);
+                        ctx.send_viewport_cmd(ViewportCommand::Close);
+                    }
+                    if ui.button("🗕 Minimize instead").clicked() {
+                        self.confirm_exit = false;
+                        ctx.send_viewport_cmd(ViewportCommand::Minimized(true));
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.confirm_exit = false;
+                    }
+                });
+            });
+    }
+
+    /// Switch observe mode on or off in the detector thread
+    fn set_observe(&mut self, observe: bool) {
+        self.observe = observe;
+        let _ = self.sender.send(GuiMessage::SetObserve(observe));
+        if observe {
+            self.activity_log.push(
+                LogLevel::Info,
+                "👁 Observe mode: events are reported, nothing is saved, uploaded or switched",
+            );
+        } else {
+            self.activity_log
+                .push(LogLevel::Info, "Live: events are saved and acted on again");
+        }
+    }
+
+    /// Shown for as long as observe mode is on, so a quiet output directory
+    /// isn't mistaken for a quiet camera
+    fn render_observe_banner(&mut self, ctx: &egui::Context) {
+        if !self.observe {
+            return;
+        }
+        TopBottomPanel::top("observe_banner")
+            .frame(
+                Frame::default()
+                    .fill(Color32::from_rgb(255, 165, 0))
+                    .inner_margin(6.0),
+            )
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(
+                        RichText::new(
+                            "👁 OBSERVE MODE: events are reported, but nothing is saved, \
+                             uploaded or switched",
+                        )
+                        .color(Color32::BLACK)
+                        .strong(),
+                    );
+                    if ui.button("Go live").clicked() {
+                        self.set_observe(false);
+                    }
+                });
+            });
+    }
+
     fn render_green_light_panel(&mut self, ctx: &egui::Context) {
         TopBottomPanel::top("motion_indicator").show(ctx, |ui| {
             ui.horizontal(|ui| {
@@ -600,41 +3494,106 @@ impl MotionDetectorGui {
 }
 
 impl eframe::App for MotionDetectorGui {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        let _ = self.sender.send(GuiMessage::Shutdown);
+        if let Some(journal) = self.journal.take()
+            && let Err(e) = journal.close()
+        {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Update animation time
         self.motion_animation_time += ctx.input(|i| i.stable_dt);
 
+        // Relative times ("12 s ago", uptime) keep counting without new state
+        ctx.request_repaint_after(std::time::Duration::from_secs(1));
+
         // Update motion detection state
+        let intrusion = self.motion_state.intrusion;
+        let motion_count = self.motion_state.motion_count;
         self.update_settings_from_receiver();
+        if self.motion_state.intrusion == IntrusionState::Alarm
+            && intrusion != IntrusionState::Alarm
+        {
+            ctx.send_viewport_cmd(ViewportCommand::RequestUserAttention(
+                UserAttentionType::Critical,
+            ));
+        } else if self.motion_state.motion_count > motion_count
+            && self.minimize_on_close
+            && self.notify_when_hidden
+            && ctx.input(|i| i.viewport().minimized) == Some(true)
+        {
+            ctx.send_viewport_cmd(ViewportCommand::RequestUserAttention(
+                UserAttentionType::Informational,
+            ));
+        }
+        self.update_preview_from_receiver(ctx);
+        self.update_journal();
+        self.poll_dialog();
+        self.poll_graph_export();
+        if self.pending_dialog.is_some() || self.graph_export.is_some() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+
+        if ctx.input(|i| i.viewport().close_requested()) && !self.allow_close {
+            let quit = std::mem::take(&mut self.quit_requested);
+            if self.minimize_on_close && !quit {
+                ctx.send_viewport_cmd(ViewportCommand::CancelClose);
+                ctx.send_viewport_cmd(ViewportCommand::Minimized(true));
+                if !self.minimize_noted {
+                    self.minimize_noted = true;
+                    self.activity_log.push(
+                        LogLevel::Info,
+                        "No system tray available, so closing minimizes the window and detection keeps running. Use File ▸ Quit to quit",
+                    );
+                }
+            } else if self.is_detecting {
+                ctx.send_viewport_cmd(ViewportCommand::CancelClose);
+                self.confirm_exit = true;
+            }
+        }
+
+        // A video file dropped on the window replaces the camera
+        let dropped = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .find_map(|file| file.path.clone())
+        });
+        if let Some(path) = dropped {
+            self.open_file(path);
+        }
 
         // Render menu bar
         self.render_menu_bar(ctx);
+        self.render_observe_banner(ctx);
 
         // Render prominent green light indicator at top
         self.render_green_light_panel(ctx);
 
         // About window
         if self.show_about {
-            Window::new("About Motion Detector")
-                .collapsible(false)
-                .resizable(false)
-                .show(ctx, |ui| {
-                    ui.heading("Motion Detector v0.1.0");
-                    ui.label("A Rust-based motion detection application");
-                    ui.label("with enhanced GUI and real-time visualization");
-                    ui.separator();
-                    ui.label("Features:");
-                    ui.label("• Real-time motion detection");
-                    ui.label("• Animated green light indicators");
-                    ui.label("• Live motion graph visualization");
-                    ui.label("• Configurable sensitivity");
-                    ui.label("• Snapshot capture");
-                    ui.label("• GUI control panel");
-                    ui.separator();
-                    if ui.button("Close").clicked() {
-                        self.show_about = false;
-                    }
-                });
+            self.render_about_window(ctx);
+        }
+
+        if self.show_self_test {
+            self.render_self_test_window(ctx);
+        }
+        if self.show_hourly {
+            self.render_hourly_window(ctx);
+        }
+        if self.show_tuning {
+            self.render_tuning_window(ctx);
+        }
+        if self.show_notifications {
+            self.render_notifications_window(ctx);
+        }
+        self.render_toast(ctx);
+        self.render_recovery_window(ctx);
+        if self.confirm_exit {
+            self.render_exit_dialog(ctx);
         }
 
         // Main layout
@@ -652,6 +3611,9 @@ impl eframe::App for MotionDetectorGui {
                 ui.vertical(|ui| {
                     ui.set_min_width(300.0);
                     self.render_status_panel(ui);
+                    self.render_advanced_status(ui);
+                    ui.add_space(10.0);
+                    self.render_preview(ui);
                     ui.add_space(10.0);
                     self.render_motion_graph(ui);
                 });
@@ -661,6 +3623,8 @@ impl eframe::App for MotionDetectorGui {
                 // Right panel - Activity Log
                 ui.vertical(|ui| {
                     ui.set_min_width(350.0);
+                    self.render_incidents(ui);
+                    ui.add_space(10.0);
                     self.render_log_panel(ui);
                 });
             });
@@ -0,0 +1,130 @@
+// Without the `camera` feature only the tests use this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use crate::probe;
+use crossbeam_channel::{Receiver, TryRecvError};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// A V4L2 capture device node
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CameraInfo {
+    /// N in /dev/videoN, the index passed to `--device`
+    pub index: u32,
+    pub path: PathBuf,
+    /// Product name reported by the driver, e.g. "HD Pro Webcam C920"
+    pub name: String,
+}
+
+impl CameraInfo {
+    pub fn label(&self) -> String {
+        format!("Camera {} - {}", self.index, self.name)
+    }
+
+    /// `--prefer-device` matches the device path or part of the name,
+    /// ignoring case
+    pub fn matches(&self, pattern: &str) -> bool {
+        self.path.as_os_str() == pattern
+            || self.name.to_lowercase().contains(&pattern.to_lowercase())
+    }
+}
+
+/// Capture nodes under `dev_dir` (/dev), named from `sys_dir`
+/// (/sys/class/video4linux). A camera usually has a second node for
+/// metadata, which reports a non-zero `index` and is skipped. Each node's
+/// attributes are read with [`probe::probe_all`], so a node whose driver
+/// hangs is left out instead of stalling the scan.
+pub fn scan(dev_dir: &Path, sys_dir: &Path) -> Vec<CameraInfo> {
+    let Ok(entries) = std::fs::read_dir(dev_dir) else {
+        return Vec::new();
+    };
+    let nodes: Vec<(u32, String)> = entries
+        .filter_map(|entry| {
+            let file_name = entry.ok()?.file_name().into_string().ok()?;
+            let index = file_name.strip_prefix("video")?.parse().ok()?;
+            Some((index, file_name))
+        })
+        .collect();
+    let (dev_dir, sys_dir) = (dev_dir.to_path_buf(), sys_dir.to_path_buf());
+    let mut cameras: Vec<CameraInfo> =
+        probe::probe_all(nodes, probe::PROBE_TIMEOUT, move |(index, file_name)| {
+            let sys = sys_dir.join(&file_name);
+            let read = |attr: &str| {
+                std::fs::read_to_string(sys.join(attr))
+                    .ok()
+                    .map(|text| text.trim().to_string())
+            };
+            if read("index").is_some_and(|node| node != "0") {
+                return None;
+            }
+            Some(CameraInfo {
+                index,
+                path: dev_dir.join(&file_name),
+                name: read("name").unwrap_or_else(|| "Unknown camera".to_string()),
+            })
+        })
+        .into_iter()
+        .flatten()
+        .flatten()
+        .collect();
+    cameras.sort_by_key(|camera| camera.index);
+    cameras
+}
+
+pub fn scan_system() -> Vec<CameraInfo> {
+    scan(Path::new("/dev"), Path::new("/sys/class/video4linux"))
+}
+
+/// Polls the device nodes on a background thread and reports the new list
+/// whenever a camera is plugged in or removed
+pub struct HotplugWatcher {
+    changes: Receiver<Vec<CameraInfo>>,
+}
+
+impl HotplugWatcher {
+    /// The first report is the list at startup. Returns `None` where there
+    /// are no V4L2 device nodes to watch.
+    pub fn spawn(interval: Duration) -> Option<Self> {
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+        let (sender, changes) = crossbeam_channel::unbounded();
+        thread::spawn(move || {
+            let mut previous = None;
+            loop {
+                let cameras = scan_system();
+                if previous.as_ref() != Some(&cameras) {
+                    // The receiver is gone once the detector stops
+                    if sender.send(cameras.clone()).is_err() {
+                        return;
+                    }
+                    previous = Some(cameras);
+                }
+                thread::sleep(interval);
+            }
+        });
+        Some(Self { changes })
+    }
+
+    /// The newest device list, if it changed since the last call
+    pub fn changed(&self) -> Option<Vec<CameraInfo>> {
+        let mut latest = None;
+        loop {
+            match self.changes.try_recv() {
+                Ok(cameras) => latest = Some(cameras),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => return latest,
+            }
+        }
+    }
+}
+
+/// Cameras in `current` that weren't in `previous`, and the other way round
+pub fn diff<'a>(
+    previous: &'a [CameraInfo],
+    current: &'a [CameraInfo],
+) -> (Vec<&'a CameraInfo>, Vec<&'a CameraInfo>) {
+    let added = current.iter().filter(|c| !previous.contains(c)).collect();
+    let removed = previous.iter().filter(|c| !current.contains(c)).collect();
+    (added, removed)
+}
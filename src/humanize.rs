@@ -0,0 +1,39 @@
+//! Human-readable durations shared by the GUI and the CLI. All wording is
+//! produced here, so translations can hook in at one place.
+
+use chrono::{DateTime, Local};
+use std::time::Duration;
+
+/// "45 s", "12 min", "3 h 12 min" or "2 d 5 h"
+pub fn humanize_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{} s", secs)
+    } else if secs < 3600 {
+        format!("{} min", secs / 60)
+    } else if secs < 86400 {
+        format!("{} h {} min", secs / 3600, secs % 3600 / 60)
+    } else {
+        format!("{} d {} h", secs / 86400, secs % 86400 / 3600)
+    }
+}
+
+/// How long before `now` something happened: "just now", "45 s ago",
+/// "12 min ago", "3 h 12 min ago", then "yesterday 14:02" or a date once
+/// it was on an earlier day. Times in the future (clock skew) are "just now".
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn humanize_ago(then: DateTime<Local>, now: DateTime<Local>) -> String {
+    let Ok(elapsed) = now.signed_duration_since(then).to_std() else {
+        return "just now".to_string();
+    };
+    if elapsed < Duration::from_secs(1) {
+        return "just now".to_string();
+    }
+    if elapsed < Duration::from_secs(3600) || then.date_naive() == now.date_naive() {
+        return format!("{} ago", humanize_duration(elapsed));
+    }
+    if now.date_naive().pred_opt() == Some(then.date_naive()) {
+        return format!("yesterday {}", then.format("%H:%M"));
+    }
+    then.format("%Y-%m-%d %H:%M").to_string()
+}
@@ -0,0 +1,369 @@
+use crate::humanize::humanize_duration;
+use chrono::{DateTime, Local, NaiveDate};
+use std::time::{Duration, Instant};
+
+/// Result of feeding one motion event into the [`IncidentTracker`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IncidentUpdate {
+    /// The event opened a new incident; notify once here
+    Started(u64),
+    /// The event joined the incident that is already open
+    Continued(u64),
+}
+
+impl IncidentUpdate {
+    pub fn id(&self) -> u64 {
+        match self {
+            IncidentUpdate::Started(id) | IncidentUpdate::Continued(id) => *id,
+        }
+    }
+}
+
+/// A group of events whose gaps were all shorter than the incident gap
+#[derive(Clone, Debug, PartialEq)]
+pub struct Incident {
+    pub id: u64,
+    pub event_count: u32,
+    pub first_event: Instant,
+    pub last_event: Instant,
+    /// Follow-up alerts sent while it lasted (`--re-alert`)
+    pub followups: u32,
+    /// The announcement or the latest follow-up
+    last_alert: Instant,
+}
+
+impl Incident {
+    pub fn duration(&self) -> Duration {
+        self.last_event.duration_since(self.first_event)
+    }
+
+    pub fn summary(&self) -> String {
+        let mut text = format!(
+            "Incident #{} ended, {} events over {}",
+            self.id,
+            self.event_count,
+            humanize_duration(self.duration())
+        );
+        if self.followups > 0 {
+            text.push_str(&format!(", {} follow-up(s)", self.followups));
+        }
+        text
+    }
+}
+
+/// A reminder that an incident is still going on, from `--re-alert`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FollowUp {
+    pub id: u64,
+    /// 1 for the first follow-up of the incident
+    pub number: u32,
+    /// Since the incident's first event
+    pub ongoing: Duration,
+}
+
+impl FollowUp {
+    /// e.g. "MOTION ONGOING for 6 min in zone porch (incident #3, follow-up 2)"
+    pub fn message(&self, zone: Option<&str>) -> String {
+        let zone = zone
+            .map(|zone| format!(" in zone {}", zone))
+            .unwrap_or_default();
+        format!(
+            "MOTION ONGOING for {}{} (incident #{}, follow-up {})",
+            humanize_duration(self.ongoing),
+            zone,
+            self.id,
+            self.number
+        )
+    }
+}
+
+/// Someone reviewed an incident, e.g. "it was the cat". Covers every event
+/// in the incident.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct Acknowledgement {
+    pub who: String,
+    pub when: DateTime<Local>,
+    pub note: String,
+    /// Short labels such as "dog" or "delivery", see [`normalize_tag`]
+    pub tags: Vec<String>,
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl Acknowledgement {
+    /// Acknowledged now by the user running the detector
+    pub fn now(note: &str, tags: &[String]) -> Self {
+        let who = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| "unknown".to_string());
+        Self {
+            who,
+            when: Local::now(),
+            note: note.trim().to_string(),
+            tags: tags.to_vec(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "Acknowledged by {} at {}",
+            self.who,
+            self.when.format("%H:%M:%S")
+        );
+        if !self.note.is_empty() {
+            text.push_str(&format!(": {}", self.note));
+        }
+        if !self.tags.is_empty() {
+            text.push_str(&format!(" [{}]", self.tags.join(", ")));
+        }
+        text
+    }
+}
+
+/// A tag as stored: lowercase, single spaces, or `None` when empty
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn normalize_tag(text: &str) -> Option<String> {
+    let tag = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    (!tag.is_empty()).then_some(tag)
+}
+
+/// How often each tag was used, since `since` if given, most used first,
+/// at most `limit` of them
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn top_tags<'a>(
+    acknowledgements: impl IntoIterator<Item = &'a Acknowledgement>,
+    since: Option<DateTime<Local>>,
+    limit: usize,
+) -> Vec<(String, usize)> {
+    let mut counts = std::collections::BTreeMap::new();
+    for ack in acknowledgements {
+        if since.is_none_or(|since| ack.when >= since) {
+            for tag in &ack.tags {
+                *counts.entry(tag.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    // Ties stay alphabetical
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    counts.truncate(limit);
+    counts
+}
+
+/// Used tags starting with what has been typed so far, most used first,
+/// leaving out those already chosen
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn suggest_tags(used: &[(String, usize)], typed: &str, chosen: &[String]) -> Vec<String> {
+    let typed = normalize_tag(typed).unwrap_or_default();
+    used.iter()
+        .map(|(tag, _)| tag)
+        .filter(|tag| tag.starts_with(&typed) && !chosen.contains(tag))
+        .take(5)
+        .cloned()
+        .collect()
+}
+
+/// Which incidents the GUI's incident list shows
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct IncidentFilter {
+    /// Searched for in acknowledgement notes and tags, ignoring case
+    pub text: String,
+    /// Only incidents with exactly this tag
+    pub tag: Option<String>,
+    pub unacknowledged: bool,
+    /// Only incidents with events on or after this day
+    pub since: Option<NaiveDate>,
+    /// Only incidents with events on or before this day
+    pub until: Option<NaiveDate>,
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl IncidentFilter {
+    /// Whether an incident with these events (oldest first) matches
+    pub fn matches(
+        &self,
+        events: &[DateTime<Local>],
+        acknowledged: Option<&Acknowledgement>,
+    ) -> bool {
+        let (Some(first), Some(last)) = (events.first(), events.last()) else {
+            return false;
+        };
+        if self.unacknowledged && acknowledged.is_some() {
+            return false;
+        }
+        if self.since.is_some_and(|since| last.date_naive() < since)
+            || self.until.is_some_and(|until| first.date_naive() > until)
+        {
+            return false;
+        }
+        if let Some(tag) = &self.tag
+            && !acknowledged.is_some_and(|ack| ack.tags.contains(tag))
+        {
+            return false;
+        }
+        let text = self.text.trim().to_lowercase();
+        text.is_empty()
+            || acknowledged.is_some_and(|ack| {
+                ack.note.to_lowercase().contains(&text)
+                    || ack.tags.iter().any(|tag| tag.contains(&text))
+            })
+    }
+
+    pub fn is_active(&self) -> bool {
+        *self != Self::default()
+    }
+}
+
+/// Page `page` (from 0) of `items`, `per_page` at a time; the last page when
+/// `page` is past the end
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn page<T>(items: &[T], page: usize, per_page: usize) -> &[T] {
+    let pages = items.len().div_ceil(per_page).max(1);
+    let start = page.min(pages - 1) * per_page;
+    &items[start..items.len().min(start + per_page)]
+}
+
+/// Groups debounced motion events into incidents. Pure, so it can be driven
+/// with synthetic timestamps.
+pub struct IncidentTracker {
+    gap: Duration,
+    /// `--re-alert`: follow up on an open incident this often
+    re_alert: Option<Duration>,
+    next_id: u64,
+    current: Option<Incident>,
+}
+
+impl IncidentTracker {
+    pub fn new(gap: Duration) -> Self {
+        Self {
+            gap,
+            re_alert: None,
+            next_id: 1,
+            current: None,
+        }
+    }
+
+    /// Follow up every `interval` while an incident stays open
+    pub fn with_re_alert(mut self, interval: Option<Duration>) -> Self {
+        self.re_alert = interval;
+        self
+    }
+
+    /// Record an event at `at`. A gap of exactly `gap` starts a new incident.
+    /// Returns the closed previous incident, if this event ended one.
+    pub fn record(&mut self, at: Instant) -> (IncidentUpdate, Option<Incident>) {
+        if let Some(ref mut incident) = self.current
+            && at.saturating_duration_since(incident.last_event) < self.gap
+        {
+            incident.event_count += 1;
+            incident.last_event = at;
+            return (IncidentUpdate::Continued(incident.id), None);
+        }
+
+        let closed = self.current.take();
+        let id = self.next_id;
+        self.next_id += 1;
+        self.current = Some(Incident {
+            id,
+            event_count: 1,
+            first_event: at,
+            last_event: at,
+            followups: 0,
+            last_alert: at,
+        });
+        (IncidentUpdate::Started(id), closed)
+    }
+
+    /// After `record`ing an event at `at`: a follow-up when the open
+    /// incident has gone `--re-alert` without an alert. Only events can
+    /// follow up, so a disarmed schedule or the cooldown holds them back.
+    pub fn follow_up(&mut self, at: Instant) -> Option<FollowUp> {
+        let interval = self.re_alert?;
+        let incident = self.current.as_mut()?;
+        if at.saturating_duration_since(incident.last_alert) < interval {
+            return None;
+        }
+        incident.followups += 1;
+        incident.last_alert = at;
+        Some(FollowUp {
+            id: incident.id,
+            number: incident.followups,
+            ongoing: at.saturating_duration_since(incident.first_event),
+        })
+    }
+
+    /// Id of the incident that is still open, if any
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn open_id(&self) -> Option<u64> {
+        self.current.as_ref().map(|incident| incident.id)
+    }
+
+    /// Close the open incident now, e.g. when detection shuts down
+    pub fn close(&mut self) -> Option<Incident> {
+        self.current.take()
+    }
+
+    /// Close the open incident once `now` is a full gap past its last event
+    pub fn poll(&mut self, now: Instant) -> Option<Incident> {
+        match self.current {
+            Some(ref incident)
+                if now.saturating_duration_since(incident.last_event) >= self.gap =>
+            {
+                self.current.take()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Keeps the best frame of the open incident, scored by e.g. total motion
+/// area, so the most telling image can be saved when the incident ends
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct KeyFrame<T> {
+    incident: Option<u64>,
+    best: Option<(f64, T)>,
+}
+
+impl<T> Default for KeyFrame<T> {
+    fn default() -> Self {
+        Self {
+            incident: None,
+            best: None,
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl<T> KeyFrame<T> {
+    /// Offer a frame seen during incident `id`. `frame` is only called (to
+    /// copy the image) when the score beats the best so far.
+    pub fn offer<E>(
+        &mut self,
+        id: u64,
+        score: f64,
+        frame: impl FnOnce() -> Result<T, E>,
+    ) -> Result<(), E> {
+        if self.incident != Some(id) {
+            self.incident = Some(id);
+            self.best = None;
+        }
+        if self.best.as_ref().is_none_or(|(best, _)| score > *best) {
+            self.best = Some((score, frame()?));
+        }
+        Ok(())
+    }
+
+    /// The best frame of incident `id`, once it has ended
+    pub fn take(&mut self, id: u64) -> Option<T> {
+        if self.incident != Some(id) {
+            return None;
+        }
+        self.incident = None;
+        self.best.take().map(|(_, frame)| frame)
+    }
+}
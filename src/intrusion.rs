@@ -0,0 +1,108 @@
+//! Intrusion mode (`--arm`, the GUI's "Arm" button): the next frame is
+//! taken as a picture of the empty scene, and every frame is compared with
+//! it instead of the frames just before. Anything that stays different from
+//! that picture for `--intrusion-secs`, even if it stands still, sets off an
+//! alarm that holds until the detector is disarmed.
+
+// Without the `camera` feature only the GUI and the tests use this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use std::time::{Duration, Instant};
+
+/// How long a difference from the empty scene must last by default
+pub const DEFAULT_SUSTAIN: Duration = Duration::from_secs(3);
+
+/// Where intrusion mode stands, as the GUI shows it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntrusionState {
+    /// Rolling comparison with the previous frames
+    #[default]
+    Disarmed,
+    /// Waiting for the frame to take as the empty scene
+    Arming,
+    Armed,
+    /// Set off, until disarmed
+    Alarm,
+}
+
+impl IntrusionState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            IntrusionState::Disarmed => "Disarmed",
+            IntrusionState::Arming => "Arming...",
+            IntrusionState::Armed => "Armed on empty scene",
+            IntrusionState::Alarm => "INTRUSION",
+        }
+    }
+
+    /// Comparing against a fixed reference rather than the previous frames
+    pub fn is_armed(&self) -> bool {
+        *self != IntrusionState::Disarmed
+    }
+}
+
+/// Turns the per-frame "differs from the empty scene" into an alarm
+#[derive(Clone, Debug)]
+pub struct IntrusionGuard {
+    sustain: Duration,
+    state: IntrusionState,
+    /// Start of the current run of differing frames
+    deviating_since: Option<Instant>,
+}
+
+impl IntrusionGuard {
+    pub fn new(sustain: Duration) -> Self {
+        Self {
+            sustain,
+            state: IntrusionState::Disarmed,
+            deviating_since: None,
+        }
+    }
+
+    pub fn state(&self) -> IntrusionState {
+        self.state
+    }
+
+    /// Wait for the next frame to become the reference
+    pub fn arm(&mut self) {
+        self.state = IntrusionState::Arming;
+        self.deviating_since = None;
+    }
+
+    /// The reference frame was taken
+    pub fn reference_taken(&mut self) {
+        if self.state == IntrusionState::Arming {
+            self.state = IntrusionState::Armed;
+        }
+    }
+
+    /// Back to rolling comparison; also silences an alarm
+    pub fn disarm(&mut self) {
+        self.state = IntrusionState::Disarmed;
+        self.deviating_since = None;
+    }
+
+    /// Whether this frame, which did or didn't differ from the reference,
+    /// sets off the alarm. True once per alarm.
+    pub fn update(&mut self, now: Instant, deviating: bool) -> bool {
+        if self.state != IntrusionState::Armed {
+            return false;
+        }
+        if !deviating {
+            self.deviating_since = None;
+            return false;
+        }
+        let since = *self.deviating_since.get_or_insert(now);
+        if now.duration_since(since) < self.sustain {
+            return false;
+        }
+        self.state = IntrusionState::Alarm;
+        true
+    }
+}
+
+impl Default for IntrusionGuard {
+    fn default() -> Self {
+        Self::new(DEFAULT_SUSTAIN)
+    }
+}
@@ -0,0 +1,115 @@
+//! Session journal for the GUI. It is rewritten every few seconds while the
+//! window is open and removed on a clean exit, so a journal found at startup
+//! means the previous session crashed or was killed.
+
+// Without the `camera` feature only the tests use this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use crate::regions::MinArea;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// What the GUI needs to pick up where a session left off
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// When the session started (RFC 3339)
+    pub started: String,
+    /// Last time the journal was written (RFC 3339), about when it ended
+    pub updated: String,
+    pub events: u64,
+    pub device: u32,
+    pub sensitivity: f64,
+    /// Written as a number of pixels or a string like "0.5%"
+    pub min_area: MinArea,
+    /// Detection was running
+    pub detecting: bool,
+}
+
+impl Session {
+    /// "previous session ended unexpectedly at 14:32 after 312 events"
+    pub fn describe(&self) -> String {
+        let ended = DateTime::parse_from_rfc3339(&self.updated)
+            .map(|time| time.with_timezone(&Local).format("%H:%M").to_string())
+            .unwrap_or_else(|_| self.updated.clone());
+        format!(
+            "Previous session ended unexpectedly at {} after {} events",
+            ended, self.events
+        )
+    }
+}
+
+/// `session.json` in the output directory
+pub fn path(output_dir: &Path) -> PathBuf {
+    output_dir.join("session.json")
+}
+
+pub struct Journal {
+    path: PathBuf,
+    last_saved: Option<Instant>,
+}
+
+impl Journal {
+    const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Take over the journal at `path`, returning the session an earlier run
+    /// left behind
+    pub fn open(path: PathBuf) -> Result<(Self, Option<Session>)> {
+        let previous = match std::fs::read_to_string(&path) {
+            Ok(text) => Some(
+                serde_json::from_str(&text)
+                    .with_context(|| format!("Failed to parse {}", path.display()))?,
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+        let journal = Self {
+            path,
+            last_saved: None,
+        };
+        Ok((journal, previous))
+    }
+
+    /// Write `session` unless the journal was written recently
+    pub fn update(&mut self, session: &Session) -> Result<()> {
+        if self
+            .last_saved
+            .is_some_and(|saved| saved.elapsed() < Self::SAVE_INTERVAL)
+        {
+            return Ok(());
+        }
+        self.write(session)
+    }
+
+    /// Write `session` now and wait for it to reach the disk
+    /// (`--flush-on-event`)
+    pub fn sync(&mut self, session: &Session) -> Result<()> {
+        self.write(session)?;
+        crate::state::sync_path(&self.path)
+            .with_context(|| format!("Failed to sync {}", self.path.display()))
+    }
+
+    fn write(&mut self, session: &Session) -> Result<()> {
+        self.last_saved = Some(Instant::now());
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(session)?;
+        crate::state::write_atomic(&self.path, json.as_bytes())
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+
+    /// Clean exit: nothing to recover next time
+    pub fn close(self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to remove {}", self.path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! JPEG encoding of snapshots: progressive scans and chroma subsampling.
+//! OpenCV ignores parameters it doesn't know, so support is checked by
+//! reading back the header of a test image.
+
+// Without the `camera` feature nothing is encoded
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use crate::config::SnapshotConfig;
+use clap::ValueEnum;
+#[cfg(feature = "camera")]
+use opencv::{
+    core::{self, Mat, Scalar, Vector},
+    imgcodecs,
+};
+use serde::Deserialize;
+
+/// Color resolution kept by `--jpeg-chroma`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ValueEnum)]
+pub enum ChromaSubsampling {
+    /// Full color resolution, for fine colored detail
+    #[value(name = "444")]
+    #[serde(rename = "444")]
+    Chroma444,
+    /// Half the color resolution each way, the usual and smaller choice
+    #[value(name = "420")]
+    #[serde(rename = "420")]
+    Chroma420,
+}
+
+impl ChromaSubsampling {
+    pub fn name(self) -> &'static str {
+        match self {
+            ChromaSubsampling::Chroma444 => "4:4:4",
+            ChromaSubsampling::Chroma420 => "4:2:0",
+        }
+    }
+}
+
+/// What a JPEG's frame header says about how it was encoded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JpegHeader {
+    pub progressive: bool,
+    /// `None` for grayscale or other subsampling
+    pub chroma: Option<ChromaSubsampling>,
+}
+
+/// Read the frame header (SOF segment) of a JPEG file
+pub fn read_header(bytes: &[u8]) -> Option<JpegHeader> {
+    if bytes.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut at = 2;
+    while let (Some(0xFF), Some(&marker)) = (bytes.get(at), bytes.get(at + 1)) {
+        let length = u16::from_be_bytes([*bytes.get(at + 2)?, *bytes.get(at + 3)?]) as usize;
+        // SOF0 to SOF15, except DHT, JPG and DAC which share the range
+        if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            // Precision, height, width, component count, then three bytes
+            // per component: id, sampling factors and quantization table
+            let segment = bytes.get(at + 4..at + 2 + length)?;
+            let sampling = |component: usize| segment.get(7 + component * 3).copied();
+            let chroma = match (segment.get(5), sampling(0), sampling(1), sampling(2)) {
+                (Some(3), Some(0x11), Some(0x11), Some(0x11)) => Some(ChromaSubsampling::Chroma444),
+                (Some(3), Some(0x22), Some(0x11), Some(0x11)) => Some(ChromaSubsampling::Chroma420),
+                _ => None,
+            };
+            return Some(JpegHeader {
+                progressive: matches!(marker, 0xC2 | 0xC6 | 0xCA | 0xCE),
+                chroma,
+            });
+        }
+        at += 2 + length;
+    }
+    None
+}
+
+/// Settings asked for in `config` that the encoder left out of `header`
+pub fn unsupported(config: &SnapshotConfig, header: &JpegHeader) -> Vec<String> {
+    let mut problems = Vec::new();
+    if config.jpeg_progressive && !header.progressive {
+        problems.push("progressive encoding".to_string());
+    }
+    if let Some(chroma) = config
+        .jpeg_chroma
+        .filter(|&chroma| header.chroma != Some(chroma))
+    {
+        problems.push(format!("{} chroma subsampling", chroma.name()));
+    }
+    problems
+}
+
+/// `imwrite`/`imencode` parameters for `config`
+#[cfg(feature = "camera")]
+pub fn params(config: &SnapshotConfig) -> Vector<i32> {
+    // IMWRITE_JPEG_SAMPLING_FACTOR and its values only exist from OpenCV
+    // 4.5.5; spelled out so older builds, such as Raspberry Pi OS
+    // bullseye's 4.5.1, still compile and get the warning below
+    const SAMPLING_FACTOR: i32 = 7;
+    const SAMPLING_444: i32 = 0x111111;
+    const SAMPLING_420: i32 = 0x221111;
+
+    let mut params = Vector::new();
+    if config.jpeg_progressive {
+        params.push(imgcodecs::IMWRITE_JPEG_PROGRESSIVE);
+        params.push(1);
+    }
+    if let Some(chroma) = config.jpeg_chroma {
+        params.push(SAMPLING_FACTOR);
+        params.push(match chroma {
+            ChromaSubsampling::Chroma444 => SAMPLING_444,
+            ChromaSubsampling::Chroma420 => SAMPLING_420,
+        });
+    }
+    params
+}
+
+/// Warn about settings this OpenCV build ignores, found by encoding a
+/// small color image with them
+#[cfg(feature = "camera")]
+pub fn check_support(config: &SnapshotConfig) {
+    if !config.jpeg_progressive && config.jpeg_chroma.is_none() {
+        return;
+    }
+    let encoded = Mat::new_rows_cols_with_default(
+        16,
+        16,
+        core::CV_8UC3,
+        Scalar::new(40.0, 120.0, 200.0, 0.0),
+    )
+    .and_then(|image| {
+        let mut jpeg = Vector::<u8>::new();
+        imgcodecs::imencode(".jpg", &image, &mut jpeg, &params(config))?;
+        Ok(jpeg.to_vec())
+    });
+    let Some(header) = encoded.ok().as_deref().and_then(read_header) else {
+        eprintln!("Warning: couldn't check the JPEG settings with a test image");
+        return;
+    };
+    let problems = unsupported(config, &header);
+    if !problems.is_empty() {
+        eprintln!(
+            "Warning: this OpenCV build ignores {}; snapshots use its defaults",
+            problems.join(" and ")
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! `--log-file`: a copy of everything written to stdout and stderr, rotated
+//! by size. Output is captured at the file descriptors, so messages from
+//! OpenCV and GStreamer end up in the file too.
+
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// When the log file is rotated and how many old ones are kept
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rotation {
+    pub max_bytes: u64,
+    /// `<path>.1` (newest) to `<path>.<keep>`; 0 starts the file over
+    pub keep: usize,
+}
+
+/// "10M", "512K", "1G" or plain bytes
+pub fn parse_size(text: &str) -> Result<u64> {
+    let text = text.trim();
+    let (number, multiplier) = match text.char_indices().last() {
+        Some((index, 'K' | 'k')) => (&text[..index], 1 << 10),
+        Some((index, 'M' | 'm')) => (&text[..index], 1 << 20),
+        Some((index, 'G' | 'g')) => (&text[..index], 1 << 30),
+        _ => (text, 1),
+    };
+    let size = number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .with_context(|| format!("invalid size \"{}\" (use e.g. 10M, 512K)", text))?;
+    if size == 0 {
+        bail!("size must be greater than 0");
+    }
+    Ok(size)
+}
+
+/// `<path>.<index>`, e.g. motion.log.1
+pub fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+/// A log file that moves itself aside once it reaches `max_bytes`
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    /// Append to `path`, counting what an earlier run left in it
+    pub fn open(path: PathBuf, rotation: Rotation) -> Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let file = Self::append(&path)
+            .with_context(|| format!("Failed to open log file {}", path.display()))?;
+        let written = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+        Ok(Self {
+            path,
+            rotation,
+            file,
+            written,
+        })
+    }
+
+    fn append(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// Write one line, rotating first if it would take the file past the
+    /// limit. A line longer than the limit gets a file of its own.
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let length = line.len() as u64 + 1;
+        if self.written > 0 && self.written + length > self.rotation.max_bytes {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.written += length;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.rotation.keep == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for index in (1..self.rotation.keep).rev() {
+                let from = rotated_path(&self.path, index);
+                if from.exists() {
+                    std::fs::rename(&from, rotated_path(&self.path, index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+            self.file = Self::append(&self.path)?;
+        }
+        self.written = 0;
+        Ok(())
+    }
+}
+
+/// Capturing stdout and stderr into the log file until dropped. Dropping it
+/// puts the console back and writes out whatever is still in the pipes.
+pub struct LogFile {
+    #[cfg(unix)]
+    streams: Vec<capture::Stream>,
+}
+
+impl LogFile {
+    /// Send stdout and stderr to `path`, and on to the console unless
+    /// `console` is false
+    #[cfg(unix)]
+    pub fn start(path: PathBuf, rotation: Rotation, console: bool) -> Result<Self> {
+        let file = std::sync::Arc::new(std::sync::Mutex::new(RotatingFile::open(path, rotation)?));
+        let streams = [libc::STDOUT_FILENO, libc::STDERR_FILENO]
+            .into_iter()
+            .map(|fd| capture::Stream::start(fd, file.clone(), console))
+            .collect::<io::Result<_>>()
+            .context("Failed to capture the console output")?;
+        Ok(Self { streams })
+    }
+
+    #[cfg(not(unix))]
+    pub fn start(_path: PathBuf, _rotation: Rotation, _console: bool) -> Result<Self> {
+        bail!("--log-file is only supported on Unix")
+    }
+}
+
+impl Drop for LogFile {
+    fn drop(&mut self) {
+        let _ = io::stdout().flush();
+        #[cfg(unix)]
+        for stream in self.streams.drain(..) {
+            stream.stop();
+        }
+    }
+}
+
+/// "2024-01-01 12:00:00 <line>"
+fn stamp(line: &str) -> String {
+    format!("{} {}", Local::now().format("%Y-%m-%d %H:%M:%S"), line)
+}
+
+#[cfg(unix)]
+mod capture {
+    use super::{stamp, RotatingFile};
+    use std::fs::File;
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+    use std::sync::{Arc, Mutex};
+    use std::thread::JoinHandle;
+
+    /// One of stdout and stderr, redirected into a pipe whose lines a
+    /// thread copies to the log file and the original console
+    pub struct Stream {
+        fd: RawFd,
+        console: OwnedFd,
+        thread: JoinHandle<()>,
+    }
+
+    impl Stream {
+        pub fn start(fd: RawFd, file: Arc<Mutex<RotatingFile>>, echo: bool) -> io::Result<Self> {
+            let console = dup(fd)?;
+            let mut echo = echo
+                .then(|| console.try_clone().map(File::from))
+                .transpose()?;
+            let (reader, writer) = io::pipe()?;
+            redirect(writer.as_raw_fd(), fd)?;
+            // `fd` now holds the only write end
+            drop(writer);
+            let thread = std::thread::Builder::new()
+                .name("log".to_string())
+                .spawn(move || {
+                    let mut reader = BufReader::new(reader);
+                    let mut line = Vec::new();
+                    while matches!(reader.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+                        if let Some(console) = echo.as_mut() {
+                            let _ = console.write_all(&line);
+                        }
+                        let text = String::from_utf8_lossy(&line);
+                        if let Ok(mut file) = file.lock() {
+                            // Nowhere left to report a failed write
+                            let _ = file.write_line(&stamp(text.trim_end_matches(['\r', '\n'])));
+                        }
+                        line.clear();
+                    }
+                })?;
+            Ok(Self {
+                fd,
+                console,
+                thread,
+            })
+        }
+
+        /// Point the descriptor back at the console, which closes the pipe,
+        /// and wait for the thread to copy what was left in it
+        pub fn stop(self) {
+            if redirect(self.console.as_raw_fd(), self.fd).is_ok() {
+                let _ = self.thread.join();
+            }
+        }
+    }
+
+    fn dup(fd: RawFd) -> io::Result<OwnedFd> {
+        // SAFETY: the descriptor lives for the whole process; it is only
+        // borrowed long enough to duplicate it
+        unsafe { std::os::fd::BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()
+    }
+
+    /// Make `target` a copy of `source`
+    fn redirect(source: RawFd, target: RawFd) -> io::Result<()> {
+        // SAFETY: dup2 on two open descriptors
+        if unsafe { libc::dup2(source, target) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
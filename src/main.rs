@@ -1,564 +1,992 @@
+#[cfg(not(any(feature = "camera", feature = "mock-camera")))]
+compile_error!("enable the `camera` feature (default) or `mock-camera`");
+
 #[cfg(test)]
 mod tests;
 
+mod absence;
+mod alerts;
+mod annotate;
+mod audio;
+mod buildinfo;
+mod clock;
+mod config;
+#[cfg(feature = "camera")]
+mod crash;
+mod dbus;
+mod debug_dump;
+mod dedup;
+#[cfg(feature = "camera")]
+mod detector;
+mod dialogs;
+mod error;
+mod events;
+mod gpio;
+mod graph_export;
 mod gui;
+mod hotplug;
+mod humanize;
+mod incident;
+mod intrusion;
+mod journal;
+mod jpeg;
+mod logfile;
+#[cfg(feature = "camera")]
+mod mask;
+mod mask_editor;
+#[cfg(all(feature = "mock-camera", not(feature = "camera")))]
+mod mock_camera;
+mod monitor;
+mod naming;
+mod normalize;
+mod notify;
+mod observe;
+mod overlay;
+mod pacing;
+mod phase;
+mod privacy;
+mod probe;
+mod pyramid;
+mod quality;
+mod reconnect;
+mod recording;
+mod regions;
+mod reposition;
+mod rules;
+mod run;
+mod scale;
+mod schedule;
+mod selftest;
+mod sensitivity;
+mod smoothing;
+mod source;
+mod state;
+mod sun;
+mod switch;
+mod template;
+mod timestamp;
+mod tracker;
+mod tuning;
+mod upload;
+
+#[cfg(all(feature = "mock-camera", not(feature = "camera")))]
+use mock_camera as detector;
 
 use anyhow::Result;
-use chrono::Local;
-use clap::Parser;
-use opencv::{
-    core::{self, Mat, Vector},
-    imgcodecs, imgproc,
-    prelude::*,
-    videoio::{VideoCapture, CAP_ANY, CAP_V4L2},
+use clap::{Parser, Subcommand};
+use detector::{
+    run_bench, run_check, run_cli_mode, run_dry_run, run_gui_mode, run_self_test, MotionDetector,
 };
-use std::thread;
-use std::time::{Duration, Instant};
+use error::DetectorError;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_help = error::EXIT_CODES_HELP)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Camera device index (default: 0)
-    #[arg(short, long, default_value = "0")]
+    #[arg(short, long, default_value = "0", global = true)]
     device: u32,
 
     /// Motion detection sensitivity (0.0-1.0, default: 0.3)
-    #[arg(short, long, default_value = "0.3")]
+    #[arg(short, long, default_value_t = config::DEFAULT_SENSITIVITY, global = true)]
     sensitivity: f64,
 
-    /// Minimum area for motion detection (default: 500)
-    #[arg(short, long, default_value = "500")]
-    min_area: u32,
+    /// Minimum area for motion detection: pixels, or a share of the frame
+    /// area like `0.5%` that means the same at any resolution (default: 500)
+    #[arg(
+        short,
+        long,
+        value_name = "PIXELS|PERCENT",
+        default_value_t = regions::MinArea::default(),
+        global = true
+    )]
+    min_area: regions::MinArea,
 
     /// Enable verbose output
     #[arg(short, long)]
     verbose: bool,
 
+    /// Print the cameras found on devices 0-3 once they have all been
+    /// probed, without holding up detection
+    #[arg(long)]
+    list_cameras: bool,
+
+    /// Also write everything printed to this file, each line with the date
+    /// and time, e.g. for a detector running as a daemon (Unix only)
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Size at which --log-file is rotated to <PATH>.1, e.g. 512K or 10M
+    #[arg(long, default_value = "10M", value_name = "SIZE")]
+    log_max_size: String,
+
+    /// Rotated log files kept, <PATH>.1 being the newest; 0 starts the file
+    /// over instead
+    #[arg(long, default_value = "5", value_name = "N")]
+    log_keep: usize,
+
+    /// Write only to --log-file, not the console
+    #[arg(long, requires = "log_file")]
+    log_only: bool,
+
     /// Enable GUI control panel
     #[arg(short, long)]
     gui: bool,
+
+    /// Also offer the GUI's controls as org.motiondetector.Detector1 on the
+    /// session bus, or the system bus with `--dbus system` (Linux builds
+    /// with the `dbus` feature). Methods: Start, Stop, Snapshot,
+    /// SetSensitivity, GetStatus; signal: MotionDetected
+    ///
+    /// Example: busctl --user call org.motiondetector.Detector1
+    /// /org/motiondetector/Detector1 org.motiondetector.Detector1 GetStatus
+    #[arg(
+        long,
+        value_enum,
+        value_name = "BUS",
+        num_args = 0..=1,
+        default_missing_value = "session",
+        requires = "gui"
+    )]
+    dbus: Option<dbus::Bus>,
+
+    /// Path to a TOML configuration file
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Use the settings of a `[camera.<NAME>]` config section
+    #[arg(long, global = true, value_name = "NAME")]
+    camera: Option<String>,
+
+    /// Directory where snapshots are written (default: pics)
+    #[arg(short, long, default_value = "pics", global = true)]
+    output_dir: PathBuf,
+
+    /// JSON file where event and frame counts are saved, so they add up across restarts
+    #[arg(long)]
+    state_file: Option<PathBuf>,
+
+    /// Wait for each event's files (snapshot, state file, journal, finished
+    /// clips and audio) to reach the disk before going on, for devices that
+    /// may lose power
+    #[arg(long)]
+    flush_on_event: bool,
+
+    /// Stop after this many seconds; exits with code 4 if no motion was seen
+    #[arg(long)]
+    timeout: Option<u64>,
+
+    /// Stop cleanly after this many motion events, once the last one's
+    /// snapshot is saved; 0 is unlimited. With --timeout, whichever comes
+    /// first stops the detector
+    #[arg(long, value_name = "N")]
+    max_events: Option<u64>,
+
+    /// Detect and report events, marked [OBSERVE], without acting on them:
+    /// no snapshots, clips, audio, uploads, relay switching or state file.
+    /// Each kind of skipped effect is logged once. The GUI can go live
+    /// without a restart
+    #[arg(long, conflicts_with_all = ["dry_run", "check"])]
+    observe: bool,
+
+    /// Don't write snapshot images for motion events (events are still reported)
+    #[arg(long)]
+    no_snapshot: bool,
+
+    /// Skip snapshots that look like one saved within --dedup-window: their
+    /// 64-bit perceptual hashes differ in fewer than BITS bits (e.g. 6;
+    /// default: 0, keep all)
+    #[arg(long, value_name = "BITS")]
+    dedup_threshold: Option<u32>,
+
+    /// How long a saved snapshot suppresses look-alikes (default: 10m)
+    #[arg(long, value_name = "DURATION")]
+    dedup_window: Option<String>,
+
+    /// Save snapshots under YYYY/MM/DD/ subdirectories of the output
+    /// directory, by the day they were captured
+    #[arg(long)]
+    date_tree: bool,
+
+    /// Write snapshots as progressive JPEGs, which browsers show in rough
+    /// form before they have fully loaded
+    #[arg(long)]
+    jpeg_progressive: bool,
+
+    /// Chroma subsampling of snapshots: 444 keeps full color detail, 420 is
+    /// smaller (default: OpenCV's, 420)
+    #[arg(long, value_enum, value_name = "SAMPLING")]
+    jpeg_chroma: Option<jpeg::ChromaSubsampling>,
+
+    /// Obscure this area of every snapshot, clip and preview frame, as
+    /// x,y,w,h in snapshot pixels; repeat for more areas. Added to the
+    /// config file's privacy_regions
+    #[arg(long, value_name = "X,Y,W,H")]
+    privacy_region: Vec<privacy::PrivacyRegion>,
+
+    /// How privacy regions are obscured
+    #[arg(long, value_enum)]
+    privacy_style: Option<privacy::PrivacyStyle>,
+
+    /// Also blur faces, or every motion box, in snapshots, clips, uploads
+    /// and the preview; detection still sees the whole frame (default: off)
+    #[arg(long, value_enum, value_name = "WHAT")]
+    privacy_blur: Option<privacy::PrivacyBlur>,
+
+    /// Haar cascade XML for --privacy-blur faces (default: OpenCV's
+    /// haarcascade_frontalface_default.xml from its usual install paths)
+    #[arg(long, value_name = "FILE")]
+    face_cascade: Option<PathBuf>,
+
+    /// Keep an unblurred copy of each event snapshot in DIR, readable only
+    /// by the current user, next to the blurred one in the output directory
+    #[arg(long, value_name = "DIR")]
+    privacy_keep_original: Option<PathBuf>,
+
+    /// Draw the motion onto event snapshots: bounding boxes, the outline of
+    /// the changed pixels, or both (default: snapshots are saved unmarked;
+    /// rect when only --box-color or --box-thickness is given)
+    #[arg(long, value_enum, value_name = "STYLE")]
+    annotation_style: Option<annotate::AnnotationStyle>,
+
+    /// Color of the snapshot annotation, as #rrggbb or red, green, blue,
+    /// yellow, orange, magenta, cyan or white (default: green)
+    #[arg(long, value_name = "COLOR")]
+    box_color: Option<annotate::BoxColor>,
+
+    /// Line width of the snapshot annotation, in snapshot pixels, 1 to 20
+    /// (default: 2)
+    #[arg(long, value_name = "PIXELS")]
+    box_thickness: Option<u32>,
+
+    /// Save microphone audio for each incident as motion_<id>.wav
+    /// (needs the `audio` feature)
+    #[arg(long)]
+    record_audio: bool,
+
+    /// Save a video clip of each incident as motion_<id>.mp4 (or .avi)
+    #[arg(long)]
+    record: bool,
+
+    /// Video codec for --record; hardware encoders are probed at startup and
+    /// MJPEG is the fallback (default: auto)
+    #[arg(long, value_enum)]
+    record_codec: Option<recording::RecordCodec>,
+
+    /// Once an incident's clip closes, cut it down to the motion in it, from
+    /// a little before the first motion frame to a little after the last
+    #[arg(long)]
+    trim_clips: bool,
+
+    /// With --trim-clips, keep the full clip next to the trimmed one
+    #[arg(long)]
+    keep_untrimmed: bool,
+
+    /// How the sensitivity maps to the pixel difference that counts as
+    /// change: "exponential" spreads the useful range over the whole scale,
+    /// "linear" steps evenly in grey levels (default: exponential)
+    #[arg(long, value_enum, value_name = "CURVE")]
+    sensitivity_curve: Option<sensitivity::SensitivityCurve>,
+
+    /// Equalize contrast before differencing, against slow brightness
+    /// changes such as passing clouds (default: off)
+    #[arg(long, value_enum)]
+    normalize: Option<normalize::Normalize>,
+
+    /// Remove isolated noise pixels from the motion mask with a
+    /// morphological opening of this kernel size (odd, e.g. 3) before
+    /// dilating it (default: off)
+    #[arg(long, value_name = "SIZE")]
+    open_kernel: Option<i32>,
+
+    /// Erode/dilate passes of the opening (default: 1)
+    #[arg(long, value_name = "N")]
+    open_iterations: Option<i32>,
+
+    /// Target bitrate for hardware-encoded clips, in kbit/s (default: 4000)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(100..=100_000))]
+    record_bitrate: Option<u32>,
+
+    /// Events closer together than this many seconds share one incident (default: 120)
+    #[arg(long, default_value = "120")]
+    incident_gap: u64,
+
+    /// Intrusion mode: take the first frame as a picture of the empty scene
+    /// and compare every frame with it instead of the frames just before,
+    /// so someone standing still is still noticed. A difference lasting
+    /// --intrusion-secs sets off an alarm that holds until the run ends
+    #[arg(long)]
+    arm: bool,
+
+    /// Seconds a difference from the empty scene must last to set off the
+    /// intrusion alarm (default: 3)
+    #[arg(long, default_value = "3", value_name = "SECS")]
+    intrusion_secs: u64,
+
+    /// Only raise events and snapshots during these hours, e.g.
+    /// "22:00-06:00" (may cross midnight); frames are still processed
+    /// outside them. Overrides `active` in the `[schedule]` config section
+    #[arg(long, value_name = "START-END")]
+    active_hours: Option<String>,
+
+    /// Alert when no motion is seen for this long, e.g. 12h (units: s, m, h, d)
+    #[arg(long)]
+    no_motion_alert: Option<String>,
+
+    /// Repeat the no-motion alert this often until motion resumes (default: 1h)
+    #[arg(long)]
+    no_motion_repeat: Option<String>,
+
+    /// Shortest time between two motion events, e.g. 2s (units: s, m, h, d)
+    #[arg(long, default_value = "2s")]
+    min_cooldown: String,
+
+    /// Longest time between two motion events; the cooldown grows towards it
+    /// while motion is continuous (default: --min-cooldown, a fixed cooldown)
+    #[arg(long)]
+    max_cooldown: Option<String>,
+
+    /// Switch a relay or siren on this Raspberry Pi GPIO pin (BCM numbering)
+    /// while motion lasts (needs the `gpio` feature)
+    #[arg(long)]
+    gpio_pin: Option<u8>,
+
+    /// Drive the GPIO pin high when active instead of low
+    #[arg(long)]
+    gpio_active_high: bool,
+
+    /// Keep the GPIO pin active this long after the last motion, e.g. 5s
+    #[arg(long, default_value = "5s")]
+    gpio_hold: String,
+
+    /// Also upload snapshots and finished clips to an S3-compatible server,
+    /// e.g. https://s3.eu-central-1.amazonaws.com (needs the `s3` feature)
+    #[arg(long, requires = "s3_bucket")]
+    s3_endpoint: Option<String>,
+
+    /// Bucket to upload to; with --no-snapshot, snapshots go only there
+    #[arg(long, requires = "s3_endpoint")]
+    s3_bucket: Option<String>,
+
+    /// Region to sign requests for
+    #[arg(long, default_value = "us-east-1")]
+    s3_region: String,
+
+    /// Prefix for object keys, e.g. porch/
+    #[arg(long, default_value = "")]
+    s3_prefix: String,
+
+    /// Access key ID; without a key pair, uploads are anonymous
+    #[arg(long, env = "AWS_ACCESS_KEY_ID", hide_env_values = true)]
+    s3_access_key: Option<String>,
+
+    /// Secret access key
+    #[arg(long, env = "AWS_SECRET_ACCESS_KEY", hide_env_values = true)]
+    s3_secret_key: Option<String>,
+
+    /// Print a summary when an incident ends
+    #[arg(long)]
+    incident_summary: bool,
+
+    /// While an incident stays open, follow up this often with how long the
+    /// motion has lasted and a fresh snapshot, e.g. 2m (units: s, m, h, d;
+    /// default: off, only the start is announced)
+    #[arg(long, value_name = "DURATION")]
+    re_alert: Option<String>,
+
+    /// Treat motion contours whose bounding boxes are within this gap as one
+    /// object, held against --min-area by their combined area: pixels, or a
+    /// share of the frame width like `2%` (overlapping boxes always merge)
+    #[arg(
+        long,
+        alias = "merge-distance",
+        value_name = "PIXELS|PERCENT",
+        default_value = "0",
+        global = true
+    )]
+    merge_gap: regions::MergeGap,
+
+    /// Compare each frame with the one N captures earlier instead of the
+    /// previous one, so slow motion adds up (default: 1)
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..=300), global = true)]
+    diff_lag: u32,
+
+    /// Check the config, camera, output directory and microphone, then exit
+    /// without detecting (exit code 5 if a check fails)
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Validate the config, read a few camera frames and test the output
+    /// directory, then exit without detecting: 0 when all is well, otherwise
+    /// the failure's own code (3 config, 2 camera, 7 output directory). Meant
+    /// for systemd's ExecStartPre or a deployment script
+    #[arg(long, conflicts_with = "dry_run")]
+    check: bool,
+
+    /// Print the version, git commit, build date, OpenCV version, features
+    /// and capture backends, for bug reports
+    #[arg(long)]
+    version_full: bool,
+
+    /// Every this many minutes, save the frame motion is measured against
+    /// to <output-dir>/background/
+    #[arg(long, value_name = "MINUTES", value_parser = clap::value_parser!(u64).range(1..))]
+    background_snapshot_interval: Option<u64>,
+
+    /// Write the diff, threshold and dilated masks of every Nth frame to this
+    /// directory as PNGs, with a JSON record of each in dump.jsonl
+    #[arg(long, value_name = "DIR")]
+    debug_dump: Option<PathBuf>,
+
+    /// Dump every Nth frame (default: 30)
+    #[arg(long, value_name = "N", default_value = "30", value_parser = clap::value_parser!(u32).range(1..))]
+    debug_dump_every: u32,
+
+    /// Stop dumping once the dump directory holds this many megabytes (default: 500)
+    #[arg(long, value_name = "MB", default_value = "500", value_parser = clap::value_parser!(u64).range(1..))]
+    debug_dump_max_mb: u64,
+
+    /// Track moving objects across frames and emit one event per new object
+    #[arg(long)]
+    track: bool,
+
+    /// Drain frames queued by the camera and process only the newest one
+    #[arg(long)]
+    grab_latest: bool,
+
+    /// Read the camera's first frame up to this many more times, 200 ms
+    /// apart, before giving up, for cameras that deliver empty frames while
+    /// they warm up
+    #[arg(long, value_name = "N", default_value_t = probe::INITIAL_FRAME_RETRIES)]
+    initial_frame_retries: u32,
+
+    /// Give up with exit code 6 after this many attempts in a row to reopen
+    /// a camera that stopped delivering frames (default: keep trying)
+    #[arg(long, value_name = "N")]
+    reconnect_attempts: Option<u32>,
+
+    /// Stream to run motion detection on, for multi-stream (depth/IR) cameras
+    #[arg(long, default_value = "0")]
+    stream_index: i32,
+
+    /// Stream to take snapshots from (default: the detection stream)
+    #[arg(long)]
+    snapshot_stream: Option<i32>,
+
+    /// In the GUI, switch to a camera whose name contains this text (or whose
+    /// device path is this) as soon as it is plugged in, e.g. "C920"
+    #[arg(long, value_name = "NAME")]
+    prefer_device: Option<String>,
+
+    /// Separate capture device to run detection on (e.g. an IR endpoint);
+    /// --device is then only used for snapshots
+    #[arg(long)]
+    detect_device: Option<u32>,
+
+    /// Camera mode to request: "native" for the largest the camera supports,
+    /// or WIDTHxHEIGHT (default: probe the usual C920 modes)
+    #[arg(long, value_name = "MODE")]
+    capture_resolution: Option<scale::CaptureResolution>,
+
+    /// Run detection on a copy shrunk by this factor, e.g. 0.33; snapshots
+    /// and clips keep the full resolution (default: 1.0)
+    #[arg(long, value_name = "FACTOR")]
+    process_scale: Option<f64>,
+
+    /// Also look for motion on the mask halved once (2) or twice (3), so
+    /// large slow objects join up as well as small ones being found
+    /// (default: 1, full size only)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=pyramid::MAX_LEVELS as i64))]
+    pyramid_levels: Option<u32>,
+
+    /// When frames per second stay below this for a few seconds, run
+    /// detection on a smaller copy of the frame, and go back up once the
+    /// rate recovers (default: off)
+    #[arg(long, value_name = "FPS")]
+    min_fps: Option<f32>,
+
+    /// Ignore contours whose bounding box is narrower than this, as width
+    /// over height, e.g. 0.2 against tall thin streaks (default: off)
+    #[arg(long, value_name = "RATIO")]
+    min_aspect: Option<f64>,
+
+    /// Ignore contours whose bounding box is wider than this, as width over
+    /// height, e.g. 5 against flat streaks from cable shadows (default: off)
+    #[arg(long, value_name = "RATIO")]
+    max_aspect: Option<f64>,
+
+    /// Average the motion score over about this many seconds of real time
+    /// before comparing it with the threshold, e.g. 0.5, so brief flickers
+    /// count the same at 10 and 60 FPS (default: 0, off)
+    #[arg(long, value_name = "SECS")]
+    smoothing: Option<f64>,
+
+    /// Process at most this many frames per second, sleeping only what is
+    /// left of each frame's interval (default: as fast as the camera
+    /// delivers). The GUI can change it live
+    #[arg(long, value_name = "FPS")]
+    target_fps: Option<f64>,
+
+    /// Image whose black pixels are never counted as motion, e.g. a PNG
+    /// painted black over a swaying tree; resized to the frame if needed
+    #[arg(long, value_name = "PATH")]
+    mask_file: Option<PathBuf>,
 }
 
-struct MotionDetector {
-    camera: VideoCapture,
-    #[allow(dead_code)]
-    sensitivity: f64,
-    min_area: u32,
-    previous_frame: Mat,
-    frame_count: u32,
-    motion_count: u32,
-    last_motion_time: Option<Instant>,
-    last_fps_update: Instant,
-    fps_frames: u32,
-    current_fps: f32,
+#[derive(Subcommand)]
+enum Command {
+    /// Check the camera, output directory, disk space and clock, then exit
+    SelfTest,
+    /// Measure detection throughput on a video file or synthetic frames,
+    /// without a camera
+    Bench(BenchArgs),
+    /// Summarize a --debug-dump directory: frames, decisions and timing percentiles
+    DebugReport {
+        /// The directory passed to --debug-dump
+        dir: PathBuf,
+    },
 }
 
-impl MotionDetector {
-    fn new(device: u32, sensitivity: f64, min_area: u32) -> Result<Self> {
-        // Try V4L2 first (better for Logitech on Linux)
-        let mut camera = match VideoCapture::new(device as i32, CAP_V4L2) {
-            Ok(cam) => cam,
-            Err(_) => {
-                println!("V4L2 failed, falling back to default backend");
-                VideoCapture::new(device as i32, CAP_ANY)?
-            }
-        };
+#[derive(clap::Args, Clone, Debug)]
+struct BenchArgs {
+    /// Video file to run on, looped if it is shorter than --frames
+    /// (default: synthetic frames)
+    #[arg(long)]
+    input: Option<PathBuf>,
 
-        if !camera.is_opened()? {
-            return Err(anyhow::anyhow!(
-                "Failed to open camera device {} - check if device exists and user has permissions",
-                device
-            ));
-        }
+    /// Frames to process (default: 500)
+    #[arg(long, default_value = "500", value_parser = clap::value_parser!(u32).range(1..))]
+    frames: u32,
 
-        // Enhanced Logitech C920 initialization
-        println!("Initializing Logitech C920 camera...");
-
-        // First, set the backend and basic properties
-        camera.set(opencv::videoio::CAP_PROP_FOURCC, 1196444237.0)?; // MJPG
-        camera.set(opencv::videoio::CAP_PROP_FPS, 30.0)?;
-
-        // Try multiple resolution settings optimized for Logitech C920
-        let resolutions = vec![
-            (1920, 1080, "1080p HD"),
-            (1280, 720, "720p HD"),
-            (960, 540, "540p"),
-            (640, 480, "480p SD"),
-        ];
-
-        let mut actual_resolution = (640, 480);
-        let mut camera_working = false;
-
-        for (width, height, desc) in resolutions {
-            println!("Trying Logitech C920 at {}x{} ({})...", width, height, desc);
-
-            // Set resolution
-            camera.set(opencv::videoio::CAP_PROP_FRAME_WIDTH, width as f64)?;
-            camera.set(opencv::videoio::CAP_PROP_FRAME_HEIGHT, height as f64)?;
-
-            // Reset format after resolution change
-            camera.set(opencv::videoio::CAP_PROP_FOURCC, 1196444237.0)?; // MJPG
-
-            // Give camera time to adjust
-            std::thread::sleep(Duration::from_millis(1000));
-
-            // Test frame capture multiple times
-            let mut success_count = 0;
-            for _ in 0..5 {
-                let mut test_frame = Mat::default();
-                if camera.read(&mut test_frame)? && !test_frame.empty() {
-                    success_count += 1;
-                }
-                std::thread::sleep(Duration::from_millis(100));
-            }
+    /// Width of the synthetic frames (default: 640)
+    #[arg(long, default_value = "640", value_parser = clap::value_parser!(u32).range(16..))]
+    width: u32,
 
-            if success_count >= 3 {
-                let actual_width = camera.get(opencv::videoio::CAP_PROP_FRAME_WIDTH)? as i32;
-                let actual_height = camera.get(opencv::videoio::CAP_PROP_FRAME_HEIGHT)? as i32;
-
-                println!(
-                    "✓ Logitech C920 working at {}x{} (success rate: {}/5)",
-                    actual_width, actual_height, success_count
-                );
-
-                // Update actual resolution based on what the camera reports
-                actual_resolution = (actual_width as u32, actual_height as u32);
-                camera_working = true;
-                break;
-            } else {
-                println!(
-                    "✗ {}x{} failed (success rate: {}/5)",
-                    width, height, success_count
-                );
-            }
-        }
+    /// Height of the synthetic frames (default: 480)
+    #[arg(long, default_value = "480", value_parser = clap::value_parser!(u32).range(16..))]
+    height: u32,
+}
 
-        if !camera_working {
-            return Err(anyhow::anyhow!(
-                "Failed to get stable frame capture from Logitech C920"
+impl Args {
+    /// Reject values clap accepts syntactically but the detector can't use
+    fn validate(&self) -> std::result::Result<(), DetectorError> {
+        if !(0.0..=1.0).contains(&self.sensitivity) {
+            return Err(DetectorError::InvalidArguments(format!(
+                "sensitivity must be between 0.0 and 1.0, got {}",
+                self.sensitivity
+            )));
+        }
+        if self.min_area.is_zero() {
+            return Err(DetectorError::InvalidArguments(
+                "min-area must be greater than 0".to_string(),
             ));
         }
-
-        // Final camera parameters
-        let final_width = camera.get(opencv::videoio::CAP_PROP_FRAME_WIDTH)? as i32;
-        let final_height = camera.get(opencv::videoio::CAP_PROP_FRAME_HEIGHT)? as i32;
-        let final_fps = camera.get(opencv::videoio::CAP_PROP_FPS)?;
-
-        println!("Logitech C920 initialized successfully:");
-        println!("  Resolution: {}x{}", final_width, final_height);
-        println!("  Target FPS: {}", final_fps);
-        println!("  Format: MJPG");
-
-        // Wait for camera to stabilize completely
-        std::thread::sleep(Duration::from_millis(2000));
-
-        // Capture and validate initial frame
-        let mut frame = Mat::default();
-        let mut attempts = 0;
-        loop {
-            if camera.read(&mut frame)? && !frame.empty() {
-                // Validate frame properties
-                if frame.cols() == final_width && frame.rows() == final_height {
-                    break;
-                }
-            }
-
-            attempts += 1;
-            if attempts >= 10 {
-                return Err(anyhow::anyhow!(
-                    "Failed to capture valid initial frame after 10 attempts"
-                ));
-            }
-            std::thread::sleep(Duration::from_millis(200));
+        if self.timeout == Some(0) {
+            return Err(DetectorError::InvalidArguments(
+                "timeout must be greater than 0".to_string(),
+            ));
         }
-
-        // Convert to grayscale and blur for initial frame to match detection format
-        let mut gray = Mat::default();
-        imgproc::cvt_color(&frame, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?;
-        let mut blurred = Mat::default();
-        imgproc::gaussian_blur(
-            &gray,
-            &mut blurred,
-            opencv::core::Size::new(21, 21),
-            0.0,
-            0.0,
-            opencv::core::BORDER_DEFAULT,
-        )?;
-
-        Ok(Self {
-            camera,
-            sensitivity,
-            min_area,
-            previous_frame: blurred,
-            frame_count: 0,
-            motion_count: 0,
-            last_motion_time: None,
-            last_fps_update: Instant::now(),
-            fps_frames: 0,
-            current_fps: 0.0,
-        })
-    }
-
-    fn detect_motion(&mut self) -> Result<(bool, Mat)> {
-        let mut current_frame = Mat::default();
-
-        if !self.camera.read(&mut current_frame)? {
-            return Err(anyhow::anyhow!("Failed to capture frame"));
+        for duration in [&self.no_motion_alert, &self.no_motion_repeat]
+            .into_iter()
+            .flatten()
+        {
+            absence::parse_duration(duration)
+                .map_err(|e| DetectorError::InvalidArguments(format!("{:#}", e)))?;
         }
-
-        if current_frame.empty() {
-            return Ok((false, Mat::default()));
+        let (min, max) = self.cooldowns()?;
+        if min > max {
+            return Err(DetectorError::InvalidArguments(format!(
+                "max-cooldown ({}) must not be shorter than min-cooldown ({})",
+                self.max_cooldown.as_deref().unwrap_or_default(),
+                self.min_cooldown
+            )));
         }
-
-        // Convert to grayscale
-        let mut gray = Mat::default();
-        imgproc::cvt_color(&current_frame, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?;
-
-        // Apply Gaussian blur to reduce noise
-        let mut blurred = Mat::default();
-        imgproc::gaussian_blur(
-            &gray,
-            &mut blurred,
-            opencv::core::Size::new(21, 21),
-            0.0,
-            0.0,
-            opencv::core::BORDER_DEFAULT,
-        )?;
-
-        // Compute difference between current frame and previous frame
-        let mut diff = Mat::default();
-        core::absdiff(&blurred, &self.previous_frame, &mut diff)?;
-
-        // Apply threshold to get binary image
-        let mut thresh = Mat::default();
-        imgproc::threshold(&diff, &mut thresh, 25.0, 255.0, imgproc::THRESH_BINARY)?;
-
-        // Dilate to fill in holes
-        let mut dilated = Mat::default();
-        let kernel = Mat::ones(3, 3, opencv::core::CV_8UC1)?;
-        imgproc::dilate(
-            &thresh,
-            &mut dilated,
-            &kernel,
-            opencv::core::Point::new(-1, -1),
-            2,
-            opencv::core::BORDER_DEFAULT,
-            opencv::core::Scalar::all(0.0),
-        )?;
-
-        // Find contours
-        let mut contours = Vector::<Vector<opencv::core::Point>>::new();
-        imgproc::find_contours(
-            &dilated,
-            &mut contours,
-            imgproc::RETR_EXTERNAL,
-            imgproc::CHAIN_APPROX_SIMPLE,
-            opencv::core::Point::new(-1, -1),
-        )?;
-
-        // Check if any contour meets the minimum area requirement
-        let mut motion_detected = false;
-        for contour in &contours {
-            let area = imgproc::contour_area(&contour, false)?;
-            if area > self.min_area as f64 {
-                motion_detected = true;
-                break;
-            }
+        if self.dedup_threshold.is_some_and(|bits| bits > 64) {
+            return Err(DetectorError::InvalidArguments(format!(
+                "dedup-threshold must be at most 64 bits, got {}",
+                self.dedup_threshold.unwrap_or_default()
+            )));
         }
-
-        // Update previous frame
-        self.previous_frame = blurred;
-        self.frame_count += 1;
-
-        // Update FPS calculation
-        self.fps_frames += 1;
-        let now = Instant::now();
-        if now.duration_since(self.last_fps_update) >= Duration::from_secs(1) {
-            self.current_fps = self.fps_frames as f32;
-            self.fps_frames = 0;
-            self.last_fps_update = now;
+        self.re_alert()?;
+        if let Some(window) = &self.dedup_window {
+            absence::parse_duration(window)
+                .map_err(|e| DetectorError::InvalidArguments(format!("{:#}", e)))?;
         }
-
-        // Update motion count and time
-        if motion_detected {
-            self.motion_count += 1;
-            self.last_motion_time = Some(now);
+        self.gpio()?;
+        self.s3()?;
+        self.log_rotation()?;
+        if self.debug_dump.as_ref() == Some(&self.output_dir) {
+            return Err(DetectorError::InvalidArguments(
+                "debug-dump must be a different directory from output-dir".to_string(),
+            ));
         }
-
-        Ok((motion_detected, current_frame))
+        if self.detect_device == Some(self.device) {
+            return Err(DetectorError::InvalidArguments(
+                "detect-device must differ from device".to_string(),
+            ));
+        }
+        if self.open_kernel.is_some() || self.open_iterations.is_some() {
+            config::opening_problem(
+                self.open_kernel.unwrap_or(3),
+                self.open_iterations.unwrap_or(1),
+            )
+            .map_err(|message| DetectorError::InvalidArguments(message.replace('_', "-")))?;
+        }
+        if let Some(factor) = self.process_scale
+            && !(factor > 0.0 && factor <= 1.0)
+        {
+            return Err(DetectorError::InvalidArguments(format!(
+                "process-scale must be above 0 and at most 1, got {}",
+                factor
+            )));
+        }
+        if let Some(message) = self.target_fps.and_then(pacing::target_fps_problem) {
+            return Err(DetectorError::InvalidArguments(message));
+        }
+        Ok(())
     }
 
-    fn save_snapshot(&self, frame: &Mat) -> Result<String> {
-        // Create pics directory if it doesn't exist
-        std::fs::create_dir_all("pics")?;
-
-        let timestamp = Local::now().format("%Y%m%d_%H%M%S");
-        let filename = format!("pics/motion_{}.jpg", timestamp);
-        imgcodecs::imwrite(&filename, frame, &Vector::new())?;
-        Ok(filename)
+    /// `--log-max-size` and `--log-keep`
+    fn log_rotation(&self) -> std::result::Result<logfile::Rotation, DetectorError> {
+        let max_bytes = logfile::parse_size(&self.log_max_size)
+            .map_err(|e| DetectorError::InvalidArguments(format!("log-max-size: {:#}", e)))?;
+        Ok(logfile::Rotation {
+            max_bytes,
+            keep: self.log_keep,
+        })
     }
 
-    #[allow(dead_code)]
-    #[allow(dead_code)]
-    fn release(&mut self) {
-        let _ = self.camera.release();
+    /// Start copying the console to `--log-file`, if given
+    fn log_file(&self) -> Result<Option<logfile::LogFile>> {
+        let Some(path) = &self.log_file else {
+            return Ok(None);
+        };
+        let log = logfile::LogFile::start(path.clone(), self.log_rotation()?, !self.log_only)?;
+        Ok(Some(log))
     }
 
-    fn get_resolution(&self) -> (i32, i32) {
-        (
-            self.previous_frame.cols() as i32,
-            self.previous_frame.rows() as i32,
-        )
+    /// `--min-cooldown` and `--max-cooldown`
+    fn cooldowns(&self) -> std::result::Result<(Duration, Duration), DetectorError> {
+        let parse = |text: &str| {
+            absence::parse_duration(text)
+                .map_err(|e| DetectorError::InvalidArguments(format!("{:#}", e)))
+        };
+        let min = parse(&self.min_cooldown)?;
+        let max = match &self.max_cooldown {
+            Some(max) => parse(max)?,
+            None => min,
+        };
+        Ok((min, max))
     }
 
-    fn list_cameras() -> Result<Vec<String>> {
-        let mut cameras = Vec::new();
-
-        // Try to detect available cameras (typically 0-3)
-        for i in 0..4 {
-            let mut cam = match VideoCapture::new(i, CAP_V4L2) {
-                Ok(cam) => cam,
-                Err(_) => match VideoCapture::new(i, CAP_ANY) {
-                    Ok(cam) => cam,
-                    Err(_) => continue,
-                },
-            };
-
-            if cam.is_opened()? {
-                let mut frame = Mat::default();
-                if cam.read(&mut frame)? && !frame.empty() {
-                    cameras.push(format!("Camera {} - {}x{}", i, frame.cols(), frame.rows()));
-                }
-                cam.release()?;
-            }
-        }
-
-        Ok(cameras)
+    /// `--re-alert`
+    fn re_alert(&self) -> std::result::Result<Option<Duration>, DetectorError> {
+        self.re_alert
+            .as_deref()
+            .map(absence::parse_duration)
+            .transpose()
+            .map_err(|e| DetectorError::InvalidArguments(format!("{:#}", e)))
     }
-}
-
-fn run_cli_mode(args: Args) -> Result<()> {
-    let mut detector = MotionDetector::new(args.device, args.sensitivity, args.min_area)?;
 
-    if args.verbose {
-        println!("Motion detector active. Press Ctrl+C to stop.");
+    /// `--max-events`, `None` when unlimited
+    fn max_events(&self) -> Option<u64> {
+        self.max_events.filter(|&max| max > 0)
     }
 
-    let mut motion_count = 0;
-    let mut last_motion_time = std::time::Instant::now();
-
-    loop {
-        match detector.detect_motion() {
-            Ok((true, color_frame)) => {
-                let now = std::time::Instant::now();
-                if now.duration_since(last_motion_time) > Duration::from_secs(2) {
-                    motion_count += 1;
-                    last_motion_time = now;
-
-                    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
-                    println!("[{}] MOTION DETECTED! (#{})", timestamp, motion_count);
+    /// `--gpio-pin`, `--gpio-active-high` and `--gpio-hold`
+    fn gpio(&self) -> std::result::Result<Option<gpio::GpioOptions>, DetectorError> {
+        let Some(pin) = self.gpio_pin else {
+            return Ok(None);
+        };
+        let hold = absence::parse_duration(&self.gpio_hold)
+            .map_err(|e| DetectorError::InvalidArguments(format!("{:#}", e)))?;
+        Ok(Some(gpio::GpioOptions {
+            pin,
+            active_high: self.gpio_active_high,
+            hold,
+        }))
+    }
 
-                    // Save color snapshot when motion is detected
-                    if let Ok(filename) = detector.save_snapshot(&color_frame) {
-                        println!("  Color snapshot saved: {}", filename);
-                    }
-                }
-            }
-            Ok((false, _)) => {
-                // No motion detected, continue
-            }
-            Err(e) => {
-                eprintln!("Error detecting motion: {}", e);
-                std::thread::sleep(Duration::from_secs(1));
-            }
+    /// `--s3-endpoint`, `--s3-bucket` and the rest of the `--s3-*` flags
+    fn s3(&self) -> std::result::Result<Option<upload::S3Options>, DetectorError> {
+        let (Some(endpoint), Some(bucket)) = (&self.s3_endpoint, &self.s3_bucket) else {
+            return Ok(None);
+        };
+        if !(endpoint.starts_with("http://") || endpoint.starts_with("https://")) {
+            return Err(DetectorError::InvalidArguments(format!(
+                "s3-endpoint must start with http:// or https://, got \"{}\"",
+                endpoint
+            )));
         }
-
-        // Small delay to prevent excessive CPU usage
-        std::thread::sleep(Duration::from_millis(33)); // ~30 FPS
+        let credentials = match (&self.s3_access_key, &self.s3_secret_key) {
+            (Some(access_key), Some(secret_key)) => Some((access_key.clone(), secret_key.clone())),
+            (None, None) => None,
+            _ => {
+                return Err(DetectorError::InvalidArguments(
+                    "s3-access-key and s3-secret-key must be given together".to_string(),
+                ));
+            }
+        };
+        Ok(Some(upload::S3Options {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            bucket: bucket.clone(),
+            region: self.s3_region.clone(),
+            prefix: self.s3_prefix.clone(),
+            credentials,
+        }))
     }
 }
 
-fn run_gui_mode() -> Result<()> {
-    use crossbeam_channel::bounded;
-    use gui::{GuiMessage, MotionDetectorGui, MotionState};
-
-    let (gui_sender, detector_receiver) = bounded::<GuiMessage>(100);
-    let (detector_sender, gui_state_receiver) = bounded::<MotionState>(100);
-
-    // Start detector thread
-    let detector_handle =
-        thread::spawn(move || run_detector_thread(detector_receiver, detector_sender));
+fn run(mut args: Args) -> Result<()> {
+    if args.version_full {
+        print!("{}", buildinfo::BuildInfo::collect());
+        return Ok(());
+    }
+    args.validate()?;
 
-    // Start the GUI in the main thread
-    let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([800.0, 600.0])
-            .with_title("Motion Detector"),
-        ..Default::default()
+    let mut config = match &args.config {
+        Some(path) => config::Config::load(path)
+            .map_err(|e| DetectorError::InvalidArguments(format!("{:#}", e)))?,
+        None => config::Config::default(),
     };
-
-    eframe::run_native(
-        "Motion Detector",
-        options,
-        Box::new(move |cc| {
-            let mut gui = MotionDetectorGui::new_with_sender(cc, gui_sender.clone());
-            gui.state_receiver = Some(gui_state_receiver.clone());
-            Box::new(gui)
-        }),
-    )
-    .map_err(|e| anyhow::anyhow!("GUI error: {}", e))?;
-
-    // Wait for detector thread to finish
-    let _ = detector_handle.join();
-
-    Ok(())
-}
-
-fn run_detector_thread(
-    receiver: crossbeam_channel::Receiver<gui::GuiMessage>,
-    sender: crossbeam_channel::Sender<gui::MotionState>,
-) -> Result<()> {
-    use gui::{GuiMessage, MotionState};
-
-    let mut detector = match MotionDetector::new(0, 0.3, 500) {
-        Ok(det) => det,
-        Err(e) => {
-            eprintln!("ERROR: Failed to initialize detector: {}", e);
-            return Err(e);
+    if let Some(name) = &args.camera {
+        let camera = config
+            .named_camera(name)
+            .map_err(|e| DetectorError::InvalidArguments(format!("{:#}", e)))?;
+        args.device = camera.device;
+        if let Some(sensitivity) = camera.sensitivity {
+            args.sensitivity = sensitivity;
         }
-    };
-    let mut is_running = false;
-    let mut last_snapshot_time = std::time::Instant::now();
-
-    loop {
-        // Process GUI messages
-        while let Ok(msg) = receiver.try_recv() {
-            match msg {
-                GuiMessage::StartDetection => {
-                    println!("DEBUG: Received StartDetection message");
-                    is_running = true;
-                }
-                GuiMessage::StopDetection => {
-                    println!("DEBUG: Received StopDetection message");
-                    is_running = false;
-                }
-                GuiMessage::UpdateSensitivity(s) => {
-                    detector.sensitivity = s;
-                }
-                GuiMessage::UpdateMinArea(area) => {
-                    detector.min_area = area;
-                }
-                GuiMessage::UpdateDevice(device) => {
-                    // Stop detection first
-                    is_running = false;
-
-                    // Release current camera
-                    let _ = detector.camera.release();
-
-                    // Small delay to ensure camera is fully released
-                    std::thread::sleep(Duration::from_millis(500));
-
-                    // Try to create new detector with new device
-                    match MotionDetector::new(device, detector.sensitivity, detector.min_area) {
-                        Ok(new_detector) => {
-                            detector = new_detector;
-                            println!("Successfully switched to device {}", device);
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to switch to device {}: {}", device, e);
-                            // Try to recreate with original device (0) as fallback
-                            match MotionDetector::new(0, detector.sensitivity, detector.min_area) {
-                                Ok(fallback_detector) => {
-                                    detector = fallback_detector;
-                                    println!("Fallback to device 0 successful");
-                                }
-                                Err(fallback_err) => {
-                                    eprintln!("Fallback to device 0 also failed: {}", fallback_err);
-                                }
-                            }
-                        }
-                    }
-                }
-                GuiMessage::SaveSnapshot => {
-                    // Capture and save a fresh color frame as snapshot
-                    let mut fresh_frame = Mat::default();
-                    if detector.camera.read(&mut fresh_frame).is_ok() && !fresh_frame.empty() {
-                        if let Err(e) = detector.save_snapshot(&fresh_frame) {
-                            eprintln!("Failed to save color snapshot: {}", e);
-                        } else {
-                            println!("  Manual color snapshot saved");
-                        }
-                    } else {
-                        eprintln!("Failed to capture frame for manual snapshot");
-                    }
-                }
-            }
+        if let Some(min_area) = camera.min_area {
+            args.min_area = min_area;
         }
-
-        // Run detection if active
-        if is_running {
-            match detector.detect_motion() {
-                Ok((motion_detected, color_frame)) => {
-                    let motion_state = MotionState {
-                        motion_detected,
-                        motion_count: detector.motion_count,
-                        last_motion_time: detector.last_motion_time.map(|_| Local::now()),
-                        fps: detector.current_fps,
-                        resolution: (
-                            detector.previous_frame.cols() as i32,
-                            detector.previous_frame.rows() as i32,
-                        ),
-                    };
-
-                    // Send state to GUI (non-blocking)
-                    let _ = sender.try_send(motion_state.clone());
-
-                    // Save color snapshot when motion is detected (same logic as CLI mode)
-                    if motion_detected {
-                        let now = std::time::Instant::now();
-                        if now.duration_since(last_snapshot_time) > Duration::from_secs(2) {
-                            if let Ok(filename) = detector.save_snapshot(&color_frame) {
-                                println!("  Color motion snapshot saved: {}", filename);
-                                last_snapshot_time = now;
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Detection error: {}", e);
-                    thread::sleep(Duration::from_secs(1));
-                }
-            }
-        } else {
-            thread::sleep(Duration::from_millis(100));
+        args.output_dir = camera
+            .output_dir
+            .unwrap_or_else(|| args.output_dir.join(name));
+        config = camera.config;
+        args.validate()?;
+    }
+    if let Some(problem) = config::frame_area_problem(args.min_area, &config.detector) {
+        return Err(DetectorError::InvalidArguments(problem).into());
+    }
+    // Command-line flags override the camera's section
+    if args.track {
+        config.tracking.enabled = true;
+    }
+    if args.no_snapshot {
+        config.snapshots.enabled = false;
+    }
+    if let Some(bits) = args.dedup_threshold {
+        config.snapshots.dedup_threshold = bits;
+    }
+    if let Some(window) = &args.dedup_window {
+        config.snapshots.dedup_window = window.clone();
+    }
+    if args.date_tree {
+        config.snapshots.date_tree = true;
+    }
+    if args.jpeg_progressive {
+        config.snapshots.jpeg_progressive = true;
+    }
+    if let Some(chroma) = args.jpeg_chroma {
+        config.snapshots.jpeg_chroma = Some(chroma);
+    }
+    // Added rather than replaced, so a flag can't unmask a configured area
+    config
+        .snapshots
+        .privacy_regions
+        .extend(args.privacy_region.iter().copied());
+    if let Some(style) = args.privacy_style {
+        config.snapshots.privacy_style = style;
+    }
+    if let Some(blur) = args.privacy_blur {
+        config.snapshots.privacy_blur = blur;
+    }
+    if args.face_cascade.is_some() {
+        config.snapshots.face_cascade = args.face_cascade.clone();
+    }
+    if args.privacy_keep_original.is_some() {
+        config.snapshots.privacy_keep_original = args.privacy_keep_original.clone();
+    }
+    if let Some(problem) = privacy::blur_problem(&config.snapshots, &args.output_dir) {
+        return Err(DetectorError::InvalidArguments(problem).into());
+    }
+    if let Some(color) = args.box_color {
+        config.snapshots.box_color = color;
+    }
+    if let Some(thickness) = args.box_thickness {
+        if let Some(problem) = annotate::thickness_problem(thickness) {
+            return Err(DetectorError::InvalidArguments(problem.replace('_', "-")).into());
         }
+        config.snapshots.box_thickness = thickness;
+    }
+    // Asking for a look implies wanting the boxes
+    if args.annotation_style.is_some() || args.box_color.is_some() || args.box_thickness.is_some() {
+        config.snapshots.annotation_style = args
+            .annotation_style
+            .or(config.snapshots.annotation_style)
+            .or(Some(annotate::AnnotationStyle::Rect));
+    }
+    if args.record_audio {
+        config.audio.enabled = true;
+    }
+    if args.record {
+        config.recording.enabled = true;
+    }
+    if let Some(codec) = args.record_codec {
+        config.recording.codec = codec;
+    }
+    if args.trim_clips {
+        config.recording.trim = true;
+    }
+    if args.keep_untrimmed {
+        config.recording.keep_untrimmed = true;
+    }
+    if let Some(curve) = args.sensitivity_curve {
+        config.detector.sensitivity_curve = curve;
+    }
+    if let Some(normalize) = args.normalize {
+        config.detector.normalize = normalize;
+    }
+    if let Some(kernel) = args.open_kernel {
+        config.detector.open_kernel = kernel;
+    }
+    if let Some(iterations) = args.open_iterations {
+        config.detector.open_iterations = iterations;
+    }
+    if let Some(factor) = args.process_scale {
+        config.detector.process_scale = factor;
+    }
+    if let Some(levels) = args.pyramid_levels {
+        config.detector.pyramid_levels = levels;
+    }
+    if args.min_fps.is_some() {
+        config.detector.min_fps = args.min_fps;
+    }
+    if args.min_aspect.is_some() {
+        config.detector.min_aspect = args.min_aspect;
+    }
+    if args.max_aspect.is_some() {
+        config.detector.max_aspect = args.max_aspect;
+    }
+    if let Some(secs) = args.smoothing {
+        if let Some(problem) = smoothing::smoothing_problem(secs) {
+            return Err(DetectorError::InvalidArguments(
+                problem.replace("smoothing_secs", "smoothing"),
+            )
+            .into());
+        }
+        config.detector.smoothing_secs = secs;
+    }
+    // A flag may pair with the other bound from the config file
+    if let Some(problem) =
+        regions::aspect_problem(config.detector.min_aspect, config.detector.max_aspect)
+    {
+        return Err(DetectorError::InvalidArguments(problem.replace('_', "-")).into());
+    }
+    if let Some(bitrate) = args.record_bitrate {
+        config.recording.bitrate_kbps = bitrate;
+    }
+    if args.no_motion_alert.is_some() {
+        config.absence.alert_after = args.no_motion_alert.clone();
+    }
+    if args.no_motion_repeat.is_some() {
+        config.absence.repeat_every = args.no_motion_repeat.clone();
+    }
+    if let Some(hours) = &args.active_hours {
+        config.schedule.active = Some(hours.clone());
+        schedule::ScheduleClock::from_config(&config.schedule, config.location)
+            .map_err(|e| DetectorError::InvalidArguments(format!("active-hours: {:#}", e)))?;
     }
-}
-
-fn main() -> Result<()> {
-    let args = Args::parse();
 
-    if args.gui {
-        run_gui_mode()
+    if let Some(Command::SelfTest) = args.command {
+        run_self_test(config, args)
+    } else if let Some(Command::DebugReport { dir }) = &args.command {
+        println!("{}", debug_dump::report(&debug_dump::load(dir)?));
+        Ok(())
+    } else if let Some(Command::Bench(bench)) = args.command.take() {
+        run_bench(config, args, bench)
+    } else if args.dry_run {
+        run_dry_run(config, args)
+    } else if args.check {
+        run_check(config, args)
+    } else if args.gui {
+        if args.list_cameras {
+            print_cameras_later(Some(args.device));
+        }
+        run_gui_mode(config, args)
     } else {
         if args.verbose {
             println!("Motion Detector Starting...");
+            if let Some(name) = &args.camera {
+                println!("Camera: {}", name);
+            }
             println!("Device: {}", args.device);
             println!("Sensitivity: {}", args.sensitivity);
             println!("Min Area: {}", args.min_area);
+            println!("Output Dir: {}", args.output_dir.display());
+            if let Ok(Some(mut schedule)) =
+                schedule::ScheduleClock::from_config(&config.schedule, config.location)
+            {
+                let today = schedule.plan(chrono::Local::now()).describe();
+                println!("Schedule: {}, {}", schedule.schedule().text(), today);
+            }
+        }
+        if args.list_cameras {
+            print_cameras_later(Some(args.device));
+        }
+
+        run_cli_mode(config, args)
+    }
+}
 
-            // List available cameras
-            match MotionDetector::list_cameras() {
-                Ok(cameras) => {
-                    println!("Available cameras:");
-                    for camera in cameras {
-                        println!("  {}", camera);
-                    }
-                }
-                Err(e) => println!("Warning: Could not list cameras: {}", e),
+/// `--list-cameras`: probe in the background while the detector opens its
+/// own camera, and print the list when it is complete
+fn print_cameras_later(in_use: Option<u32>) {
+    std::thread::spawn(move || match MotionDetector::list_cameras(in_use) {
+        Ok(cameras) => {
+            println!("Available cameras:");
+            for camera in cameras {
+                println!("  {}", camera);
             }
         }
+        Err(e) => println!("Warning: Could not list cameras: {}", e),
+    });
+}
+
+fn main() {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            // --help and --version are not failures
+            let code = if e.use_stderr() { 3 } else { 0 };
+            let _ = e.print();
+            std::process::exit(code);
+        }
+    };
 
-        run_cli_mode(args)
+    let log = match args.log_file() {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(error::exit_code_for(&e));
+        }
+    };
+    let code = match run(args) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            error::exit_code_for(&e)
+        }
+    };
+    // Puts the console back and writes out the last lines, which exit()
+    // would lose
+    drop(log);
+    if code != 0 {
+        std::process::exit(code);
     }
 }
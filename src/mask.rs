@@ -0,0 +1,166 @@
+//! `--mask-file`: an image whose black pixels are never counted as motion,
+//! for shapes a rectangle can't cover, such as a swaying tree or a flag.
+//! Masks painted in the GUI are applied the same way.
+
+use crate::error::DetectorError;
+use crate::mask_editor::MaskImage;
+use anyhow::{bail, Context, Result};
+use opencv::{
+    core::{self, Mat, Size, Vector},
+    imgcodecs, imgproc,
+    prelude::*,
+};
+use std::path::{Path, PathBuf};
+
+pub struct IgnoreMask {
+    path: PathBuf,
+    /// 255 where motion counts, 0 where it is ignored
+    source: Mat,
+    /// `source` at the size of the frames being processed
+    sized: Option<Mat>,
+    /// `apply_to`'s output, swapped with the frame it masked
+    scratch: Mat,
+}
+
+impl IgnoreMask {
+    /// Read `path` as grayscale; any pixel that isn't black keeps detection on
+    pub fn load(path: &Path) -> Result<Self> {
+        let image = imgcodecs::imread(&path.to_string_lossy(), imgcodecs::IMREAD_GRAYSCALE)?;
+        if image.empty() {
+            return Err(DetectorError::InvalidArguments(format!(
+                "mask-file {} is missing or not an image",
+                path.display()
+            ))
+            .into());
+        }
+        let mask = Self::new(path.to_path_buf(), &image)?;
+        if core::count_non_zero(&mask.source)? == 0 {
+            eprintln!(
+                "Warning: mask {} is all black, so no motion will ever be detected",
+                path.display()
+            );
+        }
+        Ok(mask)
+    }
+
+    pub fn new(path: PathBuf, image: &Mat) -> Result<Self> {
+        let mut source = Mat::default();
+        imgproc::threshold(image, &mut source, 0.0, 255.0, imgproc::THRESH_BINARY)?;
+        Ok(Self {
+            path,
+            source,
+            sized: None,
+            scratch: Mat::default(),
+        })
+    }
+
+    /// A mask painted in the GUI, to be saved at `path`
+    pub fn from_image(path: PathBuf, image: &MaskImage) -> Result<Self> {
+        if image.pixels.len() != image.width * image.height || image.pixels.is_empty() {
+            bail!(
+                "mask of {}x{} has {} pixels",
+                image.width,
+                image.height,
+                image.pixels.len()
+            );
+        }
+        // The view borrows the pixels; `new` wants a Mat of its own
+        let mat =
+            Mat::new_rows_cols_with_data(image.height as i32, image.width as i32, &image.pixels)?
+                .try_clone()?;
+        Self::new(path, &mat)
+    }
+
+    /// A copy with its own resized mask, e.g. for replays on another thread
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(Self {
+            path: self.path.clone(),
+            source: self.source.try_clone()?,
+            sized: None,
+            scratch: Mat::default(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The mask at its own size, for the GUI's editor
+    pub fn image(&self) -> Result<MaskImage> {
+        Ok(MaskImage {
+            width: self.source.cols() as usize,
+            height: self.source.rows() as usize,
+            pixels: self.source.data_bytes()?.to_vec(),
+        })
+    }
+
+    /// Write the mask to its path as a PNG, where the next start loads it
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        if !imgcodecs::imwrite(&self.path.to_string_lossy(), &self.source, &Vector::new())? {
+            bail!("Failed to write mask {}", self.path.display());
+        }
+        Ok(())
+    }
+
+    /// Clear the ignored pixels of a thresholded frame. The mask is resized
+    /// to the frame the first time, and again if the frame size changes.
+    pub fn apply(&mut self, thresh: &Mat) -> Result<Mat> {
+        let mut masked = thresh.try_clone()?;
+        self.apply_to(&mut masked)?;
+        Ok(masked)
+    }
+
+    /// `apply` in place. The detector calls this every frame; the two
+    /// buffers trade places, so neither is allocated again while the frame
+    /// size stays the same.
+    pub fn apply_to(&mut self, thresh: &mut Mat) -> Result<()> {
+        let size = thresh.size()?;
+        if self.sized.as_ref().map(|sized| sized.size()).transpose()? != Some(size) {
+            self.sized = Some(self.resized(size)?);
+        }
+        core::bitwise_and(
+            &*thresh,
+            self.sized.as_ref().unwrap_or(&self.source),
+            &mut self.scratch,
+            &core::no_array(),
+        )?;
+        std::mem::swap(thresh, &mut self.scratch);
+        Ok(())
+    }
+
+    fn resized(&self, size: Size) -> Result<Mat> {
+        let source = self.source.size()?;
+        if source == size {
+            return Ok(self.source.try_clone()?);
+        }
+        let (mask_aspect, frame_aspect) = (
+            source.width as f64 / source.height as f64,
+            size.width as f64 / size.height as f64,
+        );
+        if (mask_aspect / frame_aspect - 1.0).abs() > 0.01 {
+            eprintln!(
+                "Warning: mask {} is {}x{}, stretching it to the {}x{} frames",
+                self.path.display(),
+                source.width,
+                source.height,
+                size.width,
+                size.height
+            );
+        }
+        // Nearest neighbour keeps the mask strictly black and white
+        let mut resized = Mat::default();
+        imgproc::resize(
+            &self.source,
+            &mut resized,
+            size,
+            0.0,
+            0.0,
+            imgproc::INTER_NEAREST,
+        )?;
+        Ok(resized)
+    }
+}
@@ -0,0 +1,223 @@
+//! The GUI's mask editor: a black and white image at the processing
+//! resolution, painted over the preview. As with `--mask-file`, motion under
+//! black pixels is ignored.
+
+// Without the `camera` feature only the tests use this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+/// Grayscale mask, row by row: 255 where motion counts, 0 where it is ignored
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaskImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl MaskImage {
+    /// Motion counts everywhere
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![255; width * height],
+        }
+    }
+
+    /// Nothing is ignored, so the detector needs no mask
+    pub fn is_clear(&self) -> bool {
+        self.pixels.iter().all(|&pixel| pixel > 0)
+    }
+
+    /// Stretched to `width`x`height`, nearest neighbour so it stays black
+    /// and white
+    pub fn rescaled(&self, width: usize, height: usize) -> Self {
+        if self.width == 0 || self.height == 0 {
+            return Self::new(width, height);
+        }
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let row = y * self.height / height * self.width;
+            for x in 0..width {
+                pixels.push(self.pixels[row + x * self.width / width]);
+            }
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Set the pixels within `radius` of `center`, in mask pixels
+    pub fn paint(&mut self, center: (f32, f32), radius: f32, value: u8) {
+        let (cx, cy) = center;
+        let y0 = (cy - radius).floor().max(0.0) as usize;
+        let y1 = ((cy + radius).ceil().max(0.0) as usize).min(self.height);
+        let x0 = (cx - radius).floor().max(0.0) as usize;
+        let x1 = ((cx + radius).ceil().max(0.0) as usize).min(self.width);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+                if dx * dx + dy * dy <= radius * radius {
+                    self.pixels[y * self.width + x] = value;
+                }
+            }
+        }
+    }
+
+    /// Paint along the line from `from` to `to`, without gaps however fast
+    /// the pointer moved
+    pub fn stroke(&mut self, from: (f32, f32), to: (f32, f32), radius: f32, value: u8) {
+        let length = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+        let steps = (length / (radius / 2.0).max(0.5)).ceil().max(1.0) as usize;
+        for step in 0..=steps {
+            let t = step as f32 / steps as f32;
+            let point = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+            self.paint(point, radius, value);
+        }
+    }
+
+    pub fn fill(&mut self, value: u8) {
+        self.pixels.fill(value);
+    }
+
+    pub fn invert(&mut self) {
+        for pixel in &mut self.pixels {
+            *pixel = if *pixel > 0 { 0 } else { 255 };
+        }
+    }
+
+    /// RGBA for drawing over the preview: ignored areas tinted red with
+    /// `opacity` (0-1), the rest transparent
+    pub fn overlay(&self, opacity: f32) -> Vec<u8> {
+        let alpha = (opacity.clamp(0.0, 1.0) * 255.0) as u8;
+        self.pixels
+            .iter()
+            .flat_map(|&pixel| match pixel {
+                0 => [255, 0, 0, alpha],
+                _ => [0, 0, 0, 0],
+            })
+            .collect()
+    }
+}
+
+/// What the brush paints
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Brush {
+    /// Ignore motion under the brush
+    Exclude,
+    /// Count motion under the brush again, i.e. erase
+    Include,
+}
+
+impl Brush {
+    pub fn value(self) -> u8 {
+        match self {
+            Brush::Exclude => 0,
+            Brush::Include => 255,
+        }
+    }
+}
+
+/// The mask being edited, with its undo history
+pub struct MaskEditor {
+    pub mask: Option<MaskImage>,
+    pub brush: Brush,
+    /// Brush radius in mask pixels
+    pub radius: f32,
+    /// How strongly ignored areas are tinted on the preview (0-1)
+    pub opacity: f32,
+    undo: Vec<MaskImage>,
+    /// Last point of the stroke in progress
+    last: Option<(f32, f32)>,
+}
+
+impl Default for MaskEditor {
+    fn default() -> Self {
+        Self {
+            mask: None,
+            brush: Brush::Exclude,
+            radius: 12.0,
+            opacity: 0.4,
+            undo: Vec::new(),
+            last: None,
+        }
+    }
+}
+
+impl MaskEditor {
+    /// Steps "Undo" can go back
+    const UNDO_LIMIT: usize = 20;
+
+    /// The mask at `width`x`height`, created clear or rescaled when the
+    /// processing resolution changed
+    pub fn mask_for(&mut self, width: usize, height: usize) -> &mut MaskImage {
+        let mask = match self.mask.take() {
+            Some(mask) if (mask.width, mask.height) == (width, height) => mask,
+            Some(mask) => mask.rescaled(width, height),
+            None => MaskImage::new(width, height),
+        };
+        self.mask.insert(mask)
+    }
+
+    /// Remember the mask as it is, before a change
+    fn checkpoint(&mut self) {
+        if let Some(mask) = &self.mask {
+            if self.undo.len() == Self::UNDO_LIMIT {
+                self.undo.remove(0);
+            }
+            self.undo.push(mask.clone());
+        }
+    }
+
+    /// Start a stroke at `point`, in mask pixels
+    pub fn press(&mut self, point: (f32, f32)) {
+        self.checkpoint();
+        self.last = Some(point);
+        let (radius, value) = (self.radius, self.brush.value());
+        if let Some(mask) = &mut self.mask {
+            mask.paint(point, radius, value);
+        }
+    }
+
+    /// Continue the stroke to `point`
+    pub fn drag(&mut self, point: (f32, f32)) {
+        let Some(last) = self.last.replace(point) else {
+            return self.press(point);
+        };
+        let (radius, value) = (self.radius, self.brush.value());
+        if let Some(mask) = &mut self.mask {
+            mask.stroke(last, point, radius, value);
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.last = None;
+    }
+
+    /// Apply `change` to the whole mask, undoably
+    pub fn edit(&mut self, change: impl FnOnce(&mut MaskImage)) {
+        self.checkpoint();
+        if let Some(mask) = &mut self.mask {
+            change(mask);
+        }
+    }
+
+    /// Go back one change; false when there is nothing to undo
+    pub fn undo(&mut self) -> bool {
+        let Some(previous) = self.undo.pop() else {
+            return false;
+        };
+        // Older steps may be at another resolution
+        let mask = match &self.mask {
+            Some(mask) => previous.rescaled(mask.width, mask.height),
+            None => previous,
+        };
+        self.mask = Some(mask);
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+}
@@ -0,0 +1,652 @@
+//! Stand-in for `detector.rs` in builds without OpenCV
+//! (`--no-default-features --features mock-camera`). Frames are synthetic
+//! and snapshots are never written, so the rest of the crate can be built
+//! and tested on machines with no OpenCV install.
+
+use crate::{
+    config, events, gui, incident, intrusion, mask_editor, monitor, naming, normalize, observe,
+    overlay, phase, privacy, recording, regions, reposition, run, selftest, sensitivity, source,
+    timestamp, tuning, upload, Args,
+};
+use anyhow::Result;
+use chrono::Local;
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Synthetic frame: only what the event logic looks at
+#[derive(Clone, Debug)]
+pub struct MockFrame {
+    pub index: u64,
+    pub motion: bool,
+}
+
+/// Stub frame source that replays a repeating motion pattern
+pub struct MockCamera {
+    pattern: Vec<bool>,
+    index: u64,
+}
+
+impl MockCamera {
+    pub fn new(pattern: Vec<bool>) -> Self {
+        Self { pattern, index: 0 }
+    }
+
+    pub fn read(&mut self) -> MockFrame {
+        let motion = match self.pattern.len() {
+            0 => false,
+            len => self.pattern[(self.index % len as u64) as usize],
+        };
+        let frame = MockFrame {
+            index: self.index,
+            motion,
+        };
+        self.index += 1;
+        frame
+    }
+}
+
+impl selftest::FrameSource for MockCamera {
+    fn grab_frame(&mut self) -> Result<bool> {
+        self.read();
+        Ok(true)
+    }
+}
+
+/// Opens the mock camera; the counterpart of the OpenCV build's builder.
+/// Some settings are only read by the GUI's detector thread, which only
+/// the tests run in this build.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(test), allow(dead_code))]
+pub struct DetectorBuilder {
+    device: u32,
+    sensitivity: f64,
+    sensitivity_curve: sensitivity::SensitivityCurve,
+    min_area: regions::MinArea,
+    opening: (i32, i32),
+    output_dir: PathBuf,
+    snapshots: config::SnapshotConfig,
+    reposition: reposition::RepositionConfig,
+    cooldowns: (Duration, Duration),
+    intrusion_sustain: Duration,
+    /// Motion per frame, repeated
+    pattern: Vec<bool>,
+}
+
+impl DetectorBuilder {
+    /// Everything the CLI flags and config file specify that the mock
+    /// camera can honour
+    pub fn from_args(args: &Args, config: &config::Config) -> Self {
+        // A short burst of motion every ~5s at 30 FPS
+        let mut pattern = vec![false; 150];
+        pattern[..5].fill(true);
+        Self {
+            device: args.device,
+            sensitivity: args.sensitivity,
+            sensitivity_curve: config.detector.sensitivity_curve,
+            min_area: args.min_area,
+            opening: (config.detector.open_kernel, config.detector.open_iterations),
+            output_dir: args.output_dir.clone(),
+            snapshots: config.snapshots.clone(),
+            reposition: config.reposition.clone(),
+            cooldowns: (Duration::from_secs(2), Duration::from_secs(2)),
+            intrusion_sustain: Duration::from_secs(args.intrusion_secs),
+            pattern,
+        }
+    }
+
+    pub fn cooldowns(mut self, cooldowns: (Duration, Duration)) -> Self {
+        self.cooldowns = cooldowns;
+        self
+    }
+
+    /// Replay `pattern` instead of the default bursts
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn pattern(mut self, pattern: Vec<bool>) -> Self {
+        self.pattern = pattern;
+        self
+    }
+}
+
+impl source::SourceBuilder for DetectorBuilder {
+    type Source = MotionDetector;
+
+    fn build(&self) -> Result<MotionDetector> {
+        let (min_cooldown, max_cooldown) = self.cooldowns;
+        Ok(MotionDetector {
+            camera: MockCamera::new(self.pattern.clone()),
+            sensitivity: self.sensitivity,
+            sensitivity_curve: self.sensitivity_curve,
+            min_area: self.min_area,
+            opening: self.opening,
+            output_dir: self.output_dir.clone(),
+            snapshots: self.snapshots.clone(),
+            observer: observe::Observer::new(false),
+            events: events::EventSource::new(events::Debouncer::adaptive(
+                min_cooldown,
+                max_cooldown,
+                Instant::now(),
+            )),
+            guard: intrusion::IntrusionGuard::new(self.intrusion_sustain),
+            intrusion_alarm: false,
+            repositioning: reposition::Repositioning::new(self.reposition.clone()),
+            frame: MockFrame {
+                index: 0,
+                motion: false,
+            },
+            frame_time: timestamp::FrameTime::processed(&timestamp::Clocks::now()),
+            frame_count: 0,
+            motion_count: 0,
+            last_motion_time: None,
+        })
+    }
+
+    fn device_index(&self) -> u32 {
+        self.device
+    }
+
+    fn device(mut self, device: u32) -> Self {
+        self.device = device;
+        self
+    }
+
+    fn camera_config(mut self, config: &config::Config) -> Self {
+        self.sensitivity_curve = config.detector.sensitivity_curve;
+        self.opening = (config.detector.open_kernel, config.detector.open_iterations);
+        self.snapshots = config.snapshots.clone();
+        self
+    }
+
+    fn input_file(self, path: Option<PathBuf>, _loop_file: bool) -> Self {
+        if let Some(path) = path {
+            eprintln!(
+                "Warning: the mock camera can't read {}, using the camera",
+                path.display()
+            );
+        }
+        self
+    }
+
+    fn mask_file(self, _path: Option<PathBuf>) -> Self {
+        self
+    }
+
+    fn live_settings(mut self, live: &source::LiveSettings) -> Self {
+        self.sensitivity = live.sensitivity;
+        self.sensitivity_curve = live.sensitivity_curve;
+        self.min_area = live.min_area;
+        self.opening = live.opening;
+        self.output_dir = live.output_dir.clone();
+        self
+    }
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+pub struct MotionDetector {
+    camera: MockCamera,
+    sensitivity: f64,
+    sensitivity_curve: sensitivity::SensitivityCurve,
+    min_area: regions::MinArea,
+    opening: (i32, i32),
+    output_dir: PathBuf,
+    snapshots: config::SnapshotConfig,
+    observer: observe::Observer,
+    events: events::EventSource,
+    guard: intrusion::IntrusionGuard,
+    intrusion_alarm: bool,
+    repositioning: reposition::Repositioning,
+    /// What the last `poll` saw
+    frame: MockFrame,
+    frame_time: timestamp::FrameTime,
+    frame_count: u32,
+    motion_count: u32,
+    last_motion_time: Option<Instant>,
+}
+
+impl MotionDetector {
+    pub fn list_cameras(_in_use: Option<u32>) -> Result<Vec<String>> {
+        Ok(vec!["Mock camera".to_string()])
+    }
+
+    /// Image-write stub: where the snapshot would have gone
+    fn snapshot_path(&self, frame: &MockFrame) -> String {
+        let now = Local::now();
+        let mut name = format!("motion_{}_{}.jpg", now.format("%Y%m%d_%H%M%S"), frame.index);
+        if self.snapshots.date_tree {
+            name = naming::in_date_tree(&name, now);
+        }
+        self.output_dir.join(name).to_string_lossy().into_owned()
+    }
+}
+
+impl selftest::FrameSource for MotionDetector {
+    fn grab_frame(&mut self) -> Result<bool> {
+        self.camera.grab_frame()
+    }
+}
+
+impl source::Frame for MockFrame {
+    fn is_empty(&self) -> bool {
+        false
+    }
+
+    fn try_clone(&self) -> Result<Self> {
+        Ok(self.clone())
+    }
+}
+
+impl source::FrameSource for MotionDetector {
+    type Frame = MockFrame;
+    type Builder = DetectorBuilder;
+    type Clips = NoClips;
+    type Tuner = NoTuner;
+
+    fn poll(&mut self) -> Result<Vec<events::MotionEvent>> {
+        let now = Instant::now();
+        self.frame = self.camera.read();
+        self.frame_time = timestamp::FrameTime::processed(&timestamp::Clocks::now());
+        self.frame_count += 1;
+        // The first frame after arming is the empty scene
+        self.guard.reference_taken();
+        self.intrusion_alarm = self.guard.update(now, self.frame.motion);
+        let motion = self.frame.motion && !self.repositioning.is_active();
+        if motion {
+            self.last_motion_time = Some(now);
+        }
+        let events = self.events.frame(now, motion, None);
+        self.motion_count += events.len() as u32;
+        Ok(events)
+    }
+
+    fn take_frame(&mut self) -> MockFrame {
+        self.frame.clone()
+    }
+
+    fn stats(&self) -> source::FrameStats {
+        source::FrameStats {
+            motion_detected: self.frame.motion && !self.repositioning.is_active(),
+            frame_time: self.frame_time,
+            frame_count: self.frame_count,
+            motion_count: self.motion_count,
+            last_motion_time: self.last_motion_time,
+            current_fps: 0.0,
+            timings: monitor::StageTimings::default(),
+            score: regions::MotionScore::default(),
+            contours_found: 0,
+            regions_found: 0,
+            motion_regions: Vec::new(),
+            motion_area: if self.frame.motion { 1.0 } else { 0.0 },
+            aspect_rejected: 0,
+            frames_skipped: 0,
+            total_frames_skipped: 0,
+            average_frames_skipped: 0.0,
+            grab_latest: false,
+            rule_hits: Vec::new(),
+            new_tracks: Vec::new(),
+            active_tracks: Vec::new(),
+            quality_change: None,
+            intrusion_alarm: self.intrusion_alarm,
+            intrusion: self.guard.state(),
+            reposition_transition: None,
+            repositioning: self.repositioning.is_active(),
+            snapshots_deduplicated: None,
+            resolution: MOCK_RESOLUTION,
+            process_resolution: MOCK_RESOLUTION,
+        }
+    }
+
+    fn release(&mut self) {}
+
+    fn reconnect(&mut self, _builder: &DetectorBuilder) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+
+    fn file_finished(&self) -> bool {
+        false
+    }
+
+    fn rewind(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_loop_file(&mut self, _enabled: bool) {}
+
+    fn events(&self) -> &events::EventSource {
+        &self.events
+    }
+
+    fn events_mut(&mut self) -> &mut events::EventSource {
+        &mut self.events
+    }
+
+    fn observer(&self) -> &observe::Observer {
+        &self.observer
+    }
+
+    fn observer_mut(&mut self) -> &mut observe::Observer {
+        &mut self.observer
+    }
+
+    fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    fn set_output_dir(&mut self, dir: PathBuf) {
+        self.output_dir = dir;
+    }
+
+    fn uploader(&self) -> Option<&upload::Uploader> {
+        None
+    }
+
+    fn phase(&self) -> phase::DetectorPhase {
+        phase::DetectorPhase::of_running(0, self.repositioning.is_active(), self.events.armed)
+    }
+
+    fn event_context(&self) -> naming::EventContext {
+        naming::EventContext::default()
+    }
+
+    fn describe_resolution(&self) -> String {
+        let (width, height) = MOCK_RESOLUTION;
+        format!("{}x{} (mock)", width, height)
+    }
+
+    fn min_area(&self) -> regions::MinArea {
+        self.min_area
+    }
+
+    fn live_settings(&self) -> source::LiveSettings {
+        source::LiveSettings {
+            sensitivity: self.sensitivity,
+            sensitivity_curve: self.sensitivity_curve,
+            min_area: self.min_area,
+            opening: self.opening,
+            output_dir: self.output_dir.clone(),
+            observe: self.observer.is_active(),
+            armed: self.guard.state().is_armed(),
+        }
+    }
+
+    fn set_sensitivity(&mut self, sensitivity: f64) {
+        self.sensitivity = sensitivity;
+    }
+
+    fn set_sensitivity_curve(&mut self, curve: sensitivity::SensitivityCurve) {
+        self.sensitivity_curve = curve;
+    }
+
+    fn set_min_area(&mut self, min_area: regions::MinArea) {
+        self.min_area = min_area;
+    }
+
+    fn set_opening(&mut self, kernel: i32, iterations: i32) {
+        self.opening = (kernel, iterations);
+    }
+
+    fn arm(&mut self) {
+        self.guard.arm();
+    }
+
+    fn disarm(&mut self) {
+        self.guard.disarm();
+    }
+
+    fn toggle_repositioning(&mut self) -> reposition::Transition {
+        self.repositioning.toggle(Instant::now())
+    }
+
+    fn mask_image(&self) -> Option<mask_editor::MaskImage> {
+        None
+    }
+
+    fn set_painted_mask(
+        &mut self,
+        _image: &mask_editor::MaskImage,
+        _path: &Path,
+        _created: bool,
+    ) -> Result<()> {
+        anyhow::bail!("masks need the camera feature")
+    }
+
+    fn mask_file(&self) -> Option<PathBuf> {
+        None
+    }
+
+    fn save_event_snapshot(
+        &mut self,
+        frame: &MockFrame,
+        _event: u64,
+    ) -> Result<source::EventSnapshot> {
+        let path = self.snapshot_path(frame);
+        if self.snapshots.enabled
+            && self
+                .observer
+                .allows(observe::Effect::Image, || format!("saved {}", path))
+        {
+            println!("  Snapshot skipped (mock): {}", path);
+        }
+        Ok(source::EventSnapshot::Stored(None))
+    }
+
+    fn save_intrusion_snapshot(&self, _frame: &MockFrame) -> Result<String> {
+        anyhow::bail!("the mock camera has no images to save")
+    }
+
+    fn save_key_frame(
+        &self,
+        incident_id: u64,
+        key_frames: &mut incident::KeyFrame<MockFrame>,
+    ) -> Result<Option<String>> {
+        key_frames.take(incident_id);
+        Ok(None)
+    }
+
+    fn save_background_if_due(&mut self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn manual_snapshot(
+        &mut self,
+        _running: bool,
+        _last_frame: Option<&MockFrame>,
+    ) -> Result<String> {
+        anyhow::bail!("the mock camera has no images to save")
+    }
+
+    fn preview_frame(
+        frame: &MockFrame,
+        _overlay_config: &overlay::OverlayConfig,
+        _info: &overlay::OverlayInfo,
+        _max_size: [f32; 2],
+    ) -> Result<gui::PreviewFrame> {
+        // Gray, white while there is motion
+        let level = if frame.motion { 255 } else { 128 };
+        let (width, height) = (64, 48);
+        Ok(gui::PreviewFrame {
+            width,
+            height,
+            rgb: vec![level; width * height * 3],
+        })
+    }
+
+    fn clips(_config: &recording::RecordingConfig) -> Option<NoClips> {
+        None
+    }
+
+    fn take_recording(&mut self) -> Result<Option<Infallible>> {
+        Ok(None)
+    }
+}
+
+/// Size the mock camera reports for its frames
+const MOCK_RESOLUTION: (i32, i32) = (640, 480);
+
+/// `--record` needs an encoder, so the mock camera never has a clip writer
+pub enum NoClips {}
+
+impl source::ClipSink<MockFrame> for NoClips {
+    fn prepare(&mut self, _frame: &MockFrame) -> Result<recording::Selection> {
+        match *self {}
+    }
+
+    fn selection(&self) -> Option<recording::Selection> {
+        match *self {}
+    }
+
+    fn encode_fps(&self) -> Option<f64> {
+        match *self {}
+    }
+
+    fn start(&mut self, _incident_id: u64, _dir: &Path, _frame: &MockFrame) -> Result<()> {
+        match *self {}
+    }
+
+    fn write(&mut self, _frame: &MockFrame, _motion: bool) -> Result<()> {
+        match *self {}
+    }
+
+    fn finish(&mut self, _incident_id: u64) -> Result<Option<PathBuf>> {
+        match *self {}
+    }
+
+    fn trims(&self) -> bool {
+        match *self {}
+    }
+
+    fn take_trimmed(&self) -> Vec<recording::Trimmed> {
+        match *self {}
+    }
+}
+
+/// Nothing is ever recorded for the tuning window to replay
+pub struct NoTuner;
+
+impl source::Tuner for NoTuner {
+    type Recording = Infallible;
+
+    fn spawn(_states: crossbeam_channel::Sender<gui::MotionState>) -> Self {
+        NoTuner
+    }
+
+    fn is_loaded(&self) -> bool {
+        false
+    }
+
+    fn load(&mut self, recording: Infallible, _params: tuning::TuneParams) {
+        match recording {}
+    }
+
+    fn run(&self, _params: tuning::TuneParams) {}
+}
+
+/// Options that work on images the mock camera doesn't have
+fn warn_unsupported(config: &config::Config, args: &Args) {
+    let aspect = regions::AspectRange {
+        min: config.detector.min_aspect,
+        max: config.detector.max_aspect,
+    };
+    let unsupported = [
+        (
+            config.tracking.enabled || !config.rules.is_empty(),
+            "tracking and zone rules",
+        ),
+        (config.recording.enabled, "--record"),
+        (args.debug_dump.is_some(), "--debug-dump"),
+        (
+            config.detector.normalize != normalize::Normalize::Off,
+            "--normalize",
+        ),
+        (config.detector.open_kernel > 0, "--open-kernel"),
+        (config.detector.pyramid_levels > 1, "--pyramid-levels"),
+        (!aspect.is_unbounded(), "--min-aspect/--max-aspect"),
+        (config.detector.smoothing_secs > 0.0, "--smoothing"),
+        (config.detector.min_fps.is_some(), "--min-fps"),
+        (
+            config.detector.process_scale != 1.0 || args.capture_resolution.is_some(),
+            "--process-scale and --capture-resolution",
+        ),
+        (args.mask_file.is_some(), "--mask-file"),
+        (args.s3_bucket.is_some(), "--s3-bucket"),
+        (config.snapshots.dedup_threshold > 0, "--dedup-threshold"),
+        (
+            config.snapshots.jpeg_progressive || config.snapshots.jpeg_chroma.is_some(),
+            "--jpeg-progressive and --jpeg-chroma",
+        ),
+        (
+            !config.snapshots.privacy_regions.is_empty(),
+            "--privacy-region",
+        ),
+        (
+            config.snapshots.privacy_blur != privacy::PrivacyBlur::Off,
+            "--privacy-blur",
+        ),
+        (
+            config.snapshots.annotation_style.is_some(),
+            "--annotation-style",
+        ),
+    ];
+    for (_, option) in unsupported.iter().filter(|(set, _)| *set) {
+        eprintln!("Warning: ignoring {} (needs the camera feature)", option);
+    }
+}
+
+pub fn run_cli_mode(config: config::Config, mut args: Args) -> Result<()> {
+    selftest::require_output_dir(&args.output_dir)?;
+    warn_unsupported(&config, &args);
+    // Mock frames are ready at once, so pace them like a 30 FPS camera
+    args.target_fps = args.target_fps.or(Some(30.0));
+    let builder = DetectorBuilder::from_args(&args, &config).cooldowns(args.cooldowns()?);
+    let detector = source::SourceBuilder::build(&builder)?;
+    println!("Running against the mock camera (built without OpenCV)");
+    run::detect_cli(&config, &args, builder, detector)
+}
+
+pub fn run_self_test(config: config::Config, args: Args) -> Result<()> {
+    println!("Running self-test against the mock camera (built without OpenCV)...");
+    let mut detector = source::SourceBuilder::build(&DetectorBuilder::from_args(&args, &config))?;
+    let mut results = vec![selftest::check_camera(
+        &mut detector,
+        selftest::CAMERA_FRAMES,
+    )];
+    results.extend(selftest::common_checks(&args.output_dir));
+    selftest::print_report("Self-test", &results);
+    selftest::finish("Self-test", &results)
+}
+
+pub fn run_dry_run(config: config::Config, args: Args) -> Result<()> {
+    let results = vec![
+        selftest::check_config(args.config.as_deref()),
+        selftest::CheckResult::new(
+            "Camera",
+            selftest::CheckStatus::Pass,
+            "mock camera (built without OpenCV)",
+        ),
+        selftest::check_output_dir(&args.output_dir),
+        selftest::check_microphone(&config.audio),
+        selftest::check_notifiers(),
+    ];
+    selftest::print_report("Dry run", &results);
+    selftest::finish("Dry run", &results)
+}
+
+pub fn run_check(_config: config::Config, args: Args) -> Result<()> {
+    let mut camera = MockCamera::new(vec![false]);
+    selftest::finish_check(
+        selftest::check_config(args.config.as_deref()),
+        selftest::check_camera(&mut camera, selftest::CHECK_FRAMES),
+        selftest::check_output_dir(&args.output_dir),
+    )
+}
+
+pub fn run_bench(_config: config::Config, _args: Args, _bench: crate::BenchArgs) -> Result<()> {
+    anyhow::bail!("bench needs the camera feature; this build only has the mock camera")
+}
+
+pub fn run_gui_mode(_config: config::Config, _args: Args) -> Result<()> {
+    anyhow::bail!("the GUI needs the camera feature; this build only has the mock camera")
+}
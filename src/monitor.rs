@@ -0,0 +1,187 @@
+// Without the `camera` feature only the tests use this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// CPU and memory used by this process
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResourceUsage {
+    /// Percent of one core, so a busy multi-threaded process can exceed 100
+    pub cpu_percent: f32,
+    pub rss_bytes: u64,
+}
+
+impl ResourceUsage {
+    pub fn rss_mb(&self) -> f32 {
+        self.rss_bytes as f32 / (1024.0 * 1024.0)
+    }
+}
+
+/// Samples the process's own resource usage on a background thread
+pub struct ResourceMonitor {
+    latest: Arc<Mutex<Option<ResourceUsage>>>,
+}
+
+impl ResourceMonitor {
+    /// Start sampling every `interval`. On platforms without `/proc` the
+    /// thread exits after the first failed read and `latest` stays `None`.
+    pub fn spawn(interval: Duration) -> Self {
+        let latest = Arc::new(Mutex::new(None));
+        let shared = Arc::clone(&latest);
+
+        thread::spawn(move || {
+            let Some(mut previous) = read_cpu_seconds() else {
+                return;
+            };
+            let mut previous_at = Instant::now();
+
+            loop {
+                thread::sleep(interval);
+                let (Some(cpu), Some(rss_bytes)) = (read_cpu_seconds(), read_rss_bytes()) else {
+                    return;
+                };
+                let now = Instant::now();
+                let wall = now.duration_since(previous_at).as_secs_f64();
+                let usage = ResourceUsage {
+                    cpu_percent: cpu_percent(previous, cpu, wall),
+                    rss_bytes,
+                };
+                previous = cpu;
+                previous_at = now;
+
+                if let Ok(mut latest) = shared.lock() {
+                    *latest = Some(usage);
+                }
+            }
+        });
+
+        Self { latest }
+    }
+
+    pub fn latest(&self) -> Option<ResourceUsage> {
+        self.latest.lock().ok().and_then(|latest| *latest)
+    }
+}
+
+/// CPU time used between two samples as a percentage of the wall time
+pub fn cpu_percent(previous_cpu: f64, cpu: f64, wall_secs: f64) -> f32 {
+    if wall_secs <= 0.0 {
+        return 0.0;
+    }
+    ((cpu - previous_cpu).max(0.0) / wall_secs * 100.0) as f32
+}
+
+/// User + system CPU seconds from `/proc/self/stat`
+fn read_cpu_seconds() -> Option<f64> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    parse_cpu_seconds(&stat)
+}
+
+pub fn parse_cpu_seconds(stat: &str) -> Option<f64> {
+    // The command name may contain spaces, so split after its closing paren
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    // utime and stime are fields 14 and 15 of the full line
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    // USER_HZ is 100 on every mainstream Linux build
+    Some((utime + stime) as f64 / 100.0)
+}
+
+/// Resident set size from `/proc/self/status`
+fn read_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    parse_rss_bytes(&status)
+}
+
+pub fn parse_rss_bytes(status: &str) -> Option<u64> {
+    parse_status_kb(status, "VmRSS:")
+}
+
+/// Highest resident set size so far, from `/proc/self/status`
+pub fn read_peak_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    parse_status_kb(&status, "VmHWM:")
+}
+
+/// A `kB` value from `/proc/self/status`, in bytes
+pub fn parse_status_kb(status: &str, field: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with(field))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Rolling averages of the time spent in each detection stage, in milliseconds
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StageTimings {
+    pub capture_ms: f32,
+    pub blur_ms: f32,
+    pub diff_ms: f32,
+    pub contours_ms: f32,
+}
+
+impl StageTimings {
+    /// Fold one frame's stage durations into the averages
+    pub fn update(
+        &mut self,
+        capture: Duration,
+        blur: Duration,
+        diff: Duration,
+        contours: Duration,
+    ) {
+        average(&mut self.capture_ms, capture);
+        average(&mut self.blur_ms, blur);
+        average(&mut self.diff_ms, diff);
+        average(&mut self.contours_ms, contours);
+    }
+
+    pub fn total_ms(&self) -> f32 {
+        self.capture_ms + self.blur_ms + self.diff_ms + self.contours_ms
+    }
+}
+
+fn average(current: &mut f32, sample: Duration) {
+    // Exponential moving average, roughly the last 20 frames
+    const ALPHA: f32 = 0.1;
+    let sample_ms = sample.as_secs_f32() * 1000.0;
+    if *current == 0.0 {
+        *current = sample_ms;
+    } else {
+        *current += ALPHA * (sample_ms - *current);
+    }
+}
+
+/// Total time spent in each detection stage, for averages over a whole run
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StageTotals {
+    pub frames: u32,
+    pub capture: Duration,
+    pub blur: Duration,
+    pub diff: Duration,
+    pub contours: Duration,
+}
+
+impl StageTotals {
+    pub fn add(&mut self, capture: Duration, blur: Duration, diff: Duration, contours: Duration) {
+        self.frames += 1;
+        self.capture += capture;
+        self.blur += blur;
+        self.diff += diff;
+        self.contours += contours;
+    }
+
+    /// Mean time per frame of each stage
+    pub fn average(&self) -> StageTimings {
+        let mean = |total: Duration| match self.frames {
+            0 => 0.0,
+            frames => total.as_secs_f32() * 1000.0 / frames as f32,
+        };
+        StageTimings {
+            capture_ms: mean(self.capture),
+            blur_ms: mean(self.blur),
+            diff_ms: mean(self.diff),
+            contours_ms: mean(self.contours),
+        }
+    }
+}
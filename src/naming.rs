@@ -0,0 +1,173 @@
+//! Event snapshot file names from the `[snapshots] filename` template, e.g.
+//! `{zone}_{direction}_{timestamp}` gives `frontdoor_left_20240101_120000.jpg`
+
+use crate::template::Template;
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TEMPLATE: &str = "motion_{timestamp}";
+
+/// `{zone}` when no zone contains the motion, or none are configured
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub const NO_ZONE: &str = "frame";
+/// `{direction}` when the motion hasn't moved far enough to tell
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub const NO_DIRECTION: &str = "none";
+
+/// Way the motion was heading, in frame coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl Direction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Direction::Left => "left",
+            Direction::Right => "right",
+            Direction::Up => "up",
+            Direction::Down => "down",
+        }
+    }
+
+    /// The dominant axis of the move from `from` to `to`, if it covered at
+    /// least `min_distance` pixels
+    pub fn between(from: (f64, f64), to: (f64, f64), min_distance: f64) -> Option<Self> {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        if dx.hypot(dy) < min_distance {
+            return None;
+        }
+        Some(if dx.abs() >= dy.abs() {
+            if dx < 0.0 {
+                Direction::Left
+            } else {
+                Direction::Right
+            }
+        } else if dy < 0.0 {
+            Direction::Up
+        } else {
+            Direction::Down
+        })
+    }
+}
+
+/// What the template can refer to besides the capture time
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct EventContext {
+    pub zone: Option<String>,
+    pub direction: Option<Direction>,
+}
+
+/// Centroids of the largest motion region over recent frames. A pause in
+/// the motion starts a new trail.
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct MotionTrail {
+    points: VecDeque<(f64, f64)>,
+    last_seen: Option<Instant>,
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl MotionTrail {
+    const LENGTH: usize = 15;
+    const GAP: Duration = Duration::from_secs(1);
+    /// Pixels the motion has to travel before it has a direction
+    const MIN_TRAVEL: f64 = 20.0;
+
+    pub fn new() -> Self {
+        Self {
+            points: VecDeque::with_capacity(Self::LENGTH),
+            last_seen: None,
+        }
+    }
+
+    pub fn push(&mut self, now: Instant, centroid: (f64, f64)) {
+        if self
+            .last_seen
+            .is_some_and(|seen| now.duration_since(seen) > Self::GAP)
+        {
+            self.points.clear();
+        }
+        if self.points.len() == Self::LENGTH {
+            self.points.pop_front();
+        }
+        self.points.push_back(centroid);
+        self.last_seen = Some(now);
+    }
+
+    pub fn latest(&self) -> Option<(f64, f64)> {
+        self.points.back().copied()
+    }
+
+    pub fn direction(&self) -> Option<Direction> {
+        let (first, last) = (self.points.front()?, self.points.back()?);
+        Direction::between(*first, *last, Self::MIN_TRAVEL)
+    }
+}
+
+/// Placeholders a snapshot name can use
+pub const FIELDS: [&str; 3] = ["timestamp", "zone", "direction"];
+
+/// Problems with a `filename` template, checked when the config is loaded
+pub fn check(template: &str) -> Result<(), String> {
+    if template.contains(['/', '\\']) {
+        return Err(format!(
+            "\"{}\" must not contain a path separator",
+            template
+        ));
+    }
+    let parsed = Template::parse(template, &FIELDS).map_err(|e| e.to_string())?;
+    if !parsed.uses("timestamp") {
+        return Err(format!(
+            "\"{}\" needs {{timestamp}}, or every event overwrites the last",
+            template
+        ));
+    }
+    Ok(())
+}
+
+/// Zone names can hold anything; keep file names portable
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The file name, without extension, for an event captured at `captured`.
+/// Templates are checked at load time; a bad one falls back to the default.
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn render(template: &str, captured: DateTime<Local>, context: &EventContext) -> String {
+    let template = Template::parse(template, &FIELDS)
+        .or_else(|_| Template::parse(DEFAULT_TEMPLATE, &FIELDS))
+        .expect("the default template parses");
+    template.render(|field| {
+        Some(match field {
+            "timestamp" => captured.format("%Y%m%d_%H%M%S").to_string(),
+            "zone" => sanitize(context.zone.as_deref().unwrap_or(NO_ZONE)),
+            _ => context
+                .direction
+                .map_or(NO_DIRECTION, Direction::as_str)
+                .to_string(),
+        })
+    })
+}
+
+/// `name` under `YYYY/MM/DD/` for `captured`, with `--date-tree`. Always
+/// `/`-separated, so it also works as an upload key.
+pub fn in_date_tree(name: &str, captured: DateTime<Local>) -> String {
+    format!("{}/{}", captured.format("%Y/%m/%d"), name)
+}
@@ -0,0 +1,55 @@
+// Without the `camera` feature only the config uses this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use clap::ValueEnum;
+#[cfg(feature = "camera")]
+use opencv::{
+    core::{Mat, Ptr, Size},
+    imgproc::{self, CLAHE},
+    prelude::*,
+};
+use serde::Deserialize;
+
+/// Contrast normalization of the grayscale frame before blurring, so slow
+/// global brightness changes (clouds, dusk) don't read as motion
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalize {
+    #[default]
+    Off,
+    /// Global histogram equalization
+    Equalize,
+    /// Contrast-limited adaptive equalization, per tile
+    Clahe,
+}
+
+#[cfg(feature = "camera")]
+pub struct Normalizer {
+    /// `None` for plain equalization
+    clahe: Option<Ptr<CLAHE>>,
+}
+
+#[cfg(feature = "camera")]
+impl Normalizer {
+    /// `None` when normalization is off
+    pub fn new(config: &crate::config::DetectorConfig) -> anyhow::Result<Option<Self>> {
+        let clahe = match config.normalize {
+            Normalize::Off => return Ok(None),
+            Normalize::Equalize => None,
+            Normalize::Clahe => {
+                let tiles = Size::new(config.clahe_tile_size, config.clahe_tile_size);
+                Some(imgproc::create_clahe(config.clahe_clip_limit, tiles)?)
+            }
+        };
+        Ok(Some(Self { clahe }))
+    }
+
+    pub fn apply(&mut self, gray: &Mat) -> opencv::Result<Mat> {
+        let mut normalized = Mat::default();
+        match self.clahe.as_mut() {
+            Some(clahe) => clahe.apply(gray, &mut normalized)?,
+            None => imgproc::equalize_hist(gray, &mut normalized)?,
+        }
+        Ok(normalized)
+    }
+}
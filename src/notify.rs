@@ -0,0 +1,93 @@
+//! Wording of notification messages, read from `[messages.<notifier>]`
+//! config sections. Notifiers look up their own section and fall back to
+//! `MessageTemplate::default()`.
+
+// No notifier sends messages yet; the config and tests use the templates
+#![allow(dead_code)]
+
+use crate::template::{Template, TemplateError};
+use serde::Deserialize;
+
+/// Placeholders a message can use
+pub const FIELDS: [&str; 7] = [
+    "camera",
+    "time",
+    "count",
+    "zone",
+    "severity",
+    "duration",
+    "snapshot_url",
+];
+
+/// Subject and body of one notifier's messages. Bodies may span several
+/// lines, e.g. for email.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MessageTemplate {
+    pub subject: String,
+    pub body: String,
+}
+
+impl Default for MessageTemplate {
+    fn default() -> Self {
+        Self {
+            subject: "{camera}MOTION DETECTED".to_string(),
+            body: "[{time}] {camera}MOTION DETECTED! (#{count})".to_string(),
+        }
+    }
+}
+
+impl MessageTemplate {
+    /// Parse both parts, naming the part that failed
+    pub fn parse(&self) -> Result<(Template, Template), (&'static str, TemplateError)> {
+        let subject = Template::parse(&self.subject, &FIELDS).map_err(|e| ("subject", e))?;
+        let body = Template::parse(&self.body, &FIELDS).map_err(|e| ("body", e))?;
+        Ok((subject, body))
+    }
+
+    /// The subject and body for `message`. Templates are checked when the
+    /// config is loaded, so a bad one here falls back to the default wording.
+    pub fn render(&self, message: &Message) -> (String, String) {
+        let (subject, body) = self
+            .parse()
+            .or_else(|_| Self::default().parse())
+            .expect("the default templates parse");
+        let value = |field: &str| message.field(field);
+        (subject.render(value), body.render(value))
+    }
+}
+
+/// The wording `notifier` uses, from its `[messages.<notifier>]` section
+pub fn template_for(config: &crate::config::Config, notifier: &str) -> MessageTemplate {
+    config.messages.get(notifier).cloned().unwrap_or_default()
+}
+
+/// What a notification is about. Optional fields render empty when unset.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Message {
+    /// `[name] ` with `--camera`, else empty, matching the console lines
+    pub camera: String,
+    pub time: String,
+    /// Motion events so far
+    pub count: u64,
+    pub zone: Option<String>,
+    pub severity: Option<String>,
+    /// How long the incident has lasted, e.g. "2m 5s"
+    pub duration: Option<String>,
+    pub snapshot_url: Option<String>,
+}
+
+impl Message {
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "camera" => Some(self.camera.clone()),
+            "time" => Some(self.time.clone()),
+            "count" => Some(self.count.to_string()),
+            "zone" => self.zone.clone(),
+            "severity" => self.severity.clone(),
+            "duration" => self.duration.clone(),
+            "snapshot_url" => self.snapshot_url.clone(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Observe mode (`--observe`): trying a new config against a live camera
+//! without acting on it. Detection, incidents and event lines run as usual,
+//! marked `[OBSERVE]`, but nothing is written or sent: no snapshots, key
+//! frames, clips or audio, no uploads, no relay switching and no state
+//! file. The first skipped effect of each kind is logged, so the log still
+//! shows what a live run would have done. The GUI can go live without a
+//! restart.
+
+use std::cell::{Cell, RefCell};
+
+/// A kind of side effect observe mode skips
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effect {
+    /// Snapshots, key frames and background images
+    Image,
+    /// Copies queued for `--s3-bucket`
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    Upload,
+    /// Incident clips under `[recording]`
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    Clip,
+    /// Incident audio under `[audio]`
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    Audio,
+    /// Switching the `--gpio-pin` relay
+    Relay,
+    /// Writing the `--state-file`
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    StateFile,
+}
+
+impl Effect {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Whether side effects happen, and what observe mode skipped so far
+#[derive(Debug, Default)]
+pub struct Observer {
+    active: bool,
+    /// `Effect` bits already logged since observe mode was turned on
+    logged: Cell<u8>,
+    notes: RefCell<Vec<String>>,
+}
+
+impl Observer {
+    pub fn new(active: bool) -> Self {
+        Self {
+            active,
+            ..Default::default()
+        }
+    }
+
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Turn observe mode on or off. Turning it on again logs each kind of
+    /// skipped effect once more.
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn set_active(&mut self, active: bool) {
+        if active && !self.active {
+            self.logged.set(0);
+        }
+        self.active = active;
+    }
+
+    /// Whether `effect` may happen. In observe mode it may not, and the
+    /// first of its kind is noted as "would have `what`".
+    pub fn allows(&self, effect: Effect, what: impl FnOnce() -> String) -> bool {
+        if !self.active {
+            return true;
+        }
+        let logged = self.logged.get();
+        if logged & effect.bit() == 0 {
+            self.logged.set(logged | effect.bit());
+            self.notes
+                .borrow_mut()
+                .push(format!("Would have {}", what()));
+        }
+        false
+    }
+
+    /// The notes since the last call, for the event log
+    pub fn take_notes(&self) -> Vec<String> {
+        self.notes.take()
+    }
+
+    /// The tag event lines carry in observe mode
+    pub fn tag(&self) -> &'static str {
+        if self.active {
+            "[OBSERVE] "
+        } else {
+            ""
+        }
+    }
+}
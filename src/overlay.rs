@@ -0,0 +1,266 @@
+use crate::regions::Region;
+#[cfg(feature = "camera")]
+use anyhow::Result;
+#[cfg(feature = "camera")]
+use chrono::Local;
+#[cfg(feature = "camera")]
+use opencv::{
+    core::{Mat, Point, Rect, Scalar},
+    imgproc,
+    prelude::*,
+};
+use serde::Deserialize;
+
+/// Corner of the frame the overlay text block is anchored to
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverlayPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl OverlayPosition {
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub const ALL: [OverlayPosition; 4] = [
+        OverlayPosition::TopLeft,
+        OverlayPosition::TopRight,
+        OverlayPosition::BottomLeft,
+        OverlayPosition::BottomRight,
+    ];
+
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn label(&self) -> &'static str {
+        match self {
+            OverlayPosition::TopLeft => "Top left",
+            OverlayPosition::TopRight => "Top right",
+            OverlayPosition::BottomLeft => "Bottom left",
+            OverlayPosition::BottomRight => "Bottom right",
+        }
+    }
+}
+
+/// On-screen display settings, read from the `[overlay]` config section
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct OverlayConfig {
+    pub enabled: bool,
+    pub position: OverlayPosition,
+    /// Font scale relative to a 480-line frame
+    pub font_scale: f64,
+    pub show_time: bool,
+    pub show_camera: bool,
+    pub show_fps: bool,
+    pub show_armed: bool,
+    pub show_count: bool,
+    pub show_motion_banner: bool,
+    /// Boxes around motion regions, merged ones in orange
+    pub show_regions: bool,
+}
+
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            position: OverlayPosition::TopLeft,
+            font_scale: 0.6,
+            show_time: true,
+            show_camera: true,
+            show_fps: true,
+            show_armed: true,
+            show_count: true,
+            show_motion_banner: true,
+            show_regions: true,
+        }
+    }
+}
+
+/// Live values drawn by the overlay
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct OverlayInfo<'a> {
+    pub camera_name: &'a str,
+    pub fps: f32,
+    pub armed: bool,
+    pub motion_detected: bool,
+    pub motion_count: u32,
+    /// Motion regions of the frame, in its pixels
+    pub regions: &'a [Region],
+}
+
+/// Draw the OSD onto a preview/stream frame. Never call this on detection input.
+#[cfg(feature = "camera")]
+pub fn render_overlay(frame: &mut Mat, config: &OverlayConfig, info: &OverlayInfo) -> Result<()> {
+    if !config.enabled || frame.empty() {
+        return Ok(());
+    }
+
+    // Scale text with the frame so 1080p looks like 480p
+    let scale = config.font_scale * frame.rows() as f64 / 480.0;
+    let thickness = ((scale * 2.0).round() as i32).max(1);
+    let margin = ((10.0 * scale).round() as i32).max(4);
+
+    if config.show_regions {
+        for region in info.regions {
+            draw_region(frame, region, thickness)?;
+        }
+    }
+
+    let mut lines = Vec::new();
+    if config.show_time {
+        lines.push(Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+    }
+    if config.show_camera {
+        lines.push(info.camera_name.to_string());
+    }
+    if config.show_fps {
+        lines.push(format!("{:.1} FPS", info.fps));
+    }
+    if config.show_armed {
+        lines.push(if info.armed { "ARMED" } else { "DISARMED" }.to_string());
+    }
+    if config.show_count {
+        lines.push(format!("Events: {}", info.motion_count));
+    }
+
+    let mut baseline = 0;
+    let line_height = imgproc::get_text_size(
+        "Ag",
+        imgproc::FONT_HERSHEY_SIMPLEX,
+        scale,
+        thickness,
+        &mut baseline,
+    )?
+    .height
+        + baseline
+        + margin / 2;
+
+    let block_height = line_height * lines.len() as i32;
+    let top = match config.position {
+        OverlayPosition::TopLeft | OverlayPosition::TopRight => margin,
+        OverlayPosition::BottomLeft | OverlayPosition::BottomRight => {
+            frame.rows() - margin - block_height
+        }
+    };
+
+    for (i, line) in lines.iter().enumerate() {
+        let size = imgproc::get_text_size(
+            line,
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            scale,
+            thickness,
+            &mut baseline,
+        )?;
+        let x = match config.position {
+            OverlayPosition::TopLeft | OverlayPosition::BottomLeft => margin,
+            OverlayPosition::TopRight | OverlayPosition::BottomRight => {
+                frame.cols() - margin - size.width
+            }
+        };
+        let y = top + line_height * i as i32 + size.height;
+        draw_outlined_text(frame, line, Point::new(x, y), scale, thickness)?;
+    }
+
+    // Flash the banner at 1 Hz while motion is active
+    let flash_on = Local::now().timestamp_subsec_millis() < 500;
+    if config.show_motion_banner && info.motion_detected && flash_on {
+        let banner_scale = scale * 1.5;
+        let banner_thickness = thickness + 1;
+        let size = imgproc::get_text_size(
+            "MOTION",
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            banner_scale,
+            banner_thickness,
+            &mut baseline,
+        )?;
+        let banner = Rect::new(
+            (frame.cols() - size.width) / 2 - margin,
+            frame.rows() - size.height - baseline - margin * 3,
+            size.width + margin * 2,
+            size.height + baseline + margin * 2,
+        );
+        imgproc::rectangle(
+            frame,
+            banner,
+            Scalar::new(0.0, 0.0, 200.0, 0.0),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )?;
+        draw_outlined_text(
+            frame,
+            "MOTION",
+            Point::new(banner.x + margin, banner.y + margin + size.height),
+            banner_scale,
+            banner_thickness,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Green box around a single contour; orange, with the number of contours,
+/// around a region `--merge-gap` joined from several
+#[cfg(feature = "camera")]
+fn draw_region(frame: &mut Mat, region: &Region, thickness: i32) -> Result<()> {
+    let rect = Rect::new(
+        region.x.round() as i32,
+        region.y.round() as i32,
+        region.width.round() as i32,
+        region.height.round() as i32,
+    );
+    let color = if region.is_merged() {
+        Scalar::new(0.0, 165.0, 255.0, 0.0)
+    } else {
+        Scalar::new(0.0, 255.0, 0.0, 0.0)
+    };
+    imgproc::rectangle(frame, rect, color, thickness, imgproc::LINE_8, 0)?;
+    if region.is_merged() {
+        imgproc::put_text(
+            frame,
+            &format!("x{}", region.contours),
+            Point::new(rect.x + thickness * 2, rect.y + rect.height - thickness * 2),
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            0.4 * thickness as f64,
+            color,
+            thickness,
+            imgproc::LINE_8,
+            false,
+        )?;
+    }
+    Ok(())
+}
+
+/// White text over a black outline so it stays readable on any background
+#[cfg(feature = "camera")]
+fn draw_outlined_text(
+    frame: &mut Mat,
+    text: &str,
+    origin: Point,
+    scale: f64,
+    thickness: i32,
+) -> Result<()> {
+    imgproc::put_text(
+        frame,
+        text,
+        origin,
+        imgproc::FONT_HERSHEY_SIMPLEX,
+        scale,
+        Scalar::new(0.0, 0.0, 0.0, 0.0),
+        thickness + 2,
+        imgproc::LINE_AA,
+        false,
+    )?;
+    imgproc::put_text(
+        frame,
+        text,
+        origin,
+        imgproc::FONT_HERSHEY_SIMPLEX,
+        scale,
+        Scalar::new(255.0, 255.0, 255.0, 0.0),
+        thickness,
+        imgproc::LINE_AA,
+        false,
+    )?;
+    Ok(())
+}
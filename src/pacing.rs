@@ -0,0 +1,70 @@
+//! Frame pacing (`--target-fps`). By default the loop runs as fast as the
+//! camera delivers, since reading a frame already blocks until the next one
+//! is ready. With a target, each pass sleeps only what is left of its frame
+//! interval after capture and processing, so a slow camera isn't slowed down
+//! further and a fast one is throttled to the rate asked for.
+
+// Without the `camera` feature only the flag check uses this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use std::time::{Duration, Instant};
+
+/// `--target-fps` is positive and finite
+pub fn target_fps_problem(fps: f64) -> Option<String> {
+    if fps > 0.0 && fps.is_finite() {
+        None
+    } else {
+        Some(format!("target FPS must be above 0, got {}", fps))
+    }
+}
+
+/// Sleeps out the rest of each frame interval
+#[derive(Clone, Debug, Default)]
+pub struct FramePacer {
+    target: Option<f64>,
+    /// When the current frame's interval started
+    started: Option<Instant>,
+}
+
+impl FramePacer {
+    /// `None` follows the camera without sleeping
+    pub fn new(target: Option<f64>) -> Self {
+        Self {
+            target,
+            started: None,
+        }
+    }
+
+    pub fn target(&self) -> Option<f64> {
+        self.target
+    }
+
+    pub fn set_target(&mut self, target: Option<f64>) {
+        self.target = target;
+        self.started = None;
+    }
+
+    /// Time left of the frame interval when a pass ends at `now`. A pass
+    /// that overran starts the next interval at `now`, so a slow frame isn't
+    /// made up for with a burst of unpaced ones.
+    pub fn remaining(&mut self, now: Instant) -> Duration {
+        let Some(target) = self.target else {
+            return Duration::ZERO;
+        };
+        let interval = Duration::from_secs_f64(1.0 / target);
+        let remaining = match self.started {
+            Some(started) => (started + interval).saturating_duration_since(now),
+            None => Duration::ZERO,
+        };
+        self.started = Some(now + remaining);
+        remaining
+    }
+
+    /// Sleep until the next frame is due
+    pub fn wait(&mut self) {
+        let remaining = self.remaining(Instant::now());
+        if !remaining.is_zero() {
+            std::thread::sleep(remaining);
+        }
+    }
+}
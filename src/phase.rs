@@ -0,0 +1,88 @@
+//! The detector's phase: opening the camera, filling the frame history,
+//! comparing frames, paused, stopped or failed. The GUI status panel shows
+//! it, and `--verbose` logs each change.
+
+use std::fmt;
+
+/// What the detector is doing
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub enum DetectorPhase {
+    /// Not started, or stopped by hand
+    #[default]
+    Stopped,
+    /// Opening the camera or starting the detector thread
+    Initializing,
+    /// Filling the frame history; nothing is compared until it is full
+    WarmingUp { frames_left: usize },
+    /// Comparing frames and raising events
+    Running,
+    /// Frames are read but raise no events
+    Paused(PauseReason),
+    /// Stopped on a problem retrying won't fix
+    Error(String),
+}
+
+/// Why a running detector raises no events
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub enum PauseReason {
+    /// Outside the `[schedule]` windows
+    Schedule,
+    /// The camera is being moved
+    Repositioning,
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl DetectorPhase {
+    /// The phase of a detector that is reading frames
+    pub fn of_running(frames_left: usize, repositioning: bool, armed: bool) -> Self {
+        if repositioning {
+            DetectorPhase::Paused(PauseReason::Repositioning)
+        } else if frames_left > 0 {
+            DetectorPhase::WarmingUp { frames_left }
+        } else if !armed {
+            DetectorPhase::Paused(PauseReason::Schedule)
+        } else {
+            DetectorPhase::Running
+        }
+    }
+
+    /// Reading frames, whether or not they raise events
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            DetectorPhase::WarmingUp { .. } | DetectorPhase::Running | DetectorPhase::Paused(_)
+        )
+    }
+
+    /// Whether `other` is the same phase, with the warm-up countdown
+    /// ignored, so logging changes doesn't log every frame of it
+    pub fn same_stage(&self, other: &DetectorPhase) -> bool {
+        match (self, other) {
+            (DetectorPhase::WarmingUp { .. }, DetectorPhase::WarmingUp { .. }) => true,
+            _ => self == other,
+        }
+    }
+}
+
+impl fmt::Display for DetectorPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DetectorPhase::Stopped => write!(f, "Stopped"),
+            DetectorPhase::Initializing => write!(f, "Starting..."),
+            DetectorPhase::WarmingUp { frames_left: 1 } => write!(f, "Warming up: 1 frame left"),
+            DetectorPhase::WarmingUp { frames_left } => {
+                write!(f, "Warming up: {} frames left", frames_left)
+            }
+            DetectorPhase::Running => write!(f, "Running"),
+            DetectorPhase::Paused(PauseReason::Schedule) => {
+                write!(f, "Paused: outside the schedule")
+            }
+            DetectorPhase::Paused(PauseReason::Repositioning) => {
+                write!(f, "Paused: camera being repositioned")
+            }
+            DetectorPhase::Error(e) => write!(f, "Error: {}", e),
+        }
+    }
+}
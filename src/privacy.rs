@@ -0,0 +1,405 @@
+//! Privacy regions: fixed areas, such as a neighbor's window, blurred or
+//! blacked out in every image that leaves the detector (snapshots, clips,
+//! the GUI preview). Detection still sees the whole frame.
+//!
+//! `--privacy-blur` also blurs what moves through the picture: faces found
+//! by an OpenCV Haar cascade, or every motion box.
+
+// Without the `camera` feature nothing is redacted
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use crate::config::SnapshotConfig;
+#[cfg(feature = "camera")]
+use crate::regions::Region;
+#[cfg(feature = "camera")]
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+#[cfg(feature = "camera")]
+use opencv::{
+    core::{Mat, Rect, Scalar, Size, Vector},
+    imgproc,
+    objdetect::CascadeClassifier,
+    prelude::*,
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// `--privacy-region x,y,w,h`, in pixels of the snapshot frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "[u32; 4]")]
+pub struct PrivacyRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TryFrom<[u32; 4]> for PrivacyRegion {
+    type Error = String;
+
+    fn try_from([x, y, width, height]: [u32; 4]) -> Result<Self, String> {
+        if width == 0 || height == 0 {
+            return Err(format!(
+                "privacy region {},{},{},{} must have a positive width and height",
+                x, y, width, height
+            ));
+        }
+        Ok(Self {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+impl FromStr for PrivacyRegion {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let numbers: Vec<u32> = text
+            .split(',')
+            .map(|part| part.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("expected x,y,w,h in pixels, got \"{}\"", text))?;
+        let rect: [u32; 4] = numbers
+            .try_into()
+            .map_err(|_| format!("expected x,y,w,h in pixels, got \"{}\"", text))?;
+        Self::try_from(rect)
+    }
+}
+
+impl PrivacyRegion {
+    /// The part inside a `width`x`height` frame whose coordinates are
+    /// `scale` times the snapshot frame's, as x, y, width, height; `None`
+    /// if it lies entirely outside
+    pub fn clip(&self, (width, height): (i32, i32), scale: f64) -> Option<(i32, i32, i32, i32)> {
+        let x0 = (self.x as f64 * scale).floor().min(width as f64) as i32;
+        let y0 = (self.y as f64 * scale).floor().min(height as f64) as i32;
+        let x1 = ((self.x + self.width) as f64 * scale)
+            .ceil()
+            .min(width as f64) as i32;
+        let y1 = ((self.y + self.height) as f64 * scale)
+            .ceil()
+            .min(height as f64) as i32;
+        (x1 > x0 && y1 > y0).then_some((x0, y0, x1 - x0, y1 - y0))
+    }
+}
+
+/// How a privacy region is obscured
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivacyStyle {
+    /// A blur too heavy to make out faces or text
+    #[default]
+    Blur,
+    /// A solid black box
+    Black,
+}
+
+/// Applies the privacy regions of `[snapshots]` to outgoing frames
+#[derive(Clone, Debug, Default)]
+pub struct Redactor {
+    regions: Vec<PrivacyRegion>,
+    style: PrivacyStyle,
+    /// Regions outside the frame were reported
+    warned: bool,
+}
+
+impl Redactor {
+    pub fn from_config(config: &SnapshotConfig) -> Self {
+        Self {
+            regions: config.privacy_regions.clone(),
+            style: config.privacy_style,
+            warned: false,
+        }
+    }
+
+    /// Obscure the regions in `frame`, a snapshot frame shrunk by `scale`
+    /// (1.0 for the snapshot frame itself)
+    #[cfg(feature = "camera")]
+    pub fn apply(&mut self, frame: &mut Mat, scale: f64) -> opencv::Result<()> {
+        if self.regions.is_empty() || frame.empty() {
+            return Ok(());
+        }
+        let size = (frame.cols(), frame.rows());
+        for region in &self.regions {
+            let Some((x, y, width, height)) = region.clip(size, scale) else {
+                if !self.warned {
+                    eprintln!(
+                        "Warning: privacy region {},{},{},{} lies outside the {}x{} frame",
+                        region.x, region.y, region.width, region.height, size.0, size.1
+                    );
+                }
+                continue;
+            };
+            let rect = Rect::new(x, y, width, height);
+            match self.style {
+                PrivacyStyle::Black => imgproc::rectangle(
+                    frame,
+                    rect,
+                    Scalar::all(0.0),
+                    imgproc::FILLED,
+                    imgproc::LINE_8,
+                    0,
+                )?,
+                PrivacyStyle::Blur => blur(frame, rect)?,
+            }
+        }
+        self.warned = true;
+        Ok(())
+    }
+}
+
+/// What `--privacy-blur` hides on top of the fixed privacy regions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PrivacyBlur {
+    #[default]
+    Off,
+    /// Faces found by a Haar cascade (`--face-cascade`)
+    Faces,
+    /// Every motion box of the frame
+    AllMotion,
+}
+
+/// Where distributions install OpenCV's frontal face cascade
+pub const CASCADE_PATHS: [&str; 4] = [
+    "/usr/share/opencv4/haarcascades/haarcascade_frontalface_default.xml",
+    "/usr/local/share/opencv4/haarcascades/haarcascade_frontalface_default.xml",
+    "/usr/share/opencv/haarcascades/haarcascade_frontalface_default.xml",
+    "/opt/homebrew/share/opencv4/haarcascades/haarcascade_frontalface_default.xml",
+];
+
+/// The `--face-cascade` file, or the first of `CASCADE_PATHS` installed
+pub fn find_cascade(configured: Option<&Path>) -> Option<PathBuf> {
+    match configured {
+        Some(path) => path.is_file().then(|| path.to_path_buf()),
+        None => CASCADE_PATHS
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.is_file()),
+    }
+}
+
+/// `--privacy-blur` settings that can't work: no cascade to find faces
+/// with, or originals kept where the blurred copies go
+pub fn blur_problem(config: &SnapshotConfig, output_dir: &Path) -> Option<String> {
+    if config.privacy_blur == PrivacyBlur::Faces
+        && find_cascade(config.face_cascade.as_deref()).is_none()
+    {
+        return Some(match &config.face_cascade {
+            Some(path) => format!("face cascade {} not found", path.display()),
+            None => format!(
+                "privacy-blur faces needs a face cascade, none found in {}; set face-cascade",
+                CASCADE_PATHS[0]
+            ),
+        });
+    }
+    let keep = config.privacy_keep_original.as_deref()?;
+    if config.privacy_blur == PrivacyBlur::Off {
+        return Some("privacy-keep-original needs privacy-blur faces or all-motion".to_string());
+    }
+    (keep == output_dir).then(|| {
+        format!(
+            "privacy-keep-original must be a separate directory, not the output directory {}",
+            output_dir.display()
+        )
+    })
+}
+
+/// Write `bytes` as `name` under `dir` for `--privacy-keep-original`.
+/// On Unix the directory is made private to the current user and the file
+/// readable only by them.
+pub fn write_private(dir: &Path, name: &str, bytes: &[u8]) -> std::io::Result<PathBuf> {
+    let path = dir.join(name);
+    std::fs::create_dir_all(path.parent().unwrap_or(dir))?;
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&path)?
+            .write_all(bytes)?;
+    }
+    #[cfg(not(unix))]
+    std::fs::write(&path, bytes)?;
+    Ok(path)
+}
+
+/// A box grown by `margin` of its size on every side and clipped to a
+/// `width`x`height` frame, as x, y, width, height; `None` if nothing is
+/// left. Faces are grown so hair and chin are covered too.
+pub fn cover(
+    (x, y, width, height): (f64, f64, f64, f64),
+    margin: f64,
+    (frame_width, frame_height): (i32, i32),
+) -> Option<(i32, i32, i32, i32)> {
+    let x0 = (x - width * margin).floor().max(0.0) as i32;
+    let y0 = (y - height * margin).floor().max(0.0) as i32;
+    let x1 = ((x + width * (1.0 + margin)).ceil() as i32).min(frame_width);
+    let y1 = ((y + height * (1.0 + margin)).ceil() as i32).min(frame_height);
+    (x1 > x0 && y1 > y0).then_some((x0, y0, x1 - x0, y1 - y0))
+}
+
+/// Share of a face box added on each side
+const FACE_MARGIN: f64 = 0.15;
+/// Frames are shrunk to this width before looking for faces, which keeps
+/// the cascade affordable on every frame
+#[cfg(feature = "camera")]
+const FACE_SEARCH_WIDTH: i32 = 640;
+
+/// Applies `--privacy-blur` to outgoing frames
+#[cfg(feature = "camera")]
+pub struct PrivacyFilter {
+    /// Set for `faces`
+    faces: Option<CascadeClassifier>,
+}
+
+#[cfg(feature = "camera")]
+impl PrivacyFilter {
+    /// `None` when `privacy_blur` is off
+    pub fn from_config(config: &SnapshotConfig) -> Result<Option<Self>> {
+        let faces = match config.privacy_blur {
+            PrivacyBlur::Off => return Ok(None),
+            PrivacyBlur::AllMotion => None,
+            PrivacyBlur::Faces => {
+                let path = find_cascade(config.face_cascade.as_deref())
+                    .context("No face cascade found for --privacy-blur faces")?;
+                let classifier = CascadeClassifier::new(&path.to_string_lossy())
+                    .with_context(|| format!("Failed to load {}", path.display()))?;
+                if classifier.empty()? {
+                    anyhow::bail!("{} is not a cascade classifier", path.display());
+                }
+                Some(classifier)
+            }
+        };
+        Ok(Some(Self { faces }))
+    }
+
+    /// Blur the faces in `frame`, or the `motion` boxes given in pixels of
+    /// a frame `frame`'s size times 1/`scale`. Returns how many areas were
+    /// blurred.
+    pub fn apply(&mut self, frame: &mut Mat, motion: &[Region], scale: f64) -> Result<usize> {
+        if frame.empty() {
+            return Ok(0);
+        }
+        let size = (frame.cols(), frame.rows());
+        let boxes: Vec<(i32, i32, i32, i32)> = match self.faces.as_mut() {
+            Some(classifier) => find_faces(classifier, frame)?,
+            None => motion
+                .iter()
+                .filter_map(|region| {
+                    cover(
+                        (
+                            region.x * scale,
+                            region.y * scale,
+                            region.width * scale,
+                            region.height * scale,
+                        ),
+                        0.0,
+                        size,
+                    )
+                })
+                .collect(),
+        };
+        for &(x, y, width, height) in &boxes {
+            blur(frame, Rect::new(x, y, width, height))?;
+        }
+        Ok(boxes.len())
+    }
+}
+
+/// Faces in `frame` as boxes in its pixels, grown by `FACE_MARGIN`
+#[cfg(feature = "camera")]
+pub fn find_faces(
+    classifier: &mut CascadeClassifier,
+    frame: &Mat,
+) -> Result<Vec<(i32, i32, i32, i32)>> {
+    let gray = crate::detector::to_grayscale(frame)?;
+    let shrink = (FACE_SEARCH_WIDTH as f64 / gray.cols().max(1) as f64).min(1.0);
+    let mut small = Mat::default();
+    imgproc::resize(
+        &gray,
+        &mut small,
+        Size::default(),
+        shrink,
+        shrink,
+        imgproc::INTER_AREA,
+    )?;
+    let mut equalized = Mat::default();
+    imgproc::equalize_hist(&small, &mut equalized)?;
+    let mut found = Vector::<Rect>::new();
+    classifier.detect_multi_scale(
+        &equalized,
+        &mut found,
+        1.1,
+        4,
+        0,
+        Size::new(20, 20),
+        Size::default(),
+    )?;
+    let size = (frame.cols(), frame.rows());
+    Ok(found
+        .iter()
+        .filter_map(|face| {
+            cover(
+                (
+                    face.x as f64 / shrink,
+                    face.y as f64 / shrink,
+                    face.width as f64 / shrink,
+                    face.height as f64 / shrink,
+                ),
+                FACE_MARGIN,
+                size,
+            )
+        })
+        .collect())
+}
+
+/// Blur `rect` beyond recognition. A Gaussian as wide as the region would
+/// cost too much per frame, so the region is shrunk to a few pixels,
+/// blurred there and scaled back up.
+#[cfg(feature = "camera")]
+pub fn blur(frame: &mut Mat, rect: Rect) -> opencv::Result<()> {
+    const CELLS: i32 = 8;
+    let longest = rect.width.max(rect.height);
+    let small_size = Size::new(
+        (rect.width * CELLS / longest).max(1),
+        (rect.height * CELLS / longest).max(1),
+    );
+    let region = Mat::roi(frame, rect)?.try_clone()?;
+    let mut small = Mat::default();
+    imgproc::resize(
+        &region,
+        &mut small,
+        small_size,
+        0.0,
+        0.0,
+        imgproc::INTER_AREA,
+    )?;
+    let mut blurred = Mat::default();
+    imgproc::gaussian_blur(
+        &small,
+        &mut blurred,
+        Size::new(3, 3),
+        0.0,
+        0.0,
+        opencv::core::BORDER_REPLICATE,
+    )?;
+    let mut target = Mat::roi_mut(frame, rect)?;
+    imgproc::resize(
+        &blurred,
+        &mut target,
+        Size::new(rect.width, rect.height),
+        0.0,
+        0.0,
+        imgproc::INTER_LINEAR,
+    )
+}
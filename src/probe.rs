@@ -0,0 +1,76 @@
+//! Probing devices without letting one slow device hold up the rest. A
+//! phantom V4L2 node can take several seconds to fail, so every device is
+//! probed on its own thread and whatever hasn't answered by the deadline
+//! is given up on.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long `--list-cameras` and the hotplug scan wait for one device
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Run `probe` on every item at once and return the results in the order
+/// of `items`, `None` for probes still running after `timeout`. The whole
+/// call takes about as long as the slowest probe, at most `timeout`. A probe
+/// that hangs is left to finish on its own; its result is dropped.
+pub fn probe_all<T, R, F>(items: Vec<T>, timeout: Duration, probe: F) -> Vec<Option<R>>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    let deadline = Instant::now() + timeout;
+    let count = items.len();
+    let probe = Arc::new(probe);
+    let (sender, results) = crossbeam_channel::unbounded();
+    for (index, item) in items.into_iter().enumerate() {
+        let probe = Arc::clone(&probe);
+        let sender = sender.clone();
+        let spawned = thread::Builder::new()
+            .name("probe".to_string())
+            .spawn(move || {
+                // Nobody is listening any more once the deadline has passed
+                let _ = sender.send((index, probe(item)));
+            });
+        if spawned.is_err() {
+            break;
+        }
+    }
+    drop(sender);
+
+    let mut found: Vec<Option<R>> = (0..count).map(|_| None).collect();
+    for _ in 0..count {
+        match results.recv_deadline(deadline) {
+            Ok((index, result)) => found[index] = Some(result),
+            Err(_) => break,
+        }
+    }
+    found
+}
+
+/// Reads of a camera's first frame before giving up (`--initial-frame-retries`)
+pub const INITIAL_FRAME_RETRIES: u32 = 10;
+
+/// Call `read` until it returns a value, retrying up to `retries` times with
+/// `delay` in between, for cameras that hand out empty frames while they warm
+/// up. `on_retry` is told the number of each retry before it is made. `None`
+/// once every attempt came back empty; an error ends the attempts at once.
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn retry_read<T, E>(
+    retries: u32,
+    delay: Duration,
+    mut read: impl FnMut() -> Result<Option<T>, E>,
+    mut on_retry: impl FnMut(u32),
+) -> Result<Option<T>, E> {
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            on_retry(attempt);
+            thread::sleep(delay);
+        }
+        if let Some(value) = read()? {
+            return Ok(Some(value));
+        }
+    }
+    Ok(None)
+}
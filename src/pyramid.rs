@@ -0,0 +1,132 @@
+//! Multi-scale detection (`--pyramid-levels`). Besides the full motion
+//! mask, contours are also found on copies halved once or twice. A large,
+//! slow-moving object only changes along its edges, which leaves scattered
+//! specks at full size; at a coarser level the specks fill whole cells and
+//! join up. Small objects are still found at the full size.
+
+// Without the `camera` feature only the config check uses this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use crate::regions::Region;
+#[cfg(feature = "camera")]
+use crate::scale::FrameScale;
+#[cfg(feature = "camera")]
+use anyhow::Result;
+#[cfg(feature = "camera")]
+use opencv::{
+    core::{self, Mat, Size, Vector},
+    imgproc,
+    prelude::*,
+};
+
+/// Levels including the full-size one; each further level halves the mask
+pub const MAX_LEVELS: u32 = 3;
+
+/// A coarse cell counts as motion when at least this share of the pixels
+/// it covers changed
+pub const CELL_FILL: f64 = 0.25;
+
+/// `pyramid_levels` is between 1 (off) and `MAX_LEVELS`
+pub fn levels_problem(levels: u32) -> Option<String> {
+    (!(1..=MAX_LEVELS).contains(&levels)).then(|| {
+        format!(
+            "pyramid_levels must be between 1 (off) and {}, got {}",
+            MAX_LEVELS, levels
+        )
+    })
+}
+
+/// Union the regions found at each level, full size first, all in the same
+/// pixels. Boxes from different levels that overlap are one object seen at
+/// two scales: they become one box with the larger of their areas, so the
+/// object isn't counted twice.
+pub fn combine(mut levels: Vec<Vec<Region>>) -> Vec<Region> {
+    if levels.is_empty() {
+        return Vec::new();
+    }
+    let mut combined = levels.remove(0);
+    for level in levels {
+        for region in level {
+            let (overlapping, rest): (Vec<Region>, Vec<Region>) = combined
+                .into_iter()
+                .partition(|other| other.gap(&region) == 0.0);
+            combined = rest;
+            let finer_area: f64 = overlapping.iter().map(|other| other.area).sum();
+            let finer_contours: usize = overlapping.iter().map(|other| other.contours).sum();
+            let mut merged = overlapping
+                .iter()
+                .fold(region, |merged, other| merged.union(other));
+            merged.area = finer_area.max(region.area);
+            merged.contours = finer_contours.max(region.contours);
+            combined.push(merged);
+        }
+    }
+    combined
+}
+
+/// Regions in the coarser levels of `thresh`, one list per level from the
+/// second on, in `thresh` pixels. Each level is cleaned like the full-size
+/// mask.
+#[cfg(feature = "camera")]
+pub fn coarse_regions(
+    thresh: &Mat,
+    levels: u32,
+    open_kernel: i32,
+    open_iterations: i32,
+) -> Result<Vec<Vec<Region>>> {
+    let full = (thresh.cols(), thresh.rows());
+    let mut found = Vec::new();
+    for level in 1..levels.min(MAX_LEVELS) {
+        let size = (full.0 >> level, full.1 >> level);
+        if size.0 < 1 || size.1 < 1 {
+            break;
+        }
+        // Averaging the 0/255 mask gives each cell its share of changed pixels
+        let mut shrunk = Mat::default();
+        imgproc::resize(
+            thresh,
+            &mut shrunk,
+            Size::new(size.0, size.1),
+            0.0,
+            0.0,
+            imgproc::INTER_AREA,
+        )?;
+        let mut cells = Mat::default();
+        imgproc::threshold(
+            &shrunk,
+            &mut cells,
+            CELL_FILL * 255.0 - 1.0,
+            255.0,
+            imgproc::THRESH_BINARY,
+        )?;
+        let (_, dilated) = crate::detector::clean_mask(&cells, open_kernel, open_iterations)?;
+
+        let mut contours = Vector::<Vector<core::Point>>::new();
+        imgproc::find_contours(
+            &dilated,
+            &mut contours,
+            imgproc::RETR_EXTERNAL,
+            imgproc::CHAIN_APPROX_SIMPLE,
+            core::Point::new(-1, -1),
+        )?;
+        // Back to full-size pixels
+        let scale = FrameScale {
+            capture: full,
+            process: size,
+        };
+        let mut regions = Vec::new();
+        for contour in &contours {
+            let rect = imgproc::bounding_rect(&contour)?;
+            regions.push(scale.region_to_capture(&Region {
+                x: rect.x as f64,
+                y: rect.y as f64,
+                width: rect.width as f64,
+                height: rect.height as f64,
+                area: imgproc::contour_area(&contour, false)?,
+                contours: 1,
+            }));
+        }
+        found.push(regions);
+    }
+    Ok(found)
+}
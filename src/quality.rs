@@ -0,0 +1,117 @@
+//! Adaptive quality (`--min-fps`). On hardware too slow for the chosen
+//! settings the detector would rather look at a smaller picture than run at
+//! a few frames per second and miss fast motion. While the measured rate
+//! stays below the minimum, detection steps down to a smaller processing
+//! scale; once there is headroom again it steps back up.
+
+// Without the `camera` feature only the config check uses this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use std::time::{Duration, Instant};
+
+/// Each step shrinks the processing frame to this share of the last one
+pub const STEP: f64 = 0.7;
+/// Steps below the configured `process_scale`
+pub const MAX_STEPS: u32 = 4;
+/// How long the rate must stay below the minimum before stepping down
+pub const SLOW_FOR: Duration = Duration::from_secs(5);
+/// How long the rate must stay above the minimum times `HEADROOM` before
+/// stepping back up
+pub const FAST_FOR: Duration = Duration::from_secs(30);
+/// Stepping up makes each frame cost about 1 / STEP² more, so only do it
+/// with at least this much to spare
+pub const HEADROOM: f32 = 2.2;
+
+/// `min_fps` is positive when set
+pub fn min_fps_problem(min_fps: Option<f32>) -> Option<String> {
+    match min_fps {
+        Some(fps) if !(fps > 0.0 && fps.is_finite()) => {
+            Some(format!("min_fps must be above 0, got {}", fps))
+        }
+        _ => None,
+    }
+}
+
+/// A change of processing scale
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Adjustment {
+    pub from: f64,
+    pub to: f64,
+    /// The rate that prompted it
+    pub fps: f32,
+}
+
+impl Adjustment {
+    pub fn message(&self) -> String {
+        let direction = if self.to < self.from { "down" } else { "up" };
+        format!(
+            "QUALITY {} at {:.0} FPS: processing at {:.0}% (was {:.0}%)",
+            direction,
+            self.fps,
+            self.to * 100.0,
+            self.from * 100.0
+        )
+    }
+}
+
+/// Picks the processing scale from the measured frame rate
+#[derive(Clone, Debug)]
+pub struct QualityController {
+    min_fps: f32,
+    /// The configured `process_scale`, never exceeded
+    base: f64,
+    steps: u32,
+    /// Since when the rate has been below the minimum, or above it with headroom
+    slow_since: Option<Instant>,
+    fast_since: Option<Instant>,
+}
+
+impl QualityController {
+    pub fn new(min_fps: f32, base: f64) -> Self {
+        Self {
+            min_fps,
+            base,
+            steps: 0,
+            slow_since: None,
+            fast_since: None,
+        }
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.base * STEP.powi(self.steps as i32)
+    }
+
+    /// Take a rate measured at `now`; returns the change of scale, if any
+    pub fn update(&mut self, now: Instant, fps: f32) -> Option<Adjustment> {
+        if fps < self.min_fps {
+            self.fast_since = None;
+            let since = *self.slow_since.get_or_insert(now);
+            if now.duration_since(since) >= SLOW_FOR && self.steps < MAX_STEPS {
+                return Some(self.step(fps, 1));
+            }
+        } else if fps >= self.min_fps * HEADROOM {
+            self.slow_since = None;
+            let since = *self.fast_since.get_or_insert(now);
+            if now.duration_since(since) >= FAST_FOR && self.steps > 0 {
+                return Some(self.step(fps, -1));
+            }
+        } else {
+            self.slow_since = None;
+            self.fast_since = None;
+        }
+        None
+    }
+
+    /// Move `by` steps; the new scale gets a full window before the next one
+    fn step(&mut self, fps: f32, by: i32) -> Adjustment {
+        let from = self.scale();
+        self.steps = self.steps.saturating_add_signed(by);
+        self.slow_since = None;
+        self.fast_since = None;
+        Adjustment {
+            from,
+            to: self.scale(),
+            fps,
+        }
+    }
+}
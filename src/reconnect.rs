@@ -0,0 +1,104 @@
+//! Recovering from a camera that stops delivering frames, e.g. a USB cable
+//! pulled mid-run. A few failed reads in a row reopen the device; with
+//! `--reconnect-attempts` the run gives up once that many reopens in a row
+//! haven't brought frames back.
+//!
+//! The scene may have changed while the camera was away, and a freshly
+//! opened camera's exposure takes a few frames to settle, so comparing with
+//! the frames from before the loss would fire a false event. A reconnect
+//! drops the old background instead: the first `SETTLE_FRAMES` frames
+//! afterwards are skipped, the next one becomes the new background, and the
+//! camera is reported as reconnected rather than as motion.
+
+// Without the `camera` feature only the tests use this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+/// Failed reads in a row before the camera counts as lost, about 5 seconds
+/// with the 1 second pause after each failure
+pub const FAILURES_BEFORE_RECONNECT: u32 = 5;
+
+/// Frames skipped after a reconnect while the camera's exposure settles
+pub const SETTLE_FRAMES: usize = 3;
+
+/// What to do after a failed read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// Try the next frame
+    Retry,
+    /// Reopen the device
+    Reconnect,
+    /// Every allowed reconnect has failed; the camera is gone
+    GiveUp,
+}
+
+/// Counts failed reads and reconnects since the last good frame
+#[derive(Clone, Debug, Default)]
+pub struct Reconnects {
+    /// `None` reconnects for as long as it takes
+    limit: Option<u32>,
+    failures: u32,
+    attempts: u32,
+}
+
+impl Reconnects {
+    pub fn new(limit: Option<u32>) -> Self {
+        Self {
+            limit,
+            ..Self::default()
+        }
+    }
+
+    /// Reconnects tried since the last good frame
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// A frame arrived, so the camera is healthy again. True when it is
+    /// the first since a reconnect.
+    pub fn success(&mut self) -> bool {
+        let reconnected = self.attempts > 0;
+        self.failures = 0;
+        self.attempts = 0;
+        reconnected
+    }
+
+    pub fn failure(&mut self) -> Recovery {
+        self.failures += 1;
+        if self.failures < FAILURES_BEFORE_RECONNECT {
+            return Recovery::Retry;
+        }
+        if self.limit.is_some_and(|limit| self.attempts >= limit) {
+            return Recovery::GiveUp;
+        }
+        self.failures = 0;
+        self.attempts += 1;
+        Recovery::Reconnect
+    }
+}
+
+/// Counts down the frames skipped after a reconnect
+#[derive(Clone, Debug, Default)]
+pub struct Settle {
+    frames_left: usize,
+}
+
+impl Settle {
+    /// The camera was just reopened
+    pub fn start(&mut self) {
+        self.frames_left = SETTLE_FRAMES;
+    }
+
+    /// Whether to skip the frame that just arrived
+    pub fn skip(&mut self) -> bool {
+        if self.frames_left == 0 {
+            return false;
+        }
+        self.frames_left -= 1;
+        true
+    }
+
+    /// Frames still to skip
+    pub fn frames_left(&self) -> usize {
+        self.frames_left
+    }
+}
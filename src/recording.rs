@@ -0,0 +1,524 @@
+// Without the `camera` feature only the config and tests use the encoder table
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+#[cfg(feature = "camera")]
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+#[cfg(feature = "camera")]
+use opencv::{core::Mat, core::Size, prelude::*, videoio};
+use serde::Deserialize;
+use std::ops::Range;
+#[cfg(feature = "camera")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+#[cfg(feature = "camera")]
+use std::time::Instant;
+
+/// Codec asked for with `--record-codec`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordCodec {
+    /// The first hardware H.264 or HEVC encoder that works, else MJPEG
+    #[default]
+    Auto,
+    H264,
+    Hevc,
+    Mjpeg,
+}
+
+/// Incident clips, read from the `[recording]` config section
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RecordingConfig {
+    /// Save a video clip of each incident (`--record`)
+    pub enabled: bool,
+    pub codec: RecordCodec,
+    /// Target bitrate for the GStreamer encoders, in kbit/s. OpenCV's own
+    /// FFmpeg and MJPEG writers pick their own.
+    pub bitrate_kbps: u32,
+    /// Frame rate written into the clip; the detector loop runs at up to 30
+    pub fps: f64,
+    /// Cut each finished clip down to its motion (`--trim-clips`)
+    pub trim: bool,
+    /// Seconds kept before the first and after the last motion frame
+    pub trim_padding: f64,
+    /// Keep the full clip next to the trimmed one (`--keep-untrimmed`)
+    pub keep_untrimmed: bool,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            codec: RecordCodec::Auto,
+            bitrate_kbps: 4000,
+            fps: 15.0,
+            trim: false,
+            trim_padding: 2.0,
+            keep_untrimmed: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EncoderKind {
+    /// A GStreamer pipeline; `{path}` and `{kbps}`/`{bps}` are filled in
+    Pipeline(&'static str),
+    /// OpenCV's FFmpeg writer with this fourcc
+    Ffmpeg([char; 4]),
+}
+
+/// One way of writing a clip
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Encoder {
+    pub name: &'static str,
+    pub codec: RecordCodec,
+    pub extension: &'static str,
+    kind: EncoderKind,
+}
+
+// Raspberry Pi (V4L2 M2M) and Intel (VA-API) hardware encoders, tried first
+const V4L2_H264: Encoder = Encoder {
+    name: "H.264 v4l2h264enc (GStreamer)",
+    codec: RecordCodec::H264,
+    extension: "mp4",
+    kind: EncoderKind::Pipeline(
+        "appsrc ! videoconvert ! video/x-raw,format=I420 ! v4l2h264enc extra-controls=\"controls,video_bitrate={bps}\" ! video/x-h264,level=(string)4 ! h264parse ! mp4mux ! filesink location={path}",
+    ),
+};
+const VAAPI_H264: Encoder = Encoder {
+    name: "H.264 vaapih264enc (GStreamer)",
+    codec: RecordCodec::H264,
+    extension: "mp4",
+    kind: EncoderKind::Pipeline(
+        "appsrc ! videoconvert ! vaapih264enc bitrate={kbps} ! h264parse ! mp4mux ! filesink location={path}",
+    ),
+};
+const VAAPI_HEVC: Encoder = Encoder {
+    name: "HEVC vaapih265enc (GStreamer)",
+    codec: RecordCodec::Hevc,
+    extension: "mp4",
+    kind: EncoderKind::Pipeline(
+        "appsrc ! videoconvert ! vaapih265enc bitrate={kbps} ! h265parse ! mp4mux ! filesink location={path}",
+    ),
+};
+const FFMPEG_H264: Encoder = Encoder {
+    name: "H.264 avc1 (FFmpeg)",
+    codec: RecordCodec::H264,
+    extension: "mp4",
+    kind: EncoderKind::Ffmpeg(['a', 'v', 'c', '1']),
+};
+const FFMPEG_HEVC: Encoder = Encoder {
+    name: "HEVC hvc1 (FFmpeg)",
+    codec: RecordCodec::Hevc,
+    extension: "mp4",
+    kind: EncoderKind::Ffmpeg(['h', 'v', 'c', '1']),
+};
+/// Software fallback that every OpenCV build can write
+const MJPEG: Encoder = Encoder {
+    name: "MJPEG (FFmpeg)",
+    codec: RecordCodec::Mjpeg,
+    extension: "avi",
+    kind: EncoderKind::Ffmpeg(['M', 'J', 'P', 'G']),
+};
+
+/// Encoders to probe for `codec`, best first; MJPEG always comes last
+pub fn candidates(codec: RecordCodec) -> Vec<Encoder> {
+    match codec {
+        RecordCodec::Auto => vec![
+            V4L2_H264,
+            VAAPI_H264,
+            VAAPI_HEVC,
+            FFMPEG_H264,
+            FFMPEG_HEVC,
+            MJPEG,
+        ],
+        RecordCodec::H264 => vec![V4L2_H264, VAAPI_H264, FFMPEG_H264, MJPEG],
+        RecordCodec::Hevc => vec![VAAPI_HEVC, FFMPEG_HEVC, MJPEG],
+        RecordCodec::Mjpeg => vec![MJPEG],
+    }
+}
+
+impl Encoder {
+    /// GStreamer pipeline writing to `path`; `None` for the FFmpeg writers
+    pub fn pipeline(&self, path: &str, bitrate_kbps: u32) -> Option<String> {
+        match self.kind {
+            EncoderKind::Pipeline(template) => Some(
+                template
+                    .replace("{path}", path)
+                    .replace("{kbps}", &bitrate_kbps.to_string())
+                    .replace("{bps}", &(bitrate_kbps as u64 * 1000).to_string()),
+            ),
+            EncoderKind::Ffmpeg(_) => None,
+        }
+    }
+
+    pub fn file_name(&self, incident_id: u64) -> String {
+        format!("motion_{}.{}", incident_id, self.extension)
+    }
+}
+
+/// Frames of a clip to keep: from `padding` frames before the first motion
+/// frame to `padding` after the last. `None` when there is nothing to cut.
+pub fn trim_range(motion: &[bool], padding: usize) -> Option<Range<usize>> {
+    let first = motion.iter().position(|&moving| moving)?;
+    let last = motion.iter().rposition(|&moving| moving)?;
+    let range = first.saturating_sub(padding)..(last + 1 + padding).min(motion.len());
+    (range.len() < motion.len()).then_some(range)
+}
+
+/// The encoder picked at startup and how fast it was in the probe
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Selection {
+    pub encoder: Encoder,
+    pub hardware: bool,
+    pub probe_fps: f64,
+}
+
+impl Selection {
+    pub fn describe(&self) -> String {
+        format!(
+            "{}{}",
+            self.encoder.name,
+            if self.hardware { ", hardware" } else { "" }
+        )
+    }
+}
+
+#[cfg(feature = "camera")]
+fn open_writer(
+    encoder: &Encoder,
+    path: &Path,
+    config: &RecordingConfig,
+    size: Size,
+) -> Result<(videoio::VideoWriter, bool)> {
+    let path = path.to_str().context("clip path is not valid UTF-8")?;
+    let (writer, hardware) = match encoder.kind {
+        EncoderKind::Pipeline(_) => {
+            let pipeline = encoder
+                .pipeline(path, config.bitrate_kbps)
+                .unwrap_or_default();
+            let writer = videoio::VideoWriter::new_with_backend(
+                &pipeline,
+                videoio::CAP_GSTREAMER,
+                0,
+                config.fps,
+                size,
+                true,
+            )?;
+            // Every pipeline here is a hardware encoder
+            (writer, true)
+        }
+        EncoderKind::Ffmpeg([a, b, c, d]) => {
+            let writer = videoio::VideoWriter::new_with_backend(
+                path,
+                videoio::CAP_FFMPEG,
+                videoio::VideoWriter::fourcc(a, b, c, d)?,
+                config.fps,
+                size,
+                true,
+            )?;
+            // Non-zero when FFmpeg picked a hardware encoder by itself
+            let hardware = writer
+                .get(videoio::VIDEOWRITER_PROP_HW_ACCELERATION)
+                .unwrap_or(0.0)
+                > 0.0;
+            (writer, hardware)
+        }
+    };
+    if !writer.is_opened()? {
+        bail!("{} is not available in this OpenCV build", encoder.name);
+    }
+    Ok((writer, hardware))
+}
+
+/// Write one second of frames with `encoder` to a temp file and time it
+#[cfg(feature = "camera")]
+fn probe(encoder: &Encoder, config: &RecordingConfig, frame: &Mat) -> Result<Selection> {
+    let path = std::env::temp_dir().join(format!(
+        ".motion_probe_{}.{}",
+        std::process::id(),
+        encoder.extension
+    ));
+    let result = (|| {
+        let (mut writer, hardware) = open_writer(encoder, &path, config, frame.size()?)?;
+        let frames = config.fps.ceil().max(1.0) as u32;
+        let started = Instant::now();
+        for _ in 0..frames {
+            writer.write(frame)?;
+        }
+        writer.release()?;
+        let elapsed = started.elapsed().as_secs_f64().max(1e-6);
+        let written = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if written == 0 {
+            bail!("{} wrote an empty test clip", encoder.name);
+        }
+        Ok(Selection {
+            encoder: *encoder,
+            hardware,
+            probe_fps: frames as f64 / elapsed,
+        })
+    })();
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// Probe the candidates for `config.codec` against a real camera frame and
+/// keep the first that writes a clip
+#[cfg(feature = "camera")]
+pub fn select_encoder(config: &RecordingConfig, frame: &Mat) -> Result<Selection> {
+    let mut failures = Vec::new();
+    for encoder in candidates(config.codec) {
+        match probe(&encoder, config, frame) {
+            Ok(selection) => {
+                if encoder.codec != config.codec && config.codec != RecordCodec::Auto {
+                    eprintln!(
+                        "Warning: no {:?} encoder works here, recording falls back to {}",
+                        config.codec, encoder.name
+                    );
+                }
+                return Ok(selection);
+            }
+            Err(e) => failures.push(format!("{}: {:#}", encoder.name, e)),
+        }
+    }
+    bail!(
+        "no video encoder works, not even the MJPEG fallback ({}); recording is disabled",
+        failures.join("; ")
+    )
+}
+
+/// A clip cut down by `--trim-clips`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trimmed {
+    pub incident_id: u64,
+    /// The clip to keep: the trimmed one, or the original if trimming failed
+    pub path: PathBuf,
+    pub original: Duration,
+    /// `None` when trimming failed and the original was kept
+    pub trimmed: Option<Duration>,
+}
+
+#[cfg(feature = "camera")]
+struct OpenClip {
+    incident_id: u64,
+    path: PathBuf,
+    writer: videoio::VideoWriter,
+    /// Whether each frame written had motion, for trimming
+    motion: Vec<bool>,
+}
+
+/// Writes the frames of the open incident to `motion_<id>.<ext>`
+#[cfg(feature = "camera")]
+pub struct ClipRecorder {
+    config: RecordingConfig,
+    selection: Option<Selection>,
+    clip: Option<OpenClip>,
+    /// Smoothed frames per second the encoder sustains
+    encode_fps: Option<f64>,
+    /// Clips trimmed on worker threads
+    trimmed: (
+        crossbeam_channel::Sender<Trimmed>,
+        crossbeam_channel::Receiver<Trimmed>,
+    ),
+}
+
+#[cfg(feature = "camera")]
+impl ClipRecorder {
+    /// The encoder is picked on the first frame, once its size is known
+    pub fn new(config: &RecordingConfig) -> Self {
+        Self {
+            config: config.clone(),
+            selection: None,
+            clip: None,
+            encode_fps: None,
+            trimmed: crossbeam_channel::unbounded(),
+        }
+    }
+
+    /// Probe encoders with `frame` unless that was already done
+    pub fn prepare(&mut self, frame: &Mat) -> Result<Selection> {
+        if let Some(selection) = self.selection {
+            return Ok(selection);
+        }
+        let selection = select_encoder(&self.config, frame)?;
+        println!(
+            "Recording with {} ({:.0} FPS in the probe)",
+            selection.describe(),
+            selection.probe_fps
+        );
+        self.selection = Some(selection);
+        Ok(selection)
+    }
+
+    pub fn selection(&self) -> Option<Selection> {
+        self.selection
+    }
+
+    pub fn encode_fps(&self) -> Option<f64> {
+        self.encode_fps
+    }
+
+    /// Start the clip for a new incident, closing any other one first
+    pub fn start(&mut self, incident_id: u64, dir: &Path, frame: &Mat) -> Result<()> {
+        let selection = self.prepare(frame)?;
+        if let Some(id) = self.clip.as_ref().map(|clip| clip.incident_id) {
+            self.finish(id)?;
+        }
+        let path = dir.join(selection.encoder.file_name(incident_id));
+        let (writer, _) = open_writer(&selection.encoder, &path, &self.config, frame.size()?)?;
+        self.clip = Some(OpenClip {
+            incident_id,
+            path,
+            writer,
+            motion: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Append a frame to the open clip, if any; `motion` is whether it had
+    /// motion
+    pub fn write(&mut self, frame: &Mat, motion: bool) -> Result<()> {
+        let Some(clip) = self.clip.as_mut() else {
+            return Ok(());
+        };
+        let started = Instant::now();
+        clip.writer.write(frame)?;
+        clip.motion.push(motion);
+        let fps = 1.0
+            / started
+                .elapsed()
+                .max(Duration::from_micros(1))
+                .as_secs_f64();
+        self.encode_fps = Some(match self.encode_fps {
+            Some(previous) => previous * 0.9 + fps * 0.1,
+            None => fps,
+        });
+        Ok(())
+    }
+
+    /// Close the clip of `incident_id`; `None` when it wasn't being recorded.
+    /// With `--trim-clips` it is then trimmed on a worker thread and reported
+    /// by `take_trimmed`.
+    pub fn finish(&mut self, incident_id: u64) -> Result<Option<PathBuf>> {
+        if self.clip.as_ref().map(|clip| clip.incident_id) != Some(incident_id) {
+            return Ok(None);
+        }
+        let mut clip = self.clip.take().unwrap();
+        clip.writer
+            .release()
+            .with_context(|| format!("Failed to finish {}", clip.path.display()))?;
+        if let (true, Some(selection)) = (self.config.trim, self.selection) {
+            let config = self.config.clone();
+            let sender = self.trimmed.0.clone();
+            let path = clip.path.clone();
+            std::thread::Builder::new()
+                .name("clip-trim".to_string())
+                .spawn(move || {
+                    let _ = sender.send(trim_clip(&selection.encoder, &config, clip));
+                })
+                .context("Failed to start trimming the clip")?;
+            return Ok(Some(path));
+        }
+        Ok(Some(clip.path))
+    }
+
+    /// Whether finished clips are uploaded by `take_trimmed` instead of `finish`
+    pub fn trims(&self) -> bool {
+        self.config.trim
+    }
+
+    /// Clips whose trimming has finished since the last call
+    pub fn take_trimmed(&self) -> Vec<Trimmed> {
+        self.trimmed.1.try_iter().collect()
+    }
+}
+
+/// Cut `clip` down to its motion. On failure the original is kept and the
+/// reason logged.
+#[cfg(feature = "camera")]
+fn trim_clip(encoder: &Encoder, config: &RecordingConfig, clip: OpenClip) -> Trimmed {
+    let seconds = |frames: usize| Duration::from_secs_f64(frames as f64 / config.fps);
+    let original = seconds(clip.motion.len());
+    let padding = (config.trim_padding * config.fps).round() as usize;
+    let untrimmed = Trimmed {
+        incident_id: clip.incident_id,
+        path: clip.path.clone(),
+        original,
+        trimmed: None,
+    };
+    let Some(range) = trim_range(&clip.motion, padding) else {
+        return Trimmed {
+            trimmed: Some(original),
+            ..untrimmed
+        };
+    };
+    let stem = clip
+        .path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let trimmed_path = clip
+        .path
+        .with_file_name(format!("{}_trimmed.{}", stem, encoder.extension));
+    match copy_frames(encoder, config, &clip.path, &trimmed_path, range.clone()) {
+        Ok(()) => {
+            if !config.keep_untrimmed {
+                if let Err(e) = std::fs::remove_file(&clip.path) {
+                    eprintln!("Warning: failed to remove {}: {}", clip.path.display(), e);
+                }
+            }
+            Trimmed {
+                path: trimmed_path,
+                trimmed: Some(seconds(range.len())),
+                ..untrimmed
+            }
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&trimmed_path);
+            eprintln!("Kept {} untrimmed: {:#}", clip.path.display(), e);
+            untrimmed
+        }
+    }
+}
+
+/// Re-encode frames `range` of the clip at `from` into `to`
+#[cfg(feature = "camera")]
+fn copy_frames(
+    encoder: &Encoder,
+    config: &RecordingConfig,
+    from: &Path,
+    to: &Path,
+    range: Range<usize>,
+) -> Result<()> {
+    let source = from.to_str().context("clip path is not valid UTF-8")?;
+    let mut reader = videoio::VideoCapture::from_file(source, videoio::CAP_ANY)?;
+    if !reader.is_opened()? {
+        bail!("this OpenCV build can't read {} clips", encoder.name);
+    }
+    let mut frame = Mat::default();
+    let mut writer = None;
+    for index in 0..range.end {
+        if !reader.read(&mut frame)? || frame.empty() {
+            bail!(
+                "the clip ended after {} frames, expected {}",
+                index,
+                range.end
+            );
+        }
+        if index < range.start {
+            continue;
+        }
+        if writer.is_none() {
+            writer = Some(open_writer(encoder, to, config, frame.size()?)?.0);
+        }
+        if let Some(writer) = writer.as_mut() {
+            writer.write(&frame)?;
+        }
+    }
+    if let Some(mut writer) = writer {
+        writer.release()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,321 @@
+use crate::tracker::Detection;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Bounding box of one or more motion contours, in frame pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Summed contour area, not the box area
+    pub area: f64,
+    /// Contours the region was merged from, 1 for a single one
+    pub contours: usize,
+}
+
+impl Region {
+    /// Distance between the two boxes' nearest edges, 0 when they touch or overlap
+    pub fn gap(&self, other: &Region) -> f64 {
+        let dx = (self.x.max(other.x) - (self.x + self.width).min(other.x + other.width)).max(0.0);
+        let dy =
+            (self.y.max(other.y) - (self.y + self.height).min(other.y + other.height)).max(0.0);
+        dx.hypot(dy)
+    }
+
+    pub fn union(&self, other: &Region) -> Region {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Region {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+            area: self.area + other.area,
+            contours: self.contours + other.contours,
+        }
+    }
+
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn is_merged(&self) -> bool {
+        self.contours > 1
+    }
+
+    /// Width over height of the box: above 1 for wide, below 1 for tall
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn aspect(&self) -> f64 {
+        self.width / self.height.max(1.0)
+    }
+
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn detection(&self) -> Detection {
+        Detection {
+            centroid: (self.x + self.width / 2.0, self.y + self.height / 2.0),
+            area: self.area,
+        }
+    }
+}
+
+/// Width-to-height ratios a contour's box must fall within
+/// (`--min-aspect`, `--max-aspect`), against thin streaks from sensor noise
+/// or cable shadows that are large enough to pass the area test
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AspectRange {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl AspectRange {
+    pub fn is_unbounded(&self) -> bool {
+        self.min.is_none() && self.max.is_none()
+    }
+
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn contains(&self, aspect: f64) -> bool {
+        self.min.is_none_or(|min| aspect >= min) && self.max.is_none_or(|max| aspect <= max)
+    }
+}
+
+/// Both bounds are positive, and the minimum isn't above the maximum
+pub fn aspect_problem(min: Option<f64>, max: Option<f64>) -> Option<String> {
+    for (name, bound) in [("min_aspect", min), ("max_aspect", max)] {
+        if let Some(bound) = bound.filter(|bound| !(*bound > 0.0 && bound.is_finite())) {
+            return Some(format!("{} must be above 0, got {}", name, bound));
+        }
+    }
+    match (min, max) {
+        (Some(min), Some(max)) if min > max => Some(format!(
+            "min_aspect ({}) is above max_aspect ({})",
+            min, max
+        )),
+        _ => None,
+    }
+}
+
+/// How close two motion boxes must be to count as one object
+/// (`--merge-gap`): pixels, or a share of the frame width written as a
+/// percentage like `2%`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MergeGap {
+    Pixels(u32),
+    /// Share of the frame width, 0-1
+    Fraction(f64),
+}
+
+impl Default for MergeGap {
+    fn default() -> Self {
+        MergeGap::Pixels(0)
+    }
+}
+
+impl MergeGap {
+    /// The gap in pixels for a frame `width` pixels wide
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn pixels(&self, width: u32) -> f64 {
+        match *self {
+            MergeGap::Pixels(pixels) => pixels as f64,
+            MergeGap::Fraction(fraction) => fraction * width as f64,
+        }
+    }
+}
+
+impl FromStr for MergeGap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(percent) = s.strip_suffix('%') {
+            let percent: f64 = percent
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid percentage '{}'", s))?;
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(format!("merge gap must be 0-100% of the frame, got {}", s));
+            }
+            return Ok(MergeGap::Fraction(percent / 100.0));
+        }
+        s.parse().map(MergeGap::Pixels).map_err(|_| {
+            format!(
+                "expected pixels like 12 or a percentage like 2%, got '{}'",
+                s
+            )
+        })
+    }
+}
+
+/// Smallest motion region that counts (`--min-area`): pixels, or a share
+/// of the frame area written as a percentage like `0.5%`, which means the
+/// same at any resolution. Both are held against regions in capture pixels,
+/// so neither changes with `--process-scale`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "MinAreaValue", into = "MinAreaValue")]
+pub enum MinArea {
+    Pixels(u32),
+    /// Share of the frame area, 0-1
+    Fraction(f64),
+}
+
+impl Default for MinArea {
+    fn default() -> Self {
+        MinArea::Pixels(crate::config::DEFAULT_MIN_AREA)
+    }
+}
+
+impl MinArea {
+    /// The area in pixels for a frame of `frame_area` pixels; a share of
+    /// the frame never resolves below 1
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn pixels(&self, frame_area: f64) -> u32 {
+        match *self {
+            MinArea::Pixels(pixels) => pixels,
+            MinArea::Fraction(fraction) => ((fraction * frame_area).round() as u32).max(1),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match *self {
+            MinArea::Pixels(pixels) => pixels == 0,
+            MinArea::Fraction(fraction) => fraction <= 0.0,
+        }
+    }
+
+    /// "500 px", or "0.5% (1536 px)" once the frame size is known
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn describe(&self, frame_area: Option<f64>) -> String {
+        match (self, frame_area) {
+            (MinArea::Pixels(pixels), _) => format!("{} px", pixels),
+            (MinArea::Fraction(_), Some(area)) if area > 0.0 => {
+                format!("{} ({} px)", self, self.pixels(area))
+            }
+            (MinArea::Fraction(_), _) => self.to_string(),
+        }
+    }
+}
+
+/// As written on the command line: `500` or `0.5%`
+impl fmt::Display for MinArea {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MinArea::Pixels(pixels) => write!(f, "{}", pixels),
+            // Rounded so 0.07 doesn't print as 7.000000000000001%
+            MinArea::Fraction(fraction) => {
+                write!(f, "{}%", (fraction * 100.0 * 10_000.0).round() / 10_000.0)
+            }
+        }
+    }
+}
+
+impl FromStr for MinArea {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(percent) = s.strip_suffix('%') {
+            let percent: f64 = percent
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid percentage '{}'", s))?;
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(format!("min area must be 0-100% of the frame, got {}", s));
+            }
+            return Ok(MinArea::Fraction(percent / 100.0));
+        }
+        s.parse().map(MinArea::Pixels).map_err(|_| {
+            format!(
+                "expected pixels like 500 or a percentage like 0.5%, got '{}'",
+                s
+            )
+        })
+    }
+}
+
+/// `min_area` in the config and the session journal: a plain number of
+/// pixels, as before percentages existed, or a string like "0.5%"
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum MinAreaValue {
+    Pixels(u32),
+    Text(String),
+}
+
+impl TryFrom<MinAreaValue> for MinArea {
+    type Error = String;
+
+    fn try_from(value: MinAreaValue) -> Result<Self, Self::Error> {
+        match value {
+            MinAreaValue::Pixels(pixels) => Ok(MinArea::Pixels(pixels)),
+            MinAreaValue::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl From<MinArea> for MinAreaValue {
+    fn from(area: MinArea) -> Self {
+        match area {
+            MinArea::Pixels(pixels) => MinAreaValue::Pixels(pixels),
+            MinArea::Fraction(_) => MinAreaValue::Text(area.to_string()),
+        }
+    }
+}
+
+/// Merge regions whose boxes overlap or lie within `distance` pixels of each
+/// other, so an object split into several contours yields one region. Boxes
+/// chain: if A is near B and B near C, all three become one region.
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn merge_regions(regions: Vec<Region>, distance: f64) -> Vec<Region> {
+    // Union-find: boxes within the gap of each other share a root
+    let mut parent: Vec<usize> = (0..regions.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for i in 0..regions.len() {
+        for j in i + 1..regions.len() {
+            if regions[i].gap(&regions[j]) <= distance {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[b] = a;
+            }
+        }
+    }
+
+    let mut clusters: Vec<(usize, Region)> = Vec::new();
+    for (i, region) in regions.iter().enumerate() {
+        let cluster = root(&mut parent, i);
+        match clusters.iter_mut().find(|(root, _)| *root == cluster) {
+            Some((_, merged)) => *merged = merged.union(region),
+            None => clusters.push((cluster, *region)),
+        }
+    }
+    clusters.into_iter().map(|(_, region)| region).collect()
+}
+
+/// How much of the frame the largest motion region covers (0-1), and the
+/// share `min_area` stands for. A frame has motion when the score is above
+/// the threshold.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct MotionScore {
+    pub score: f64,
+    pub threshold: f64,
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl MotionScore {
+    pub fn new(regions: &[Region], min_area: u32, frame_area: f64) -> Self {
+        let frame_area = frame_area.max(1.0);
+        let largest = regions.iter().map(|region| region.area).fold(0.0, f64::max);
+        Self {
+            score: largest / frame_area,
+            threshold: min_area as f64 / frame_area,
+        }
+    }
+
+    pub fn triggered(&self) -> bool {
+        self.score > self.threshold
+    }
+}
@@ -0,0 +1,193 @@
+//! Pausing detection while the camera is moved. Repositioning starts on
+//! request (GUI button, SIGUSR2) or when most of the frame changes for
+//! several frames in a row, and ends on request or once the scene has been
+//! still for a while. The reference frames are rebuilt before detection
+//! resumes, so the new view isn't compared with the old one.
+
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Automatic detection, read from the `[reposition]` config section
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RepositionConfig {
+    /// Start repositioning on its own when the whole view changes
+    pub auto: bool,
+    /// Fraction of the frame (0-1) that must change to count as a scene change
+    pub scene_change: f64,
+    /// Consecutive scene-change frames before repositioning starts
+    pub frames: u32,
+    /// Seconds the view must stay still before detection resumes
+    pub settle_secs: f64,
+}
+
+impl Default for RepositionConfig {
+    fn default() -> Self {
+        Self {
+            auto: true,
+            scene_change: 0.6,
+            frames: 5,
+            settle_secs: 3.0,
+        }
+    }
+}
+
+impl RepositionConfig {
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !(self.scene_change > 0.0 && self.scene_change <= 1.0) {
+            problems.push(format!(
+                "scene_change must be above 0 and at most 1, got {}",
+                self.scene_change
+            ));
+        }
+        if self.frames == 0 {
+            problems.push("frames must be at least 1".to_string());
+        }
+        if !(self.settle_secs >= 0.0 && self.settle_secs.is_finite()) {
+            problems.push(format!(
+                "settle_secs must not be negative, got {}",
+                self.settle_secs
+            ));
+        }
+        problems
+    }
+}
+
+/// Why repositioning started
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub enum Trigger {
+    Manual,
+    SceneChange,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub enum Transition {
+    Started(Trigger),
+    /// Detection resumed after this long
+    Ended(Duration),
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl Transition {
+    /// Line for the console and the GUI's activity log
+    pub fn message(&self) -> String {
+        match self {
+            Transition::Started(Trigger::Manual) => "REPOSITIONING: detection paused".to_string(),
+            Transition::Started(Trigger::SceneChange) => {
+                "REPOSITIONING: the whole view changed, detection paused".to_string()
+            }
+            Transition::Ended(paused) => format!(
+                "REPOSITIONING ended after {}, background rebuilt",
+                crate::humanize::humanize_duration(*paused)
+            ),
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct Repositioning {
+    config: RepositionConfig,
+    started: Option<Instant>,
+    /// Consecutive frames over `scene_change`
+    streak: u32,
+    /// The view has changed since repositioning started. A manual pause
+    /// doesn't end on its own before the camera has actually moved.
+    moved: bool,
+    still_since: Option<Instant>,
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl Repositioning {
+    /// Changed fraction below which the view counts as still
+    const STILL: f64 = 0.01;
+
+    pub fn new(config: RepositionConfig) -> Self {
+        Self {
+            config,
+            started: None,
+            streak: 0,
+            moved: false,
+            still_since: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Manual start or stop
+    pub fn toggle(&mut self, now: Instant) -> Transition {
+        match self.started.take() {
+            Some(started) => self.end(now, started),
+            None => self.start(now, Trigger::Manual),
+        }
+    }
+
+    /// Feed the fraction of the frame (0-1) that changed since the reference
+    pub fn update(&mut self, now: Instant, changed: f64) -> Option<Transition> {
+        let Some(started) = self.started else {
+            if !self.config.auto {
+                return None;
+            }
+            self.streak = if changed >= self.config.scene_change {
+                self.streak + 1
+            } else {
+                0
+            };
+            return (self.streak >= self.config.frames)
+                .then(|| self.start(now, Trigger::SceneChange));
+        };
+
+        if changed >= Self::STILL {
+            self.moved = true;
+            self.still_since = None;
+            return None;
+        }
+        if !self.moved {
+            return None;
+        }
+        let still_since = *self.still_since.get_or_insert(now);
+        let settle = Duration::from_secs_f64(self.config.settle_secs);
+        (now.duration_since(still_since) >= settle).then(|| {
+            self.started = None;
+            self.end(now, started)
+        })
+    }
+
+    fn start(&mut self, now: Instant, trigger: Trigger) -> Transition {
+        self.started = Some(now);
+        self.moved = trigger == Trigger::SceneChange;
+        self.streak = 0;
+        self.still_since = None;
+        Transition::Started(trigger)
+    }
+
+    fn end(&mut self, now: Instant, started: Instant) -> Transition {
+        self.streak = 0;
+        self.still_since = None;
+        Transition::Ended(now.duration_since(started))
+    }
+}
+
+/// Set whenever SIGUSR2 arrives; the detector loop toggles repositioning
+/// and clears it
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn signal_flag() -> Arc<AtomicBool> {
+    let flag = Arc::new(AtomicBool::new(false));
+    #[cfg(unix)]
+    if let Err(e) = signal_hook::flag::register(signal_hook::consts::SIGUSR2, flag.clone()) {
+        eprintln!("Warning: SIGUSR2 won't toggle repositioning: {}", e);
+    }
+    flag
+}
+
+/// Whether SIGUSR2 arrived since the last call
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn signalled(flag: &AtomicBool) -> bool {
+    flag.swap(false, Ordering::Relaxed)
+}
@@ -0,0 +1,148 @@
+use crate::tracker::Detection;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Named rectangle in frame pixels, from a `[[zones]]` config entry
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Zone {
+    pub name: String,
+    /// `[x, y, width, height]`
+    pub rect: [f64; 4],
+}
+
+impl Zone {
+    #[cfg_attr(not(feature = "camera"), allow(dead_code))]
+    pub fn contains(&self, (x, y): (f64, f64)) -> bool {
+        let [left, top, width, height] = self.rect;
+        x >= left && x < left + width && y >= top && y < top + height
+    }
+}
+
+/// "Object moved from zone `from` to zone `to` within `within_secs`",
+/// from a `[[rules]]` config entry
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct TransitionRule {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    pub within_secs: f64,
+    /// Ignore objects whose motion area is smaller than this (pixels)
+    #[serde(default)]
+    pub min_area: f64,
+}
+
+/// A rule matched by one tracked object
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuleHit {
+    pub rule: String,
+    pub track_id: u64,
+}
+
+/// Zones without an area and rules that name zones that don't exist
+pub fn problems(zones: &[Zone], rules: &[TransitionRule]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for zone in zones {
+        let [_, _, width, height] = zone.rect;
+        if width <= 0.0 || height <= 0.0 {
+            problems.push(format!(
+                "zone \"{}\" needs a positive width and height",
+                zone.name
+            ));
+        }
+    }
+    for rule in rules {
+        for zone in [&rule.from, &rule.to] {
+            if !zones.iter().any(|z| &z.name == zone) {
+                problems.push(format!(
+                    "rule \"{}\" refers to unknown zone \"{}\"",
+                    rule.name, zone
+                ));
+            }
+        }
+        if rule.within_secs <= 0.0 {
+            problems.push(format!(
+                "rule \"{}\" needs a positive within_secs",
+                rule.name
+            ));
+        }
+    }
+    problems
+}
+
+#[derive(Default)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+struct TrackState {
+    /// Last time the object was inside each zone
+    last_seen: HashMap<String, Instant>,
+    /// Rules that already fired for this object
+    fired: HashSet<usize>,
+}
+
+/// Evaluates transition rules against tracked objects, frame by frame
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct RuleEngine {
+    zones: Vec<Zone>,
+    rules: Vec<TransitionRule>,
+    tracks: HashMap<u64, TrackState>,
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl RuleEngine {
+    pub fn new(zones: Vec<Zone>, rules: Vec<TransitionRule>) -> Self {
+        Self {
+            zones,
+            rules,
+            tracks: HashMap::new(),
+        }
+    }
+
+    /// Feed the objects seen in one frame and the ids of all live tracks.
+    /// Each rule fires at most once per object.
+    pub fn evaluate(
+        &mut self,
+        now: Instant,
+        visible: &[(u64, Detection)],
+        alive: &[u64],
+    ) -> Vec<RuleHit> {
+        // Forget objects the tracker has dropped
+        self.tracks.retain(|id, _| alive.contains(id));
+
+        let mut hits = Vec::new();
+        for (id, detection) in visible {
+            let state = self.tracks.entry(*id).or_default();
+            let inside: Vec<&str> = self
+                .zones
+                .iter()
+                .filter(|zone| zone.contains(detection.centroid))
+                .map(|zone| zone.name.as_str())
+                .collect();
+
+            for (index, rule) in self.rules.iter().enumerate() {
+                if state.fired.contains(&index)
+                    || detection.area < rule.min_area
+                    || !inside.contains(&rule.to.as_str())
+                    // Must have left the start zone, not just overlap both
+                    || inside.contains(&rule.from.as_str())
+                {
+                    continue;
+                }
+                let within = Duration::from_secs_f64(rule.within_secs);
+                if let Some(left) = state.last_seen.get(&rule.from)
+                    && now.duration_since(*left) <= within
+                {
+                    state.fired.insert(index);
+                    hits.push(RuleHit {
+                        rule: rule.name.clone(),
+                        track_id: *id,
+                    });
+                }
+            }
+
+            for zone in inside {
+                state.last_seen.insert(zone.to_string(), now);
+            }
+        }
+        hits
+    }
+}
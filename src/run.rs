@@ -0,0 +1,1518 @@
+//! The detection loops: `run_cli_mode`'s and the GUI's detector thread.
+//! They only see the detector as a `FrameSource`, so builds with the mock
+//! camera run and test the same loops as builds with OpenCV.
+
+use crate::error::DetectorError;
+use crate::source::{ClipSink, EventSnapshot, Frame, FrameSource, SourceBuilder, Tuner};
+use crate::{
+    absence, alerts, audio, clock, config, gpio, gui, hotplug, humanize, incident, mask_editor,
+    monitor, observe, overlay, pacing, phase, reconnect, recording, reposition, schedule, selftest,
+    state, switch, tuning, upload, Args,
+};
+use anyhow::Result;
+use chrono::Local;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The CLI's detection loop, until `--timeout`, `--max-events` or a
+/// camera that stays lost. `builder` opened `detector` and reopens it
+/// after a reconnect.
+pub fn detect_cli<S: FrameSource>(
+    config: &config::Config,
+    args: &Args,
+    builder: S::Builder,
+    mut detector: S,
+) -> Result<()> {
+    detector.observer_mut().set_active(args.observe);
+
+    if args.verbose {
+        println!("Resolution: {}", detector.describe_resolution());
+        let (width, height) = detector.stats().resolution;
+        println!(
+            "Min area: {}",
+            detector
+                .min_area()
+                .describe(Some(width as f64 * height as f64))
+        );
+        println!("Motion detector active. Press Ctrl+C to stop.");
+    }
+
+    // Tags event lines with the `--camera` name, and `--observe`
+    let camera = args
+        .camera
+        .as_deref()
+        .map(|name| format!("[{}] ", name))
+        .unwrap_or_default()
+        + detector.observer().tag();
+    if args.arm {
+        detector.arm();
+        println!(
+            "[{}] {}ARMED on the empty scene: any difference lasting {} s is an intrusion",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            camera,
+            args.intrusion_secs
+        );
+    }
+    let started = Instant::now();
+    let timeout = args.timeout.map(Duration::from_secs);
+    let max_events = args.max_events();
+    let mut incidents = incident::IncidentTracker::new(Duration::from_secs(args.incident_gap))
+        .with_re_alert(args.re_alert()?);
+    let resources = monitor::ResourceMonitor::spawn(Duration::from_secs(2));
+    let mut last_summary = Instant::now();
+    let mut phase = phase::DetectorPhase::Initializing;
+    let mut key_frames = incident::KeyFrame::default();
+    let audio = audio::recorder(&config.audio)?;
+    let mut clips = S::clips(&config.recording);
+    let mut absence = absence::AbsenceMonitor::from_config(&config.absence)?;
+    let mut schedule = schedule::ScheduleClock::from_config(&config.schedule, config.location)?;
+    let mut armed = true;
+    let mut relay = args
+        .gpio()?
+        .filter(|gpio| {
+            detector.observer().allows(observe::Effect::Relay, || {
+                format!("switched the relay on GPIO {}", gpio.pin)
+            })
+        })
+        .as_ref()
+        .and_then(gpio::open);
+    let reposition_signal = reposition::signal_flag();
+    clock::warn_if_unset(Local::now());
+    let mut clock_watch = clock::JumpWatch::new();
+    // Reading a frame blocks until the camera has one, so only a target
+    // rate adds any sleep
+    let mut pacer = pacing::FramePacer::new(args.target_fps);
+    let mut reconnects = reconnect::Reconnects::new(args.reconnect_attempts);
+    let mut state = args
+        .state_file
+        .clone()
+        .filter(|path| {
+            detector.observer().allows(observe::Effect::StateFile, || {
+                format!("updated {}", path.display())
+            })
+        })
+        .map(state::StateFile::open)
+        .transpose()?;
+    if let Some(state) = &state {
+        let previous = state.previous();
+        println!(
+            "Run #{}: {} events and {} frames counted since {}",
+            previous.sessions, previous.motion_count, previous.total_frames, previous.first_started
+        );
+    }
+    report_observed(&camera, detector.observer());
+
+    loop {
+        if let Some(state) = state.as_mut()
+            && let Err(e) = state.update(
+                detector.events().count(),
+                detector.stats().frame_count as u64,
+            )
+        {
+            eprintln!("Warning: {:#}", e);
+        }
+
+        if let Some(closed) = incidents.poll(Instant::now()) {
+            on_incident_closed(
+                &detector,
+                closed.id,
+                (args.incident_summary || closed.followups > 0).then(|| closed.summary()),
+                &mut key_frames,
+                audio.as_ref(),
+                clips.as_mut(),
+                args.flush_on_event,
+            );
+        }
+
+        // A jump of the wall clock only moves the schedule; cooldowns and
+        // the no-motion countdown run on the monotonic clock
+        if let Some(jump) = clock_watch.check(&clock::SystemClock) {
+            println!(
+                "[{}] {}{}",
+                jump.to.format("%Y-%m-%d %H:%M:%S"),
+                camera,
+                jump.message()
+            );
+            if let Some(schedule) = schedule.as_mut() {
+                schedule.reset();
+            }
+        }
+
+        // Outside the [schedule] window frames are still read, but motion
+        // doesn't raise events
+        if let Some(schedule) = schedule.as_mut() {
+            let now = Local::now();
+            if schedule.is_active(now) != armed {
+                armed = !armed;
+                println!(
+                    "[{}] {}{} by schedule",
+                    now.format("%Y-%m-%d %H:%M:%S"),
+                    camera,
+                    if armed { "ARMED" } else { "DISARMED" }
+                );
+            }
+        }
+        detector.events_mut().armed = armed;
+
+        if reposition::signalled(&reposition_signal) {
+            report_reposition(&camera, detector.toggle_repositioning());
+        }
+
+        // The no-motion countdown runs whenever the detector is armed
+        if let Some(alert) = absence
+            .as_mut()
+            .and_then(|absence| absence.tick(Instant::now(), Local::now().time(), armed))
+        {
+            println!(
+                "[{}] {}{}",
+                Local::now().format("%Y-%m-%d %H:%M:%S"),
+                camera,
+                alert.message()
+            );
+        }
+
+        // Periodic status summary
+        if args.verbose && last_summary.elapsed() >= Duration::from_secs(60) {
+            last_summary = Instant::now();
+            let stats = detector.stats();
+            let usage = match resources.latest() {
+                Some(usage) => format!(
+                    "CPU: {:.0}%, RSS: {:.1} MB",
+                    usage.cpu_percent,
+                    usage.rss_mb()
+                ),
+                None => "CPU/RSS: n/a".to_string(),
+            };
+            println!(
+                "[{}] Status: {:.1} FPS, {} events, {}, {:.1} ms/frame",
+                Local::now().format("%Y-%m-%d %H:%M:%S"),
+                stats.current_fps,
+                detector.events().count(),
+                usage,
+                stats.timings.total_ms()
+            );
+            println!("  Phase: {}", phase);
+            println!(
+                "  Event cooldown: {:.1} s",
+                detector.events().cooldown(Instant::now()).as_secs_f64()
+            );
+            if let Some(deduplicated) = stats.snapshots_deduplicated {
+                println!("  Snapshots deduplicated: {}", deduplicated);
+            }
+            if let Some(state) = &state {
+                let totals = state.totals(detector.events().count(), stats.frame_count as u64);
+                println!(
+                    "  All runs: {} events, {} frames since {}",
+                    totals.motion_count, totals.total_frames, totals.first_started
+                );
+            }
+            if let Some(selection) = clips.as_ref().and_then(|clips| clips.selection()) {
+                match clips.as_ref().and_then(|clips| clips.encode_fps()) {
+                    Some(fps) => println!(
+                        "  Recording: {}, encoding at {:.0} FPS",
+                        selection.describe(),
+                        fps
+                    ),
+                    None => println!("  Recording: {}", selection.describe()),
+                }
+            }
+            if let Some(absence) = &absence {
+                println!(
+                    "  No-motion alert in {}",
+                    humanize::humanize_duration(absence.status().remaining)
+                );
+            }
+            println!(
+                "  Capture latency: {:.0} ms ({})",
+                stats.frame_time.latency.as_secs_f64() * 1000.0,
+                stats.frame_time.source.describe()
+            );
+            if stats.grab_latest {
+                println!(
+                    "  Stale frames skipped: {:.1} per cycle ({} total)",
+                    stats.average_frames_skipped, stats.total_frames_skipped
+                );
+            }
+        }
+
+        // Checked before the next frame, so the last event's snapshot and
+        // clip frames are already written
+        let limit_reached = max_events.is_some_and(|max| detector.events().count() >= max);
+        let timed_out = timeout.filter(|&timeout| started.elapsed() >= timeout);
+        if limit_reached || timed_out.is_some() {
+            if let Some(id) = incidents.open_id() {
+                on_incident_closed(
+                    &detector,
+                    id,
+                    None,
+                    &mut key_frames,
+                    audio.as_ref(),
+                    clips.as_mut(),
+                    args.flush_on_event,
+                );
+            }
+            detector.release();
+            if let Some(state) = state.as_mut() {
+                state.flush(
+                    detector.events().count(),
+                    detector.stats().frame_count as u64,
+                )?;
+            }
+            if limit_reached {
+                println!(
+                    "Stopped after {} motion events in {} (--max-events)",
+                    detector.events().count(),
+                    humanize::humanize_duration(started.elapsed())
+                );
+                return Ok(());
+            }
+            if let Some(timeout) = timed_out.filter(|_| detector.events().count() == 0) {
+                return Err(DetectorError::NoMotionTimeout(timeout).into());
+            }
+            println!(
+                "Timeout reached after {} motion events",
+                detector.events().count()
+            );
+            return Ok(());
+        }
+
+        match detector.poll() {
+            Ok(events) => {
+                if reconnects.success() {
+                    println!(
+                        "[{}] {}CAMERA RECONNECTED, rebuilding the background",
+                        Local::now().format("%Y-%m-%d %H:%M:%S"),
+                        camera
+                    );
+                }
+                let stats = detector.stats();
+                let motion_detected = stats.motion_detected;
+                let color_frame = detector.take_frame();
+                if let Some(transition) = stats.reposition_transition {
+                    report_reposition(&camera, transition);
+                }
+                let current = detector.phase();
+                if args.verbose && !current.same_stage(&phase) {
+                    println!(
+                        "[{}] {}{}",
+                        Local::now().format("%Y-%m-%d %H:%M:%S"),
+                        camera,
+                        current
+                    );
+                }
+                phase = current;
+                if let Some(change) = stats.quality_change {
+                    println!(
+                        "[{}] {}{}",
+                        Local::now().format("%Y-%m-%d %H:%M:%S"),
+                        camera,
+                        change.message()
+                    );
+                }
+                if stats.intrusion_alarm {
+                    report_intrusion(&camera, detector.save_intrusion_snapshot(&color_frame));
+                }
+
+                // Encoders are probed on the first frame; without a working
+                // one, --record can't be honoured
+                if let Some(clips) = clips.as_mut()
+                    && let Err(e) = clips.prepare(&color_frame)
+                {
+                    detector.release();
+                    return Err(e);
+                }
+
+                report_background(detector.save_background_if_due());
+
+                if args.verbose && stats.frames_skipped > 0 {
+                    println!("  Skipped {} stale frame(s)", stats.frames_skipped);
+                }
+
+                if args.verbose && stats.aspect_rejected > 0 {
+                    println!(
+                        "  Ignored {} contour(s) outside the aspect range",
+                        stats.aspect_rejected
+                    );
+                }
+                if args.verbose && motion_detected && stats.contours_found > 1 {
+                    println!(
+                        "  {} contour(s) merged into {} region(s)",
+                        stats.contours_found, stats.regions_found
+                    );
+                }
+                if args.verbose && motion_detected {
+                    println!(
+                        "  Score {:.2}% of the frame (threshold {:.2}%)",
+                        stats.score.score * 100.0,
+                        stats.score.threshold * 100.0
+                    );
+                }
+
+                for hit in &stats.rule_hits {
+                    println!(
+                        "[{}] RULE \"{}\" matched by object #{}",
+                        Local::now().format("%Y-%m-%d %H:%M:%S"),
+                        hit.rule,
+                        hit.track_id
+                    );
+                }
+
+                if let Some(active) = relay.as_mut().and_then(|relay| {
+                    relay.update(Instant::now(), !events.is_empty(), motion_detected, armed)
+                }) {
+                    println!(
+                        "[{}] {}RELAY {}",
+                        Local::now().format("%Y-%m-%d %H:%M:%S"),
+                        camera,
+                        if active { "ON" } else { "OFF" }
+                    );
+                }
+                for event in &events {
+                    let timestamp = stats.frame_time.label();
+                    let (update, closed) = incidents.record(stats.frame_time.captured);
+                    if let Some(closed) = closed {
+                        on_incident_closed(
+                            &detector,
+                            closed.id,
+                            (args.incident_summary || closed.followups > 0)
+                                .then(|| closed.summary()),
+                            &mut key_frames,
+                            audio.as_ref(),
+                            clips.as_mut(),
+                            args.flush_on_event,
+                        );
+                    }
+                    // Notify once per incident, later events are only logged
+                    if let incident::IncidentUpdate::Started(id) = update {
+                        start_audio(audio.as_ref(), id, detector.observer());
+                        start_clip(
+                            clips.as_mut(),
+                            id,
+                            detector.output_dir(),
+                            &color_frame,
+                            detector.observer(),
+                        );
+                        println!("[{}] {}NEW INCIDENT #{}", timestamp, camera, id);
+                    }
+                    if let Some(alert) = absence.as_mut().and_then(|absence| absence.motion()) {
+                        println!("[{}] {}{}", timestamp, camera, alert.message());
+                    }
+                    match event.track {
+                        Some(track) => println!(
+                            "[{}] {}MOTION DETECTED! (#{}, track #{}, incident #{})",
+                            timestamp,
+                            camera,
+                            event.number,
+                            track,
+                            update.id()
+                        ),
+                        None => println!(
+                            "[{}] {}MOTION DETECTED! (#{}, incident #{})",
+                            timestamp,
+                            camera,
+                            event.number,
+                            update.id()
+                        ),
+                    }
+                    // The event's snapshot, saved below, goes with it
+                    if let Some(followup) = incidents.follow_up(stats.frame_time.captured) {
+                        let zone = detector.event_context().zone;
+                        println!(
+                            "[{}] {}{}",
+                            timestamp,
+                            camera,
+                            followup.message(zone.as_deref())
+                        );
+                    }
+                }
+
+                // Save one color snapshot per frame that produced events
+                if let Some(last) = events.last() {
+                    match detector.save_event_snapshot(&color_frame, last.number) {
+                        Ok(EventSnapshot::Stored(Some(filename))) => {
+                            println!("  Color snapshot saved: {}", filename);
+                            if args.flush_on_event {
+                                sync_output(Path::new(&filename));
+                            }
+                        }
+                        Ok(EventSnapshot::Duplicate(original)) => {
+                            println!("  Snapshot deduplicated against event #{}", original)
+                        }
+                        _ => {}
+                    }
+                    if let Some(state) = state.as_mut().filter(|_| args.flush_on_event)
+                        && let Err(e) = state.sync(
+                            detector.events().count(),
+                            detector.stats().frame_count as u64,
+                        )
+                    {
+                        eprintln!("Warning: {:#}", e);
+                    }
+                }
+                if let (true, Some(id)) = (motion_detected, incidents.open_id()) {
+                    key_frames.offer(id, stats.motion_area, || color_frame.try_clone())?;
+                }
+                write_clip(clips.as_mut(), &color_frame, motion_detected);
+                report_trimmed(clips.as_ref(), args.flush_on_event, detector.uploader());
+                report_observed(&camera, detector.observer());
+            }
+            Err(e) => {
+                // A format we can't process won't fix itself, report it once
+                if matches!(e.downcast_ref(), Some(DetectorError::UnsupportedFormat(_))) {
+                    detector.release();
+                    return Err(e);
+                }
+                eprintln!("Error detecting motion: {}", e);
+                // Files and synthetic frames don't come back by reopening
+                let recovery = if detector.is_live() {
+                    reconnects.failure()
+                } else {
+                    reconnect::Recovery::Retry
+                };
+                match recovery {
+                    reconnect::Recovery::Retry => {}
+                    reconnect::Recovery::Reconnect => {
+                        println!(
+                            "[{}] {}CAMERA LOST, reconnecting (attempt {})",
+                            Local::now().format("%Y-%m-%d %H:%M:%S"),
+                            camera,
+                            reconnects.attempts()
+                        );
+                        if let Err(e) = detector.reconnect(&builder) {
+                            eprintln!("Reconnect failed: {:#}", e);
+                        }
+                    }
+                    reconnect::Recovery::GiveUp => {
+                        detector.release();
+                        if let Some(state) = state.as_mut() {
+                            state.flush(
+                                detector.events().count(),
+                                detector.stats().frame_count as u64,
+                            )?;
+                        }
+                        return Err(DetectorError::CameraLost(format!(
+                            "no frames after {} reconnect attempt(s) ({})",
+                            reconnects.attempts(),
+                            e
+                        ))
+                        .into());
+                    }
+                }
+                std::thread::sleep(Duration::from_secs(1));
+            }
+        }
+
+        pacer.wait();
+    }
+}
+
+/// Command-line settings the GUI's detector thread needs
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct ThreadOptions {
+    pub incident_gap: Duration,
+    pub state_file: Option<PathBuf>,
+    pub prefer_device: Option<String>,
+    pub gpio: Option<gpio::GpioOptions>,
+    pub flush_on_event: bool,
+    /// Where a mask painted in the GUI is saved
+    pub mask_path: PathBuf,
+    /// `mask_path` is `GUI_MASK_FILE`, not the user's `--mask-file`
+    pub default_mask: bool,
+    /// `--target-fps`, until the GUI changes it
+    pub target_fps: Option<f64>,
+    /// `--arm`: start in intrusion mode
+    pub arm: bool,
+    /// `--re-alert`
+    pub re_alert: Option<Duration>,
+    /// `--observe`, until the GUI goes live
+    pub observe: bool,
+}
+
+/// How often the GUI's detector thread checks free space for snapshots
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// The GUI's detector thread: follows the window's messages and sends back
+/// a `MotionState` per frame, until the window is gone
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn detector_thread<S: FrameSource>(
+    receiver: crossbeam_channel::Receiver<gui::GuiMessage>,
+    sender: crossbeam_channel::Sender<gui::MotionState>,
+    preview_sender: crossbeam_channel::Sender<gui::PreviewFrame>,
+    config: config::Config,
+    mut builder: S::Builder,
+    options: ThreadOptions,
+) -> Result<()> {
+    use gui::{GuiMessage, MotionState};
+    use mask_editor::MaskImage;
+
+    let mut detector = match builder.build() {
+        Ok(det) => det,
+        Err(e) => {
+            eprintln!("ERROR: Failed to initialize detector: {}", e);
+            return Err(e);
+        }
+    };
+    let mut overlay_config = config.overlay.clone();
+    let mut is_running = false;
+    let mut preview_enabled = true;
+    // `None` once a failed switch left no camera open, so any camera picked
+    // next is opened
+    let mut device_index = Some(builder.device_index());
+    let ThreadOptions {
+        incident_gap,
+        state_file,
+        prefer_device,
+        gpio,
+        flush_on_event,
+        mask_path,
+        default_mask,
+        target_fps,
+        arm,
+        re_alert,
+        observe,
+    } = options;
+    if arm {
+        detector.arm();
+    }
+    detector.observer_mut().set_active(observe);
+    let mut pacer = pacing::FramePacer::new(target_fps);
+    let alerts = alerts::AlertSender::new(sender.clone());
+    let mut tuner = S::Tuner::spawn(sender.clone());
+    // The tuning window's trial settings while it is open
+    let mut tune_params: Option<tuning::TuneParams> = None;
+    // The last event's frames, replayed once the tuning window asks. While
+    // the window is open it keeps the event it has.
+    let mut recorded = None;
+    // The GUI keeps reopening a lost camera; each attempt is a notification
+    let mut reconnects = reconnect::Reconnects::new(None);
+    let mut last_disk_check: Option<Instant> = None;
+    // Loaded from a file, so the GUI can show and edit it
+    if let Some(image) = detector.mask_image() {
+        let _ = sender.send(MotionState {
+            mask: Some(image),
+            ..Default::default()
+        });
+    }
+    let mut relay = gpio
+        .as_ref()
+        .filter(|gpio| {
+            detector.observer().allows(observe::Effect::Relay, || {
+                format!("switched the relay on GPIO {}", gpio.pin)
+            })
+        })
+        .and_then(gpio::open);
+    clock::warn_if_unset(Local::now());
+    let mut clock_watch = clock::JumpWatch::new();
+    let mut incidents = incident::IncidentTracker::new(incident_gap).with_re_alert(re_alert);
+    let resources = monitor::ResourceMonitor::spawn(Duration::from_secs(2));
+    let mut absence = absence::AbsenceMonitor::from_config(&config.absence)?;
+    let mut schedule = schedule::ScheduleClock::from_config(&config.schedule, config.location)?;
+    let mut state = state_file
+        .clone()
+        .filter(|path| {
+            detector.observer().allows(observe::Effect::StateFile, || {
+                format!("updated {}", path.display())
+            })
+        })
+        .map(state::StateFile::open)
+        .transpose()?;
+    // This run's totals for the state file; the detector's own counters
+    // restart when the device is switched
+    let mut session_events: u64 = 0;
+    let mut session_frames: u64 = 0;
+    let mut key_frames = incident::KeyFrame::default();
+    let audio = audio::recorder(&config.audio)?;
+    let mut clips = S::clips(&config.recording);
+    // A dropped video file replaces the camera until a device is picked again
+    let mut playing_file: Option<PathBuf> = None;
+    let mut loop_file = false;
+    let hotplug = hotplug::HotplugWatcher::spawn(Duration::from_secs(2));
+    // Latest frame detection ran on, for manual snapshots
+    let mut last_frame: Option<S::Frame> = None;
+    let reposition_signal = reposition::signal_flag();
+    // A manual toggle, reported with the next frame
+    let mut toggled: Option<reposition::Transition> = None;
+    let mut known_cameras: Option<Vec<hotplug::CameraInfo>> = None;
+
+    // Process GUI messages each pass, stopping once the window is gone
+    while let Some(messages) = gui::pending_messages(&receiver) {
+        let mut requested_device = None;
+        let mut requested_file = None;
+        for msg in messages {
+            match msg {
+                GuiMessage::StartDetection => {
+                    println!("DEBUG: Received StartDetection message");
+                    if device_index.is_none() && playing_file.is_none() {
+                        let _ = sender.send(MotionState {
+                            error: Some("No camera is open, pick one to retry".to_string()),
+                            ..Default::default()
+                        });
+                    } else {
+                        // Starting again after a file ended plays it from the top
+                        if detector.file_finished()
+                            && let Err(e) = detector.rewind()
+                        {
+                            eprintln!("Failed to rewind video file: {}", e);
+                        }
+                        is_running = true;
+                    }
+                }
+                GuiMessage::StopDetection => {
+                    println!("DEBUG: Received StopDetection message");
+                    is_running = false;
+                }
+                GuiMessage::UpdateSensitivity(s) => {
+                    detector.set_sensitivity(s);
+                }
+                GuiMessage::UpdateSensitivityCurve(curve) => {
+                    detector.set_sensitivity_curve(curve);
+                }
+                GuiMessage::UpdateMinArea(area) => {
+                    detector.set_min_area(area);
+                }
+                GuiMessage::UpdateOpening(kernel, iterations) => {
+                    detector.set_opening(kernel, iterations);
+                }
+                GuiMessage::UpdateTargetFps(fps) => {
+                    pacer.set_target(Some(fps).filter(|fps| *fps > 0.0));
+                }
+                GuiMessage::UpdateOverlay(config) => {
+                    overlay_config = config;
+                }
+                GuiMessage::SetPreviewEnabled(enabled) => {
+                    preview_enabled = enabled;
+                }
+                GuiMessage::UpdateOutputDir(dir) => match std::fs::create_dir_all(&dir) {
+                    Ok(()) => {
+                        println!("Snapshots will be saved to {}", dir.display());
+                        detector.set_output_dir(dir);
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to create output directory {}: {}", dir.display(), e);
+                    }
+                },
+                GuiMessage::UpdateDevice(device) => {
+                    // Only the newest request counts when several are queued
+                    requested_device = Some(device);
+                    requested_file = None;
+                }
+                GuiMessage::OpenFile(path) => {
+                    requested_file = Some(path);
+                    requested_device = None;
+                }
+                GuiMessage::SetLoopFile(enabled) => {
+                    loop_file = enabled;
+                    detector.set_loop_file(enabled);
+                }
+                GuiMessage::SaveSnapshot => {
+                    // An explicit request, so it is written even with --no-snapshot
+                    let saved = detector.manual_snapshot(is_running, last_frame.as_ref());
+                    match &saved {
+                        Ok(filename) => println!("  Manual color snapshot saved: {}", filename),
+                        Err(e) => eprintln!("Failed to save manual snapshot: {:#}", e),
+                    }
+                    let _ = sender.send(MotionState {
+                        snapshot: Some(saved.map_err(|e| format!("{:#}", e))),
+                        ..Default::default()
+                    });
+                }
+                GuiMessage::UpdateSchedule(update) => {
+                    schedule =
+                        update.map(|update| schedule::ScheduleClock::new(update, config.location));
+                }
+                GuiMessage::RunSelfTest => {
+                    // Checks the camera this thread already holds open
+                    let mut results = vec![selftest::check_camera(
+                        &mut detector,
+                        selftest::CAMERA_FRAMES,
+                    )];
+                    results.extend(selftest::common_checks(detector.output_dir()));
+                    selftest::print_report("Self-test", &results);
+                    let _ = sender.send(MotionState {
+                        self_test: Some(results),
+                        ..Default::default()
+                    });
+                }
+                GuiMessage::ToggleRepositioning => {
+                    toggled = Some(detector.toggle_repositioning());
+                }
+                GuiMessage::Tune(params) => {
+                    tune_params = params;
+                    if let Some(params) = params {
+                        if let Some(recording) = recorded.take() {
+                            tuner.load(recording, params);
+                        } else if tuner.is_loaded() {
+                            tuner.run(params);
+                        }
+                    }
+                }
+                GuiMessage::Arm => {
+                    detector.arm();
+                    println!(
+                        "[{}] ARMED on the empty scene",
+                        Local::now().format("%H:%M:%S")
+                    );
+                }
+                GuiMessage::SetObserve(observe) => {
+                    detector.observer_mut().set_active(observe);
+                    if observe {
+                        // Dropping the relay switches it off
+                        relay = None;
+                        if let Some(mut state) = state.take()
+                            && let Err(e) = state.flush(session_events, session_frames)
+                        {
+                            eprintln!("Warning: {:#}", e);
+                        }
+                        println!(
+                            "[{}] Observe mode: events are reported, nothing is saved, uploaded or switched",
+                            Local::now().format("%H:%M:%S")
+                        );
+                    } else {
+                        if relay.is_none() {
+                            relay = gpio.as_ref().and_then(gpio::open);
+                        }
+                        if state.is_none() {
+                            match state_file.clone().map(state::StateFile::open).transpose() {
+                                Ok(opened) => state = opened,
+                                Err(e) => eprintln!("Warning: {:#}", e),
+                            }
+                        }
+                        println!(
+                            "[{}] Live: events are saved and acted on again",
+                            Local::now().format("%H:%M:%S")
+                        );
+                    }
+                }
+                GuiMessage::Disarm => {
+                    detector.disarm();
+                    println!(
+                        "[{}] Intrusion mode disarmed",
+                        Local::now().format("%H:%M:%S")
+                    );
+                }
+                GuiMessage::UpdateMask(pixels, width, height) => {
+                    let image = MaskImage {
+                        width,
+                        height,
+                        pixels,
+                    };
+                    match detector.set_painted_mask(&image, &mask_path, default_mask) {
+                        // Kept when the device is switched
+                        Ok(()) => builder = builder.mask_file(detector.mask_file()),
+                        Err(e) => eprintln!("Failed to apply the mask: {:#}", e),
+                    }
+                }
+                // Never returned by pending_messages
+                GuiMessage::Shutdown => {}
+            }
+        }
+
+        if reposition::signalled(&reposition_signal) {
+            toggled = Some(detector.toggle_repositioning());
+        }
+        if let Some(transition) = toggled.filter(|_| !is_running) {
+            // Nothing is reported while stopped; don't hold it back for later
+            println!(
+                "[{}] {}",
+                Local::now().format("%H:%M:%S"),
+                transition.message()
+            );
+            toggled = None;
+        }
+
+        if let Some(cameras) = hotplug.as_ref().and_then(|watcher| watcher.changed()) {
+            if let Some(previous) = &known_cameras {
+                let (added, removed) = hotplug::diff(previous, &cameras);
+                for camera in &added {
+                    println!("Camera connected: {}", camera.label());
+                }
+                for camera in &removed {
+                    println!("Camera disconnected: {}", camera.label());
+                }
+                // Stop now instead of waiting for reads from the camera to fail
+                if playing_file.is_none()
+                    && let Some(device) =
+                        device_index.filter(|device| removed.iter().any(|c| c.index == *device))
+                {
+                    is_running = false;
+                    let _ = sender.send(MotionState {
+                        error: Some(format!("Camera {} was disconnected", device)),
+                        ..Default::default()
+                    });
+                }
+                if let Some(preferred) = prefer_device
+                    .as_deref()
+                    .and_then(|pattern| added.iter().find(|c| c.matches(pattern)))
+                {
+                    println!(
+                        "Preferred camera connected, switching to {}",
+                        preferred.label()
+                    );
+                    requested_device = Some(preferred.index);
+                }
+            }
+            let _ = sender.send(MotionState {
+                cameras: Some(cameras.clone()),
+                ..Default::default()
+            });
+            known_cameras = Some(cameras);
+        }
+
+        // Picking the current device again goes back to it from a video file
+        if let Some(device) = requested_device
+            .filter(|device| Some(*device) != device_index || playing_file.is_some())
+        {
+            is_running = false;
+            // Named cameras bring their own zones, rules and detector
+            // settings; keep the ones changed live in the GUI
+            let builder = match config.camera_for_device(device) {
+                Some(camera) => builder.clone().camera_config(&camera.config),
+                None => builder.clone(),
+            };
+            let live = detector.live_settings();
+            let builder = builder.live_settings(&live);
+            let change = switch::switch_device(
+                &mut detector,
+                device_index,
+                device,
+                |device| {
+                    let mut opened = builder.clone().device(device).build()?;
+                    live.restore(&mut opened);
+                    Ok(opened)
+                },
+                S::release,
+                thread::sleep,
+            );
+            if change.succeeded() {
+                println!("{}", change.message());
+            } else {
+                eprintln!("{}", change.message());
+            }
+            // Whatever was open before has been released, even a video file
+            device_index = change.actual;
+            playing_file = None;
+            last_frame = None;
+            let _ = sender.send(MotionState {
+                device_change: Some(change),
+                ..Default::default()
+            });
+        }
+
+        if let Some(path) = requested_file {
+            is_running = false;
+            let live = detector.live_settings();
+            let file_builder = builder
+                .clone()
+                .live_settings(&live)
+                .input_file(Some(path.clone()), loop_file);
+            let reply = match file_builder.build() {
+                Ok(mut file_detector) => {
+                    live.restore(&mut file_detector);
+                    detector.release();
+                    detector = file_detector;
+                    playing_file = Some(path.clone());
+                    last_frame = None;
+                    MotionState {
+                        active_file: Some(path),
+                        ..Default::default()
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to open {}: {:#}", path.display(), e);
+                    MotionState {
+                        error: Some(format!("Failed to open {}: {:#}", path.display(), e)),
+                        ..Default::default()
+                    }
+                }
+            };
+            let _ = sender.send(reply);
+        }
+
+        // A snapshot disk filling up is a notification well before saves fail
+        if last_disk_check.is_none_or(|checked| checked.elapsed() >= DISK_CHECK_INTERVAL) {
+            last_disk_check = Some(Instant::now());
+            let output_dir = detector.output_dir();
+            let disk =
+                selftest::check_disk_space(output_dir, selftest::available_bytes(output_dir));
+            let severity = match disk.status {
+                selftest::CheckStatus::Warn => Some(alerts::Severity::Warning),
+                selftest::CheckStatus::Fail => Some(alerts::Severity::Critical),
+                selftest::CheckStatus::Pass | selftest::CheckStatus::Skip => None,
+            };
+            if let Some(severity) = severity {
+                alerts.raise(alerts::AppAlert::new(
+                    alerts::AlertKind::LowDisk,
+                    severity,
+                    disk.message,
+                ));
+            }
+        }
+
+        // Run detection if active
+        if is_running {
+            let clock_jump = clock_watch.check(&clock::SystemClock);
+            if let Some(jump) = &clock_jump {
+                println!("[{}] {}", jump.to.format("%H:%M:%S"), jump.message());
+                if let Some(schedule) = schedule.as_mut() {
+                    schedule.reset();
+                }
+            }
+            let armed = schedule
+                .as_mut()
+                .is_none_or(|schedule| schedule.is_active(Local::now()));
+            detector.events_mut().armed = armed;
+            match detector.poll() {
+                Ok(events) => {
+                    let reconnected = reconnects.success();
+                    if reconnected && let Some(device) = device_index {
+                        println!("Camera {} reconnected, rebuilding the background", device);
+                    }
+                    let stats = detector.stats();
+                    let motion_detected = stats.motion_detected;
+                    let color_frame = detector.take_frame();
+                    match detector.take_recording() {
+                        Ok(Some(recording)) => match tune_params {
+                            Some(params) if !tuner.is_loaded() => tuner.load(recording, params),
+                            _ => recorded = Some(recording),
+                        },
+                        Ok(None) => {}
+                        Err(e) => {
+                            eprintln!("Warning: could not keep the event for tuning: {:#}", e)
+                        }
+                    }
+                    if let Some(change) = stats.quality_change {
+                        println!("[{}] {}", Local::now().format("%H:%M:%S"), change.message());
+                    }
+                    if stats.intrusion_alarm {
+                        let message =
+                            report_intrusion("", detector.save_intrusion_snapshot(&color_frame));
+                        alerts.raise(alerts::AppAlert::new(
+                            alerts::AlertKind::Intrusion,
+                            alerts::Severity::Critical,
+                            message,
+                        ));
+                    }
+                    // Without a working encoder, recording is turned off
+                    // and detection carries on
+                    if let Some(Err(e)) = clips.as_mut().map(|clips| clips.prepare(&color_frame)) {
+                        eprintln!("Recording disabled: {:#}", e);
+                        clips = None;
+                        let _ = sender.try_send(MotionState {
+                            error: Some(format!("Recording disabled: {:#}", e)),
+                            ..MotionState::default()
+                        });
+                    }
+
+                    report_background(detector.save_background_if_due());
+
+                    // Turn frames into events (same logic as CLI mode) and group them
+                    let now = std::time::Instant::now();
+                    if let Some(closed) = incidents.poll(now) {
+                        on_incident_closed(
+                            &detector,
+                            closed.id,
+                            Some(closed.summary()),
+                            &mut key_frames,
+                            audio.as_ref(),
+                            clips.as_mut(),
+                            flush_on_event,
+                        );
+                    }
+                    if let Some(relay) = relay.as_mut() {
+                        relay.update(now, !events.is_empty(), motion_detected, armed);
+                    }
+                    let mut incident_id = None;
+                    let mut followup = false;
+                    let mut absence_alert = None;
+                    session_frames += 1;
+                    session_events += events.len() as u64;
+                    if let Some(state) = state.as_mut() {
+                        let saved = if flush_on_event && !events.is_empty() {
+                            state.sync(session_events, session_frames)
+                        } else {
+                            state.update(session_events, session_frames)
+                        };
+                        if let Err(e) = saved {
+                            eprintln!("Warning: {:#}", e);
+                        }
+                    }
+                    for _ in &events {
+                        let (update, closed) = incidents.record(stats.frame_time.captured);
+                        if let Some(closed) = closed {
+                            on_incident_closed(
+                                &detector,
+                                closed.id,
+                                Some(closed.summary()),
+                                &mut key_frames,
+                                audio.as_ref(),
+                                clips.as_mut(),
+                                flush_on_event,
+                            );
+                        }
+                        if let incident::IncidentUpdate::Started(id) = update {
+                            start_audio(audio.as_ref(), id, detector.observer());
+                            start_clip(
+                                clips.as_mut(),
+                                id,
+                                detector.output_dir(),
+                                &color_frame,
+                                detector.observer(),
+                            );
+                        }
+                        incident_id = Some(update.id());
+                        if let Some(next) = incidents.follow_up(stats.frame_time.captured) {
+                            let zone = detector.event_context().zone;
+                            println!(
+                                "[{}] {}",
+                                Local::now().format("%H:%M:%S"),
+                                next.message(zone.as_deref())
+                            );
+                            followup = true;
+                        }
+                        if let Some(alert) = absence.as_mut().and_then(|a| a.motion()) {
+                            absence_alert = Some(alert);
+                        }
+                    }
+                    if let Some(alert) = absence
+                        .as_mut()
+                        .and_then(|a| a.tick(now, Local::now().time(), armed))
+                    {
+                        absence_alert = Some(alert);
+                    }
+                    if let Some(alert) = &absence_alert {
+                        println!("[{}] {}", Local::now().format("%H:%M:%S"), alert.message());
+                    }
+
+                    // Save the triggering frame first, before preview work delays it
+                    let mut event_snapshot = None;
+                    if let Some(last) = events.last() {
+                        match detector.save_event_snapshot(&color_frame, last.number) {
+                            Ok(EventSnapshot::Stored(Some(filename))) => {
+                                println!("  Color motion snapshot saved: {}", filename);
+                                if flush_on_event {
+                                    sync_output(Path::new(&filename));
+                                }
+                                event_snapshot = Some(filename);
+                            }
+                            Ok(EventSnapshot::Duplicate(original)) => {
+                                println!("  Snapshot deduplicated against event #{}", original)
+                            }
+                            Ok(EventSnapshot::Stored(None)) => {}
+                            Err(e) => {
+                                eprintln!("Failed to save event snapshot: {:#}", e);
+                                alerts.raise(alerts::AppAlert::new(
+                                    alerts::AlertKind::SnapshotFailed,
+                                    alerts::Severity::Error,
+                                    format!("event #{}: {:#}", last.number, e),
+                                ));
+                            }
+                        }
+                    }
+                    if let (true, Some(id)) = (motion_detected, incidents.open_id())
+                        && let Err(e) =
+                            key_frames.offer(id, stats.motion_area, || color_frame.try_clone())
+                    {
+                        eprintln!("Failed to keep key frame: {}", e);
+                    }
+                    write_clip(clips.as_mut(), &color_frame, motion_detected);
+                    let clip_lengths =
+                        report_trimmed(clips.as_ref(), flush_on_event, detector.uploader())
+                            .into_iter()
+                            .filter_map(|clip| {
+                                Some((clip.incident_id, clip.original, clip.trimmed?))
+                            })
+                            .collect();
+
+                    let reposition = toggled
+                        .take()
+                        .or(stats.reposition_transition)
+                        .map(|transition| transition.message());
+                    if let Some(message) = &reposition {
+                        println!("[{}] {}", Local::now().format("%H:%M:%S"), message);
+                    }
+                    let motion_state = MotionState {
+                        motion_detected,
+                        incident_id,
+                        followup,
+                        new_tracks: stats.new_tracks.clone(),
+                        rule_hits: stats.rule_hits.clone(),
+                        active_tracks: stats.active_tracks.clone(),
+                        motion_count: stats.motion_count,
+                        resources: resources.latest(),
+                        timings: stats.timings,
+                        frames_skipped: stats.frames_skipped,
+                        snapshots_deduplicated: stats.snapshots_deduplicated.unwrap_or(0),
+                        contours: stats.contours_found,
+                        regions: stats.regions_found,
+                        score: stats.score.score as f32,
+                        score_threshold: stats.score.threshold as f32,
+                        // Dated from the monotonic clock, so a clock jump
+                        // doesn't change how long ago it was
+                        last_motion_time: stats.last_motion_time.map(|at| {
+                            Local::now()
+                                - chrono::TimeDelta::from_std(at.elapsed()).unwrap_or_default()
+                        }),
+                        fps: stats.current_fps,
+                        resolution: stats.resolution,
+                        process_resolution: stats.process_resolution,
+                        absence: absence.as_ref().map(|a| a.status()),
+                        absence_alert,
+                        error: None,
+                        alert: None,
+                        device_change: None,
+                        active_file: None,
+                        cameras: None,
+                        file_finished: false,
+                        self_test: None,
+                        recording: clips
+                            .as_ref()
+                            .and_then(|clips| clips.selection())
+                            .map(|selection| selection.describe()),
+                        target_fps: pacer.target(),
+                        encode_fps: clips.as_ref().and_then(|clips| clips.encode_fps()),
+                        event_snapshot,
+                        clip_lengths,
+                        cooldown: Some(detector.events().cooldown(Instant::now())),
+                        relay: relay.as_ref().map(|relay| relay.is_active()),
+                        snapshot: None,
+                        tuning: None,
+                        phase: Some(detector.phase()),
+                        observing: detector.observer().is_active(),
+                        observed: report_observed(detector.observer().tag(), detector.observer()),
+                        repositioning: stats.repositioning,
+                        reposition,
+                        intrusion: stats.intrusion,
+                        clock_jump: clock_jump.map(|jump| jump.message()),
+                        reconnected,
+                        frame_time: Some(stats.frame_time),
+                        mask: None,
+                    };
+
+                    // Send state to GUI (non-blocking)
+                    let _ = sender.try_send(motion_state.clone());
+
+                    // Only pay for overlay and conversion while the preview is shown
+                    if preview_enabled && !color_frame.is_empty() {
+                        let camera_name = match (&playing_file, device_index) {
+                            (Some(path), _) => path
+                                .file_name()
+                                .unwrap_or(path.as_os_str())
+                                .to_string_lossy()
+                                .into_owned(),
+                            (None, Some(device)) => format!("Camera {}", device),
+                            (None, None) => String::new(),
+                        };
+                        let info = overlay::OverlayInfo {
+                            camera_name: &camera_name,
+                            fps: stats.current_fps,
+                            armed,
+                            motion_detected,
+                            motion_count: stats.motion_count,
+                            regions: &stats.motion_regions,
+                        };
+                        match S::preview_frame(
+                            &color_frame,
+                            &overlay_config,
+                            &info,
+                            config.gui.preview_max_size,
+                        ) {
+                            Ok(preview) => {
+                                let _ = preview_sender.try_send(preview);
+                            }
+                            Err(e) => eprintln!("Failed to build preview frame: {}", e),
+                        }
+                    }
+                    last_frame = Some(color_frame);
+                }
+                Err(e) => {
+                    if detector.file_finished() {
+                        println!("{}", e);
+                        is_running = false;
+                        let _ = sender.try_send(MotionState {
+                            file_finished: true,
+                            ..MotionState::default()
+                        });
+                    } else if matches!(e.downcast_ref(), Some(DetectorError::UnsupportedFormat(_)))
+                    {
+                        // Stop instead of failing every frame, and tell the GUI why
+                        eprintln!("Detection stopped: {}", e);
+                        is_running = false;
+                        let _ = sender.try_send(MotionState {
+                            error: Some(e.to_string()),
+                            ..MotionState::default()
+                        });
+                    } else {
+                        eprintln!("Detection error: {}", e);
+                        if let Some(device) = device_index
+                            && detector.is_live()
+                            && reconnects.failure() == reconnect::Recovery::Reconnect
+                        {
+                            let message = format!(
+                                "no frames from camera {}, reconnecting (attempt {})",
+                                device,
+                                reconnects.attempts()
+                            );
+                            eprintln!("Camera lost: {}", message);
+                            alerts.raise(alerts::AppAlert::new(
+                                alerts::AlertKind::CameraLost,
+                                alerts::Severity::Critical,
+                                message,
+                            ));
+                            if let Err(e) = detector.reconnect(&builder.clone().device(device)) {
+                                eprintln!("Reconnect failed: {:#}", e);
+                            }
+                        }
+                        thread::sleep(Duration::from_secs(1));
+                    }
+                }
+            }
+            pacer.wait();
+        } else {
+            // Stopped: the no-motion countdown is paused, not counting,
+            // and the relay is released
+            if let Some(absence) = absence.as_mut() {
+                absence.tick(Instant::now(), Local::now().time(), false);
+            }
+            if let Some(relay) = relay.as_mut() {
+                relay.update(Instant::now(), false, false, false);
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    // Snapshots are written synchronously, so only the state file can be behind
+    if let Some(state) = state.as_mut()
+        && let Err(e) = state.flush(session_events, session_frames)
+    {
+        eprintln!("Warning: {:#}", e);
+    }
+    // Save the open incident's key frame, audio and clip while the camera
+    // is still held, as the CLI does when it stops
+    if let Some(closed) = incidents.close() {
+        on_incident_closed(
+            &detector,
+            closed.id,
+            Some(closed.summary()),
+            &mut key_frames,
+            audio.as_ref(),
+            clips.as_mut(),
+            flush_on_event,
+        );
+    }
+    detector.release();
+    println!("Detector thread stopped, camera released");
+    Ok(())
+}
+
+/// The intrusion alarm, on stderr as well and with the terminal bell, so it
+/// stands out from event lines and reaches a log that only keeps errors.
+/// Returns the message for the GUI.
+fn report_intrusion(camera: &str, snapshot: Result<String>) -> String {
+    let message = match snapshot {
+        Ok(filename) => format!("The scene differs from the empty reference ({})", filename),
+        Err(e) => format!(
+            "The scene differs from the empty reference (snapshot failed: {:#})",
+            e
+        ),
+    };
+    let line = format!(
+        "[{}] {}INTRUSION ALARM: {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        camera,
+        message
+    );
+    println!("\x07{}", line);
+    eprintln!("{}", line);
+    message
+}
+
+fn report_reposition(camera: &str, transition: reposition::Transition) {
+    println!(
+        "[{}] {}{}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        camera,
+        transition.message()
+    );
+}
+
+/// Everything done when an incident ends, or is cut short by the CLI's
+/// limits: print its summary, if one is given, and save its key frame,
+/// audio and clip
+fn on_incident_closed<S: FrameSource>(
+    detector: &S,
+    incident_id: u64,
+    summary: Option<String>,
+    key_frames: &mut incident::KeyFrame<S::Frame>,
+    audio: Option<&audio::AudioRecorder>,
+    clips: Option<&mut S::Clips>,
+    flush: bool,
+) {
+    if let Some(summary) = summary {
+        println!("{}", summary);
+    }
+    report_key_frame(detector.save_key_frame(incident_id, key_frames), flush);
+    save_audio(audio, incident_id, detector.output_dir(), flush);
+    save_clip(clips, incident_id, flush, detector.uploader());
+}
+
+fn report_key_frame(saved: Result<Option<String>>, flush: bool) {
+    match saved {
+        Ok(Some(filename)) => {
+            println!("  Key frame saved: {}", filename);
+            if flush {
+                sync_output(Path::new(&filename));
+            }
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to save key frame: {}", e),
+    }
+}
+
+/// Log what `--observe` skipped since the last call. `camera` tags the
+/// lines; the notes are returned for the GUI's activity log.
+fn report_observed(camera: &str, observer: &observe::Observer) -> Vec<String> {
+    let notes = observer.take_notes();
+    for note in &notes {
+        println!(
+            "[{}] {}{}",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            camera,
+            note
+        );
+    }
+    notes
+}
+
+fn report_background(saved: Result<Option<String>>) {
+    match saved {
+        Ok(Some(filename)) => println!("  Background saved: {}", filename),
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to save background: {}", e),
+    }
+}
+
+/// Save a finished incident's audio, if it was being recorded
+fn save_audio(audio: Option<&audio::AudioRecorder>, incident_id: u64, dir: &Path, flush: bool) {
+    let Some(audio) = audio else {
+        return;
+    };
+    match audio.finish(incident_id, dir) {
+        Ok(Some(path)) => {
+            println!("  Audio saved: {}", path.display());
+            if flush {
+                sync_output(&path);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to save audio: {:#}", e),
+    }
+}
+
+/// Start recording a new incident's audio, unless `--observe` is on
+fn start_audio(
+    audio: Option<&audio::AudioRecorder>,
+    incident_id: u64,
+    observer: &observe::Observer,
+) {
+    if let Some(audio) = audio.filter(|_| {
+        observer.allows(observe::Effect::Audio, || {
+            format!("recorded the audio of incident #{}", incident_id)
+        })
+    }) {
+        audio.start(incident_id);
+    }
+}
+
+/// Start a new incident's clip, unless `--observe` is on. A clip that was
+/// never started is neither written nor saved.
+fn start_clip<F>(
+    clips: Option<&mut impl ClipSink<F>>,
+    incident_id: u64,
+    dir: &Path,
+    frame: &F,
+    observer: &observe::Observer,
+) {
+    if let Some(clips) = clips.filter(|_| {
+        observer.allows(observe::Effect::Clip, || {
+            format!("recorded a clip of incident #{}", incident_id)
+        })
+    }) && let Err(e) = clips.start(incident_id, dir, frame)
+    {
+        eprintln!("Failed to start clip: {:#}", e);
+    }
+}
+
+fn write_clip<F>(clips: Option<&mut impl ClipSink<F>>, frame: &F, motion: bool) {
+    if let Some(clips) = clips
+        && let Err(e) = clips.write(frame, motion)
+    {
+        eprintln!("Failed to write clip frame: {:#}", e);
+    }
+}
+
+/// Close a finished incident's clip, if it was being recorded, and queue
+/// it for upload with `--s3-bucket`
+fn save_clip<F>(
+    clips: Option<&mut impl ClipSink<F>>,
+    incident_id: u64,
+    flush: bool,
+    uploader: Option<&upload::Uploader>,
+) {
+    let Some(clips) = clips else {
+        return;
+    };
+    match clips.finish(incident_id) {
+        Ok(Some(path)) => {
+            println!("  Clip saved: {}", path.display());
+            if flush {
+                sync_output(&path);
+            }
+            // Trimmed clips are uploaded once they're cut
+            if let (false, Some(uploader)) = (clips.trims(), uploader) {
+                uploader.upload_file(&path);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to save clip: {:#}", e),
+    }
+}
+
+/// Report clips `--trim-clips` has finished cutting, and queue them for
+/// upload with `--s3-bucket`
+fn report_trimmed<F>(
+    clips: Option<&impl ClipSink<F>>,
+    flush: bool,
+    uploader: Option<&upload::Uploader>,
+) -> Vec<recording::Trimmed> {
+    let Some(clips) = clips else {
+        return Vec::new();
+    };
+    let trimmed = clips.take_trimmed();
+    for clip in &trimmed {
+        if let Some(length) = clip.trimmed {
+            println!(
+                "  Clip of incident #{} trimmed to {} of {}: {}",
+                clip.incident_id,
+                humanize::humanize_duration(length),
+                humanize::humanize_duration(clip.original),
+                clip.path.display()
+            );
+        }
+        if flush {
+            sync_output(&clip.path);
+        }
+        if let Some(uploader) = uploader {
+            uploader.upload_file(&clip.path);
+        }
+    }
+    trimmed
+}
+
+/// `--flush-on-event`: make sure a file written for an event is on disk
+fn sync_output(path: &Path) {
+    if let Err(e) = state::sync_path(path) {
+        eprintln!("Warning: failed to sync {}: {}", path.display(), e);
+    }
+}
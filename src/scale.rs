@@ -0,0 +1,90 @@
+//! Capture resolution and processing scale. With `--process-scale` the
+//! differencing runs on a shrunken copy of each frame, while snapshots and
+//! clips keep the full-resolution frame. `min_area`, zones and motion
+//! regions are all in capture pixels; `FrameScale` is the one place that
+//! converts from the processing frame.
+
+// Without the `camera` feature only the argument parsing uses this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use crate::regions::Region;
+use std::str::FromStr;
+
+/// `--capture-resolution`: the mode to ask the camera for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaptureResolution {
+    /// Probe the usual C920 modes, largest first
+    #[default]
+    Auto,
+    /// The largest mode the camera supports
+    Native,
+    Size(u32, u32),
+}
+
+impl FromStr for CaptureResolution {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        if text.eq_ignore_ascii_case("native") {
+            return Ok(Self::Native);
+        }
+        if text.eq_ignore_ascii_case("auto") {
+            return Ok(Self::Auto);
+        }
+        let size = text.split_once(['x', 'X']).and_then(|(width, height)| {
+            Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+        });
+        match size {
+            Some((width, height)) if width > 0 && height > 0 => Ok(Self::Size(width, height)),
+            _ => Err(format!(
+                "expected \"native\", \"auto\" or WIDTHxHEIGHT, got \"{}\"",
+                text
+            )),
+        }
+    }
+}
+
+/// Sizes of the captured frame and of the copy detection runs on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameScale {
+    pub capture: (i32, i32),
+    pub process: (i32, i32),
+}
+
+impl FrameScale {
+    /// Processing `capture`-sized frames at `factor` (0-1] of their size
+    pub fn new(capture: (i32, i32), factor: f64) -> Self {
+        let scaled = |side: i32| ((side as f64 * factor).round() as i32).clamp(1, side.max(1));
+        Self {
+            capture,
+            process: (scaled(capture.0), scaled(capture.1)),
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.capture == self.process
+    }
+
+    /// A motion region found in the processing frame, in capture pixels, so
+    /// it can be merged, held against `min_area` and matched to zones as-is
+    pub fn region_to_capture(&self, region: &Region) -> Region {
+        let sx = self.capture.0 as f64 / self.process.0.max(1) as f64;
+        let sy = self.capture.1 as f64 / self.process.1.max(1) as f64;
+        Region {
+            x: region.x * sx,
+            y: region.y * sy,
+            width: region.width * sx,
+            height: region.height * sy,
+            area: region.area * sx * sy,
+            ..*region
+        }
+    }
+
+    /// A contour point of the processing frame, in capture pixels
+    pub fn point_to_capture(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            x * self.capture.0 as f64 / self.process.0.max(1) as f64,
+            y * self.capture.1 as f64 / self.process.1.max(1) as f64,
+        )
+    }
+}
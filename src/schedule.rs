@@ -0,0 +1,292 @@
+use crate::absence::parse_duration;
+use crate::sun::{sun_times, Location, SunTimes};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveTime, Offset};
+use serde::Deserialize;
+
+/// Arming schedule, read from the `[schedule]` config section
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// When motion events count, e.g. "22:00..06:00" or
+    /// "sunset-30m..sunrise+30m"; unset means always
+    pub active: Option<String>,
+}
+
+/// One end of a schedule window
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TimePoint {
+    Clock(NaiveTime),
+    /// Offset from sunrise, in seconds
+    Sunrise(i64),
+    /// Offset from sunset, in seconds
+    Sunset(i64),
+}
+
+impl TimePoint {
+    fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        for (name, point) in [
+            ("sunrise", TimePoint::Sunrise as fn(i64) -> TimePoint),
+            ("sunset", TimePoint::Sunset),
+        ] {
+            let Some(rest) = text.strip_prefix(name) else {
+                continue;
+            };
+            let rest = rest.trim();
+            let offset = if rest.is_empty() {
+                0
+            } else if let Some(after) = rest.strip_prefix('+') {
+                parse_duration(after)?.as_secs() as i64
+            } else if let Some(before) = rest.strip_prefix('-') {
+                -(parse_duration(before)?.as_secs() as i64)
+            } else {
+                bail!("invalid time \"{}\" (use e.g. {}-30m)", text, name);
+            };
+            return Ok(point(offset));
+        }
+        NaiveTime::parse_from_str(text, "%H:%M")
+            .map(TimePoint::Clock)
+            .with_context(|| format!("invalid time \"{}\" (use HH:MM, sunrise or sunset)", text))
+    }
+
+    fn uses_sun(&self) -> bool {
+        !matches!(self, TimePoint::Clock(_))
+    }
+
+    /// Local time of day, given today's local sunrise and sunset
+    fn resolve(&self, sunrise: NaiveTime, sunset: NaiveTime) -> NaiveTime {
+        match *self {
+            TimePoint::Clock(time) => time,
+            TimePoint::Sunrise(offset) => sunrise + Duration::seconds(offset),
+            TimePoint::Sunset(offset) => sunset + Duration::seconds(offset),
+        }
+    }
+
+    /// Only meaningful for clock times, which don't depend on the sun
+    fn resolve_clock(&self) -> NaiveTime {
+        self.resolve(NaiveTime::MIN, NaiveTime::MIN)
+    }
+}
+
+/// Daily window, where either end may be relative to sunrise or sunset
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    text: String,
+    start: TimePoint,
+    end: TimePoint,
+}
+
+/// A schedule resolved for one day, in local time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Window {
+    Always,
+    Never,
+    /// Crosses midnight when the start is later than the end
+    Between(NaiveTime, NaiveTime),
+}
+
+impl Window {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        match *self {
+            Window::Always => true,
+            Window::Never => false,
+            Window::Between(start, end) if start <= end => time >= start && time < end,
+            Window::Between(start, end) => time >= start || time < end,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Window::Always => "all day".to_string(),
+            Window::Never => "off all day".to_string(),
+            Window::Between(start, end) => {
+                format!("{}-{}", start.format("%H:%M"), end.format("%H:%M"))
+            }
+        }
+    }
+}
+
+/// What a schedule works out to on one date
+#[derive(Clone, Debug, PartialEq)]
+pub struct DayPlan {
+    pub date: NaiveDate,
+    pub window: Window,
+    /// Local sunrise and sunset, when the schedule needs them and the sun
+    /// rises and sets that day
+    pub sun: Option<(NaiveTime, NaiveTime)>,
+    /// Why the window fell back to all day or none
+    pub warning: Option<String>,
+}
+
+impl DayPlan {
+    pub fn describe(&self) -> String {
+        match self.sun {
+            Some((sunrise, sunset)) => format!(
+                "today {} (sunrise {}, sunset {})",
+                self.window.describe(),
+                sunrise.format("%H:%M"),
+                sunset.format("%H:%M")
+            ),
+            None => format!("today {}", self.window.describe()),
+        }
+    }
+}
+
+impl Schedule {
+    /// Parse "START..END", e.g. "22:00..06:00" or "sunset-30m..sunrise+30m".
+    /// Clock times may also be written "22:00-06:00"; sun offsets need ".."
+    /// since they contain a minus sign themselves.
+    pub fn parse(text: &str) -> Result<Self> {
+        let Some((start, end)) = text.split_once("..").or_else(|| text.split_once('-')) else {
+            bail!("invalid schedule \"{}\" (use START..END)", text);
+        };
+        Ok(Self {
+            text: text.trim().to_string(),
+            start: TimePoint::parse(start)?,
+            end: TimePoint::parse(end)?,
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn uses_sun(&self) -> bool {
+        self.start.uses_sun() || self.end.uses_sun()
+    }
+
+    /// Resolve the window for `date` in the time zone at `offset`
+    pub fn plan(
+        &self,
+        date: NaiveDate,
+        location: Option<Location>,
+        offset: FixedOffset,
+    ) -> DayPlan {
+        let plan = |window, sun, warning| DayPlan {
+            date,
+            window,
+            sun,
+            warning,
+        };
+        if !self.uses_sun() {
+            let window = Window::Between(self.start.resolve_clock(), self.end.resolve_clock());
+            return plan(window, None, None);
+        }
+        let Some(location) = location else {
+            return plan(
+                Window::Always,
+                None,
+                Some(format!("schedule \"{}\" needs a [location]", self.text)),
+            );
+        };
+
+        let sun_up = match sun_times(date, location) {
+            SunTimes::Normal { sunrise, sunset } => {
+                let sunrise = sunrise.with_timezone(&offset).time();
+                let sunset = sunset.with_timezone(&offset).time();
+                let window = Window::Between(
+                    self.start.resolve(sunrise, sunset),
+                    self.end.resolve(sunrise, sunset),
+                );
+                return plan(window, Some((sunrise, sunset)), None);
+            }
+            SunTimes::PolarDay => true,
+            SunTimes::PolarNight => false,
+        };
+        // Without a sunrise or sunset, a daytime window covers the whole
+        // day or none of it, and a night-time window the opposite
+        let window = if sun_up == self.is_daytime() {
+            Window::Always
+        } else {
+            Window::Never
+        };
+        let warning = format!(
+            "the sun {} on {}; schedule \"{}\" is {}",
+            if sun_up {
+                "doesn't set"
+            } else {
+                "doesn't rise"
+            },
+            date,
+            self.text,
+            match window {
+                Window::Always => "active all day",
+                _ => "off all day",
+            }
+        );
+        plan(window, None, Some(warning))
+    }
+
+    /// Whether the window covers the hours of daylight rather than darkness
+    fn is_daytime(&self) -> bool {
+        match (self.start, self.end) {
+            (TimePoint::Sunrise(_), _) => true,
+            (TimePoint::Sunset(_), _) => false,
+            (_, TimePoint::Sunset(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A schedule that re-resolves itself when the local date changes
+pub struct ScheduleClock {
+    schedule: Schedule,
+    location: Option<Location>,
+    plan: Option<DayPlan>,
+}
+
+impl ScheduleClock {
+    /// `None` when no schedule is configured
+    pub fn from_config(
+        config: &ScheduleConfig,
+        location: Option<Location>,
+    ) -> Result<Option<Self>> {
+        let Some(active) = &config.active else {
+            return Ok(None);
+        };
+        let schedule = Schedule::parse(active)?;
+        if schedule.uses_sun() && location.is_none() {
+            bail!(
+                "schedule \"{}\" uses sunrise/sunset, which needs latitude and longitude in [location]",
+                active
+            );
+        }
+        Ok(Some(Self::new(schedule, location)))
+    }
+
+    pub fn new(schedule: Schedule, location: Option<Location>) -> Self {
+        Self {
+            schedule,
+            location,
+            plan: None,
+        }
+    }
+
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    /// Today's plan, worked out again after midnight. A polar day or night
+    /// warning is printed once per day.
+    pub fn plan(&mut self, now: DateTime<Local>) -> &DayPlan {
+        let date = now.date_naive();
+        if self.plan.as_ref().map(|plan| plan.date) != Some(date) {
+            let plan = self.schedule.plan(date, self.location, now.offset().fix());
+            if let Some(warning) = &plan.warning {
+                eprintln!("Warning: {}", warning);
+            }
+            self.plan = Some(plan);
+        }
+        self.plan.as_ref().unwrap()
+    }
+
+    /// Forget today's plan, e.g. after the wall clock jumped
+    pub fn reset(&mut self) {
+        self.plan = None;
+    }
+
+    pub fn is_active(&mut self, now: DateTime<Local>) -> bool {
+        self.plan(now).window.contains(now.time())
+    }
+}
@@ -0,0 +1,288 @@
+use crate::audio::{self, AudioConfig};
+use crate::error::DetectorError;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use std::path::Path;
+
+/// Frames grabbed by the camera check, about 3 seconds at 30 FPS
+pub const CAMERA_FRAMES: u32 = 90;
+
+/// Frames grabbed by `--check`, enough to see the camera delivers at all
+pub const CHECK_FRAMES: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    /// Worth fixing, but detection still works
+    Warn,
+    /// Detection or snapshots won't work; fails the self-test
+    Fail,
+    Skip,
+}
+
+impl CheckStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+            CheckStatus::Skip => "SKIP",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    /// What was found, and what to do about it when it isn't a pass
+    pub message: String,
+}
+
+impl CheckResult {
+    pub fn new(name: &'static str, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+/// Anything the camera check can pull frames from
+pub trait FrameSource {
+    /// Read one frame; `Ok(false)` when the read came back empty
+    fn grab_frame(&mut self) -> Result<bool>;
+}
+
+/// Grab `frames` frames and judge the camera by how many arrived
+pub fn check_camera(source: &mut dyn FrameSource, frames: u32) -> CheckResult {
+    let mut good = 0;
+    let mut last_error = None;
+    for _ in 0..frames {
+        match source.grab_frame() {
+            Ok(true) => good += 1,
+            Ok(false) => {}
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    let summary = format!("{}/{} frames captured", good, frames);
+    if good == 0 {
+        let cause = last_error.map(|e| format!(" ({})", e)).unwrap_or_default();
+        CheckResult::new(
+            "Camera",
+            CheckStatus::Fail,
+            format!(
+                "{}{}; check --device, the cable and that no other program holds the camera",
+                summary, cause
+            ),
+        )
+    } else if good * 10 < frames * 9 {
+        CheckResult::new(
+            "Camera",
+            CheckStatus::Warn,
+            format!(
+                "{}; dropped frames, try a lower resolution or another USB port",
+                summary
+            ),
+        )
+    } else {
+        CheckResult::new("Camera", CheckStatus::Pass, summary)
+    }
+}
+
+/// Write, read back and delete a file in the snapshot directory
+pub fn check_output_dir(dir: &Path) -> CheckResult {
+    let probe = dir.join(format!(".selftest_{}", std::process::id()));
+    let result = std::fs::create_dir_all(dir)
+        .and_then(|()| std::fs::write(&probe, b"motion_detector self-test"))
+        .and_then(|()| std::fs::read(&probe))
+        .and_then(|_| std::fs::remove_file(&probe));
+
+    match result {
+        Ok(()) => CheckResult::new(
+            "Output directory",
+            CheckStatus::Pass,
+            format!("{} is writable", dir.display()),
+        ),
+        Err(e) => CheckResult::new(
+            "Output directory",
+            CheckStatus::Fail,
+            format!(
+                "cannot write to {} ({}); fix its permissions or pass --output-dir",
+                dir.display(),
+                e
+            ),
+        ),
+    }
+}
+
+/// Fail with exit code 7 when snapshots can't be written, before a run
+/// that would otherwise only log each failed save
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub fn require_output_dir(dir: &Path) -> Result<()> {
+    let result = check_output_dir(dir);
+    match result.status {
+        CheckStatus::Fail => Err(DetectorError::OutputDir(result.message).into()),
+        _ => Ok(()),
+    }
+}
+
+/// Judge free space on the snapshot disk; `None` when it couldn't be read
+pub fn check_disk_space(dir: &Path, available_bytes: Option<u64>) -> CheckResult {
+    const MIN_BYTES: u64 = 100 * 1024 * 1024;
+    const LOW_BYTES: u64 = 1024 * 1024 * 1024;
+
+    let Some(available) = available_bytes else {
+        return CheckResult::new(
+            "Disk space",
+            CheckStatus::Skip,
+            format!("could not read free space for {}", dir.display()),
+        );
+    };
+    let message = format!(
+        "{:.0} MB free for {}",
+        available as f64 / (1024.0 * 1024.0),
+        dir.display()
+    );
+    if available < MIN_BYTES {
+        CheckResult::new(
+            "Disk space",
+            CheckStatus::Fail,
+            format!("{}; free some space or move --output-dir", message),
+        )
+    } else if available < LOW_BYTES {
+        CheckResult::new(
+            "Disk space",
+            CheckStatus::Warn,
+            format!("{}; snapshots will fill it soon", message),
+        )
+    } else {
+        CheckResult::new("Disk space", CheckStatus::Pass, message)
+    }
+}
+
+/// Free bytes on the filesystem holding `dir`, from `df`
+pub fn available_bytes(dir: &Path) -> Option<u64> {
+    let output = std::process::Command::new("df")
+        .arg("-Pk")
+        .arg(dir)
+        .output()
+        .ok()?;
+    parse_df_available(&String::from_utf8_lossy(&output.stdout))
+}
+
+pub fn parse_df_available(output: &str) -> Option<u64> {
+    // POSIX format: a header, then "fs blocks used available capacity mount"
+    let line = output.lines().nth(1)?;
+    let kb: u64 = line.split_whitespace().nth(3)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Catch clocks that were never set, e.g. a Raspberry Pi without network time
+pub fn check_clock(now: DateTime<Local>) -> CheckResult {
+    let reading = now.format("%Y-%m-%d %H:%M:%S").to_string();
+    if !crate::clock::looks_set(now) {
+        CheckResult::new(
+            "Clock",
+            CheckStatus::Warn,
+            format!(
+                "system clock reads {}; enable NTP so snapshots and incidents are dated correctly",
+                reading
+            ),
+        )
+    } else {
+        CheckResult::new("Clock", CheckStatus::Pass, reading)
+    }
+}
+
+/// Notification sinks would get a test message here; there are none yet
+pub fn check_notifiers() -> CheckResult {
+    CheckResult::new("Notifiers", CheckStatus::Skip, "none configured")
+}
+
+/// The config file was already loaded and validated before any check runs
+pub fn check_config(path: Option<&Path>) -> CheckResult {
+    match path {
+        Some(path) => CheckResult::new(
+            "Config",
+            CheckStatus::Pass,
+            format!("{} parsed and validated", path.display()),
+        ),
+        None => CheckResult::new("Config", CheckStatus::Skip, "no --config, using defaults"),
+    }
+}
+
+/// Open and drop the microphone when `--record-audio` is on
+pub fn check_microphone(config: &AudioConfig) -> CheckResult {
+    match audio::recorder(config) {
+        Ok(Some(_)) => CheckResult::new("Microphone", CheckStatus::Pass, "default input opened"),
+        Ok(None) => CheckResult::new("Microphone", CheckStatus::Skip, "audio recording is off"),
+        Err(e) => CheckResult::new("Microphone", CheckStatus::Fail, format!("{:#}", e)),
+    }
+}
+
+/// Every check except the camera, which needs the caller's capture device
+pub fn common_checks(output_dir: &Path) -> Vec<CheckResult> {
+    let output = check_output_dir(output_dir);
+    // df needs an existing path, so only ask when the directory is usable
+    let available = match output.status {
+        CheckStatus::Pass => available_bytes(output_dir),
+        _ => None,
+    };
+    vec![
+        output,
+        check_disk_space(output_dir, available),
+        check_clock(Local::now()),
+        check_notifiers(),
+    ]
+}
+
+/// Print a table under `title`, e.g. "Self-test"
+pub fn print_report(title: &str, results: &[CheckResult]) {
+    let width = results.iter().map(|r| r.name.len()).max().unwrap_or(0);
+    println!("{} results:", title);
+    for result in results {
+        println!(
+            "  [{}] {:width$}  {}",
+            result.status.label(),
+            result.name,
+            result.message,
+            width = width
+        );
+    }
+}
+
+/// Error (exit code 5) when any check failed
+pub fn finish(title: &str, results: &[CheckResult]) -> Result<()> {
+    let failed = results
+        .iter()
+        .filter(|r| r.status == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        return Err(DetectorError::SelfTestFailed(failed).into());
+    }
+    println!("{} passed", title);
+    Ok(())
+}
+
+/// `--check`: print the config, camera and output directory results and
+/// fail with the first problem's own exit code, so scripts can tell a bad
+/// config (3) from a missing camera (2) or an unwritable directory (7)
+pub fn finish_check(config: CheckResult, camera: CheckResult, output: CheckResult) -> Result<()> {
+    let results = [config, camera, output];
+    print_report("Check", &results);
+    let [config, camera, output] = results;
+    if config.status == CheckStatus::Fail {
+        return Err(DetectorError::InvalidArguments(config.message).into());
+    }
+    if camera.status == CheckStatus::Fail {
+        return Err(DetectorError::CameraOpen(camera.message).into());
+    }
+    if output.status == CheckStatus::Fail {
+        return Err(DetectorError::OutputDir(output.message).into());
+    }
+    println!("Check passed");
+    Ok(())
+}
@@ -0,0 +1,53 @@
+//! How `--sensitivity` (0.0-1.0) maps to the pixel difference a pixel has
+//! to change by to count as motion. The thresholds worth having run from a
+//! few grey levels up to a few dozen, so the default exponential curve
+//! lowers the threshold by the same factor for each step of the slider
+//! rather than the same number of grey levels, which would leave most of
+//! the useful range crowded into the top of the slider.
+
+// Without the `camera` feature only the config and the GUI use this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// Pixel difference (0-255) at sensitivity 0.0
+pub const MAX_THRESHOLD: f64 = 55.0;
+/// Pixel difference at sensitivity 1.0, just above camera noise
+pub const MIN_THRESHOLD: f64 = 4.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SensitivityCurve {
+    /// The same number of grey levels per step
+    Linear,
+    /// The same factor per step; the default sensitivity of 0.3 gives a
+    /// threshold of 25
+    #[default]
+    Exponential,
+}
+
+impl SensitivityCurve {
+    pub const ALL: [SensitivityCurve; 2] =
+        [SensitivityCurve::Linear, SensitivityCurve::Exponential];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SensitivityCurve::Linear => "Linear",
+            SensitivityCurve::Exponential => "Exponential",
+        }
+    }
+
+    /// Pixel difference threshold for `sensitivity`, clamped to 0.0-1.0
+    pub fn threshold(&self, sensitivity: f64) -> f64 {
+        let sensitivity = sensitivity.clamp(0.0, 1.0);
+        match self {
+            SensitivityCurve::Linear => {
+                MAX_THRESHOLD - sensitivity * (MAX_THRESHOLD - MIN_THRESHOLD)
+            }
+            SensitivityCurve::Exponential => {
+                MAX_THRESHOLD * (MIN_THRESHOLD / MAX_THRESHOLD).powf(sensitivity)
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Motion smoothing over time (`--smoothing`). The motion score of single
+//! frames is noisy, and how much one frame matters depends on the frame
+//! rate: at 60 FPS a one-frame flicker lasts 17 ms, at 10 FPS it lasts
+//! 100 ms. A low-pass filter with a time constant in seconds, stepped by the
+//! real time between frames, lets through the same motion at any rate.
+
+// Without the `camera` feature only the config check uses this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use std::time::{Duration, Instant};
+
+/// `smoothing_secs` is 0 (off) or positive
+pub fn smoothing_problem(secs: f64) -> Option<String> {
+    if secs >= 0.0 && secs.is_finite() {
+        None
+    } else {
+        Some(format!("smoothing_secs must not be negative, got {}", secs))
+    }
+}
+
+/// Exponential moving average of the motion score over time
+#[derive(Clone, Debug)]
+pub struct MotionSmoother {
+    time_constant: Duration,
+    value: f64,
+    last: Option<Instant>,
+}
+
+impl MotionSmoother {
+    pub fn new(time_constant: Duration) -> Self {
+        Self {
+            time_constant,
+            value: 0.0,
+            last: None,
+        }
+    }
+
+    pub fn time_constant(&self) -> Duration {
+        self.time_constant
+    }
+
+    /// Whether scores pass through unchanged
+    pub fn is_off(&self) -> bool {
+        self.time_constant.is_zero()
+    }
+
+    /// Add the score of a frame captured at `now` and return the smoothed
+    /// score. The score is taken to have held since the previous frame, so
+    /// a frame counts for as long as it lasted rather than once.
+    pub fn update(&mut self, now: Instant, score: f64) -> f64 {
+        if self.is_off() {
+            self.value = score;
+            return score;
+        }
+        // The first frame only starts the clock
+        if let Some(last) = self.last.replace(now) {
+            let elapsed = now.saturating_duration_since(last).as_secs_f64();
+            let weight = 1.0 - (-elapsed / self.time_constant.as_secs_f64()).exp();
+            self.value += weight * (score - self.value);
+        }
+        self.value
+    }
+
+    /// Forget the motion so far, e.g. after the camera was moved
+    pub fn reset(&mut self) {
+        self.value = 0.0;
+        self.last = None;
+    }
+}
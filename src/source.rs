@@ -0,0 +1,247 @@
+//! What the detection loops in `run` need from a detector. `detector.rs`
+//! implements it on OpenCV and `mock_camera.rs` on synthetic frames, so the
+//! CLI loop and the GUI's detector thread are the same code in both builds.
+
+// Without the `camera` feature only the tests run the GUI's detector thread
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use crate::{
+    config, events, gui, incident, intrusion, mask_editor, monitor, naming, observe, overlay,
+    phase, quality, recording, regions, reposition, rules, selftest, sensitivity, timestamp,
+    tuning, upload,
+};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// A color frame handed out by `FrameSource::take_frame`
+pub trait Frame: Sized {
+    /// Nothing was read into it
+    fn is_empty(&self) -> bool;
+    /// Copy it, e.g. to keep it as the incident's key frame
+    fn try_clone(&self) -> Result<Self>;
+}
+
+/// What became of an event's snapshot
+pub(crate) enum EventSnapshot {
+    /// Saved or uploaded; the local file, if one was written
+    Stored(Option<String>),
+    /// Looked like the snapshot of this earlier event, so it was skipped
+    Duplicate(u64),
+}
+
+/// What the last `poll` measured, and the counters since the source opened
+#[derive(Clone, Debug)]
+pub struct FrameStats {
+    pub motion_detected: bool,
+    pub frame_time: timestamp::FrameTime,
+    pub frame_count: u32,
+    pub motion_count: u32,
+    pub last_motion_time: Option<Instant>,
+    pub current_fps: f32,
+    pub timings: monitor::StageTimings,
+    /// Largest region's share of the frame, against min_area's share
+    pub score: regions::MotionScore,
+    /// Contours above the noise floor, and the regions they merged into
+    pub contours_found: usize,
+    pub regions_found: usize,
+    /// Regions that met min_area, boxed on the preview
+    pub motion_regions: Vec<regions::Region>,
+    /// Total area of `motion_regions`, scores key frames
+    pub motion_area: f64,
+    /// Contours dropped for their aspect ratio
+    pub aspect_rejected: usize,
+    /// Stale frames dropped before this one with `--grab-latest`
+    pub frames_skipped: u32,
+    pub total_frames_skipped: u64,
+    pub average_frames_skipped: f64,
+    pub grab_latest: bool,
+    pub rule_hits: Vec<rules::RuleHit>,
+    pub new_tracks: Vec<u64>,
+    pub active_tracks: Vec<u64>,
+    pub quality_change: Option<quality::Adjustment>,
+    /// Set on the frame the intrusion alarm went off
+    pub intrusion_alarm: bool,
+    pub intrusion: intrusion::IntrusionState,
+    /// Set on the frame repositioning started or ended
+    pub reposition_transition: Option<reposition::Transition>,
+    pub repositioning: bool,
+    /// Event snapshots skipped as duplicates, `None` without deduplication
+    pub snapshots_deduplicated: Option<u64>,
+    /// Sizes of the captured frames and of the copy detection runs on
+    pub resolution: (i32, i32),
+    pub process_resolution: (i32, i32),
+}
+
+/// Everything the detection loops use of a detector. The camera check's
+/// part of it is `selftest::FrameSource`.
+pub trait FrameSource: selftest::FrameSource + Sized {
+    type Frame: Frame;
+    /// Opens this source, and opens it again on another device or a file
+    type Builder: SourceBuilder<Source = Self>;
+    /// `--record`'s clip writer
+    type Clips: ClipSink<Self::Frame>;
+    /// Replays the last event for the GUI's tuning window
+    type Tuner: Tuner;
+
+    /// Read and process the next frame and return the motion events it
+    /// started. The frame itself is left for `take_frame`.
+    fn poll(&mut self) -> Result<Vec<events::MotionEvent>>;
+    /// The color frame the last `poll` measured
+    fn take_frame(&mut self) -> Self::Frame;
+    fn stats(&self) -> FrameStats;
+    fn release(&mut self);
+    /// Reopen the capture after frames stopped coming, keeping everything
+    /// learnt so far except the background
+    fn reconnect(&mut self, builder: &Self::Builder) -> Result<()>;
+    /// Reading a camera, not a file or generated frames, so reopening it
+    /// can bring frames back
+    fn is_live(&self) -> bool;
+    /// The input file ended and isn't looped
+    fn file_finished(&self) -> bool;
+    fn rewind(&mut self) -> Result<()>;
+    fn set_loop_file(&mut self, enabled: bool);
+
+    fn events(&self) -> &events::EventSource;
+    fn events_mut(&mut self) -> &mut events::EventSource;
+    /// `--observe`: which side effects are skipped, and the notes on them
+    fn observer(&self) -> &observe::Observer;
+    fn observer_mut(&mut self) -> &mut observe::Observer;
+    fn output_dir(&self) -> &Path;
+    fn set_output_dir(&mut self, dir: PathBuf);
+    fn uploader(&self) -> Option<&upload::Uploader>;
+    /// What the detector is doing, as of the last frame
+    fn phase(&self) -> phase::DetectorPhase;
+    /// Zone and direction of the motion that fired the current event
+    fn event_context(&self) -> naming::EventContext;
+    /// e.g. "1920x1080, detecting at 634x356"
+    fn describe_resolution(&self) -> String;
+    fn min_area(&self) -> regions::MinArea;
+    /// Settings carried over when the GUI opens another camera or a file
+    fn live_settings(&self) -> LiveSettings;
+
+    fn set_sensitivity(&mut self, sensitivity: f64);
+    fn set_sensitivity_curve(&mut self, curve: sensitivity::SensitivityCurve);
+    fn set_min_area(&mut self, min_area: regions::MinArea);
+    fn set_opening(&mut self, kernel: i32, iterations: i32);
+    /// Take the next frame as the empty scene for the intrusion alarm
+    fn arm(&mut self);
+    fn disarm(&mut self);
+    /// Start or end repositioning by hand
+    fn toggle_repositioning(&mut self) -> reposition::Transition;
+
+    /// The mask loaded from a file, for the GUI to show and edit
+    fn mask_image(&self) -> Option<mask_editor::MaskImage>;
+    /// Apply a mask painted in the GUI and save it to `path`, removing
+    /// `path` when the mask is cleared and `created` says the GUI made it
+    fn set_painted_mask(
+        &mut self,
+        image: &mask_editor::MaskImage,
+        path: &Path,
+        created: bool,
+    ) -> Result<()>;
+    /// The file the current mask was loaded from or saved to
+    fn mask_file(&self) -> Option<PathBuf>;
+
+    /// Save the snapshot for a motion event, unless snapshots are disabled
+    fn save_event_snapshot(&mut self, frame: &Self::Frame, event: u64) -> Result<EventSnapshot>;
+    /// Save the frame that set off the intrusion alarm, even with `--no-snapshot`
+    fn save_intrusion_snapshot(&self, frame: &Self::Frame) -> Result<String>;
+    /// Save the best frame of a finished incident
+    fn save_key_frame(
+        &self,
+        incident_id: u64,
+        key_frames: &mut incident::KeyFrame<Self::Frame>,
+    ) -> Result<Option<String>>;
+    /// Save the background once per `--background-snapshot-interval`
+    fn save_background_if_due(&mut self) -> Result<Option<String>>;
+    /// `GuiMessage::SaveSnapshot`. A stopped camera is read on demand;
+    /// while detection runs, or if the read fails, `last_frame` is saved.
+    fn manual_snapshot(
+        &mut self,
+        running: bool,
+        last_frame: Option<&Self::Frame>,
+    ) -> Result<String>;
+    /// Draw the OSD on a copy of `frame`, sized for the GUI's preview
+    fn preview_frame(
+        frame: &Self::Frame,
+        overlay_config: &overlay::OverlayConfig,
+        info: &overlay::OverlayInfo,
+        max_size: [f32; 2],
+    ) -> Result<gui::PreviewFrame>;
+
+    /// The clip writer for `[recording]`, when it is enabled
+    fn clips(config: &recording::RecordingConfig) -> Option<Self::Clips>;
+    /// The frames around the last event, once recorded
+    fn take_recording(&mut self) -> Result<Option<<Self::Tuner as Tuner>::Recording>>;
+}
+
+/// Opens a `FrameSource`. The GUI's detector thread keeps one up to date
+/// and opens the source again from it.
+pub trait SourceBuilder: Clone {
+    type Source;
+
+    fn build(&self) -> Result<Self::Source>;
+    /// The device `build` opens
+    fn device_index(&self) -> u32;
+    fn device(self, device: u32) -> Self;
+    /// A named camera's zones, rules and detector settings
+    fn camera_config(self, config: &config::Config) -> Self;
+    /// Read this video file instead of the device
+    fn input_file(self, path: Option<PathBuf>, loop_file: bool) -> Self;
+    fn mask_file(self, path: Option<PathBuf>) -> Self;
+    fn live_settings(self, live: &LiveSettings) -> Self;
+}
+
+/// `--record`: writes the frames of the open incident to a clip
+pub trait ClipSink<F> {
+    /// Pick the encoder on the first frame, once its size is known
+    fn prepare(&mut self, frame: &F) -> Result<recording::Selection>;
+    fn selection(&self) -> Option<recording::Selection>;
+    /// Smoothed frames per second the encoder sustains
+    fn encode_fps(&self) -> Option<f64>;
+    fn start(&mut self, incident_id: u64, dir: &Path, frame: &F) -> Result<()>;
+    fn write(&mut self, frame: &F, motion: bool) -> Result<()>;
+    /// Close the clip of a finished incident, returning where it went
+    fn finish(&mut self, incident_id: u64) -> Result<Option<PathBuf>>;
+    /// `--trim-clips` is on, so clips are uploaded once they're cut
+    fn trims(&self) -> bool;
+    fn take_trimmed(&self) -> Vec<recording::Trimmed>;
+}
+
+/// Replays a recorded event with the tuning window's trial settings
+pub trait Tuner {
+    /// The frames and the settings to replay them with
+    type Recording;
+
+    fn spawn(states: crossbeam_channel::Sender<gui::MotionState>) -> Self;
+    /// A recording has been loaded
+    fn is_loaded(&self) -> bool;
+    /// Replace the recording, and replay it with `params`
+    fn load(&mut self, recording: Self::Recording, params: tuning::TuneParams);
+    fn run(&self, params: tuning::TuneParams);
+}
+
+/// Settings the GUI changes while it runs, carried over when the detector
+/// is rebuilt for another camera or a video file. The rest comes from the
+/// builder, which the GUI keeps up to date.
+pub struct LiveSettings {
+    pub sensitivity: f64,
+    pub sensitivity_curve: sensitivity::SensitivityCurve,
+    pub min_area: regions::MinArea,
+    pub opening: (i32, i32),
+    pub output_dir: PathBuf,
+    pub observe: bool,
+    /// Intrusion mode; the new camera's first frame becomes the empty scene
+    pub armed: bool,
+}
+
+impl LiveSettings {
+    /// What the builder can't set
+    pub fn restore<S: FrameSource>(&self, source: &mut S) {
+        source.observer_mut().set_active(self.observe);
+        if self.armed {
+            source.arm();
+        }
+    }
+}
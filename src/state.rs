@@ -0,0 +1,148 @@
+// Without the `camera` feature only the tests use this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Counters kept across restarts in the `--state-file`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersistedStats {
+    pub motion_count: u64,
+    pub total_frames: u64,
+    /// When counting began, i.e. the first run with this state file (RFC 3339)
+    pub first_started: String,
+    /// Start of the most recent run (RFC 3339)
+    pub session_started: String,
+    pub sessions: u64,
+}
+
+/// Read stats saved by an earlier run; a missing file means a fresh start
+pub fn load(path: &Path) -> Result<PersistedStats> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(PersistedStats::default()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read state file {}", path.display()))
+        }
+    };
+    serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse state file {}", path.display()))
+}
+
+/// Write via a temp file and rename, so a crash mid-write leaves the old file intact
+pub fn save(path: &Path, stats: &PersistedStats) -> Result<()> {
+    let json = serde_json::to_string_pretty(stats)?;
+    write_atomic(path, json.as_bytes())
+        .with_context(|| format!("Failed to write state file {}", path.display()))
+}
+
+/// Write `bytes` to `path` via a temp file next to it and a rename
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Wait until `path` and its directory entry are on disk, so a power cut
+/// right after an event doesn't lose the file (`--flush-on-event`)
+pub fn sync_path(path: &Path) -> std::io::Result<()> {
+    std::fs::File::open(path)?.sync_all()?;
+    sync_parent(path)
+}
+
+/// Persist renames and new files in the directory holding `path`. Windows
+/// can't open directories for this; NTFS journals the metadata anyway.
+fn sync_parent(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Adds this run's counts to the persisted totals and saves them periodically
+pub struct StateFile {
+    path: PathBuf,
+    base: PersistedStats,
+    session_started: String,
+    last_saved: Instant,
+    last_counts: (u64, u64),
+}
+
+impl StateFile {
+    const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let mut base = load(&path)?;
+        let now = Local::now().to_rfc3339();
+        if base.first_started.is_empty() {
+            base.first_started = now.clone();
+        }
+        base.sessions += 1;
+        let state = Self {
+            path,
+            base,
+            session_started: now,
+            last_saved: Instant::now(),
+            last_counts: (0, 0),
+        };
+        state.save(0, 0)?;
+        Ok(state)
+    }
+
+    /// Totals from earlier runs, as loaded at startup
+    pub fn previous(&self) -> &PersistedStats {
+        &self.base
+    }
+
+    /// Cumulative stats given this run's counts
+    pub fn totals(&self, motion_count: u64, frames: u64) -> PersistedStats {
+        PersistedStats {
+            motion_count: self.base.motion_count + motion_count,
+            total_frames: self.base.total_frames + frames,
+            first_started: self.base.first_started.clone(),
+            session_started: self.session_started.clone(),
+            sessions: self.base.sessions,
+        }
+    }
+
+    /// Save if the counts changed and the last save is old enough
+    pub fn update(&mut self, motion_count: u64, frames: u64) -> Result<()> {
+        if self.last_saved.elapsed() < Self::SAVE_INTERVAL
+            || self.last_counts == (motion_count, frames)
+        {
+            return Ok(());
+        }
+        self.flush(motion_count, frames)
+    }
+
+    /// Save now, e.g. on shutdown
+    pub fn flush(&mut self, motion_count: u64, frames: u64) -> Result<()> {
+        self.last_saved = Instant::now();
+        self.last_counts = (motion_count, frames);
+        self.save(motion_count, frames)
+    }
+
+    /// Save now and wait for the rename to reach the disk
+    pub fn sync(&mut self, motion_count: u64, frames: u64) -> Result<()> {
+        self.flush(motion_count, frames)?;
+        sync_parent(&self.path)
+            .with_context(|| format!("Failed to sync state file {}", self.path.display()))
+    }
+
+    fn save(&self, motion_count: u64, frames: u64) -> Result<()> {
+        save(&self.path, &self.totals(motion_count, frames))
+    }
+}
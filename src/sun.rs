@@ -0,0 +1,59 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::Deserialize;
+
+/// Where the cameras are, read from the `[location]` config section
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct Location {
+    /// Degrees, north positive
+    pub latitude: f64,
+    /// Degrees, east positive
+    pub longitude: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SunTimes {
+    Normal {
+        sunrise: DateTime<Utc>,
+        sunset: DateTime<Utc>,
+    },
+    /// The sun stays above the horizon all day
+    PolarDay,
+    /// The sun stays below the horizon all day
+    PolarNight,
+}
+
+/// Sunrise and sunset on `date` at `location`, using NOAA's approximate
+/// solar equations (good to a minute or two between the polar circles)
+pub fn sun_times(date: NaiveDate, location: Location) -> SunTimes {
+    // Fractional year in radians, taken at noon
+    let gamma = 2.0 * std::f64::consts::PI / 365.0 * (date.ordinal0() as f64 + 0.5);
+    let eqtime = 229.18
+        * (0.000075 + 0.001868 * gamma.cos()
+            - 0.032077 * gamma.sin()
+            - 0.014615 * (2.0 * gamma).cos()
+            - 0.040849 * (2.0 * gamma).sin());
+    let declination = 0.006918 - 0.399912 * gamma.cos() + 0.070257 * gamma.sin()
+        - 0.006758 * (2.0 * gamma).cos()
+        + 0.000907 * (2.0 * gamma).sin()
+        - 0.002697 * (3.0 * gamma).cos()
+        + 0.00148 * (3.0 * gamma).sin();
+
+    // 90.833° allows for refraction and the size of the sun's disc
+    let latitude = location.latitude.to_radians();
+    let cos_hour_angle = 90.833_f64.to_radians().cos() / (latitude.cos() * declination.cos())
+        - latitude.tan() * declination.tan();
+    if cos_hour_angle > 1.0 {
+        return SunTimes::PolarNight;
+    }
+    if cos_hour_angle < -1.0 {
+        return SunTimes::PolarDay;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+    let at_minutes = |minutes: f64| midnight + Duration::seconds((minutes * 60.0).round() as i64);
+    SunTimes::Normal {
+        sunrise: at_minutes(720.0 - 4.0 * (location.longitude + hour_angle) - eqtime),
+        sunset: at_minutes(720.0 - 4.0 * (location.longitude - hour_angle) - eqtime),
+    }
+}
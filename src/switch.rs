@@ -0,0 +1,123 @@
+//! Switching the GUI's detector to another camera. The new camera is opened
+//! while the current one still runs. Some USB buses can't feed two cameras
+//! at once, so when that fails the current camera is released and the new
+//! one retried with growing pauses. If it still won't open, the previous
+//! camera is reopened the same way. The GUI gets a `DeviceChange` either
+//! way, so its camera list shows the device actually in use.
+
+// Without the `camera` feature only the tests use this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use std::time::Duration;
+
+/// Pauses between attempts to open a camera after the current one was
+/// released. Drivers can take a moment to let go of the device.
+pub const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_millis(100),
+    Duration::from_millis(300),
+    Duration::from_millis(900),
+];
+
+/// The outcome of a camera switch, sent back to the GUI
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceChange {
+    /// The device the GUI asked for
+    pub requested: u32,
+    /// The device now open: `requested`, the previous one after a failed
+    /// switch, or `None` when neither would open
+    pub actual: Option<u32>,
+    /// Why `requested` isn't in use
+    pub error: Option<String>,
+}
+
+impl DeviceChange {
+    pub fn succeeded(&self) -> bool {
+        self.actual == Some(self.requested)
+    }
+
+    /// A line for the log and the GUI
+    pub fn message(&self) -> String {
+        match (&self.error, self.actual) {
+            (None, _) => format!("Switched to camera {}", self.requested),
+            (Some(e), Some(actual)) => format!(
+                "Camera {} could not be opened, still using camera {}: {}",
+                self.requested, actual, e
+            ),
+            (Some(e), None) => format!("Failed to switch to camera {}: {}", self.requested, e),
+        }
+    }
+}
+
+/// Replace `camera`, open on device `current`, with one on `requested`.
+/// `current` is `None` when no camera is open, after a switch where neither
+/// device opened; then there is nothing to go back to. `open` opens a
+/// device, `release` closes a camera and `sleep` waits between attempts, so
+/// tests can count them instead. When no device opens, `camera` is left
+/// released.
+pub fn switch_device<T>(
+    camera: &mut T,
+    current: Option<u32>,
+    requested: u32,
+    mut open: impl FnMut(u32) -> anyhow::Result<T>,
+    mut release: impl FnMut(&mut T),
+    mut sleep: impl FnMut(Duration),
+) -> DeviceChange {
+    let error = match open(requested) {
+        Ok(opened) => {
+            release(camera);
+            *camera = opened;
+            return DeviceChange {
+                requested,
+                actual: Some(requested),
+                error: None,
+            };
+        }
+        Err(error) => error,
+    };
+
+    release(camera);
+    let mut retry = |device: u32| {
+        let mut last = None;
+        for delay in RETRY_DELAYS {
+            sleep(delay);
+            match open(device) {
+                Ok(opened) => return Ok(opened),
+                Err(e) => last = Some(e),
+            }
+        }
+        Err(last.unwrap_or_else(|| anyhow::anyhow!("no attempts made")))
+    };
+    if let Ok(opened) = retry(requested) {
+        *camera = opened;
+        return DeviceChange {
+            requested,
+            actual: Some(requested),
+            error: None,
+        };
+    }
+    let Some(current) = current else {
+        return DeviceChange {
+            requested,
+            actual: None,
+            error: Some(format!("{:#}", error)),
+        };
+    };
+    match retry(current) {
+        Ok(previous) => {
+            *camera = previous;
+            DeviceChange {
+                requested,
+                actual: Some(current),
+                error: Some(format!("{:#}", error)),
+            }
+        }
+        Err(reopen_error) => DeviceChange {
+            requested,
+            actual: None,
+            error: Some(format!(
+                "{:#}; reopening camera {} also failed: {:#}",
+                error, current, reopen_error
+            )),
+        },
+    }
+}
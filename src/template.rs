@@ -0,0 +1,109 @@
+//! `{placeholder}` substitution for text users configure, such as snapshot
+//! names and notification messages. `{{` and `}}` stand for literal braces.
+
+use std::fmt;
+
+/// A template that didn't parse, with the byte offset of the problem
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateError {
+    pub template: String,
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at position {} in \"{}\"",
+            self.message, self.position, self.template
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Piece {
+    Text(String),
+    Field(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+impl Template {
+    /// Parse `text`, accepting only the placeholders in `fields`
+    pub fn parse(text: &str, fields: &[&str]) -> Result<Self, TemplateError> {
+        let error = |position: usize, message: String| TemplateError {
+            template: text.to_string(),
+            position,
+            message,
+        };
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.char_indices().peekable();
+        while let Some((position, c)) = chars.next() {
+            match c {
+                '{' if chars.peek().is_some_and(|&(_, next)| next == '{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek().is_some_and(|&(_, next)| next == '}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(error(position, "unmatched '}'".to_string())),
+                '{' => {
+                    let start = position + 1;
+                    let Some(end) = text[start..].find('}').map(|end| start + end) else {
+                        return Err(error(position, "unclosed '{'".to_string()));
+                    };
+                    let name = &text[start..end];
+                    if !fields.contains(&name) {
+                        let expected: Vec<String> = fields
+                            .iter()
+                            .map(|field| format!("{{{}}}", field))
+                            .collect();
+                        return Err(error(
+                            position,
+                            format!(
+                                "unknown placeholder {{{}}}, expected one of {}",
+                                name,
+                                expected.join(", ")
+                            ),
+                        ));
+                    }
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Field(name.to_string()));
+                    while chars.next_if(|&(i, _)| i <= end).is_some() {}
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Text(literal));
+        }
+        Ok(Self { pieces })
+    }
+
+    pub fn uses(&self, field: &str) -> bool {
+        self.pieces
+            .iter()
+            .any(|piece| matches!(piece, Piece::Field(name) if name == field))
+    }
+
+    /// Fill in the placeholders; fields `value` has nothing for render empty
+    pub fn render(&self, value: impl Fn(&str) -> Option<String>) -> String {
+        let mut text = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(literal) => text.push_str(literal),
+                Piece::Field(name) => text.push_str(&value(name).unwrap_or_default()),
+            }
+        }
+        text
+    }
+}
@@ -1,5 +1,6 @@
 #[cfg(test)]
 mod tests {
+    use crate::regions::MinArea;
     use crate::Args;
     use clap::Parser;
 
@@ -9,8 +10,9 @@ mod tests {
         let args = Args::parse_from(&["motion_detector"]);
         assert_eq!(args.device, 0);
         assert_eq!(args.sensitivity, 0.3);
-        assert_eq!(args.min_area, 500);
+        assert_eq!(args.min_area, MinArea::Pixels(500));
         assert!(!args.verbose);
+        assert_eq!(args.output_dir, std::path::PathBuf::from("pics"));
 
         // Test custom values
         let args = Args::parse_from(&[
@@ -25,7 +27,7 @@ mod tests {
         ]);
         assert_eq!(args.device, 1);
         assert_eq!(args.sensitivity, 0.5);
-        assert_eq!(args.min_area, 1000);
+        assert_eq!(args.min_area, MinArea::Pixels(1000));
         assert!(args.verbose);
     }
 
@@ -60,4 +62,4127 @@ mod tests {
             assert!(area > 0);
         }
     }
+
+    #[test]
+    fn test_exit_codes() {
+        use crate::error::{exit_code_for, DetectorError};
+        use std::time::Duration;
+
+        let camera: anyhow::Error = DetectorError::CameraOpen("busy".to_string()).into();
+        let args: anyhow::Error = DetectorError::InvalidArguments("bad".to_string()).into();
+        let timeout: anyhow::Error = DetectorError::NoMotionTimeout(Duration::from_secs(5)).into();
+        let other = anyhow::anyhow!("something else");
+
+        assert_eq!(exit_code_for(&camera), 2);
+        assert_eq!(exit_code_for(&args), 3);
+        assert_eq!(exit_code_for(&timeout), 4);
+        assert_eq!(exit_code_for(&DetectorError::SelfTestFailed(1).into()), 5);
+        assert_eq!(
+            exit_code_for(&DetectorError::CameraLost("unplugged".to_string()).into()),
+            6
+        );
+        assert_eq!(
+            exit_code_for(&DetectorError::OutputDir("read-only".to_string()).into()),
+            7
+        );
+        assert_eq!(exit_code_for(&other), 1);
+
+        // --help lists every code
+        let help = <Args as clap::CommandFactory>::command()
+            .render_help()
+            .to_string();
+        for code in 0..=7 {
+            assert!(
+                help.contains(&format!("\n  {}  ", code)),
+                "{} not in --help",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_exit_codes() {
+        use crate::error::exit_code_for;
+        use crate::selftest::*;
+
+        // Mock camera that delivers frames or nothing at all
+        struct MockSource(bool);
+        impl FrameSource for MockSource {
+            fn grab_frame(&mut self) -> anyhow::Result<bool> {
+                Ok(self.0)
+            }
+        }
+        let dir = std::env::temp_dir().join(format!("motion_check_{}", std::process::id()));
+        let file = dir.join("file");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&file, b"").unwrap();
+
+        let check = |config: CheckStatus, frames: bool, output: &std::path::Path| {
+            finish_check(
+                CheckResult::new("Config", config, "config"),
+                check_camera(&mut MockSource(frames), CHECK_FRAMES),
+                check_output_dir(output),
+            )
+            .map_err(|e| exit_code_for(&e))
+        };
+        assert_eq!(check(CheckStatus::Pass, true, &dir), Ok(()));
+        assert_eq!(check(CheckStatus::Fail, true, &dir), Err(3));
+        assert_eq!(check(CheckStatus::Pass, false, &dir), Err(2));
+        // A path below a regular file can't be created
+        assert_eq!(check(CheckStatus::Pass, true, &file.join("pics")), Err(7));
+        // The first failure decides the code
+        assert_eq!(check(CheckStatus::Pass, false, &file.join("pics")), Err(2));
+
+        assert!(require_output_dir(&dir).is_ok());
+        let err = require_output_dir(&file.join("pics")).unwrap_err();
+        assert_eq!(exit_code_for(&err), 7);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let args = Args::parse_from(["motion_detector", "--check"]);
+        assert!(args.check);
+        assert!(Args::try_parse_from(["motion_detector", "--check", "--dry-run"]).is_err());
+    }
+
+    #[test]
+    fn test_reconnects() {
+        use crate::reconnect::{Reconnects, Recovery, FAILURES_BEFORE_RECONNECT};
+
+        let fail_until_reconnect = |reconnects: &mut Reconnects| {
+            for _ in 1..FAILURES_BEFORE_RECONNECT {
+                assert_eq!(reconnects.failure(), Recovery::Retry);
+            }
+            reconnects.failure()
+        };
+
+        let mut reconnects = Reconnects::new(Some(2));
+        assert_eq!(fail_until_reconnect(&mut reconnects), Recovery::Reconnect);
+        assert_eq!(fail_until_reconnect(&mut reconnects), Recovery::Reconnect);
+        assert_eq!(reconnects.attempts(), 2);
+        assert_eq!(fail_until_reconnect(&mut reconnects), Recovery::GiveUp);
+
+        // A good frame starts the count over, and reports the reconnect once
+        assert!(reconnects.success());
+        assert_eq!(reconnects.attempts(), 0);
+        assert!(!reconnects.success());
+        assert_eq!(fail_until_reconnect(&mut reconnects), Recovery::Reconnect);
+
+        // Without a limit it never gives up
+        let mut reconnects = Reconnects::new(None);
+        for _ in 0..10 {
+            assert_eq!(fail_until_reconnect(&mut reconnects), Recovery::Reconnect);
+        }
+
+        let args = Args::parse_from(["motion_detector", "--reconnect-attempts", "3"]);
+        assert_eq!(args.reconnect_attempts, Some(3));
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_reconnect_rebuilds_background() {
+        use crate::detector::{DetectorBuilder, MotionDetector};
+        use crate::phase::DetectorPhase;
+        use crate::reconnect::SETTLE_FRAMES;
+        use crate::source::FrameSource;
+        use std::time::Duration;
+
+        let builder = DetectorBuilder::default()
+            .config(&crate::config::Config::default())
+            .synthetic(160, 120)
+            .cooldowns((Duration::ZERO, Duration::ZERO));
+        let mut detector = builder.build().unwrap();
+        let motion = |detector: &mut MotionDetector| {
+            FrameSource::poll(detector).unwrap();
+            FrameSource::stats(detector).motion_detected
+        };
+        assert!(
+            (0..30).any(|_| motion(&mut detector)),
+            "the moving square raised no motion"
+        );
+        let frames = FrameSource::stats(&detector).frame_count;
+
+        // The frames read while the camera settles are neither compared nor
+        // kept, and the first one after them only starts the new background
+        FrameSource::reconnect(&mut detector, &builder).unwrap();
+        assert_eq!(
+            FrameSource::phase(&detector),
+            DetectorPhase::WarmingUp {
+                frames_left: SETTLE_FRAMES + 1
+            }
+        );
+        for _ in 0..=SETTLE_FRAMES {
+            assert!(!motion(&mut detector));
+        }
+        assert_eq!(FrameSource::phase(&detector), DetectorPhase::Running);
+        assert!(motion(&mut detector));
+
+        // Only the background was rebuilt, the counters carry on
+        assert_eq!(FrameSource::stats(&detector).frame_count, frames + 1);
+    }
+
+    #[test]
+    fn test_args_validation() {
+        let args = Args::parse_from(["motion_detector"]);
+        assert!(args.validate().is_ok());
+
+        let args = Args::parse_from(["motion_detector", "--sensitivity", "1.5"]);
+        assert_eq!(args.validate().unwrap_err().exit_code(), 3);
+
+        let args = Args::parse_from(["motion_detector", "--min-area", "0"]);
+        assert!(args.validate().is_err());
+
+        let args = Args::parse_from(["motion_detector", "--detect-device", "0"]);
+        assert!(args.validate().is_err());
+
+        let args = Args::parse_from(["motion_detector", "--target-fps", "0"]);
+        assert!(args.validate().is_err());
+        let args = Args::parse_from(["motion_detector", "--target-fps", "12.5"]);
+        assert!(args.validate().is_ok());
+
+        let args = Args::parse_from(["motion_detector", "--no-snapshot"]);
+        assert!(args.no_snapshot);
+        assert!(args.validate().is_ok());
+    }
+
+    #[test]
+    fn test_incident_grouping() {
+        use crate::incident::{IncidentTracker, IncidentUpdate};
+        use std::time::{Duration, Instant};
+
+        let gap = Duration::from_secs(120);
+        let t0 = Instant::now();
+        let mut tracker = IncidentTracker::new(gap);
+
+        // Rapid-fire events share one incident
+        assert_eq!(tracker.record(t0), (IncidentUpdate::Started(1), None));
+        assert_eq!(
+            tracker.record(t0 + Duration::from_secs(5)).0,
+            IncidentUpdate::Continued(1)
+        );
+        let just_under = t0 + Duration::from_secs(5) + gap - Duration::from_millis(1);
+        assert_eq!(tracker.record(just_under).0, IncidentUpdate::Continued(1));
+
+        // A gap of exactly `gap` starts a new incident and closes the old one
+        let (update, closed) = tracker.record(just_under + gap);
+        assert_eq!(update, IncidentUpdate::Started(2));
+        let closed = closed.unwrap();
+        assert_eq!(closed.id, 1);
+        assert_eq!(closed.event_count, 3);
+        assert_eq!(closed.summary(), "Incident #1 ended, 3 events over 2 min");
+
+        // Polling closes the open incident only once the gap has passed
+        assert!(tracker
+            .poll(just_under + gap + Duration::from_secs(1))
+            .is_none());
+        assert_eq!(tracker.poll(just_under + gap * 2).unwrap().id, 2);
+        assert!(tracker.poll(just_under + gap * 3).is_none());
+
+        // Shutting down closes the open incident without waiting for the gap
+        tracker.record(just_under + gap * 3);
+        assert_eq!(tracker.close().unwrap().id, 3);
+        assert!(tracker.close().is_none());
+    }
+
+    #[test]
+    fn test_re_alert() {
+        use crate::incident::{FollowUp, IncidentTracker, IncidentUpdate};
+        use std::time::{Duration, Instant};
+
+        let t0 = Instant::now();
+        let at = |secs: u64| t0 + Duration::from_secs(secs);
+        // Off by default
+        let mut tracker = IncidentTracker::new(Duration::from_secs(120));
+        tracker.record(at(0));
+        tracker.record(at(600));
+        assert_eq!(tracker.follow_up(at(600)), None);
+
+        let mut tracker = IncidentTracker::new(Duration::from_secs(120))
+            .with_re_alert(Some(Duration::from_secs(120)));
+        assert_eq!(tracker.record(at(0)).0, IncidentUpdate::Started(1));
+        assert_eq!(tracker.follow_up(at(0)), None);
+        // Events every 30 s; a follow-up on the first one 2 min after the last alert
+        let mut followups = Vec::new();
+        for secs in (30..=360).step_by(30) {
+            tracker.record(at(secs));
+            followups.extend(tracker.follow_up(at(secs)));
+        }
+        assert_eq!(
+            followups,
+            vec![
+                FollowUp {
+                    id: 1,
+                    number: 1,
+                    ongoing: Duration::from_secs(120)
+                },
+                FollowUp {
+                    id: 1,
+                    number: 2,
+                    ongoing: Duration::from_secs(240)
+                },
+                FollowUp {
+                    id: 1,
+                    number: 3,
+                    ongoing: Duration::from_secs(360)
+                },
+            ]
+        );
+        assert_eq!(
+            followups[1].message(Some("porch")),
+            "MOTION ONGOING for 4 min in zone porch (incident #1, follow-up 2)"
+        );
+        assert_eq!(
+            followups[0].message(None),
+            "MOTION ONGOING for 2 min (incident #1, follow-up 1)"
+        );
+
+        // The count ends up in the summary; a new incident starts over
+        let (update, closed) = tracker.record(at(1000));
+        assert_eq!(update, IncidentUpdate::Started(2));
+        let closed = closed.unwrap();
+        assert_eq!(closed.followups, 3);
+        assert_eq!(
+            closed.summary(),
+            "Incident #1 ended, 13 events over 6 min, 3 follow-up(s)"
+        );
+        assert_eq!(tracker.follow_up(at(1000)), None);
+
+        let args = Args::parse_from(["motion_detector", "--re-alert", "2m"]);
+        assert_eq!(args.re_alert().unwrap(), Some(Duration::from_secs(120)));
+        assert!(Args::parse_from(["motion_detector"])
+            .re_alert()
+            .unwrap()
+            .is_none());
+        let args = Args::parse_from(["motion_detector", "--re-alert", "soon"]);
+        assert_eq!(args.validate().unwrap_err().exit_code(), 3);
+    }
+
+    #[test]
+    fn test_snapshot_annotation() {
+        use crate::annotate::{in_regions, thickness_problem, AnnotationStyle, BoxColor, Marks};
+        use crate::config::{validate, Config};
+        use crate::regions::Region;
+        use crate::scale::FrameScale;
+
+        assert_eq!("red".parse(), Ok(BoxColor::rgb(255, 0, 0)));
+        assert_eq!("Orange".parse(), Ok(BoxColor::rgb(255, 165, 0)));
+        assert_eq!("#1e90FF".parse(), Ok(BoxColor::rgb(0x1e, 0x90, 0xff)));
+        assert_eq!("00ff00".parse(), Ok(BoxColor::GREEN));
+        for bad in ["#12345", "#12345g", "purple", "#ééé", ""] {
+            assert!(bad.parse::<BoxColor>().is_err(), "{}", bad);
+        }
+        assert!(AnnotationStyle::Both.draws_rects() && AnnotationStyle::Both.draws_contours());
+        assert!(!AnnotationStyle::Rect.draws_contours());
+        assert!(!AnnotationStyle::Contour.draws_rects());
+
+        // Marks are in capture pixels and drawn on a snapshot twice the size
+        let region = Region {
+            x: 10.0,
+            y: 20.0,
+            width: 30.0,
+            height: 40.0,
+            area: 600.0,
+            contours: 1,
+        };
+        let inside = vec![(12.0, 22.0), (38.5, 22.0), (25.0, 59.0)];
+        let outside = vec![(100.0, 100.0), (110.0, 100.0)];
+        assert!(in_regions(&inside, &[region]));
+        assert!(!in_regions(&outside, &[region]));
+        assert!(!in_regions(&[], &[region]));
+        let marks = Marks {
+            regions: vec![region],
+            contours: vec![inside],
+        };
+        assert_eq!(marks.rects(2.0), vec![(20, 40, 60, 80)]);
+        assert_eq!(
+            marks.outlines(2.0),
+            vec![vec![(24, 44), (77, 44), (50, 118)]]
+        );
+
+        // Contours found at half size land where the regions are
+        let scale = FrameScale::new((640, 480), 0.5);
+        assert_eq!(scale.point_to_capture((100.0, 60.0)), (200.0, 120.0));
+
+        assert_eq!(thickness_problem(1), None);
+        assert_eq!(thickness_problem(20), None);
+        assert!(thickness_problem(0).is_some());
+        assert!(thickness_problem(21).is_some());
+
+        let config = Config::default();
+        assert_eq!(config.snapshots.annotation_style, None);
+        assert_eq!(config.snapshots.box_color, BoxColor::GREEN);
+        assert_eq!(config.snapshots.box_thickness, 2);
+        let config: Config = toml::from_str(
+            "[snapshots]\nannotation_style = \"both\"\nbox_color = \"#ff0000\"\nbox_thickness = 3",
+        )
+        .unwrap();
+        assert_eq!(
+            config.snapshots.annotation_style,
+            Some(AnnotationStyle::Both)
+        );
+        assert_eq!(config.snapshots.box_color, BoxColor::rgb(255, 0, 0));
+        assert!(validate(&config).is_ok());
+        assert!(toml::from_str::<Config>("[snapshots]\nbox_color = \"purple\"").is_err());
+        let config: Config = toml::from_str("[snapshots]\nbox_thickness = 0").unwrap();
+        assert_eq!(validate(&config).unwrap_err()[0].section, "[snapshots]");
+
+        let args = Args::parse_from([
+            "motion_detector",
+            "--annotation-style",
+            "contour",
+            "--box-color",
+            "yellow",
+            "--box-thickness",
+            "4",
+        ]);
+        assert_eq!(args.annotation_style, Some(AnnotationStyle::Contour));
+        assert_eq!(args.box_color, Some(BoxColor::rgb(255, 255, 0)));
+        assert_eq!(args.box_thickness, Some(4));
+        assert!(Args::try_parse_from(["motion_detector", "--annotation-style", "fill"]).is_err());
+        assert!(Args::try_parse_from(["motion_detector", "--box-color", "#xyz"]).is_err());
+    }
+
+    #[test]
+    fn test_incident_filter() {
+        use crate::incident::{page, Acknowledgement, IncidentFilter};
+        use chrono::{Local, NaiveDate, TimeZone};
+
+        let day = |d: u32| Local.with_ymd_and_hms(2024, 6, d, 12, 0, 0).unwrap();
+        let ack = |note: &str| Acknowledgement {
+            who: "pi".to_string(),
+            when: day(30),
+            note: note.to_string(),
+            tags: Vec::new(),
+        };
+        // Incidents on June 1, 2-3 (acknowledged as the cat) and 8 (the postman)
+        let incidents = [
+            (vec![day(1)], None),
+            (vec![day(2), day(3)], Some(ack("The CAT again"))),
+            (vec![day(8)], Some(ack("postman"))),
+        ];
+        let matching = |filter: &IncidentFilter| -> Vec<usize> {
+            (0..incidents.len())
+                .filter(|&i| filter.matches(&incidents[i].0, incidents[i].1.as_ref()))
+                .collect()
+        };
+        let date = |d: u32| NaiveDate::from_ymd_opt(2024, 6, d);
+
+        assert!(!IncidentFilter::default().is_active());
+        assert_eq!(matching(&IncidentFilter::default()), [0, 1, 2]);
+        let filter = IncidentFilter {
+            unacknowledged: true,
+            ..Default::default()
+        };
+        assert_eq!(matching(&filter), [0]);
+        let filter = IncidentFilter {
+            text: " cat ".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(matching(&filter), [1]);
+        // An incident matches when any of its days is in range
+        let filter = IncidentFilter {
+            since: date(3),
+            until: date(7),
+            ..Default::default()
+        };
+        assert!(filter.is_active());
+        assert_eq!(matching(&filter), [1]);
+        let filter = IncidentFilter {
+            until: date(2),
+            unacknowledged: true,
+            ..Default::default()
+        };
+        assert_eq!(matching(&filter), [0]);
+        assert!(!IncidentFilter::default().matches(&[], None));
+
+        let items: Vec<u32> = (1..=25).collect();
+        assert_eq!(page(&items, 0, 10), &items[..10]);
+        assert_eq!(page(&items, 2, 10), &[21, 22, 23, 24, 25]);
+        assert_eq!(page(&items, 9, 10), &[21, 22, 23, 24, 25]);
+        assert!(page::<u32>(&[], 0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_incident_tags() {
+        use crate::incident::{
+            normalize_tag, suggest_tags, top_tags, Acknowledgement, IncidentFilter,
+        };
+        use chrono::{Local, TimeZone};
+
+        assert_eq!(
+            normalize_tag("  Neighbor's   DOG "),
+            Some("neighbor's dog".to_string())
+        );
+        assert_eq!(normalize_tag(" \t"), None);
+
+        let ack = |day: u32, tags: &[&str]| Acknowledgement {
+            who: "pi".to_string(),
+            when: Local.with_ymd_and_hms(2024, 6, day, 12, 0, 0).unwrap(),
+            note: String::new(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        };
+        let acks = [
+            ack(1, &["delivery"]),
+            ack(10, &["dog", "delivery"]),
+            ack(11, &["dog"]),
+            ack(12, &["cat"]),
+            ack(13, &["dog"]),
+        ];
+        let all = top_tags(&acks, None, 10);
+        assert_eq!(
+            all,
+            [
+                ("dog".to_string(), 3),
+                ("delivery".to_string(), 2),
+                ("cat".to_string(), 1)
+            ]
+        );
+        let since = Local.with_ymd_and_hms(2024, 6, 5, 0, 0, 0).single();
+        let recent = top_tags(&acks, since, 2);
+        assert_eq!(recent, [("dog".to_string(), 3), ("cat".to_string(), 1)]);
+        assert!(top_tags(&acks, since, 0).is_empty());
+
+        // Suggestions follow use and skip tags already chosen
+        assert_eq!(suggest_tags(&all, "", &[]), ["dog", "delivery", "cat"]);
+        assert_eq!(suggest_tags(&all, " D", &[]), ["dog", "delivery"]);
+        assert_eq!(suggest_tags(&all, "d", &["dog".to_string()]), ["delivery"]);
+        assert!(suggest_tags(&all, "fox", &[]).is_empty());
+
+        let events = [Local.with_ymd_and_hms(2024, 6, 10, 11, 0, 0).unwrap()];
+        let filter = IncidentFilter {
+            tag: Some("dog".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&events, Some(&acks[1])));
+        assert!(!filter.matches(&events, Some(&acks[0])));
+        assert!(!filter.matches(&events, None));
+        let filter = IncidentFilter {
+            text: "deliv".to_string(),
+            ..Default::default()
+        };
+        assert!(filter.matches(&events, Some(&acks[0])));
+        assert!(!filter.matches(&events, Some(&acks[3])));
+        assert!(ack(1, &["dog", "cat"]).describe().ends_with(" [dog, cat]"));
+    }
+
+    #[test]
+    fn test_centroid_tracker() {
+        use crate::tracker::{CentroidTracker, Detection, TrackingConfig};
+
+        let at = |x: f64, y: f64| Detection {
+            centroid: (x, y),
+            area: 1000.0,
+        };
+        let config = TrackingConfig {
+            enabled: true,
+            max_distance: 50.0,
+            max_missed: 2,
+        };
+        let mut tracker = CentroidTracker::new(&config);
+
+        // One object walking across the frame is a single track
+        assert_eq!(tracker.update(&[at(100.0, 100.0)]), vec![1]);
+        for step in 1..10 {
+            let x = 100.0 + step as f64 * 30.0;
+            assert!(tracker.update(&[at(x, 100.0)]).is_empty());
+        }
+
+        // A second object far away gets its own id
+        assert_eq!(
+            tracker.update(&[at(400.0, 100.0), at(50.0, 400.0)]),
+            vec![2]
+        );
+        assert_eq!(tracker.active_ids(), vec![1, 2]);
+
+        // Lost tracks age out after max_missed frames
+        tracker.update(&[]);
+        tracker.update(&[]);
+        assert_eq!(tracker.active_ids().len(), 2);
+        tracker.update(&[]);
+        assert!(tracker.active_ids().is_empty());
+        assert_eq!(tracker.update(&[at(400.0, 100.0)]), vec![3]);
+    }
+
+    #[test]
+    fn test_resource_parsing() {
+        use crate::monitor::{cpu_percent, parse_cpu_seconds, parse_rss_bytes};
+
+        // Command names may contain spaces and parens
+        let stat = "1234 (motion (det)) S 1 1234 1234 0 -1 4194560 500 0 0 0 250 50 0 0 20 0 4";
+        assert_eq!(parse_cpu_seconds(stat), Some(3.0));
+        assert_eq!(parse_cpu_seconds("garbage"), None);
+
+        let status = "Name:\tmotion_detector\nVmRSS:\t  20480 kB\nThreads:\t4\n";
+        assert_eq!(parse_rss_bytes(status), Some(20480 * 1024));
+        assert_eq!(parse_rss_bytes("Name:\tx\n"), None);
+
+        assert_eq!(cpu_percent(1.0, 1.5, 2.0), 25.0);
+        assert_eq!(cpu_percent(1.0, 1.5, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_debug_report() {
+        use crate::debug_dump::{percentile, report, DumpRecord};
+
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0];
+        assert_eq!(percentile(&values, 50.0), 5.0);
+        assert_eq!(percentile(&values, 90.0), 9.0);
+        assert_eq!(percentile(&values, 99.0), 10.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+
+        let records: Vec<DumpRecord> = (1..=4)
+            .map(|n| DumpRecord {
+                frame: n * 30,
+                capture_ms: n as f32,
+                motion: n % 2 == 0,
+                merged_regions: if n == 4 { 2 } else { 0 },
+                ..Default::default()
+            })
+            .collect();
+        let text = report(&records);
+        assert!(
+            text.starts_with("4 frames dumped (frames 30 to 120)"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("Decisions: 2 motion, 2 no motion"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("capture        2.00     4.00     4.00     4.00"),
+            "{}",
+            text
+        );
+        assert!(text.contains("Merged regions: 2 in 1 frame(s)"), "{}", text);
+        assert!(!text.contains("Contour aspect"), "{}", text);
+
+        let records = vec![DumpRecord {
+            contour_aspects: vec![0.1, 1.0, 8.0],
+            aspect_rejected: 2,
+            ..Default::default()
+        }];
+        let text = report(&records);
+        assert!(
+            text.contains(
+                "Contour aspect (width/height): p1 0.10, p50 1.00, p99 8.00 over 3 contours; 2 rejected"
+            ),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn test_bench_stage_totals() {
+        use crate::monitor::{parse_status_kb, StageTotals};
+        use std::time::Duration;
+
+        let status = "VmHWM:	  40960 kB
+VmRSS:	  20480 kB
+";
+        assert_eq!(parse_status_kb(status, "VmHWM:"), Some(40960 * 1024));
+
+        let mut totals = StageTotals::default();
+        assert_eq!(totals.average().total_ms(), 0.0);
+        let ms = Duration::from_millis;
+        totals.add(ms(2), ms(4), ms(1), ms(3));
+        totals.add(ms(4), ms(2), ms(1), ms(1));
+        let average = totals.average();
+        assert_eq!((average.capture_ms, average.blur_ms), (3.0, 3.0));
+        assert_eq!(average.total_ms(), 9.0);
+
+        // Detection options placed after the subcommand still apply
+        let args = Args::parse_from(["motion_detector", "bench", "--frames", "50", "-s", "0.5"]);
+        match args.command {
+            Some(crate::Command::Bench(bench)) => {
+                assert_eq!((bench.frames, bench.width, bench.input), (50, 640, None))
+            }
+            _ => panic!("expected the bench subcommand"),
+        }
+        assert_eq!(args.sensitivity, 0.5);
+    }
+
+    #[test]
+    fn test_zone_transition_rules() {
+        use crate::rules::{problems, RuleEngine, TransitionRule, Zone};
+        use crate::tracker::Detection;
+        use std::time::{Duration, Instant};
+
+        // Gate on the left, door on the right, open ground in between
+        let zones = vec![
+            Zone {
+                name: "gate".to_string(),
+                rect: [0.0, 0.0, 100.0, 480.0],
+            },
+            Zone {
+                name: "door".to_string(),
+                rect: [540.0, 0.0, 100.0, 480.0],
+            },
+        ];
+        let rules = vec![TransitionRule {
+            name: "approach".to_string(),
+            from: "gate".to_string(),
+            to: "door".to_string(),
+            within_secs: 20.0,
+            min_area: 500.0,
+        }];
+        assert!(problems(&zones, &rules).is_empty());
+
+        // Walk one object along x, one step per second, and collect rule hits
+        let run = |engine: &mut RuleEngine, id: u64, xs: &[f64], area: f64| {
+            let t0 = Instant::now();
+            let mut hits = Vec::new();
+            for (i, &x) in xs.iter().enumerate() {
+                let detection = Detection {
+                    centroid: (x, 240.0),
+                    area,
+                };
+                let now = t0 + Duration::from_secs(i as u64);
+                hits.extend(engine.evaluate(now, &[(id, detection)], &[id]));
+            }
+            hits
+        };
+
+        // Gate to door fires once, even if the object lingers at the door
+        let mut engine = RuleEngine::new(zones.clone(), rules.clone());
+        let hits = run(&mut engine, 1, &[50.0, 300.0, 560.0, 580.0, 600.0], 1000.0);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].rule, "approach");
+        assert_eq!(hits[0].track_id, 1);
+
+        // Door to gate is the wrong direction
+        let mut engine = RuleEngine::new(zones.clone(), rules.clone());
+        assert!(run(&mut engine, 2, &[600.0, 300.0, 50.0], 1000.0).is_empty());
+
+        // Too slow, or too small
+        let mut engine = RuleEngine::new(zones.clone(), rules.clone());
+        let slow: Vec<f64> = std::iter::once(50.0)
+            .chain(std::iter::repeat_n(300.0, 25))
+            .chain(std::iter::once(600.0))
+            .collect();
+        assert!(run(&mut engine, 3, &slow, 1000.0).is_empty());
+        let mut engine = RuleEngine::new(zones.clone(), rules.clone());
+        assert!(run(&mut engine, 4, &[50.0, 600.0], 100.0).is_empty());
+
+        // Rules must name existing zones
+        let mut bad = rules.clone();
+        bad[0].to = "garage".to_string();
+        assert_eq!(problems(&zones, &bad).len(), 1);
+    }
+
+    #[test]
+    fn test_debouncer() {
+        use crate::events::Debouncer;
+        use std::time::{Duration, Instant};
+
+        let start = Instant::now();
+        let mut debouncer =
+            Debouncer::adaptive(Duration::from_secs(2), Duration::from_secs(2), start);
+
+        // Motion right after startup is ignored
+        assert!(!debouncer.ready(start + Duration::from_secs(1)));
+        assert!(debouncer.ready(start + Duration::from_millis(2001)));
+        // Exactly one interval later is still the same event
+        assert!(!debouncer.ready(start + Duration::from_millis(4001)));
+        assert!(debouncer.ready(start + Duration::from_millis(4002)));
+
+        // Continuous motion stretches the cooldown towards the maximum
+        let mut debouncer =
+            Debouncer::adaptive(Duration::from_secs(2), Duration::from_secs(60), start);
+        assert_eq!(debouncer.cooldown(start), Duration::from_secs(2));
+        for tenth in 1..=1200 {
+            debouncer.ready(start + Duration::from_millis(tenth * 100));
+        }
+        let busy = debouncer.cooldown(start + Duration::from_secs(120));
+        assert!(busy > Duration::from_secs(40), "{:?}", busy);
+        // and a quiet scene brings it back down
+        let quiet = debouncer.cooldown(start + Duration::from_secs(600));
+        assert!(quiet < Duration::from_secs(3), "{:?}", quiet);
+    }
+
+    #[test]
+    fn test_event_source() {
+        use crate::events::{Debouncer, EventSource, MotionEvent};
+        use std::time::{Duration, Instant};
+
+        let start = Instant::now();
+        let second = |secs| start + Duration::from_secs(secs);
+        let mut events = EventSource::new(Debouncer::adaptive(
+            Duration::from_secs(2),
+            Duration::from_secs(2),
+            start,
+        ));
+
+        // Debounced motion frames, numbered in order
+        assert!(events.frame(second(1), true, None).is_empty());
+        assert_eq!(
+            events.frame(second(3), true, None),
+            vec![MotionEvent {
+                number: 1,
+                track: None
+            }]
+        );
+        assert!(events.frame(second(4), true, None).is_empty());
+
+        // One event per new track, without a cooldown
+        let tracked = events.frame(second(4), true, Some(&[7, 8]));
+        assert_eq!(
+            tracked
+                .iter()
+                .map(|e| (e.number, e.track))
+                .collect::<Vec<_>>(),
+            vec![(2, Some(7)), (3, Some(8))]
+        );
+
+        // Nothing while disarmed
+        events.armed = false;
+        assert!(events.frame(second(10), true, Some(&[9])).is_empty());
+        assert_eq!(events.count(), 3);
+    }
+
+    #[test]
+    fn test_intrusion_guard() {
+        use crate::intrusion::{IntrusionGuard, IntrusionState};
+        use std::time::{Duration, Instant};
+
+        let t0 = Instant::now();
+        let at = |ms: u64| t0 + Duration::from_millis(ms);
+        let mut guard = IntrusionGuard::new(Duration::from_secs(2));
+        assert!(!guard.update(at(0), true));
+        assert!(!guard.state().is_armed());
+
+        // Nothing until the reference frame is taken
+        guard.arm();
+        assert_eq!(guard.state(), IntrusionState::Arming);
+        assert!(guard.state().is_armed());
+        assert!(!guard.update(at(0), true));
+        guard.reference_taken();
+        assert_eq!(guard.state(), IntrusionState::Armed);
+
+        // A quiet frame starts the count over
+        assert!(!guard.update(at(1000), true));
+        assert!(!guard.update(at(2500), false));
+        assert!(!guard.update(at(3000), true));
+        assert!(!guard.update(at(4500), true));
+        assert!(guard.update(at(5000), true));
+        assert_eq!(guard.state(), IntrusionState::Alarm);
+
+        // The alarm holds, and is reported once
+        assert!(!guard.update(at(6000), false));
+        assert!(!guard.update(at(9000), true));
+        assert_eq!(guard.state(), IntrusionState::Alarm);
+
+        guard.disarm();
+        assert_eq!(guard.state(), IntrusionState::Disarmed);
+        assert!(!guard.update(at(20_000), true));
+        // Taking the reference again doesn't arm a disarmed guard
+        guard.reference_taken();
+        assert_eq!(guard.state(), IntrusionState::Disarmed);
+
+        let args = Args::parse_from(["motion_detector"]);
+        assert!(!args.arm);
+        assert_eq!(args.intrusion_secs, 3);
+        let args = Args::parse_from(["motion_detector", "--arm", "--intrusion-secs", "10"]);
+        assert!(args.arm);
+        assert_eq!(args.intrusion_secs, 10);
+    }
+
+    #[test]
+    fn test_motion_smoothing() {
+        use crate::config::{validate, Config};
+        use crate::smoothing::{smoothing_problem, MotionSmoother};
+        use std::time::{Duration, Instant};
+
+        let t0 = Instant::now();
+        // The smoothed score after `secs` of a steady score, at `fps`
+        let steady = |fps: u32, secs: f64, score: f64| {
+            let mut smoother = MotionSmoother::new(Duration::from_millis(500));
+            let frames = (secs * fps as f64).round() as u32;
+            let mut value = 0.0;
+            for frame in 0..=frames {
+                let at = t0 + Duration::from_secs_f64(frame as f64 / fps as f64);
+                value = smoother.update(at, score);
+            }
+            value
+        };
+        // The same at 10 and 60 FPS: 1 - e^-(t/0.5)
+        for secs in [0.2, 0.5, 1.0] {
+            let expected = 1.0 - (-secs / 0.5_f64).exp();
+            assert!((steady(10, secs, 1.0) - expected).abs() < 1e-9);
+            assert!((steady(60, secs, 1.0) - expected).abs() < 1e-9);
+        }
+
+        // A one-frame flash weighs by how long the frame lasted
+        let flash = |fps: u32| {
+            let mut smoother = MotionSmoother::new(Duration::from_millis(500));
+            let interval = Duration::from_secs_f64(1.0 / fps as f64);
+            smoother.update(t0, 0.0);
+            smoother.update(t0 + interval, 1.0)
+        };
+        assert!(flash(60) < 0.04);
+        assert!(flash(10) > 0.18);
+
+        // Off passes scores through, reset forgets
+        let mut off = MotionSmoother::new(Duration::ZERO);
+        assert!(off.is_off());
+        assert_eq!(off.update(t0, 0.3), 0.3);
+        let mut smoother = MotionSmoother::new(Duration::from_secs(1));
+        smoother.update(t0, 1.0);
+        assert!(smoother.update(t0 + Duration::from_secs(1), 1.0) > 0.6);
+        smoother.reset();
+        assert_eq!(smoother.update(t0 + Duration::from_secs(2), 1.0), 0.0);
+
+        assert_eq!(smoothing_problem(0.0), None);
+        assert_eq!(smoothing_problem(0.5), None);
+        assert!(smoothing_problem(-1.0).is_some());
+        assert!(smoothing_problem(f64::NAN).is_some());
+        let config: Config = toml::from_str("[detector]\nsmoothing_secs = 0.5").unwrap();
+        assert_eq!(config.detector.smoothing_secs, 0.5);
+        assert!(validate(&config).is_ok());
+        let config: Config = toml::from_str("[detector]\nsmoothing_secs = -1").unwrap();
+        assert_eq!(validate(&config).unwrap_err()[0].section, "[detector]");
+    }
+
+    #[test]
+    fn test_tuning_recorder() {
+        use crate::tuning::{summarize, EventRecorder, TuneParams, POST_FRAMES, PRE_FRAMES};
+        use chrono::Local;
+        use std::time::{Duration, Instant};
+
+        let t0 = Instant::now();
+        let at = |frame: usize| t0 + Duration::from_millis(100 * frame as u64);
+        let mut recorder = EventRecorder::new();
+        for frame in 0..100 {
+            assert!(recorder.push(at(frame), frame).is_none());
+        }
+        // The event fires on frame 99; a second one while recording is part of it
+        recorder.mark_event(Local::now());
+        let mut recording = None;
+        for frame in 100..=99 + POST_FRAMES {
+            recorder.mark_event(Local::now());
+            recording = recorder.push(at(frame), frame);
+        }
+        let recording = recording.unwrap();
+        assert_eq!(recording.frames.len(), PRE_FRAMES + 1 + POST_FRAMES);
+        assert_eq!(recording.frames[recording.event_frame].1, 99);
+        assert_eq!(recording.event_frame, PRE_FRAMES);
+        assert_eq!(recording.frames[0].1, 99 - PRE_FRAMES);
+        // Back to keeping only the frames before an event
+        for frame in 200..300 {
+            assert!(recorder.push(at(frame), frame).is_none());
+        }
+
+        let params = TuneParams {
+            sensitivity: 0.5,
+            min_area: MinArea::Pixels(500),
+        };
+        let mut scores = vec![0.0; recording.frames.len()];
+        scores[PRE_FRAMES - 4..PRE_FRAMES + 3].fill(0.02);
+        let result = summarize(&recording, params, scores, 0.01);
+        assert!(result.would_trigger());
+        assert_eq!(result.first_motion, Some(PRE_FRAMES - 4));
+        assert_eq!(result.motion_frames, 7);
+        assert!((result.scores[PRE_FRAMES].0).abs() < 1e-9);
+        assert!((result.scores[0].0 + 6.0).abs() < 1e-9);
+        assert_eq!(
+            result.summary(),
+            "Would trigger 0.4 s before the recorded event (7 of 91 frames with motion)"
+        );
+        let quiet = summarize(&recording, params, vec![0.0; 91], 0.01);
+        assert!(!quiet.would_trigger());
+        assert_eq!(
+            quiet.summary(),
+            "Would not trigger (no frame above the threshold in 91)"
+        );
+    }
+
+    #[test]
+    fn test_repositioning() {
+        use crate::reposition::{RepositionConfig, Repositioning, Transition, Trigger};
+        use std::time::{Duration, Instant};
+
+        let t0 = Instant::now();
+        let at = |ms: u64| t0 + Duration::from_millis(ms);
+        let config = RepositionConfig {
+            scene_change: 0.5,
+            frames: 3,
+            settle_secs: 2.0,
+            ..Default::default()
+        };
+
+        // Two whole-view changes are a glitch, the third starts repositioning
+        let mut reposition = Repositioning::new(config.clone());
+        assert_eq!(reposition.update(at(0), 0.9), None);
+        assert_eq!(reposition.update(at(100), 0.9), None);
+        assert_eq!(reposition.update(at(200), 0.1), None);
+        assert_eq!(reposition.update(at(300), 0.9), None);
+        assert_eq!(reposition.update(at(400), 0.8), None);
+        assert_eq!(
+            reposition.update(at(500), 0.7),
+            Some(Transition::Started(Trigger::SceneChange))
+        );
+        // It ends once the view has been still for settle_secs
+        assert_eq!(reposition.update(at(1000), 0.0), None);
+        assert_eq!(reposition.update(at(2000), 0.3), None);
+        assert_eq!(reposition.update(at(2500), 0.0), None);
+        assert_eq!(
+            reposition.update(at(4500), 0.0),
+            Some(Transition::Ended(Duration::from_millis(4000)))
+        );
+        assert!(!reposition.is_active());
+
+        // A manual pause waits for the camera to move before settling
+        let mut reposition = Repositioning::new(RepositionConfig {
+            auto: false,
+            ..config
+        });
+        assert_eq!(reposition.update(at(0), 0.9), None);
+        assert_eq!(
+            reposition.toggle(at(0)),
+            Transition::Started(Trigger::Manual)
+        );
+        assert_eq!(reposition.update(at(10_000), 0.0), None);
+        assert!(reposition.is_active());
+        assert_eq!(
+            reposition.toggle(at(12_000)),
+            Transition::Ended(Duration::from_secs(12))
+        );
+
+        assert_eq!(RepositionConfig::default().problems().len(), 0);
+        let bad = RepositionConfig {
+            scene_change: 1.5,
+            frames: 0,
+            ..Default::default()
+        };
+        assert_eq!(bad.problems().len(), 2);
+    }
+
+    #[test]
+    fn test_process_scale_mapping() {
+        use crate::regions::Region;
+        use crate::rules::Zone;
+        use crate::scale::{CaptureResolution, FrameScale};
+
+        let frame_scale = FrameScale::new((1920, 1080), 0.25);
+        assert_eq!(frame_scale.process, (480, 270));
+        assert!(!frame_scale.is_identity());
+        assert!(FrameScale::new((640, 480), 1.0).is_identity());
+        assert_eq!(FrameScale::new((1920, 1080), 0.33).process, (634, 356));
+        assert_eq!(FrameScale::new((4, 4), 0.01).process, (1, 1));
+
+        // A box found at a quarter of the size covers 16x the capture area
+        let found = Region {
+            x: 10.0,
+            y: 20.0,
+            width: 30.0,
+            height: 40.0,
+            area: 400.0,
+            contours: 1,
+        };
+        let region = frame_scale.region_to_capture(&found);
+        assert_eq!(
+            region,
+            Region {
+                x: 40.0,
+                y: 80.0,
+                width: 120.0,
+                height: 160.0,
+                area: 6400.0,
+                contours: 1,
+            }
+        );
+        // so min_area and zones apply in capture pixels unchanged
+        let min_area = 5000.0;
+        assert!(found.area < min_area && region.area > min_area);
+        let zone = Zone {
+            name: "door".to_string(),
+            rect: [50.0, 100.0, 100.0, 100.0],
+        };
+        assert!(!zone.contains(found.detection().centroid));
+        assert!(zone.contains(region.detection().centroid));
+
+        assert_eq!("native".parse(), Ok(CaptureResolution::Native));
+        assert_eq!("3840x2160".parse(), Ok(CaptureResolution::Size(3840, 2160)));
+        assert!("0x480".parse::<CaptureResolution>().is_err());
+        assert!("big".parse::<CaptureResolution>().is_err());
+
+        let args = Args::parse_from(["motion_detector", "--process-scale", "1.5"]);
+        assert!(args.validate().is_err());
+        let args = Args::parse_from([
+            "motion_detector",
+            "--capture-resolution",
+            "native",
+            "--process-scale",
+            "0.33",
+        ]);
+        assert!(args.validate().is_ok());
+        assert_eq!(args.capture_resolution, Some(CaptureResolution::Native));
+    }
+
+    #[test]
+    fn test_preview_fit() {
+        use crate::config::{validate, Config};
+        use crate::gui::fit_within;
+
+        // A 4K feed shrinks to the cap, keeping 16:9
+        assert_eq!(fit_within([3840.0, 2160.0], [640.0, 480.0]), [640.0, 360.0]);
+        // A narrow panel leaves the height to follow the width
+        assert_eq!(fit_within([640.0, 480.0], [320.0, 480.0]), [320.0, 240.0]);
+        // A short panel pillarboxes a wide feed
+        assert_eq!(fit_within([1280.0, 720.0], [640.0, 180.0]), [320.0, 180.0]);
+        assert_eq!(fit_within([0.0, 480.0], [640.0, 480.0]), [0.0, 0.0]);
+
+        let config: Config = toml::from_str("[gui]\npreview_max_size = [800, 450]").unwrap();
+        assert_eq!(config.gui.preview_max_size, [800.0, 450.0]);
+        let config: Config = toml::from_str("[gui]\npreview_max_size = [0, 450]").unwrap();
+        let errors = validate(&config).unwrap_err();
+        assert_eq!(errors[0].section, "[gui]");
+    }
+
+    #[test]
+    fn test_max_events_arg() {
+        let args = Args::parse_from(["motion_detector"]);
+        assert_eq!(args.max_events(), None);
+        let args = Args::parse_from(["motion_detector", "--max-events", "0"]);
+        assert_eq!(args.max_events(), None);
+        let args = Args::parse_from(["motion_detector", "--max-events", "25", "--timeout", "600"]);
+        assert_eq!(args.max_events(), Some(25));
+        assert_eq!(args.timeout, Some(600));
+        assert!(args.validate().is_ok());
+        assert!(Args::try_parse_from(["motion_detector", "--max-events", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_observer() {
+        use crate::observe::{Effect, Observer};
+
+        let live = Observer::new(false);
+        assert!(live.allows(Effect::Image, || unreachable!()));
+        assert!(live.take_notes().is_empty());
+        assert_eq!(live.tag(), "");
+
+        // Each kind of skipped effect is noted once
+        let mut observer = Observer::new(true);
+        assert_eq!(observer.tag(), "[OBSERVE] ");
+        assert!(!observer.allows(Effect::Image, || "saved pics/a.jpg".to_string()));
+        assert!(!observer.allows(Effect::Image, || "saved pics/b.jpg".to_string()));
+        assert!(!observer.allows(Effect::Relay, || "switched the relay".to_string()));
+        assert_eq!(
+            observer.take_notes(),
+            [
+                "Would have saved pics/a.jpg",
+                "Would have switched the relay"
+            ]
+        );
+        assert!(!observer.allows(Effect::Image, || unreachable!()));
+        assert!(observer.take_notes().is_empty());
+
+        // Going live lets everything through; observing again notes afresh
+        observer.set_active(false);
+        assert!(observer.allows(Effect::Clip, || unreachable!()));
+        observer.set_active(true);
+        assert!(!observer.allows(Effect::Image, || "saved pics/c.jpg".to_string()));
+        assert_eq!(observer.take_notes(), ["Would have saved pics/c.jpg"]);
+
+        assert!(Args::parse_from(["motion_detector", "--observe"]).observe);
+        assert!(Args::try_parse_from(["motion_detector", "--observe", "--dry-run"]).is_err());
+    }
+
+    #[cfg(all(feature = "mock-camera", not(feature = "camera")))]
+    #[test]
+    fn test_cli_loop_stops_after_max_events_on_the_mock_camera() {
+        use crate::detector::DetectorBuilder;
+        use crate::source::SourceBuilder;
+        use std::time::Duration;
+
+        let dir = std::env::temp_dir().join(format!("motion_mock_cli_{}", std::process::id()));
+        let args = Args::parse_from([
+            "motion_detector",
+            "--max-events",
+            "3",
+            "--output-dir",
+            dir.to_str().unwrap(),
+        ]);
+        let config = crate::config::Config::default();
+        let builder = DetectorBuilder::from_args(&args, &config)
+            .cooldowns((Duration::ZERO, Duration::ZERO))
+            .pattern(vec![true, false]);
+        let detector = builder.build().unwrap();
+        crate::run::detect_cli(&config, &args, builder, detector).unwrap();
+    }
+
+    #[cfg(all(feature = "mock-camera", not(feature = "camera")))]
+    #[test]
+    fn test_cli_loop_times_out_without_motion_on_the_mock_camera() {
+        use crate::detector::DetectorBuilder;
+        use crate::error::DetectorError;
+        use crate::source::SourceBuilder;
+
+        let args = Args::parse_from(["motion_detector", "--timeout", "1", "--target-fps", "100"]);
+        let config = crate::config::Config::default();
+        let builder = DetectorBuilder::from_args(&args, &config).pattern(vec![false]);
+        let detector = builder.build().unwrap();
+        let error = crate::run::detect_cli(&config, &args, builder, detector).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(DetectorError::NoMotionTimeout(_))
+        ));
+    }
+
+    #[cfg(all(feature = "mock-camera", not(feature = "camera")))]
+    #[test]
+    fn test_detector_thread_reports_incidents_on_the_mock_camera() {
+        use crate::detector::{DetectorBuilder, MotionDetector};
+        use crate::gui::GuiMessage;
+        use crate::run::{detector_thread, ThreadOptions};
+        use std::time::Duration;
+
+        let dir = std::env::temp_dir().join(format!("motion_mock_gui_{}", std::process::id()));
+        let args = Args::parse_from(["motion_detector", "--output-dir", dir.to_str().unwrap()]);
+        let config = crate::config::Config::default();
+        let builder = DetectorBuilder::from_args(&args, &config)
+            .cooldowns((Duration::ZERO, Duration::ZERO))
+            .pattern(vec![true, false, false]);
+        let options = ThreadOptions {
+            incident_gap: Duration::from_secs(60),
+            state_file: None,
+            prefer_device: None,
+            gpio: None,
+            flush_on_event: false,
+            mask_path: dir.join("mask.png"),
+            default_mask: true,
+            target_fps: Some(200.0),
+            arm: false,
+            re_alert: None,
+            observe: false,
+        };
+        let (gui_sender, receiver) = crossbeam_channel::unbounded();
+        let (sender, states) = crossbeam_channel::unbounded();
+        let (preview_sender, previews) = crossbeam_channel::bounded(2);
+        gui_sender.send(GuiMessage::StartDetection).unwrap();
+        let thread = std::thread::spawn(move || {
+            detector_thread::<MotionDetector>(
+                receiver,
+                sender,
+                preview_sender,
+                config,
+                builder,
+                options,
+            )
+        });
+
+        let incident = std::iter::from_fn(|| states.recv_timeout(Duration::from_secs(5)).ok())
+            .find_map(|state| state.incident_id)
+            .expect("no incident reported");
+        assert_eq!(incident, 1);
+        gui_sender.send(GuiMessage::Shutdown).unwrap();
+        thread.join().unwrap().unwrap();
+        assert!(previews.try_recv().is_ok());
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_observe_writes_nothing() {
+        use crate::detector::DetectorBuilder;
+        use crate::source::EventSnapshot;
+        use std::time::Duration;
+
+        let dir = std::env::temp_dir().join(format!("motion_observe_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut config = crate::config::Config::default();
+        config.snapshots.privacy_keep_original = Some(dir.join("originals"));
+        let mut detector = DetectorBuilder::default()
+            .config(&config)
+            .synthetic(160, 120)
+            .output_dir(dir.clone())
+            .background_interval(Some(Duration::ZERO))
+            .cooldowns((Duration::ZERO, Duration::ZERO))
+            .build()
+            .unwrap();
+        detector.observer.set_active(true);
+        let mut events = 0;
+        for _ in 0..30 {
+            let found = detector.poll().unwrap();
+            let frame = detector.take_frame();
+            assert_eq!(detector.save_background_if_due().unwrap(), None);
+            if let Some(last) = found.last() {
+                events += 1;
+                let snapshot = detector.save_event_snapshot(&frame, last.number).unwrap();
+                assert!(matches!(snapshot, EventSnapshot::Stored(None)));
+            }
+        }
+        assert!(events > 0);
+        assert!(!dir.exists());
+        let notes = detector.observer.take_notes();
+        assert!(notes
+            .iter()
+            .any(|note| note.starts_with("Would have saved")));
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_event_snapshot_shows_where_motion_began() {
+        use crate::detector::DetectorBuilder;
+        use crate::source::EventSnapshot;
+        use opencv::{core::Mat, imgcodecs, imgproc};
+        use std::time::Duration;
+
+        let dir = std::env::temp_dir().join(format!("motion_trigger_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut detector = DetectorBuilder::default()
+            .config(&crate::config::Config::default())
+            .synthetic(160, 120)
+            .output_dir(dir.clone())
+            .cooldowns((Duration::ZERO, Duration::ZERO))
+            .build()
+            .unwrap();
+        // Synthetic frame `n` of 17 has a 30 px square at (8 * n, 45), and
+        // the detector read frame 0 when it opened
+        let square_x = |poll: usize| 8 * ((poll + 1) % 17) as i32;
+        let first = (0..30)
+            .find(|_| !detector.poll().unwrap().is_empty())
+            .expect("the moving square raised no event");
+        // The square keeps moving, so the next frames raise events as well;
+        // their snapshot still shows the frame the run of motion began with
+        for _ in 0..3 {
+            assert!(!detector.poll().unwrap().is_empty());
+        }
+        let frame = detector.take_frame();
+        let EventSnapshot::Stored(Some(path)) = detector.save_event_snapshot(&frame, 4).unwrap()
+        else {
+            panic!("no snapshot saved");
+        };
+        let saved = imgcodecs::imread(&path, imgcodecs::IMREAD_GRAYSCALE).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut square = Mat::default();
+        imgproc::threshold(&saved, &mut square, 144.0, 255.0, imgproc::THRESH_BINARY).unwrap();
+        let square = imgproc::bounding_rect(&square).unwrap();
+
+        // JPEG blurs the edges by a pixel or so; the frame that fired the
+        // last event has the square 24 px further on
+        assert!(
+            (square.x - square_x(first)).abs() <= 2,
+            "square at x = {}, expected {}",
+            square.x,
+            square_x(first)
+        );
+        assert!((square.y - 45).abs() <= 2);
+        assert!((square.width - 30).abs() <= 4);
+        assert!((square.height - 30).abs() <= 4);
+    }
+
+    #[test]
+    fn test_switch_device() {
+        use crate::switch::{switch_device, DeviceChange, RETRY_DELAYS};
+        use std::cell::RefCell;
+        use std::time::Duration;
+
+        /// A camera that is open on `device` until released
+        #[derive(Debug, PartialEq)]
+        struct FakeCamera {
+            device: u32,
+            open: bool,
+        }
+
+        /// Opens only the devices in `working`, from the given attempt on,
+        /// and counts every attempt
+        struct Factory {
+            working: Vec<(u32, usize)>,
+            attempts: RefCell<Vec<u32>>,
+        }
+
+        impl Factory {
+            fn open(&self, device: u32) -> anyhow::Result<FakeCamera> {
+                self.attempts.borrow_mut().push(device);
+                let tries = self
+                    .attempts
+                    .borrow()
+                    .iter()
+                    .filter(|&&d| d == device)
+                    .count();
+                match self.working.iter().find(|(d, _)| *d == device) {
+                    Some(&(_, from)) if tries >= from => Ok(FakeCamera { device, open: true }),
+                    _ => anyhow::bail!("camera {} is busy", device),
+                }
+            }
+        }
+
+        let run = |current: Option<u32>, working: Vec<(u32, usize)>| {
+            let factory = Factory {
+                working,
+                attempts: RefCell::new(Vec::new()),
+            };
+            let mut camera = FakeCamera {
+                device: 0,
+                open: true,
+            };
+            let mut slept = Vec::new();
+            let change = switch_device(
+                &mut camera,
+                current,
+                2,
+                |device| factory.open(device),
+                |camera: &mut FakeCamera| camera.open = false,
+                |delay| slept.push(delay),
+            );
+            (change, camera, factory.attempts.into_inner(), slept)
+        };
+
+        // Opened while the current camera still runs
+        let (change, camera, attempts, slept) = run(Some(0), vec![(0, 1), (2, 1)]);
+        assert_eq!(
+            change,
+            DeviceChange {
+                requested: 2,
+                actual: Some(2),
+                error: None
+            }
+        );
+        assert!(change.succeeded());
+        assert_eq!(
+            camera,
+            FakeCamera {
+                device: 2,
+                open: true
+            }
+        );
+        assert_eq!(attempts, [2]);
+        assert!(slept.is_empty());
+
+        // Only once the current camera let go of the bus
+        let (change, camera, attempts, slept) = run(Some(0), vec![(0, 1), (2, 3)]);
+        assert!(change.succeeded());
+        assert_eq!(camera.device, 2);
+        assert_eq!(attempts, [2, 2, 2]);
+        assert_eq!(slept, RETRY_DELAYS[..2]);
+
+        // Never opens: back on the previous camera, with the reason
+        let (change, camera, attempts, slept) = run(Some(0), vec![(0, 1)]);
+        assert_eq!(change.actual, Some(0));
+        assert!(!change.succeeded());
+        assert!(change
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("camera 2 is busy"));
+        assert_eq!(
+            camera,
+            FakeCamera {
+                device: 0,
+                open: true
+            }
+        );
+        assert_eq!(attempts, [2, 2, 2, 2, 0]);
+        assert_eq!(slept.len(), RETRY_DELAYS.len() + 1);
+        assert!(change
+            .message()
+            .starts_with("Camera 2 could not be opened, still using camera 0"));
+
+        // Neither opens: nothing is left running
+        let (change, camera, attempts, slept) = run(Some(0), vec![]);
+        assert_eq!(change.actual, None);
+        assert!(change
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("reopening camera 0 also failed"));
+        assert_eq!(
+            camera,
+            FakeCamera {
+                device: 0,
+                open: false
+            }
+        );
+        assert_eq!(attempts.len(), 1 + 2 * RETRY_DELAYS.len());
+        assert_eq!(
+            slept.iter().sum::<Duration>(),
+            RETRY_DELAYS.iter().sum::<Duration>() * 2
+        );
+
+        // With no camera open there is nothing to go back to
+        let (change, _, attempts, _) = run(None, vec![]);
+        assert_eq!(change.actual, None);
+        assert_eq!(
+            change.message(),
+            "Failed to switch to camera 2: camera 2 is busy"
+        );
+        assert_eq!(attempts, [2; 1 + RETRY_DELAYS.len()]);
+        let (change, camera, _, _) = run(None, vec![(2, 1)]);
+        assert!(change.succeeded());
+        assert_eq!(camera.device, 2);
+    }
+
+    #[test]
+    fn test_detector_phase() {
+        use crate::phase::{DetectorPhase, PauseReason};
+
+        // Moving the camera pauses even a warm-up; the schedule only
+        // matters once frames are compared
+        assert_eq!(
+            DetectorPhase::of_running(5, true, true),
+            DetectorPhase::Paused(PauseReason::Repositioning)
+        );
+        assert_eq!(
+            DetectorPhase::of_running(12, false, false),
+            DetectorPhase::WarmingUp { frames_left: 12 }
+        );
+        assert_eq!(
+            DetectorPhase::of_running(0, false, false),
+            DetectorPhase::Paused(PauseReason::Schedule)
+        );
+        assert_eq!(
+            DetectorPhase::of_running(0, false, true),
+            DetectorPhase::Running
+        );
+
+        assert_eq!(
+            DetectorPhase::WarmingUp { frames_left: 12 }.to_string(),
+            "Warming up: 12 frames left"
+        );
+        assert_eq!(
+            DetectorPhase::WarmingUp { frames_left: 1 }.to_string(),
+            "Warming up: 1 frame left"
+        );
+        assert_eq!(
+            DetectorPhase::Paused(PauseReason::Schedule).to_string(),
+            "Paused: outside the schedule"
+        );
+        assert_eq!(DetectorPhase::default(), DetectorPhase::Stopped);
+
+        // The countdown isn't a change of phase, a pause is
+        let warming = DetectorPhase::WarmingUp { frames_left: 2 };
+        assert!(warming.same_stage(&DetectorPhase::WarmingUp { frames_left: 1 }));
+        assert!(!warming.same_stage(&DetectorPhase::Running));
+        assert!(!DetectorPhase::Paused(PauseReason::Schedule)
+            .same_stage(&DetectorPhase::Paused(PauseReason::Repositioning)));
+
+        assert!(warming.is_active());
+        assert!(DetectorPhase::Paused(PauseReason::Repositioning).is_active());
+        assert!(!DetectorPhase::Initializing.is_active());
+        assert!(!DetectorPhase::Error("camera lost".into()).is_active());
+    }
+
+    #[test]
+    fn test_minimize_on_close_config() {
+        use crate::config::Config;
+
+        let config = Config::default();
+        assert!(!config.gui.minimize_on_close);
+        assert!(config.gui.notify_when_hidden);
+        let config: Config =
+            toml::from_str("[gui]\nminimize_on_close = true\nnotify_when_hidden = false").unwrap();
+        assert!(config.gui.minimize_on_close);
+        assert!(!config.gui.notify_when_hidden);
+        assert_eq!(config.gui.log_capacity, 1000);
+    }
+
+    #[test]
+    fn test_message_templates() {
+        use crate::config::{validate, Config};
+        use crate::notify::{template_for, Message, MessageTemplate};
+        use crate::template::Template;
+
+        let config: Config = toml::from_str(
+            r#"
+            [messages.email]
+            subject = "{camera}motion ({severity})"
+            body = "Motion at {time}{{!}}\nZone: {zone}\nEvents: {count}"
+            "#,
+        )
+        .unwrap();
+        assert!(validate(&config).is_ok());
+        let message = Message {
+            camera: "[porch] ".to_string(),
+            time: "12:00:00".to_string(),
+            count: 3,
+            ..Default::default()
+        };
+        // Unset optional fields render empty, not as "{zone}"
+        let (subject, body) = template_for(&config, "email").render(&message);
+        assert_eq!(subject, "[porch] motion ()");
+        assert_eq!(body, "Motion at 12:00:00{!}\nZone: \nEvents: 3");
+
+        // Notifiers without a section keep the console wording
+        let (_, body) = template_for(&config, "webhook").render(&message);
+        assert_eq!(body, "[12:00:00] [porch] MOTION DETECTED! (#3)");
+        assert_eq!(template_for(&config, "webhook"), MessageTemplate::default());
+
+        // Errors name the template and where in it the problem is
+        let err = Template::parse("Hi {camera} in {room}", &["camera"]).unwrap_err();
+        assert_eq!(err.position, 15);
+        assert!(err.to_string().contains("{room}"), "{}", err);
+        assert_eq!(
+            Template::parse("{camera", &["camera"])
+                .unwrap_err()
+                .position,
+            0
+        );
+
+        let config: Config = toml::from_str("[messages.sms]\nbody = \"{camera} {speed}\"").unwrap();
+        let errors = validate(&config).unwrap_err();
+        assert_eq!(errors[0].section, "[messages.sms] body");
+    }
+
+    #[test]
+    fn test_snapshot_filename_template() {
+        use crate::naming::{check, in_date_tree, render, Direction, EventContext, MotionTrail};
+        use chrono::{Local, TimeZone};
+        use std::time::{Duration, Instant};
+
+        let captured = Local.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let template = "{zone}_{direction}_{timestamp}";
+        assert!(check(template).is_ok());
+        let context = EventContext {
+            zone: Some("front door".to_string()),
+            direction: Some(Direction::Left),
+        };
+        assert_eq!(
+            render(template, captured, &context),
+            "front_door_left_20240101_120000"
+        );
+        // Without zones or a clear direction the defaults fill in
+        assert_eq!(
+            render(template, captured, &EventContext::default()),
+            "frame_none_20240101_120000"
+        );
+        assert_eq!(
+            render("motion_{timestamp}", captured, &context),
+            "motion_20240101_120000"
+        );
+        for bad in [
+            "{zone}",
+            "{when}_{timestamp}",
+            "{timestamp",
+            "a/{timestamp}",
+        ] {
+            assert!(check(bad).is_err(), "{}", bad);
+        }
+
+        // The trail follows the motion, and a pause starts a new one
+        let mut trail = MotionTrail::new();
+        let t0 = Instant::now();
+        trail.push(t0, (300.0, 240.0));
+        assert_eq!(trail.direction(), None);
+        trail.push(t0 + Duration::from_millis(100), (250.0, 245.0));
+        assert_eq!(trail.direction(), Some(Direction::Left));
+        trail.push(t0 + Duration::from_secs(3), (250.0, 300.0));
+        assert_eq!(trail.direction(), None);
+        trail.push(t0 + Duration::from_millis(3100), (255.0, 360.0));
+        assert_eq!(trail.direction(), Some(Direction::Down));
+
+        // --date-tree files it under the day it was captured
+        assert_eq!(
+            in_date_tree("motion_20240101_120000.jpg", captured),
+            "2024/01/01/motion_20240101_120000.jpg"
+        );
+    }
+
+    #[test]
+    fn test_detector_config() {
+        use crate::config::{Config, DEFAULT_BLUR_KERNEL};
+
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.detector.blur_kernel, DEFAULT_BLUR_KERNEL);
+        assert!(config.snapshots.enabled);
+        assert_eq!(config.snapshots.pre_event_frames, 8);
+
+        let config: Config =
+            toml::from_str("[detector]\nresolution = [1280, 720]\nblur_kernel = 15").unwrap();
+        assert_eq!(config.detector.resolution, Some([1280, 720]));
+        assert_eq!(config.detector.blur_kernel, 15);
+    }
+
+    #[test]
+    fn test_camera_blocks() {
+        use crate::config::Config;
+
+        let config: Config = toml::from_str(
+            r#"
+            [tracking]
+            enabled = true
+
+            [defaults]
+            min_area = 800
+
+            [camera.front_door]
+            device = 0
+            sensitivity = 0.4
+
+            [camera.garage]
+            device = 1
+            min_area = 2000
+            detector = { blur_kernel = 31 }
+            "#,
+        )
+        .unwrap();
+        assert!(crate::config::validate(&config).is_ok());
+
+        let front = config.named_camera("front_door").unwrap();
+        assert_eq!(front.device, 0);
+        assert_eq!(front.sensitivity, Some(0.4));
+        assert_eq!(front.min_area, Some(MinArea::Pixels(800)));
+        assert!(front.config.tracking.enabled);
+        let garage = config.named_camera("garage").unwrap();
+        assert_eq!(garage.min_area, Some(MinArea::Pixels(2000)));
+        assert_eq!(garage.config.detector.blur_kernel, 31);
+        assert_eq!(config.camera_for_device(1).unwrap().name, "garage");
+        assert!(config.named_camera("attic").is_err());
+
+        let invalid = [
+            "[camera.a]\ndevice = 0\n[camera.b]\ndevice = 0",
+            "[camera.Porch]\ndevice = 0\n[camera.porch]\ndevice = 1",
+            "[camera.\"back yard\"]\ndevice = 0",
+            "[camera.porch]\nsensitivity = 0.5",
+        ];
+        for text in invalid {
+            let config: Config = toml::from_str(text).unwrap();
+            assert!(crate::config::validate(&config).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_config_validation_reports_every_problem() {
+        use crate::config::{validate, Config};
+
+        let config: Config = toml::from_str(
+            r#"
+            [detector]
+            blur_kernel = 20
+            resolution = [320, 240]
+
+            [recording]
+            fps = 0.0
+
+            [[zones]]
+            name = "door"
+            rect = [300, 200, 100, 100]
+
+            [[rules]]
+            name = "enter"
+            from = "door"
+            to = "hall"
+            within_secs = 5
+
+            [camera.porch]
+            device = 0
+            min_area = 100000
+            "#,
+        )
+        .unwrap();
+        let errors = validate(&config).unwrap_err();
+        let sections: Vec<&str> = errors.iter().map(|e| e.section.as_str()).collect();
+        assert_eq!(
+            sections,
+            [
+                "[detector]",
+                "[[zones]]",
+                "[[rules]]",
+                "[recording]",
+                "[camera.porch]"
+            ]
+        );
+        assert!(errors[4]
+            .message
+            .contains("larger than the whole 320x240 frame"));
+        // The camera inherits the global mistakes without repeating them
+        assert_eq!(errors.len(), 5);
+    }
+
+    #[test]
+    fn test_gpio_relay_sequencing() {
+        use crate::gpio::{Relay, RelayPin};
+        use std::sync::{Arc, Mutex};
+        use std::time::{Duration, Instant};
+
+        struct MockPin(Arc<Mutex<Vec<bool>>>);
+        impl RelayPin for MockPin {
+            fn set(&mut self, active: bool) -> anyhow::Result<()> {
+                self.0.lock().unwrap().push(active);
+                Ok(())
+            }
+        }
+
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let mut relay = Relay::new(Box::new(MockPin(writes.clone())), Duration::from_secs(5));
+        let t0 = Instant::now();
+        let at = |secs: u64| t0 + Duration::from_secs(secs);
+
+        // Motion alone doesn't switch it on, an event does
+        assert_eq!(relay.update(at(0), false, true, true), None);
+        assert_eq!(relay.update(at(1), true, true, true), Some(true));
+        // Held while motion continues, released 5 s after the last of it
+        assert_eq!(relay.update(at(4), false, true, true), None);
+        assert_eq!(relay.update(at(8), false, false, true), None);
+        assert_eq!(relay.update(at(9), false, false, true), Some(false));
+
+        // Disarming releases it at once, and no event fires while disarmed
+        relay.update(at(20), true, true, true);
+        assert_eq!(relay.update(at(21), false, true, false), Some(false));
+        assert_eq!(relay.update(at(22), false, false, true), None);
+
+        // Dropping the relay never leaves the pin active
+        relay.update(at(30), true, true, true);
+        drop(relay);
+        assert_eq!(
+            *writes.lock().unwrap(),
+            [true, false, true, false, true, false]
+        );
+    }
+
+    #[test]
+    fn test_upload_retries() {
+        use crate::upload::{backoff, ObjectStore, Uploader};
+        use std::time::Duration;
+
+        // Fails the first `failures` puts, and reports every attempt
+        struct FlakyStore {
+            failures: u32,
+            attempts: crossbeam_channel::Sender<(String, String, usize)>,
+        }
+        impl ObjectStore for FlakyStore {
+            fn put(&mut self, key: &str, body: &[u8], content_type: &str) -> anyhow::Result<()> {
+                let _ = self
+                    .attempts
+                    .send((key.to_string(), content_type.to_string(), body.len()));
+                if self.failures > 0 {
+                    self.failures -= 1;
+                    anyhow::bail!("HTTP 503");
+                }
+                Ok(())
+            }
+        }
+
+        // Enough failures to give up on the first upload and retry the second
+        let (sender, attempts) = crossbeam_channel::unbounded();
+        let store = FlakyStore {
+            failures: Uploader::ATTEMPTS + 2,
+            attempts: sender,
+        };
+        let (state_sender, states) = crossbeam_channel::unbounded();
+        let uploader = Uploader::start(
+            Box::new(store),
+            "porch/".to_string(),
+            Duration::from_millis(1),
+            Some(crate::alerts::AlertSender::new(state_sender)),
+        );
+        uploader.upload_bytes("motion_20240101_120000.jpg", vec![0; 10]);
+        uploader.upload_bytes("motion_1_key.jpg", vec![0; 3]);
+        // The thread finishes the queue and drops the store once the
+        // uploader is gone, which ends `attempts`
+        drop(uploader);
+        let attempts: Vec<_> = attempts.iter().collect();
+        let first = (
+            "porch/motion_20240101_120000.jpg".to_string(),
+            "image/jpeg".to_string(),
+            10,
+        );
+        let second = (
+            "porch/motion_1_key.jpg".to_string(),
+            "image/jpeg".to_string(),
+            3,
+        );
+        let mut expected = vec![first; Uploader::ATTEMPTS as usize];
+        expected.extend([second.clone(), second.clone(), second]);
+        assert_eq!(attempts, expected);
+        // Only the upload given up on reaches the notification center
+        let alerts: Vec<_> = states.try_iter().filter_map(|state| state.alert).collect();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].kind, crate::alerts::AlertKind::UploadFailed);
+        assert!(alerts[0].message.contains("motion_20240101_120000.jpg"));
+
+        assert_eq!(
+            crate::upload::content_type("porch/motion_1.MP4"),
+            "video/mp4"
+        );
+        assert_eq!(backoff(Duration::from_secs(2), 1), Duration::from_secs(2));
+        assert_eq!(backoff(Duration::from_secs(2), 3), Duration::from_secs(8));
+        assert_eq!(backoff(Duration::from_secs(2), 40), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_sun_times() {
+        use crate::sun::{sun_times, Location, SunTimes};
+        use chrono::{NaiveDate, TimeZone, Utc};
+
+        let check = |latitude, longitude, date: (i32, u32, u32), rise, set| {
+            let location = Location {
+                latitude,
+                longitude,
+            };
+            let date = NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap();
+            let SunTimes::Normal { sunrise, sunset } = sun_times(date, location) else {
+                panic!(
+                    "expected a sunrise and sunset at {}, {}",
+                    latitude, longitude
+                );
+            };
+            for (found, expected) in [(sunrise, rise), (sunset, set)] {
+                let (y, mo, d, h, mi) = expected;
+                let expected = Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap();
+                let error = (found - expected).num_seconds().abs();
+                assert!(error <= 180, "{} vs {}", found, expected);
+            }
+        };
+        // London, summer solstice
+        check(
+            51.5074,
+            -0.1278,
+            (2024, 6, 21),
+            (2024, 6, 21, 3, 43),
+            (2024, 6, 21, 20, 21),
+        );
+        // New York, winter solstice
+        check(
+            40.7128,
+            -74.006,
+            (2024, 12, 21),
+            (2024, 12, 21, 12, 16),
+            (2024, 12, 21, 21, 32),
+        );
+        // Sydney: sunrise falls on the previous UTC day
+        check(
+            -33.8688,
+            151.2093,
+            (2024, 12, 21),
+            (2024, 12, 20, 18, 41),
+            (2024, 12, 21, 9, 5),
+        );
+
+        let tromso = Location {
+            latitude: 69.65,
+            longitude: 18.96,
+        };
+        let june = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let december = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+        assert_eq!(sun_times(june, tromso), SunTimes::PolarDay);
+        assert_eq!(sun_times(december, tromso), SunTimes::PolarNight);
+    }
+
+    #[test]
+    fn test_schedule_windows() {
+        use crate::schedule::{Schedule, Window};
+        use crate::sun::Location;
+        use chrono::{FixedOffset, NaiveDate, NaiveTime};
+
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+
+        let night = Schedule::parse("22:00..06:00").unwrap();
+        let plan = night.plan(date, None, utc);
+        assert_eq!(plan.window, Window::Between(time(22, 0), time(6, 0)));
+        assert!(plan.window.contains(time(23, 30)));
+        assert!(plan.window.contains(time(5, 59)));
+        assert!(!plan.window.contains(time(12, 0)));
+
+        // London in summer: sunset 20:21, sunrise 03:43 UTC
+        let london = Location {
+            latitude: 51.5074,
+            longitude: -0.1278,
+        };
+        let dark = Schedule::parse("sunset-30m..sunrise+30m").unwrap();
+        assert!(dark.uses_sun());
+        let plan = dark.plan(date, Some(london), utc);
+        let Window::Between(start, end) = plan.window else {
+            panic!("expected a window, got {:?}", plan.window);
+        };
+        assert!((start - time(19, 51)).num_minutes().abs() <= 3);
+        assert!((end - time(4, 13)).num_minutes().abs() <= 3);
+        assert!(plan.warning.is_none());
+
+        // No sunset in the Arctic summer: night windows never open, day windows never close
+        let tromso = Some(Location {
+            latitude: 69.65,
+            longitude: 18.96,
+        });
+        let plan = dark.plan(date, tromso, utc);
+        assert_eq!(plan.window, Window::Never);
+        assert!(plan.warning.is_some());
+        let day = Schedule::parse("sunrise..sunset").unwrap();
+        assert_eq!(day.plan(date, tromso, utc).window, Window::Always);
+
+        // --active-hours style, with the boundaries of a window across midnight
+        let night = Schedule::parse("22:00-06:00").unwrap();
+        let window = night.plan(date, None, utc).window;
+        assert_eq!(window, Window::Between(time(22, 0), time(6, 0)));
+        assert!(!window.contains(time(21, 59)));
+        assert!(window.contains(time(22, 0)));
+        assert!(window.contains(time(0, 0)));
+        assert!(window.contains(time(5, 59)));
+        assert!(!window.contains(time(6, 0)));
+        let office = Schedule::parse("09:00-17:30")
+            .unwrap()
+            .plan(date, None, utc);
+        assert!(office.window.contains(time(9, 0)) && !office.window.contains(time(17, 30)));
+
+        for text in [
+            "sunset",
+            "sunset+..sunrise",
+            "dusk..dawn",
+            "25:00..06:00",
+            "sunset-30m",
+            "22:00-",
+        ] {
+            assert!(Schedule::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_wav_encoding() {
+        use crate::audio::{file_name, wav_bytes};
+
+        let samples = [0i16, 1000, -1000, i16::MAX];
+        let wav = wav_bytes(16000, 2, &samples);
+        assert_eq!(wav.len(), 44 + samples.len() * 2);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        let u32_at = |i: usize| u32::from_le_bytes(wav[i..i + 4].try_into().unwrap());
+        let u16_at = |i: usize| u16::from_le_bytes(wav[i..i + 2].try_into().unwrap());
+        assert_eq!(u32_at(4), 36 + 8);
+        assert_eq!(u16_at(22), 2);
+        assert_eq!(u32_at(24), 16000);
+        assert_eq!(u32_at(28), 16000 * 4);
+        assert_eq!(u16_at(34), 16);
+        assert_eq!(u32_at(40), 8);
+        assert_eq!(i16::from_le_bytes([wav[46], wav[47]]), 1000);
+        assert_eq!(file_name(7), "motion_7.wav");
+    }
+
+    #[test]
+    fn test_humanized_durations() {
+        use crate::humanize::{humanize_ago, humanize_duration};
+        use chrono::{Local, TimeZone};
+        use std::time::Duration;
+
+        let secs = Duration::from_secs;
+        assert_eq!(humanize_duration(secs(0)), "0 s");
+        assert_eq!(humanize_duration(secs(59)), "59 s");
+        assert_eq!(humanize_duration(secs(60)), "1 min");
+        assert_eq!(humanize_duration(secs(3599)), "59 min");
+        assert_eq!(humanize_duration(secs(3600 * 3 + 12 * 60)), "3 h 12 min");
+        assert_eq!(humanize_duration(secs(86400 * 2 + 3600 * 5)), "2 d 5 h");
+
+        let now = Local.with_ymd_and_hms(2024, 5, 15, 15, 30, 0).unwrap();
+        let before = |s: i64| now - chrono::Duration::seconds(s);
+        assert_eq!(humanize_ago(now, now), "just now");
+        // Clock skew: a timestamp slightly in the future
+        assert_eq!(humanize_ago(before(-5), now), "just now");
+        assert_eq!(humanize_ago(before(45), now), "45 s ago");
+        assert_eq!(humanize_ago(before(59), now), "59 s ago");
+        assert_eq!(humanize_ago(before(60), now), "1 min ago");
+        assert_eq!(
+            humanize_ago(before(3 * 3600 + 12 * 60), now),
+            "3 h 12 min ago"
+        );
+
+        // Earlier days switch to clock times
+        let yesterday = Local.with_ymd_and_hms(2024, 5, 14, 14, 2, 0).unwrap();
+        assert_eq!(humanize_ago(yesterday, now), "yesterday 14:02");
+        let older = Local.with_ymd_and_hms(2024, 5, 1, 9, 5, 0).unwrap();
+        assert_eq!(humanize_ago(older, now), "2024-05-01 09:05");
+        // Across midnight, the last hour stays relative
+        let after_midnight = Local.with_ymd_and_hms(2024, 5, 15, 0, 20, 0).unwrap();
+        let late = Local.with_ymd_and_hms(2024, 5, 14, 23, 50, 0).unwrap();
+        assert_eq!(humanize_ago(late, after_midnight), "30 min ago");
+        let earlier = Local.with_ymd_and_hms(2024, 5, 14, 22, 10, 0).unwrap();
+        assert_eq!(humanize_ago(earlier, after_midnight), "yesterday 22:10");
+    }
+
+    #[test]
+    fn test_pyramid_combine() {
+        use crate::pyramid::{combine, levels_problem};
+        use crate::regions::Region;
+
+        let region = |x: f64, y: f64, size: f64, area: f64| Region {
+            x,
+            y,
+            width: size,
+            height: size,
+            area,
+            contours: 1,
+        };
+        // Full size: a small object and specks of a large one
+        let full = vec![
+            region(0.0, 0.0, 10.0, 90.0),
+            region(100.0, 100.0, 6.0, 30.0),
+            region(120.0, 100.0, 6.0, 30.0),
+        ];
+        // Halved: the specks joined into one object
+        let half = vec![region(96.0, 96.0, 40.0, 1200.0)];
+        // Quartered: the same object again, slightly larger
+        let quarter = vec![region(92.0, 92.0, 48.0, 1500.0)];
+
+        let combined = combine(vec![full.clone(), half, quarter]);
+        assert_eq!(combined.len(), 2);
+        assert_eq!(combined[0], full[0]);
+        // One box around everything, counted once at its largest area, made
+        // of the two specks' contours
+        assert_eq!(
+            combined[1],
+            Region {
+                contours: 2,
+                ..region(92.0, 92.0, 48.0, 1500.0)
+            }
+        );
+
+        // A coarse box smaller than the full-size specks keeps their area
+        let combined = combine(vec![full.clone(), vec![region(100.0, 100.0, 4.0, 10.0)]]);
+        assert_eq!(combined.len(), 3);
+        assert!(combined.iter().any(|r| r.area == 30.0 && r.x == 100.0));
+
+        assert_eq!(combine(vec![full.clone()]), full);
+        assert!(combine(Vec::new()).is_empty());
+        assert!(levels_problem(1).is_none() && levels_problem(3).is_none());
+        assert!(levels_problem(0).is_some() && levels_problem(4).is_some());
+    }
+
+    #[test]
+    fn test_frame_pacer() {
+        use crate::pacing::FramePacer;
+        use std::time::{Duration, Instant};
+
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+
+        // Following the camera never sleeps
+        let mut pacer = FramePacer::new(None);
+        assert_eq!(pacer.remaining(start), Duration::ZERO);
+        assert_eq!(pacer.remaining(start + ms(1)), Duration::ZERO);
+
+        // At 10 FPS a pass that took 30 ms sleeps the other 70 ms
+        let mut pacer = FramePacer::new(Some(10.0));
+        assert_eq!(pacer.remaining(start), Duration::ZERO);
+        assert_eq!(pacer.remaining(start + ms(30)), ms(70));
+        // measured from when the sleep ended, not from when the pass did
+        assert_eq!(pacer.remaining(start + ms(130)), ms(70));
+
+        // A slow pass isn't made up for by skipping the next sleeps
+        assert_eq!(pacer.remaining(start + ms(450)), Duration::ZERO);
+        assert_eq!(pacer.remaining(start + ms(460)), ms(90));
+
+        // A new target starts over
+        pacer.set_target(Some(20.0));
+        assert_eq!(pacer.target(), Some(20.0));
+        assert_eq!(pacer.remaining(start + ms(600)), Duration::ZERO);
+        assert_eq!(pacer.remaining(start + ms(610)), ms(40));
+        pacer.set_target(None);
+        assert_eq!(pacer.remaining(start + ms(620)), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_sensitivity_curve() {
+        use crate::config::Config;
+        use crate::sensitivity::{SensitivityCurve, MAX_THRESHOLD, MIN_THRESHOLD};
+
+        let close = |a: f64, b: f64| (a - b).abs() < 0.1;
+        for curve in SensitivityCurve::ALL {
+            assert_eq!(curve.threshold(0.0), MAX_THRESHOLD);
+            assert!(close(curve.threshold(1.0), MIN_THRESHOLD));
+            // Out-of-range values are clamped
+            assert_eq!(curve.threshold(-1.0), curve.threshold(0.0));
+            assert_eq!(curve.threshold(2.0), curve.threshold(1.0));
+        }
+
+        let linear = SensitivityCurve::Linear;
+        assert!(close(linear.threshold(0.3), 39.7));
+        assert!(close(linear.threshold(0.5), 29.5));
+        assert!(close(linear.threshold(0.9), 9.1));
+
+        // The default sensitivity keeps the long-standing threshold of 25,
+        // and each step divides the threshold by the same factor
+        let exponential = SensitivityCurve::default();
+        assert_eq!(exponential, SensitivityCurve::Exponential);
+        assert!(close(exponential.threshold(0.3), 25.0));
+        assert!(close(exponential.threshold(0.5), 14.8));
+        assert!(close(exponential.threshold(0.9), 5.2));
+        let steps: Vec<f64> = (0..10)
+            .map(|i| {
+                exponential.threshold(i as f64 / 10.0)
+                    / exponential.threshold((i + 1) as f64 / 10.0)
+            })
+            .collect();
+        assert!(steps.iter().all(|step| close(*step, steps[0])));
+
+        // The sensitive thresholds, 5 to 25, take up more of the slider
+        let span = |curve: SensitivityCurve| {
+            (0..=100)
+                .filter(|i| (5.0..=25.0).contains(&curve.threshold(*i as f64 / 100.0)))
+                .count()
+        };
+        assert!(span(exponential) > span(linear));
+
+        let config: Config = toml::from_str("[detector]\nsensitivity_curve = \"linear\"").unwrap();
+        assert_eq!(config.detector.sensitivity_curve, SensitivityCurve::Linear);
+        let args = Args::parse_from(["motion_detector", "--sensitivity-curve", "exponential"]);
+        assert_eq!(args.sensitivity_curve, Some(SensitivityCurve::Exponential));
+    }
+
+    #[test]
+    fn test_quality_controller() {
+        use crate::quality::{QualityController, FAST_FOR, MAX_STEPS, SLOW_FOR, STEP};
+        use std::time::{Duration, Instant};
+
+        let start = Instant::now();
+        let mut quality = QualityController::new(10.0, 0.5);
+        // A brief dip isn't enough
+        assert_eq!(quality.update(start, 4.0), None);
+        assert_eq!(quality.update(start + Duration::from_secs(2), 12.0), None);
+        assert_eq!(quality.update(start + Duration::from_secs(3), 4.0), None);
+
+        // Staying slow steps down, and the next step waits a full window
+        let change = quality.update(start + Duration::from_secs(3) + SLOW_FOR, 4.0);
+        let change = change.expect("stepped down");
+        assert_eq!((change.from, change.to), (0.5, 0.5 * STEP));
+        assert!(change.message().starts_with("QUALITY down"));
+        let later = start + Duration::from_secs(3) + SLOW_FOR;
+        assert_eq!(quality.update(later + Duration::from_secs(1), 4.0), None);
+
+        // It never goes below MAX_STEPS, or back above the configured scale
+        let mut now = later;
+        for _ in 0..10 {
+            now += SLOW_FOR;
+            quality.update(now, 1.0);
+            now += SLOW_FOR;
+            quality.update(now, 1.0);
+        }
+        assert!((quality.scale() - 0.5 * STEP.powi(MAX_STEPS as i32)).abs() < 1e-9);
+        for _ in 0..20 {
+            now += FAST_FOR;
+            quality.update(now, 30.0);
+            now += FAST_FOR;
+            quality.update(now, 30.0);
+        }
+        assert_eq!(quality.scale(), 0.5);
+
+        // Without headroom it stays put
+        let mut quality = QualityController::new(10.0, 1.0);
+        quality.update(start, 5.0);
+        quality.update(start + SLOW_FOR, 5.0);
+        assert_eq!(quality.update(start + SLOW_FOR * 2, 15.0), None);
+        assert_eq!(quality.update(start + SLOW_FOR * 2 + FAST_FOR, 15.0), None);
+        assert_eq!(quality.scale(), STEP);
+    }
+
+    #[test]
+    fn test_region_merging() {
+        use crate::regions::{merge_regions, MergeGap, Region};
+
+        let region = |x, y, width, height| Region {
+            x,
+            y,
+            width,
+            height,
+            area: width * height,
+            contours: 1,
+        };
+
+        // Two overlapping pieces of one object, plus one far away
+        let found = vec![
+            region(0.0, 0.0, 10.0, 10.0),
+            region(200.0, 200.0, 10.0, 10.0),
+            region(5.0, 5.0, 10.0, 10.0),
+        ];
+        let merged = merge_regions(found.clone(), 0.0);
+        assert_eq!(merged.len(), 2);
+        assert!(merged.contains(&Region {
+            x: 0.0,
+            y: 0.0,
+            width: 15.0,
+            height: 15.0,
+            area: 200.0,
+            contours: 2,
+        }));
+
+        // A gap of 5px merges only when within the merge distance
+        let split = vec![region(0.0, 0.0, 10.0, 10.0), region(15.0, 0.0, 10.0, 10.0)];
+        assert_eq!(merge_regions(split.clone(), 4.0).len(), 2);
+        assert_eq!(merge_regions(split, 5.0).len(), 1);
+        assert_eq!(merge_regions(found, 300.0).len(), 1);
+
+        // Boxes chain through a neighbour that is near both
+        let row = vec![
+            region(0.0, 0.0, 10.0, 10.0),
+            region(40.0, 0.0, 10.0, 10.0),
+            region(20.0, 0.0, 10.0, 10.0),
+        ];
+        let merged = merge_regions(row, 10.0);
+        assert_eq!(merged.len(), 1);
+        assert_eq!((merged[0].width, merged[0].contours), (50.0, 3));
+        assert!(merged[0].is_merged());
+
+        // The gap in pixels, or as a share of the frame width
+        assert_eq!("12".parse(), Ok(MergeGap::Pixels(12)));
+        assert_eq!("2.5%".parse(), Ok(MergeGap::Fraction(0.025)));
+        assert_eq!(MergeGap::Fraction(0.025).pixels(640), 16.0);
+        assert_eq!(MergeGap::default().pixels(640), 0.0);
+        assert!("150%".parse::<MergeGap>().is_err());
+        assert!("-3".parse::<MergeGap>().is_err());
+        assert!("near".parse::<MergeGap>().is_err());
+    }
+
+    #[test]
+    fn test_min_area_units() {
+        use crate::config::{frame_area_problem, validate, Config, DetectorConfig};
+        use crate::regions::Region;
+        use crate::scale::FrameScale;
+
+        // Plain numbers stay pixels
+        assert_eq!("800".parse(), Ok(MinArea::Pixels(800)));
+        assert_eq!("0.5%".parse(), Ok(MinArea::Fraction(0.005)));
+        assert_eq!(" 2 % ".parse(), Ok(MinArea::Fraction(0.02)));
+        assert!("150%".parse::<MinArea>().is_err());
+        assert!("-1".parse::<MinArea>().is_err());
+        assert!("big".parse::<MinArea>().is_err());
+        assert_eq!(MinArea::Fraction(0.07).to_string(), "7%");
+        assert_eq!(MinArea::Pixels(500).to_string(), "500");
+
+        // A share resolves against the frame it's applied to
+        let half_percent = MinArea::Fraction(0.005);
+        assert_eq!(half_percent.pixels(640.0 * 480.0), 1536);
+        assert_eq!(half_percent.pixels(1920.0 * 1080.0), 10368);
+        assert_eq!(MinArea::Pixels(500).pixels(1920.0 * 1080.0), 500);
+        assert_eq!(MinArea::Fraction(0.000001).pixels(320.0 * 240.0), 1);
+        assert_eq!(half_percent.describe(Some(640.0 * 480.0)), "0.5% (1536 px)");
+        assert_eq!(half_percent.describe(None), "0.5%");
+        assert_eq!(MinArea::Pixels(500).describe(None), "500 px");
+        assert!(MinArea::Fraction(0.0).is_zero());
+        assert!(!half_percent.is_zero());
+
+        // With --process-scale regions are scaled back to capture pixels
+        // first, so an object covering 0.5% of the shrunk frame still
+        // covers 0.5% of the full one
+        let scale = FrameScale::new((1920, 1080), 0.5);
+        let (width, height) = scale.process;
+        let side = (width as f64 * height as f64 * 0.005).sqrt();
+        let region = scale.region_to_capture(&Region {
+            x: 0.0,
+            y: 0.0,
+            width: side,
+            height: side,
+            area: side * side,
+            contours: 1,
+        });
+        let threshold = half_percent.pixels(1920.0 * 1080.0) as f64;
+        assert!((region.area - threshold).abs() < threshold * 0.01);
+
+        let args = Args::parse_from(["motion_detector", "--min-area", "0.5%"]);
+        assert_eq!(args.min_area, half_percent);
+        assert!(args.validate().is_ok());
+        let args = Args::parse_from(["motion_detector", "--min-area", "0%"]);
+        assert!(args.validate().is_err());
+
+        // Config takes either form; a share of the frame always fits it
+        let config: Config = toml::from_str(
+            "[camera.porch]\ndevice = 0\nmin_area = \"1.5%\"\n\n[camera.yard]\ndevice = 1\nmin_area = 900",
+        )
+        .unwrap();
+        let cameras = config.named_cameras();
+        assert_eq!(cameras[0].min_area, Some(MinArea::Fraction(0.015)));
+        assert_eq!(cameras[1].min_area, Some(MinArea::Pixels(900)));
+        assert!(validate(&config).is_ok());
+        assert!(toml::from_str::<Config>("[camera.porch]\nmin_area = \"lots\"").is_err());
+        let small = DetectorConfig {
+            resolution: Some([320, 240]),
+            ..DetectorConfig::default()
+        };
+        assert!(frame_area_problem(MinArea::Pixels(100_000), &small).is_some());
+        assert!(frame_area_problem(MinArea::Fraction(1.0), &small).is_none());
+
+        // The session journal keeps pixels as a number, as it used to
+        let value = serde_json::to_value(MinArea::Pixels(800)).unwrap();
+        assert_eq!(value, serde_json::json!(800));
+        let value = serde_json::to_value(half_percent).unwrap();
+        assert_eq!(value, serde_json::json!("0.5%"));
+        assert_eq!(
+            serde_json::from_value::<MinArea>(value).unwrap(),
+            half_percent
+        );
+    }
+
+    #[test]
+    fn test_aspect_filter() {
+        use crate::config::{validate, Config};
+        use crate::regions::{aspect_problem, AspectRange, Region};
+
+        let region = |width, height| Region {
+            x: 0.0,
+            y: 0.0,
+            width,
+            height,
+            area: width * height,
+            contours: 1,
+        };
+        assert_eq!(region(40.0, 10.0).aspect(), 4.0);
+        assert_eq!(region(10.0, 40.0).aspect(), 0.25);
+        assert_eq!(region(3.0, 0.0).aspect(), 3.0);
+
+        let range = AspectRange {
+            min: Some(0.2),
+            max: Some(5.0),
+        };
+        assert!(range.contains(1.0));
+        assert!(range.contains(0.2));
+        assert!(range.contains(5.0));
+        assert!(!range.contains(0.1));
+        assert!(!range.contains(8.0));
+        assert!(AspectRange::default().contains(100.0));
+        assert!(AspectRange::default().is_unbounded());
+        assert!(!range.is_unbounded());
+
+        assert_eq!(aspect_problem(None, None), None);
+        assert_eq!(aspect_problem(Some(0.5), Some(0.5)), None);
+        assert!(aspect_problem(Some(0.0), None).is_some());
+        assert!(aspect_problem(None, Some(f64::NAN)).is_some());
+        assert!(aspect_problem(Some(3.0), Some(2.0))
+            .unwrap()
+            .contains("above max_aspect"));
+
+        let args = Args::parse_from([
+            "motion_detector",
+            "--min-aspect",
+            "0.2",
+            "--max-aspect",
+            "5",
+        ]);
+        assert_eq!((args.min_aspect, args.max_aspect), (Some(0.2), Some(5.0)));
+
+        let config: Config =
+            toml::from_str("[detector]\nmin_aspect = 0.25\nmax_aspect = 4.0").unwrap();
+        assert_eq!(config.detector.min_aspect, Some(0.25));
+        assert_eq!(config.detector.max_aspect, Some(4.0));
+        assert!(validate(&config).is_ok());
+        let config: Config =
+            toml::from_str("[detector]\nmin_aspect = 4.0\nmax_aspect = 0.25").unwrap();
+        let errors = validate(&config).unwrap_err();
+        assert_eq!(errors[0].section, "[detector]");
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_merge_gap_joins_fragments() {
+        use crate::detector::clean_mask;
+        use crate::regions::{merge_regions, MotionScore, Region};
+        use opencv::core::{self, Mat, Scalar, Vector};
+        use opencv::imgproc;
+
+        // Two 20x20 blobs, each under a min_area of 600, `gap` pixels apart
+        let blobs = |gap: i32| -> Vec<Region> {
+            let mut thresh =
+                Mat::new_rows_cols_with_default(120, 320, core::CV_8UC1, Scalar::all(0.0)).unwrap();
+            for x in [20, 40 + gap] {
+                imgproc::rectangle(
+                    &mut thresh,
+                    core::Rect::new(x, 50, 20, 20),
+                    Scalar::all(255.0),
+                    imgproc::FILLED,
+                    imgproc::LINE_8,
+                    0,
+                )
+                .unwrap();
+            }
+            let (_, dilated) = clean_mask(&thresh, 0, 1).unwrap();
+            let mut contours = Vector::<Vector<core::Point>>::new();
+            imgproc::find_contours(
+                &dilated,
+                &mut contours,
+                imgproc::RETR_EXTERNAL,
+                imgproc::CHAIN_APPROX_SIMPLE,
+                core::Point::new(-1, -1),
+            )
+            .unwrap();
+            contours
+                .iter()
+                .map(|contour| {
+                    let rect = imgproc::bounding_rect(&contour).unwrap();
+                    Region {
+                        x: rect.x as f64,
+                        y: rect.y as f64,
+                        width: rect.width as f64,
+                        height: rect.height as f64,
+                        area: imgproc::contour_area(&contour, false).unwrap(),
+                        contours: 1,
+                    }
+                })
+                .collect()
+        };
+        let min_area = 600;
+        let frame_area = 320.0 * 120.0;
+        let triggered = |found: Vec<Region>| {
+            let merged = merge_regions(found, 30.0);
+            MotionScore::new(&merged, min_area, frame_area).triggered()
+        };
+
+        // Close together they count as one object over min_area
+        let near = blobs(20);
+        assert_eq!(near.len(), 2);
+        assert!(near.iter().all(|region| region.area < min_area as f64));
+        assert!(triggered(near));
+
+        // Far apart each stays a small region on its own
+        let far = blobs(200);
+        assert_eq!(far.len(), 2);
+        assert!(!triggered(far));
+    }
+
+    #[test]
+    fn test_motion_score() {
+        use crate::regions::{MotionScore, Region};
+
+        let region = |area| Region {
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+            area,
+            contours: 1,
+        };
+        let frame_area = 640.0 * 480.0;
+
+        // The largest region's share of the frame, against min_area's share
+        let score = MotionScore::new(&[region(300.0), region(3072.0)], 500, frame_area);
+        assert_eq!(score.score, 0.01);
+        assert!((score.threshold - 500.0 / frame_area).abs() < 1e-12);
+        assert!(score.triggered());
+
+        // Same decision as comparing areas with min_area
+        for area in [499.5, 500.0, 500.5] {
+            let score = MotionScore::new(&[region(area)], 500, frame_area);
+            assert_eq!(score.triggered(), area > 500.0, "{}", area);
+        }
+        let still = MotionScore::new(&[], 500, frame_area);
+        assert_eq!(still.score, 0.0);
+        assert!(!still.triggered());
+    }
+
+    #[test]
+    fn test_self_test_checks() {
+        use crate::selftest::*;
+        use chrono::{Local, TimeZone};
+        use std::path::Path;
+
+        // Mock camera that delivers every `every`-th frame
+        struct FlakyCamera {
+            reads: u32,
+            every: u32,
+        }
+        impl FrameSource for FlakyCamera {
+            fn grab_frame(&mut self) -> anyhow::Result<bool> {
+                self.reads += 1;
+                Ok(self.every > 0 && self.reads.is_multiple_of(self.every))
+            }
+        }
+        let status = |every| check_camera(&mut FlakyCamera { reads: 0, every }, 30).status;
+        assert_eq!(status(1), CheckStatus::Pass);
+        assert_eq!(status(2), CheckStatus::Warn);
+        assert_eq!(status(0), CheckStatus::Fail);
+
+        let dir = std::env::temp_dir().join(format!("motion_selftest_{}", std::process::id()));
+        assert_eq!(check_output_dir(&dir).status, CheckStatus::Pass);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        // A path below a regular file can't be created
+        let file = dir.join("file");
+        std::fs::write(&file, b"").unwrap();
+        assert_eq!(
+            check_output_dir(&file.join("pics")).status,
+            CheckStatus::Fail
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let df = "Filesystem 1024-blocks Used Available Capacity Mounted on\n\
+                  /dev/sda1 1000000 400000 600000 40% /\n";
+        assert_eq!(parse_df_available(df), Some(600000 * 1024));
+        let pics = Path::new("pics");
+        assert_eq!(
+            check_disk_space(pics, Some(10 << 20)).status,
+            CheckStatus::Fail
+        );
+        assert_eq!(
+            check_disk_space(pics, Some(500 << 20)).status,
+            CheckStatus::Warn
+        );
+        assert_eq!(
+            check_disk_space(pics, Some(5 << 30)).status,
+            CheckStatus::Pass
+        );
+        assert_eq!(check_disk_space(pics, None).status, CheckStatus::Skip);
+
+        let unset = Local.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(check_clock(unset).status, CheckStatus::Warn);
+        assert_eq!(check_clock(Local::now()).status, CheckStatus::Pass);
+
+        let results = vec![check_notifiers(), check_clock(unset)];
+        assert!(finish("Self-test", &results).is_ok());
+        let results = vec![CheckResult::new("Camera", CheckStatus::Fail, "no frames")];
+        assert!(finish("Self-test", &results).is_err());
+    }
+
+    #[test]
+    fn test_absence_alarm() {
+        use crate::absence::*;
+        use chrono::NaiveTime;
+        use std::time::{Duration, Instant};
+
+        assert_eq!(
+            parse_duration("12h").unwrap(),
+            Duration::from_secs(12 * 3600)
+        );
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert!(parse_duration("12x").is_err());
+        assert!(parse_duration("0m").is_err());
+
+        let config = AbsenceConfig {
+            alert_after: Some("10m".to_string()),
+            repeat_every: Some("5m".to_string()),
+            active_hours: Some(["07:00".to_string(), "22:00".to_string()]),
+        };
+        let mut monitor = AbsenceMonitor::from_config(&config).unwrap().unwrap();
+        let start = Instant::now();
+        let day = NaiveTime::from_hms_opt(12, 0, 0).unwrap();
+        let night = NaiveTime::from_hms_opt(23, 0, 0).unwrap();
+        let at = |mins: u64| start + Duration::from_secs(mins * 60);
+
+        assert_eq!(monitor.tick(at(0), day, true), None);
+        assert_eq!(monitor.tick(at(6), day, true), None);
+        // Stopped and night-time minutes don't count
+        assert_eq!(monitor.tick(at(30), day, false), None);
+        assert_eq!(monitor.tick(at(60), night, true), None);
+        assert!(monitor.status().paused);
+        assert_eq!(monitor.status().remaining, Duration::from_secs(4 * 60));
+
+        assert!(matches!(
+            monitor.tick(at(64), day, true),
+            Some(AbsenceAlert::NoMotion { count: 1, .. })
+        ));
+        assert_eq!(monitor.tick(at(66), day, true), None);
+        assert!(matches!(
+            monitor.tick(at(69), day, true),
+            Some(AbsenceAlert::NoMotion { count: 2, .. })
+        ));
+        assert!(matches!(
+            monitor.motion(),
+            Some(AbsenceAlert::Recovered { .. })
+        ));
+        assert_eq!(monitor.motion(), None);
+        assert!(!monitor.status().alerting);
+    }
+
+    #[test]
+    fn test_clock_jumps() {
+        use crate::absence::{AbsenceConfig, AbsenceMonitor};
+        use crate::clock::{Clock, JumpWatch};
+        use crate::events::Debouncer;
+        use crate::schedule::{Schedule, ScheduleClock};
+        use chrono::{DateTime, Local, TimeDelta, TimeZone};
+        use std::cell::Cell;
+        use std::time::{Duration, Instant};
+
+        // A monotonic clock and a wall clock that can be set on its own
+        struct FakeClock {
+            instant: Cell<Instant>,
+            local: Cell<DateTime<Local>>,
+        }
+        impl FakeClock {
+            fn advance(&self, secs: u64) {
+                self.instant
+                    .set(self.instant.get() + Duration::from_secs(secs));
+                self.local
+                    .set(self.local.get() + TimeDelta::seconds(secs as i64));
+            }
+            fn set(&self, local: DateTime<Local>) {
+                self.local.set(local);
+            }
+        }
+        impl Clock for FakeClock {
+            fn instant(&self) -> Instant {
+                self.instant.get()
+            }
+            fn local(&self) -> DateTime<Local> {
+                self.local.get()
+            }
+        }
+
+        // A Pi booting without an RTC, at noon on 1 January 1970
+        let boot = Local.with_ymd_and_hms(1970, 1, 1, 12, 0, 0).unwrap();
+        let synced = Local.with_ymd_and_hms(2026, 10, 17, 23, 0, 0).unwrap();
+        let clock = FakeClock {
+            instant: Cell::new(Instant::now()),
+            local: Cell::new(boot),
+        };
+        let mut watch = JumpWatch::new();
+        let mut schedule = ScheduleClock::new(Schedule::parse("22:00..06:00").unwrap(), None);
+        let mut debouncer = Debouncer::adaptive(
+            Duration::from_secs(10),
+            Duration::from_secs(10),
+            clock.instant(),
+        );
+        let config = AbsenceConfig {
+            alert_after: Some("1h".to_string()),
+            ..Default::default()
+        };
+        let mut absence = AbsenceMonitor::from_config(&config).unwrap().unwrap();
+        let mut tick = |clock: &FakeClock| {
+            let jump = watch.check(clock);
+            if jump.is_some() {
+                schedule.reset();
+            }
+            let alert = absence.tick(clock.instant(), clock.local().time(), true);
+            (jump, schedule.is_active(clock.local()), alert)
+        };
+
+        assert_eq!(tick(&clock), (None, false, None));
+        clock.advance(5);
+        assert_eq!(tick(&clock), (None, false, None));
+        assert!(debouncer.ready(clock.instant() + Duration::from_secs(11)));
+
+        // NTP sets the clock: one second passes, 56 years on the wall clock
+        clock.advance(1);
+        clock.set(synced);
+        let (jump, armed, alert) = tick(&clock);
+        let jump = jump.unwrap();
+        assert!(jump.offset > TimeDelta::days(365 * 56));
+        assert_eq!(jump.to, synced);
+        assert!(jump.message().contains("forward"));
+        // The schedule follows the new time, but no idle hour passed and
+        // the cooldown still runs
+        assert!(armed);
+        assert_eq!(alert, None);
+        assert!(!debouncer.ready(clock.instant() + Duration::from_secs(12)));
+        clock.advance(30);
+        assert_eq!(tick(&clock), (None, true, None));
+
+        // Set back two hours, e.g. a wrong time zone corrected
+        clock.advance(1);
+        clock.set(clock.local() - TimeDelta::hours(2));
+        let (jump, armed, alert) = tick(&clock);
+        let jump = jump.unwrap();
+        assert!(jump.message().contains("back"));
+        assert!((jump.offset + TimeDelta::hours(2)).abs() < TimeDelta::seconds(2));
+        assert!(!armed);
+        assert_eq!(alert, None);
+
+        // Drift under a minute isn't a jump
+        clock.advance(10);
+        clock.set(clock.local() + TimeDelta::seconds(30));
+        assert_eq!(tick(&clock).0, None);
+    }
+
+    #[test]
+    fn test_frame_timestamps() {
+        use crate::timestamp::{format_position, Clocks, FrameTime, TimestampSource};
+        use chrono::{Local, TimeDelta, TimeZone};
+        use std::time::{Duration, Instant};
+
+        let wall = Local.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap();
+        let clocks = Clocks {
+            instant: Instant::now(),
+            wall,
+            monotonic_ms: Some(3_600_000.0),
+        };
+
+        // A V4L2 buffer timestamp 80 ms old
+        let frame = FrameTime::from_device(3_599_920.0, &clocks);
+        assert_eq!(frame.source, TimestampSource::Monotonic);
+        assert_eq!(frame.latency, Duration::from_millis(80));
+        assert_eq!(clocks.instant - frame.captured, Duration::from_millis(80));
+        assert_eq!(frame.wall, wall - TimeDelta::milliseconds(80));
+        assert_eq!(frame.label(), "2026-10-17 11:59:59");
+
+        // A wall-clock timestamp
+        let reported = wall.timestamp_millis() as f64 - 120.0;
+        let frame = FrameTime::from_device(reported, &clocks);
+        assert_eq!(frame.source, TimestampSource::Realtime);
+        assert_eq!(frame.latency, Duration::from_millis(120));
+
+        // Nothing reported, or on a clock that matches neither
+        for reported in [0.0, -1.0, f64::NAN, 40.0] {
+            let frame = FrameTime::from_device(reported, &clocks);
+            assert_eq!(frame, FrameTime::processed(&clocks), "{}", reported);
+        }
+
+        let frame = FrameTime::in_file(83_456.0, &clocks);
+        assert_eq!(frame.source, TimestampSource::File);
+        assert_eq!(frame.label(), "2026-10-17 12:00:00 @ 00:01:23.456");
+        assert_eq!(
+            format_position(Duration::from_secs(3 * 3600 + 5)),
+            "03:00:05.000"
+        );
+    }
+
+    #[test]
+    fn test_jpeg_header() {
+        use crate::config::SnapshotConfig;
+        use crate::jpeg::{read_header, unsupported, ChromaSubsampling, JpegHeader};
+
+        // SOI, a DQT stub, then a frame header for a 16x16 color image
+        let jpeg = |sof: u8, luma: u8| -> Vec<u8> {
+            let mut bytes = vec![0xFF, 0xD8, 0xFF, 0xDB, 0x00, 0x04, 0x00, 0x00];
+            bytes.extend([0xFF, sof, 0x00, 17, 8, 0, 16, 0, 16, 3]);
+            bytes.extend([1, luma, 0, 2, 0x11, 1, 3, 0x11, 1]);
+            bytes
+        };
+        assert_eq!(
+            read_header(&jpeg(0xC0, 0x22)),
+            Some(JpegHeader {
+                progressive: false,
+                chroma: Some(ChromaSubsampling::Chroma420)
+            })
+        );
+        let header = read_header(&jpeg(0xC2, 0x11)).unwrap();
+        assert_eq!(
+            header,
+            JpegHeader {
+                progressive: true,
+                chroma: Some(ChromaSubsampling::Chroma444)
+            }
+        );
+        assert_eq!(read_header(&jpeg(0xC0, 0x21)).unwrap().chroma, None);
+        assert_eq!(read_header(b"not a jpeg"), None);
+        assert_eq!(read_header(&jpeg(0xC0, 0x22)[..12]), None);
+
+        // A build that ignored the settings is reported
+        let config: SnapshotConfig =
+            toml::from_str("jpeg_progressive = true\njpeg_chroma = \"444\"").unwrap();
+        assert!(unsupported(&config, &header).is_empty());
+        let baseline = read_header(&jpeg(0xC0, 0x22)).unwrap();
+        assert_eq!(
+            unsupported(&config, &baseline),
+            ["progressive encoding", "4:4:4 chroma subsampling"]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_dedup() {
+        use crate::config::SnapshotConfig;
+        use crate::dedup::{dhash, distance, SnapshotDedup};
+        use std::time::{Duration, Instant};
+
+        // A lit doorway on a darker wall, and the same scene moved or lit
+        // differently
+        let (width, height) = (160, 120);
+        let scene = |dx: usize, gain: f64, offset: f64| -> Vec<u8> {
+            let mut pixels = vec![0u8; width * height];
+            for y in 0..height {
+                for x in 0..width {
+                    let sx = x.saturating_sub(dx);
+                    let base = if (60..100).contains(&sx) && y > 20 {
+                        200.0
+                    } else {
+                        40.0 + sx as f64 / 4.0
+                    };
+                    pixels[y * width + x] = (base * gain + offset).clamp(0.0, 255.0) as u8;
+                }
+            }
+            pixels
+        };
+        let original = dhash(&scene(0, 1.0, 0.0), width, height);
+        let shifted = dhash(&scene(3, 1.0, 0.0), width, height);
+        let brighter = dhash(&scene(0, 1.1, 20.0), width, height);
+        let mut other = scene(0, 1.0, 0.0);
+        other.reverse();
+        let other = dhash(&other, width, height);
+        assert_eq!(distance(original, original), 0);
+        assert!(distance(original, shifted) < 6);
+        assert!(distance(original, brighter) < 6);
+        assert!(distance(original, other) > 12);
+
+        let config = SnapshotConfig {
+            dedup_threshold: 6,
+            dedup_window: "10m".to_string(),
+            ..Default::default()
+        };
+        let mut dedup = SnapshotDedup::from_config(&config).unwrap().unwrap();
+        let start = Instant::now();
+        assert_eq!(dedup.check(start, original, 1), None);
+        assert_eq!(
+            dedup.check(start + Duration::from_secs(60), shifted, 2),
+            Some(1)
+        );
+        assert_eq!(dedup.check(start + Duration::from_secs(90), other, 3), None);
+        assert_eq!(
+            dedup.check(start + Duration::from_secs(120), brighter, 4),
+            Some(1)
+        );
+        // Only saved snapshots start a window
+        assert_eq!(
+            dedup.check(start + Duration::from_secs(601), brighter, 5),
+            None
+        );
+        assert_eq!(dedup.deduplicated(), 2);
+
+        // Off by default; out-of-range settings are reported
+        assert!(SnapshotDedup::from_config(&SnapshotConfig::default())
+            .unwrap()
+            .is_none());
+        for (threshold, window) in [(65, "10m"), (6, "soon")] {
+            let config = SnapshotConfig {
+                dedup_threshold: threshold,
+                dedup_window: window.to_string(),
+                ..Default::default()
+            };
+            assert!(SnapshotDedup::from_config(&config).is_err());
+        }
+    }
+
+    #[test]
+    fn test_log_rotation() {
+        use crate::logfile::{parse_size, rotated_path, RotatingFile, Rotation};
+
+        assert_eq!(parse_size("10M").unwrap(), 10 * 1024 * 1024);
+        assert_eq!(parse_size(" 512k ").unwrap(), 512 * 1024);
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        for bad in ["", "M", "0", "ten", "1.5M", "99999999999G"] {
+            assert!(parse_size(bad).is_err(), "{}", bad);
+        }
+
+        let dir = std::env::temp_dir().join(format!("motion_log_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("motion.log");
+        let read = |path: &std::path::Path| std::fs::read_to_string(path).unwrap_or_default();
+        let rotation = Rotation {
+            max_bytes: 12,
+            keep: 2,
+        };
+
+        // Lines of 6 bytes with the newline, two to a file
+        let mut log = RotatingFile::open(path.clone(), rotation).unwrap();
+        for line in ["one..", "two..", "three"] {
+            log.write_line(line).unwrap();
+        }
+        assert_eq!(read(&path), "three\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "one..\ntwo..\n");
+
+        // A restart appends, counting what is already there
+        drop(log);
+        let mut log = RotatingFile::open(path.clone(), rotation).unwrap();
+        for line in ["four.", "five.", "six..", "seven"] {
+            log.write_line(line).unwrap();
+        }
+        assert_eq!(read(&path), "seven\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "five.\nsix..\n");
+        assert_eq!(read(&rotated_path(&path, 2)), "three\nfour.\n");
+        assert!(!rotated_path(&path, 3).exists());
+
+        // Keeping none starts the file over
+        let rotation = Rotation {
+            max_bytes: 12,
+            keep: 0,
+        };
+        let mut log = RotatingFile::open(path.clone(), rotation).unwrap();
+        log.write_line("eight").unwrap();
+        log.write_line("nine.").unwrap();
+        assert_eq!(read(&path), "nine.\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_privacy_region() {
+        use crate::privacy::{PrivacyRegion, PrivacyStyle};
+
+        let region: PrivacyRegion = "10, 20,100,50".parse().unwrap();
+        assert_eq!(
+            region,
+            PrivacyRegion {
+                x: 10,
+                y: 20,
+                width: 100,
+                height: 50
+            }
+        );
+        for bad in [
+            "",
+            "10,20,100",
+            "10,20,100,50,1",
+            "10,20,0,50",
+            "-1,0,5,5",
+            "a,b,c,d",
+        ] {
+            assert!(bad.parse::<PrivacyRegion>().is_err(), "{}", bad);
+        }
+
+        // Clipped to the frame, and scaled for the processing copy
+        assert_eq!(region.clip((640, 480), 1.0), Some((10, 20, 100, 50)));
+        assert_eq!(region.clip((80, 480), 1.0), Some((10, 20, 70, 50)));
+        assert_eq!(region.clip((320, 240), 0.5), Some((5, 10, 50, 25)));
+        assert_eq!(region.clip((10, 480), 1.0), None);
+
+        let config: crate::config::Config = toml::from_str(
+            "[snapshots]\nprivacy_regions = [[0, 0, 64, 32]]\nprivacy_style = \"black\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.snapshots.privacy_regions.len(), 1);
+        assert_eq!(config.snapshots.privacy_style, PrivacyStyle::Black);
+        assert!(toml::from_str::<crate::config::Config>(
+            "[snapshots]\nprivacy_regions = [[0, 0, 0, 32]]\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_build_info() {
+        use crate::buildinfo::BuildInfo;
+
+        let info = BuildInfo {
+            version: "1.2.3",
+            git_hash: "abc1234def",
+            build_date: "2024-05-01",
+            opencv: None,
+            features: vec!["mock-camera", "audio"],
+            backends: Vec::new(),
+        };
+        assert_eq!(
+            info.to_string(),
+            "motion_detector 1.2.3\n\
+             Commit:   abc1234def\n\
+             Built:    2024-05-01\n\
+             OpenCV:   not linked\n\
+             Features: mock-camera, audio\n\
+             Backends: none\n"
+        );
+
+        let info = BuildInfo::collect();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.features.contains(&"camera"), cfg!(feature = "camera"));
+    }
+
+    #[test]
+    fn test_state_file() {
+        use crate::state::{load, StateFile};
+
+        let dir = std::env::temp_dir().join(format!("motion_state_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+
+        // First run starts from zero and saves on flush
+        let mut first = StateFile::open(path.clone()).unwrap();
+        assert_eq!(first.previous().sessions, 1);
+        first.flush(3, 100).unwrap();
+        let saved = load(&path).unwrap();
+        assert_eq!((saved.motion_count, saved.total_frames), (3, 100));
+
+        // A restart keeps counting from the saved totals
+        let mut second = StateFile::open(path.clone()).unwrap();
+        assert_eq!(second.previous().sessions, 2);
+        assert_eq!(second.previous().first_started, saved.first_started);
+        second.flush(2, 50).unwrap();
+        let saved = load(&path).unwrap();
+        assert_eq!((saved.motion_count, saved.total_frames), (5, 150));
+
+        // Writes go through a temp file that doesn't outlive the rename
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(StateFile::open(path).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_detector_thread_shutdown() {
+        use crate::gui::{pending_messages, GuiMessage};
+        use crossbeam_channel::bounded;
+        use std::time::Duration;
+
+        // Same message loop as the detector thread, minus the camera
+        let spawn = |receiver| {
+            let (done, finished) = bounded::<usize>(1);
+            std::thread::spawn(move || {
+                let mut handled = 0;
+                while let Some(messages) = pending_messages(&receiver) {
+                    handled += messages.len();
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                let _ = done.send(handled);
+            });
+            finished
+        };
+
+        let (sender, receiver) = bounded(10);
+        let finished = spawn(receiver);
+        sender.send(GuiMessage::StartDetection).unwrap();
+        sender.send(GuiMessage::Shutdown).unwrap();
+        assert!(finished.recv_timeout(Duration::from_secs(2)).is_ok());
+
+        // Dropping the GUI's sender also stops the thread
+        let (sender, receiver) = bounded::<GuiMessage>(10);
+        let finished = spawn(receiver);
+        drop(sender);
+        assert_eq!(finished.recv_timeout(Duration::from_secs(2)), Ok(0));
+    }
+
+    #[test]
+    fn test_key_frame_selection() {
+        use crate::incident::KeyFrame;
+
+        let mut key_frames = KeyFrame::default();
+        let mut copies = 0;
+        for (score, frame) in [(900.0, "first"), (4000.0, "largest"), (1500.0, "later")] {
+            key_frames
+                .offer(1, score, || {
+                    copies += 1;
+                    Ok::<_, ()>(frame)
+                })
+                .unwrap();
+        }
+        // Only frames that beat the best so far are copied
+        assert_eq!(copies, 2);
+        assert_eq!(key_frames.take(2), None);
+        assert_eq!(key_frames.take(1), Some("largest"));
+        assert_eq!(key_frames.take(1), None);
+
+        // A new incident starts over
+        key_frames.offer(2, 10.0, || Ok::<_, ()>("a")).unwrap();
+        key_frames.offer(3, 5.0, || Ok::<_, ()>("b")).unwrap();
+        assert_eq!(key_frames.take(3), Some("b"));
+    }
+
+    #[test]
+    fn test_activity_log() {
+        use crate::gui::{ActivityLog, LogLevel};
+
+        let mut log = ActivityLog::new(3);
+        log.push(LogLevel::Error, "camera lost");
+        log.push(LogLevel::Info, "Detection started");
+        log.push(LogLevel::Event, "Motion detected");
+        log.push(LogLevel::Debug, "Sensitivity → 0.42 (final)");
+
+        // Oldest entry dropped once over capacity
+        assert_eq!(log.error_count(), 0);
+        let all = LogLevel::ALL;
+        assert_eq!(log.filtered(&all, "").count(), 3);
+        assert_eq!(log.filtered(&[LogLevel::Event], "").count(), 1);
+        assert_eq!(log.filtered(&all, "DETECT").count(), 2);
+
+        log.push(LogLevel::Info, r#"Opened "porch, east".mp4"#);
+        let csv = log.to_csv();
+        assert!(csv.starts_with("time,level,message\n"));
+        assert_eq!(csv.lines().count(), 4);
+        assert!(
+            csv.ends_with(",Info,\"Opened \"\"porch, east\"\".mp4\"\n"),
+            "{}",
+            csv
+        );
+    }
+
+    #[test]
+    fn test_notification_center() {
+        use crate::alerts::{AlertKind, AppAlert, NotificationCenter, Severity};
+        use chrono::{Duration, Local};
+
+        let start = Local::now();
+        let mut center = NotificationCenter::default();
+        let upload =
+            |message: &str| AppAlert::new(AlertKind::UploadFailed, Severity::Error, message);
+        let camera = || AppAlert::new(AlertKind::CameraLost, Severity::Critical, "no frames");
+
+        // Errors don't toast, repeats fold into one notification
+        assert!(!center.record(upload("HTTP 503"), start));
+        assert!(!center.record(upload("HTTP 500"), start + Duration::hours(48)));
+        assert_eq!(center.items().len(), 1);
+        let item = &center.items()[0];
+        assert_eq!(item.count, 2);
+        assert_eq!(item.message, "HTTP 500");
+        assert_eq!(
+            (item.first, item.last),
+            (start, start + Duration::hours(48))
+        );
+
+        // A critical alert toasts once while it waits unread, and comes first
+        assert!(center.record(camera(), start));
+        assert!(!center.record(camera(), start));
+        assert_eq!(center.items()[0].kind, AlertKind::CameraLost);
+        assert_eq!(center.unread(), 2);
+        center.mark_all_read();
+        assert_eq!(center.unread(), 0);
+        assert!(center.record(camera(), start));
+        assert_eq!(center.unread(), 1);
+
+        // Escalating to critical toasts and keeps the higher severity
+        let disk = |severity| AppAlert::new(AlertKind::LowDisk, severity, "90 MB free");
+        assert!(!center.record(disk(Severity::Warning), start));
+        assert!(center.record(disk(Severity::Critical), start));
+        assert!(!center.record(disk(Severity::Warning), start));
+        assert_eq!(center.items()[0].severity, Severity::Critical);
+
+        // Dismissing forgets the count
+        center.dismiss(AlertKind::UploadFailed);
+        assert_eq!(center.items().len(), 2);
+        center.record(upload("HTTP 503"), start);
+        assert_eq!(center.items()[0].count, 1);
+        center.clear();
+        assert!(center.items().is_empty());
+    }
+
+    #[test]
+    fn test_hourly_counts() {
+        use crate::gui::HourlyCounts;
+        use chrono::{Local, TimeZone};
+
+        let mut hourly = HourlyCounts::default();
+        assert_eq!(hourly.busiest(), None);
+
+        let at = |hour, minute| Local.with_ymd_and_hms(2024, 3, 2, hour, minute, 0).unwrap();
+        hourly.record(at(7, 5));
+        hourly.record(at(7, 59));
+        hourly.record(at(23, 30));
+        hourly.record(at(0, 0));
+        assert_eq!(hourly.counts()[7], 2);
+        assert_eq!(hourly.counts()[23], 1);
+        assert_eq!(hourly.counts()[0], 1);
+        assert_eq!(hourly.total(), 4);
+        assert_eq!(hourly.busiest(), Some(7));
+
+        // The earliest hour wins a tie
+        hourly.record(at(0, 45));
+        assert_eq!(hourly.busiest(), Some(0));
+    }
+
+    #[test]
+    fn test_graph_export() {
+        use crate::graph_export::*;
+        use chrono::{Duration, Local, TimeZone};
+        use image::Rgb;
+
+        let start = Local.with_ymd_and_hms(2024, 3, 2, 22, 0, 0).unwrap();
+        let mut timeline = Timeline::default();
+        // Frames in the same second share one sample with their peak
+        timeline.record(start, 0.01, false);
+        timeline.record(start + Duration::milliseconds(400), 0.05, true);
+        timeline.record(start + Duration::milliseconds(800), 0.02, false);
+        assert_eq!(timeline.len(), 1);
+        // Through the night, with motion and an event around 03:00
+        for minute in 1..=8 * 60 {
+            let at = start + Duration::minutes(minute);
+            let motion = (300..310).contains(&minute);
+            timeline.record(at, if motion { 0.08 } else { 0.002 }, motion);
+            if minute == 305 {
+                timeline.record_event(at);
+            }
+        }
+        assert_eq!(timeline.len(), 1 + 8 * 60);
+
+        let now = start + Duration::hours(8);
+        assert_eq!(GraphRange::Session.start(&timeline, now), start);
+        assert_eq!(
+            GraphRange::LastHour.start(&timeline, now),
+            now - Duration::hours(1)
+        );
+        let request = GraphRequest::new(&timeline, GraphRange::LastHour, now, 0.01, "Porch".into());
+        assert_eq!(request.samples.len(), 61);
+        assert!(request.samples.iter().all(|sample| sample.events == 0));
+
+        let request = GraphRequest::new(&timeline, GraphRange::Session, now, 0.01, "Porch".into());
+        assert_eq!(request.samples[0].score, 0.05);
+        assert!(request.samples[0].motion);
+        let image = render(&request).unwrap();
+        assert_eq!(image.dimensions(), IMAGE_SIZE);
+        let has = |color: Rgb<u8>| image.pixels().any(|pixel| *pixel == color);
+        assert!(has(Rgb([0, 220, 0])), "motion bars");
+        assert!(has(Rgb([200, 60, 60])), "quiet bars");
+        assert!(has(Rgb([255, 255, 0])), "threshold");
+        assert!(has(Rgb([255, 160, 0])), "event mark");
+        // Labels are drawn, not just rectangles
+        assert!(image.pixels().any(|pixel| *pixel == Rgb([220, 220, 220])));
+
+        // An empty range still draws its axes
+        let empty = GraphRequest::new(
+            &Timeline::default(),
+            GraphRange::LastHour,
+            now,
+            0.01,
+            "Porch".into(),
+        );
+        assert!(render(&empty).is_ok());
+
+        let dir = std::env::temp_dir().join(format!("motion_graph_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("night.png");
+        let export = export(request, path.clone());
+        let result = loop {
+            if let Some(result) = export.poll() {
+                break result;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+        assert_eq!(result, Ok(()));
+        assert!(std::fs::read(&path).unwrap().starts_with(b"\x89PNG"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_probe_all_is_bounded() {
+        use crate::probe::probe_all;
+        use std::time::{Duration, Instant};
+
+        // Four slow devices take about as long as one of them, not four
+        let delay = Duration::from_millis(200);
+        let started = Instant::now();
+        let results = probe_all(vec![1, 2, 3, 4], Duration::from_secs(5), move |device| {
+            std::thread::sleep(delay);
+            device * 10
+        });
+        assert_eq!(results, [Some(10), Some(20), Some(30), Some(40)]);
+        assert!(started.elapsed() < delay * 3, "{:?}", started.elapsed());
+
+        // A device that never answers is given up on at the timeout
+        let timeout = Duration::from_millis(300);
+        let started = Instant::now();
+        let results = probe_all(vec![0, 1, 2], timeout, |device| {
+            if device == 1 {
+                std::thread::sleep(Duration::from_secs(10));
+            }
+            device
+        });
+        assert_eq!(results, [Some(0), None, Some(2)]);
+        let elapsed = started.elapsed();
+        assert!(elapsed >= timeout && elapsed < timeout * 3, "{:?}", elapsed);
+
+        assert!(probe_all(Vec::<u32>::new(), timeout, |device| device).is_empty());
+    }
+
+    #[test]
+    fn test_initial_frame_retries() {
+        use crate::probe::retry_read;
+        use std::time::Duration;
+
+        // A camera that warms up after three empty reads
+        let mut reads = 0;
+        let mut retries = Vec::new();
+        let frame = retry_read(
+            5,
+            Duration::ZERO,
+            || {
+                reads += 1;
+                Ok::<_, String>((reads > 3).then_some(reads))
+            },
+            |retry| retries.push(retry),
+        );
+        assert_eq!(frame, Ok(Some(4)));
+        assert_eq!(retries, [1, 2, 3]);
+
+        // Gives up after the retries, and reads only once with none
+        let mut reads = 0;
+        let empty = retry_read(
+            2,
+            Duration::ZERO,
+            || {
+                reads += 1;
+                Ok::<Option<()>, String>(None)
+            },
+            |_| {},
+        );
+        assert_eq!((empty, reads), (Ok(None), 3));
+        let mut reads = 0;
+        let once = retry_read(
+            0,
+            Duration::ZERO,
+            || {
+                reads += 1;
+                Ok::<Option<()>, String>(None)
+            },
+            |_| {},
+        );
+        assert_eq!((once, reads), (Ok(None), 1));
+
+        // A read error isn't retried
+        let mut reads = 0;
+        let failed = retry_read(
+            5,
+            Duration::ZERO,
+            || {
+                reads += 1;
+                Err::<Option<()>, _>("device gone".to_string())
+            },
+            |_| {},
+        );
+        assert_eq!((failed, reads), (Err("device gone".to_string()), 1));
+    }
+
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    #[test]
+    fn test_dbus_service() {
+        use crate::dbus::{spawn, Bus, BUS_NAME, OBJECT_PATH};
+        use crate::gui::{GuiMessage, MotionState};
+        use std::io::{BufRead, BufReader};
+        use std::process::{Command, Stdio};
+        use std::time::Duration;
+
+        // A private bus, so the test never touches the user's session
+        let Ok(mut daemon) = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+        else {
+            eprintln!("dbus-daemon not installed, skipping");
+            return;
+        };
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim();
+
+        let (commands, command_receiver) = crossbeam_channel::unbounded();
+        let (states, state_receiver) = crossbeam_channel::unbounded();
+        let (gui, gui_receiver) = crossbeam_channel::unbounded();
+        spawn(
+            Bus::Session,
+            Some(address),
+            commands.clone(),
+            state_receiver,
+            gui.clone(),
+        )
+        .unwrap();
+        // A second instance is told the name is taken
+        let (_, other_states) = crossbeam_channel::unbounded();
+        let err = spawn(Bus::Session, Some(address), commands, other_states, gui).unwrap_err();
+        assert!(
+            err.to_string().contains("another motion_detector"),
+            "{}",
+            err
+        );
+
+        let client = zbus::blocking::connection::Builder::address(address)
+            .unwrap()
+            .build()
+            .unwrap();
+        let proxy = zbus::blocking::Proxy::new(&client, BUS_NAME, OBJECT_PATH, BUS_NAME).unwrap();
+        let mut signals = proxy.receive_signal("MotionDetected").unwrap();
+
+        // Methods become GUI commands
+        proxy.call_method("Start", &()).unwrap();
+        proxy.call_method("SetSensitivity", &(0.4f64,)).unwrap();
+        assert!(proxy.call_method("SetSensitivity", &(2.0f64,)).is_err());
+        let received: Vec<GuiMessage> = command_receiver.try_iter().collect();
+        assert!(matches!(
+            received[..],
+            [GuiMessage::StartDetection, GuiMessage::UpdateSensitivity(s)] if s == 0.4
+        ));
+
+        // States reach the window, update the status and signal events
+        states
+            .send(MotionState {
+                motion_detected: true,
+                incident_id: Some(1),
+                motion_count: 3,
+                fps: 15.0,
+                frame_time: Some(crate::timestamp::FrameTime::processed(
+                    &crate::timestamp::Clocks::now(),
+                )),
+                event_snapshot: Some("pics/motion_1.jpg".to_string()),
+                ..MotionState::default()
+            })
+            .unwrap();
+        let forwarded = gui_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(forwarded.motion_count, 3);
+        let signal = signals.next().unwrap();
+        let (_, count, snapshot): (String, u32, String) = signal.body().deserialize().unwrap();
+        assert_eq!((count, snapshot.as_str()), (3, "pics/motion_1.jpg"));
+        let (motion, count, fps, _): (bool, u32, f64, String) = proxy
+            .call_method("GetStatus", &())
+            .unwrap()
+            .body()
+            .deserialize()
+            .unwrap();
+        assert_eq!((motion, count, fps), (true, 3, 15.0));
+
+        let _ = daemon.kill();
+    }
+
+    #[test]
+    fn test_hotplug_scan() {
+        use crate::hotplug::{diff, scan};
+
+        let root = std::env::temp_dir().join(format!("motion_hotplug_{}", std::process::id()));
+        let (dev, sys) = (root.join("dev"), root.join("sys"));
+        std::fs::create_dir_all(&dev).unwrap();
+        for node in ["video0", "video1", "video2", "null"] {
+            std::fs::write(dev.join(node), b"").unwrap();
+        }
+        for (node, name, index) in [
+            ("video0", "HD Pro Webcam C920", "0"),
+            ("video1", "HD Pro Webcam C920", "1"),
+        ] {
+            std::fs::create_dir_all(sys.join(node)).unwrap();
+            std::fs::write(sys.join(node).join("name"), format!("{}\n", name)).unwrap();
+            std::fs::write(sys.join(node).join("index"), format!("{}\n", index)).unwrap();
+        }
+
+        // video1 is the C920's metadata node; video2 has no sysfs entry
+        let cameras = scan(&dev, &sys);
+        let indexes: Vec<u32> = cameras.iter().map(|c| c.index).collect();
+        assert_eq!(indexes, vec![0, 2]);
+        assert_eq!(cameras[0].label(), "Camera 0 - HD Pro Webcam C920");
+        assert!(cameras[0].matches("c920"));
+        assert!(cameras[1].matches(&dev.join("video2").to_string_lossy()));
+        assert!(!cameras[1].matches("c920"));
+
+        let (added, removed) = diff(&cameras[..1], &cameras[1..]);
+        assert_eq!((added[0].index, removed[0].index), (2, 0));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_session_journal() {
+        use crate::journal::{Journal, Session};
+
+        let dir = std::env::temp_dir().join(format!("motion_journal_{}", std::process::id()));
+        let path = crate::journal::path(&dir);
+        let session = Session {
+            started: "2024-05-01T14:00:00+00:00".to_string(),
+            updated: "2024-05-01T14:32:00+00:00".to_string(),
+            events: 312,
+            device: 1,
+            sensitivity: 0.4,
+            min_area: MinArea::Pixels(800),
+            detecting: true,
+        };
+
+        let (mut journal, previous) = Journal::open(path.clone()).unwrap();
+        assert_eq!(previous, None);
+        journal.update(&session).unwrap();
+
+        // Updates are throttled, but an event with --flush-on-event is
+        // written and synced at once
+        let later = Session {
+            events: 313,
+            ..session.clone()
+        };
+        journal.update(&later).unwrap();
+        let (_, previous) = Journal::open(path.clone()).unwrap();
+        assert_eq!(previous, Some(session.clone()));
+        journal.sync(&later).unwrap();
+        let (_, previous) = Journal::open(path.clone()).unwrap();
+        assert_eq!(previous, Some(later));
+        journal.sync(&session).unwrap();
+
+        // A journal that wasn't closed is found by the next session
+        let (journal, previous) = Journal::open(path.clone()).unwrap();
+        assert_eq!(previous, Some(session));
+        journal.close().unwrap();
+        let (_, previous) = Journal::open(path).unwrap();
+        assert_eq!(previous, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_record_codec_candidates() {
+        use crate::recording::{candidates, RecordCodec};
+
+        let args = Args::parse_from([
+            "motion_detector",
+            "--record",
+            "--record-codec",
+            "hevc",
+            "--record-bitrate",
+            "2500",
+        ]);
+        assert_eq!(args.record_codec, Some(RecordCodec::Hevc));
+        assert!(Args::try_parse_from(["motion_detector", "--record-codec", "vp9"]).is_err());
+
+        // Every choice ends with the MJPEG fallback, which is all `mjpeg` tries
+        for codec in [RecordCodec::Auto, RecordCodec::H264, RecordCodec::Hevc] {
+            let encoders = candidates(codec);
+            assert_eq!(encoders.last().unwrap().codec, RecordCodec::Mjpeg);
+            assert_eq!(encoders[0].extension, "mp4");
+        }
+        assert_eq!(candidates(RecordCodec::Mjpeg).len(), 1);
+        assert!(candidates(RecordCodec::H264)
+            .iter()
+            .all(|e| matches!(e.codec, RecordCodec::H264 | RecordCodec::Mjpeg)));
+
+        let hardware = candidates(RecordCodec::H264)[0];
+        let pipeline = hardware.pipeline("/tmp/motion_3.mp4", 2500).unwrap();
+        assert!(pipeline.contains("video_bitrate=2500000"));
+        assert!(pipeline.ends_with("location=/tmp/motion_3.mp4"));
+        assert_eq!(hardware.file_name(3), "motion_3.mp4");
+        let fallback = candidates(RecordCodec::Mjpeg)[0];
+        assert_eq!(fallback.pipeline("x.avi", 2500), None);
+        assert_eq!(fallback.file_name(3), "motion_3.avi");
+    }
+
+    #[test]
+    fn test_trim_range() {
+        use crate::recording::trim_range;
+
+        let mut motion = vec![false; 100];
+        motion[20..30].fill(true);
+        motion[40] = true;
+        // Padding on both sides of the first and last motion frame
+        assert_eq!(trim_range(&motion, 5), Some(15..46));
+        // Padding stops at the ends of the clip
+        assert_eq!(trim_range(&motion[20..], 5), Some(0..26));
+        // Nothing to cut, or no motion to cut to
+        assert_eq!(trim_range(&motion[20..41], 5), None);
+        assert_eq!(trim_range(&[false; 10], 2), None);
+    }
+
+    #[test]
+    fn test_privacy_blur_settings() {
+        use crate::config::{Config, SnapshotConfig};
+        use crate::privacy::{blur_problem, cover, find_cascade, write_private, PrivacyBlur};
+        use std::path::Path;
+
+        // Boxes grow by the margin and stay inside the frame
+        assert_eq!(
+            cover((100.0, 50.0, 40.0, 60.0), 0.0, (640, 480)),
+            Some((100, 50, 40, 60))
+        );
+        assert_eq!(
+            cover((100.0, 50.0, 40.0, 60.0), 0.25, (640, 480)),
+            Some((90, 35, 60, 90))
+        );
+        assert_eq!(
+            cover((620.0, -10.0, 40.0, 40.0), 0.0, (640, 480)),
+            Some((620, 0, 20, 30))
+        );
+        assert_eq!(cover((700.0, 0.0, 40.0, 40.0), 0.0, (640, 480)), None);
+
+        let args = Args::parse_from(["motion_detector", "--privacy-blur", "all-motion"]);
+        assert_eq!(args.privacy_blur, Some(PrivacyBlur::AllMotion));
+        assert!(Args::try_parse_from(["motion_detector", "--privacy-blur", "cars"]).is_err());
+        let config: Config = toml::from_str(
+            "[snapshots]\nprivacy_blur = \"faces\"\nface_cascade = \"faces.xml\"\nprivacy_keep_original = \"/srv/originals\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.snapshots.privacy_blur, PrivacyBlur::Faces);
+        assert_eq!(SnapshotConfig::default().privacy_blur, PrivacyBlur::Off);
+
+        // Faces need a cascade that exists
+        let dir = std::env::temp_dir().join(format!("motion_private_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cascade = dir.join("faces.xml");
+        let faces = SnapshotConfig {
+            privacy_blur: PrivacyBlur::Faces,
+            face_cascade: Some(cascade.clone()),
+            ..SnapshotConfig::default()
+        };
+        assert!(blur_problem(&faces, Path::new("pics"))
+            .unwrap()
+            .contains("not found"));
+        std::fs::write(&cascade, "<opencv_storage/>").unwrap();
+        assert_eq!(find_cascade(Some(&cascade)), Some(cascade.clone()));
+        assert_eq!(blur_problem(&faces, Path::new("pics")), None);
+
+        // Originals go to a separate directory, and only with a blur
+        let keep = SnapshotConfig {
+            privacy_keep_original: Some(dir.join("originals")),
+            ..faces.clone()
+        };
+        assert_eq!(blur_problem(&keep, Path::new("pics")), None);
+        assert!(blur_problem(&keep, &dir.join("originals")).is_some());
+        let unblurred = SnapshotConfig {
+            privacy_blur: PrivacyBlur::Off,
+            ..keep.clone()
+        };
+        assert!(blur_problem(&unblurred, Path::new("pics")).is_some());
+
+        let originals = dir.join("originals");
+        let path = write_private(&originals, "2024/05/01/motion.jpg", b"jpeg").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"jpeg");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&originals), 0o700);
+            assert_eq!(mode(&path), 0o600);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_privacy_blur_leaves_the_rest() {
+        use crate::privacy::{blur, find_cascade, find_faces};
+        use opencv::core::{self, Mat, Rect, Scalar};
+        use opencv::{imgcodecs, imgproc, objdetect::CascadeClassifier, prelude::*};
+
+        // Blur `rects` and check that they changed and nothing else did
+        let check = |input: &Mat, rects: &[Rect]| {
+            let mut output = input.try_clone().unwrap();
+            for rect in rects {
+                blur(&mut output, *rect).unwrap();
+                let mut diff = Mat::default();
+                core::absdiff(
+                    &Mat::roi(input, *rect).unwrap().try_clone().unwrap(),
+                    &Mat::roi(&output, *rect).unwrap().try_clone().unwrap(),
+                    &mut diff,
+                )
+                .unwrap();
+                assert!(core::sum_elems(&diff).unwrap().0.iter().sum::<f64>() > 0.0);
+            }
+            let (mut before, mut after) = (input.try_clone().unwrap(), output);
+            for frame in [&mut before, &mut after] {
+                for rect in rects {
+                    imgproc::rectangle(
+                        frame,
+                        *rect,
+                        Scalar::all(0.0),
+                        imgproc::FILLED,
+                        imgproc::LINE_8,
+                        0,
+                    )
+                    .unwrap();
+                }
+            }
+            let mut diff = Mat::default();
+            core::absdiff(&before, &after, &mut diff).unwrap();
+            assert_eq!(core::sum_elems(&diff).unwrap().0.iter().sum::<f64>(), 0.0);
+        };
+
+        // A textured frame
+        let mut input =
+            Mat::new_rows_cols_with_default(240, 320, core::CV_8UC3, Scalar::all(0.0)).unwrap();
+        core::randu(&mut input, &Scalar::all(0.0), &Scalar::all(255.0)).unwrap();
+        check(&input, &[Rect::new(100, 60, 80, 90)]);
+
+        // A real face, from OpenCV's own samples and cascade where they are
+        // installed
+        let image = core::find_file("lena.jpg", false, true).unwrap_or_default();
+        let Some(cascade) = find_cascade(None).filter(|_| !image.is_empty()) else {
+            eprintln!("OpenCV's lena.jpg or face cascade not installed, skipping");
+            return;
+        };
+        let input = imgcodecs::imread(&image, imgcodecs::IMREAD_COLOR).unwrap();
+        let mut classifier = CascadeClassifier::new(&cascade.to_string_lossy()).unwrap();
+        let faces: Vec<Rect> = find_faces(&mut classifier, &input)
+            .unwrap()
+            .into_iter()
+            .map(|(x, y, width, height)| Rect::new(x, y, width, height))
+            .collect();
+        assert!(!faces.is_empty());
+        check(&input, &faces);
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_diff_lag() {
+        use crate::detector::FrameHistory;
+        use opencv::core::{self, Mat, Scalar};
+        use opencv::prelude::*;
+
+        // A soft edge creeping 1px per frame changes each pixel by only 3
+        // gray levels per step, well under the threshold of 25
+        let frame = |step: i32| {
+            let mut mat =
+                Mat::new_rows_cols_with_default(16, 64, core::CV_8UC1, Scalar::all(0.0)).unwrap();
+            for row in 0..16 {
+                for col in 0..64 {
+                    *mat.at_2d_mut::<u8>(row, col).unwrap() = (3 * (col + step)).min(255) as u8;
+                }
+            }
+            mat
+        };
+        let changed_pixels = |lag| {
+            let mut history = FrameHistory::new(lag);
+            let mut changed = 0;
+            for step in 0..20 {
+                if let Some(mask) = history.diff(frame(step)).unwrap() {
+                    changed += core::count_non_zero(&mask).unwrap();
+                }
+            }
+            changed
+        };
+        assert_eq!(changed_pixels(1), 0);
+        assert!(changed_pixels(10) > 0);
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_fixed_reference() {
+        use crate::detector::FrameHistory;
+        use opencv::core::{self, Mat, Scalar};
+
+        let gray = |level: f64| {
+            Mat::new_rows_cols_with_default(16, 16, core::CV_8UC1, Scalar::all(level)).unwrap()
+        };
+        let changed = |mask: Option<Mat>| core::count_non_zero(&mask.unwrap()).unwrap();
+
+        // Rolling comparison forgets a change once it stands still
+        let mut history = FrameHistory::new(1);
+        assert!(history.diff(gray(0.0)).unwrap().is_none());
+        assert_eq!(changed(history.diff(gray(100.0)).unwrap()), 256);
+        assert_eq!(changed(history.diff(gray(100.0)).unwrap()), 0);
+
+        // A fixed reference keeps seeing it
+        history.fix_next();
+        assert_eq!(changed(history.diff(gray(0.0)).unwrap()), 0);
+        assert!(history.is_fixed());
+        assert_eq!(changed(history.diff(gray(100.0)).unwrap()), 256);
+        assert_eq!(changed(history.diff(gray(100.0)).unwrap()), 256);
+
+        history.release_reference();
+        assert!(!history.is_fixed());
+        assert_eq!(changed(history.diff(gray(100.0)).unwrap()), 0);
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_history_reuses_buffers() {
+        use crate::detector::FrameHistory;
+        use opencv::core::{self, Mat, Scalar, Size};
+        use opencv::prelude::*;
+
+        let gray = |value: f64| {
+            Mat::new_rows_cols_with_default(8, 8, core::CV_8UC1, Scalar::all(value)).unwrap()
+        };
+        let mut history = FrameHistory::new(1);
+        assert!(history.diff(gray(0.0)).unwrap().is_none());
+        assert!(history.take_buffer().empty());
+
+        let thresh = history.diff(gray(100.0)).unwrap().unwrap();
+        let mask_data = thresh.data();
+        history.recycle(thresh);
+        // The first frame dropped out and is written over next
+        let mut next = history.take_buffer();
+        assert_eq!(next.size().unwrap(), Size::new(8, 8));
+        let frame_data = next.data();
+        gray(0.0).copy_to(&mut next).unwrap();
+        let thresh = history.diff(next).unwrap().unwrap();
+        assert_eq!(thresh.data(), mask_data);
+        assert_eq!(core::count_non_zero(&thresh).unwrap(), 64);
+        assert_eq!(history.reference().unwrap().data(), frame_data);
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_warm_up_frames_left() {
+        use crate::detector::FrameHistory;
+        use opencv::core::{self, Mat, Scalar};
+
+        let gray =
+            || Mat::new_rows_cols_with_default(8, 8, core::CV_8UC1, Scalar::all(0.0)).unwrap();
+        let mut history = FrameHistory::new(3);
+        assert_eq!(history.frames_left(), 3);
+        for left in [2, 1, 0] {
+            assert!(history.diff(gray()).unwrap().is_none());
+            assert_eq!(history.frames_left(), left);
+        }
+        assert!(history.diff(gray()).unwrap().is_some());
+        assert_eq!(history.frames_left(), 0);
+
+        // After the camera moved the history refills, an armed reference
+        // compares straight away
+        history.clear();
+        assert_eq!(history.frames_left(), 3);
+        history.fix_next();
+        assert_eq!(history.frames_left(), 0);
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_opening_removes_pepper_noise() {
+        use crate::detector::{clean_mask, FrameHistory};
+        use opencv::core::{self, Mat, Scalar, Vector};
+        use opencv::{imgproc, prelude::*};
+
+        let flat =
+            || Mat::new_rows_cols_with_default(64, 64, core::CV_8UC1, Scalar::all(100.0)).unwrap();
+        // Isolated hot pixels roughly every 4px, as from a noisy sensor with
+        // little blur; no two share a 3x3 neighbourhood
+        let mut noisy = flat();
+        for i in 0..16 {
+            for j in 0..16 {
+                let (row, col) = (4 * i + (i * j) % 2, 4 * j + (i + j) % 2);
+                *noisy.at_2d_mut::<u8>(row, col).unwrap() = 255;
+            }
+        }
+        let mut history = FrameHistory::new(1);
+        assert!(history.diff(flat()).unwrap().is_none());
+        let thresh = history.diff(noisy).unwrap().unwrap();
+
+        let largest_area = |mask: &Mat| {
+            let mut contours = Vector::<Vector<core::Point>>::new();
+            imgproc::find_contours(
+                mask,
+                &mut contours,
+                imgproc::RETR_EXTERNAL,
+                imgproc::CHAIN_APPROX_SIMPLE,
+                core::Point::new(0, 0),
+            )
+            .unwrap();
+            contours
+                .iter()
+                .map(|contour| imgproc::contour_area(&contour, false).unwrap())
+                .fold(0.0, f64::max)
+        };
+        let min_area = crate::config::DEFAULT_MIN_AREA as f64;
+
+        // Dilation alone grows the noise into one blob that counts as motion
+        let (opened, dilated) = clean_mask(&thresh, 0, 1).unwrap();
+        assert!(opened.is_none());
+        assert!(largest_area(&dilated) > min_area);
+
+        // Opening removes every noise pixel first
+        let (opened, dilated) = clean_mask(&thresh, 3, 1).unwrap();
+        assert_eq!(core::count_non_zero(&opened.unwrap()).unwrap(), 0);
+        assert_eq!(largest_area(&dilated), 0.0);
+
+        // but keeps a real object
+        let mut object = thresh.try_clone().unwrap();
+        imgproc::rectangle(
+            &mut object,
+            core::Rect::new(20, 20, 24, 24),
+            Scalar::all(255.0),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )
+        .unwrap();
+        let (_, dilated) = clean_mask(&object, 3, 1).unwrap();
+        assert!(largest_area(&dilated) > 24.0 * 24.0);
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_pyramid_finds_sparse_objects() {
+        use crate::detector::clean_mask;
+        use crate::pyramid::{coarse_regions, combine};
+        use crate::regions::Region;
+        use opencv::core::{self, Mat, Scalar, Vector};
+        use opencv::imgproc;
+
+        let mut thresh =
+            Mat::new_rows_cols_with_default(160, 160, core::CV_8UC1, Scalar::all(0.0)).unwrap();
+        let mut fill = |rect: core::Rect| {
+            imgproc::rectangle(
+                &mut thresh,
+                rect,
+                Scalar::all(255.0),
+                imgproc::FILLED,
+                imgproc::LINE_8,
+                0,
+            )
+            .unwrap();
+        };
+        // A small solid object, a large one that only left 2x2 specks 8px
+        // apart (as a slow mover's edges do), and a single noisy pixel
+        fill(core::Rect::new(8, 8, 10, 10));
+        for i in 0..8 {
+            for j in 0..8 {
+                fill(core::Rect::new(80 + 8 * i, 80 + 8 * j, 2, 2));
+            }
+        }
+        fill(core::Rect::new(150, 20, 1, 1));
+
+        let (_, dilated) = clean_mask(&thresh, 0, 1).unwrap();
+        let mut contours = Vector::<Vector<core::Point>>::new();
+        imgproc::find_contours(
+            &dilated,
+            &mut contours,
+            imgproc::RETR_EXTERNAL,
+            imgproc::CHAIN_APPROX_SIMPLE,
+            core::Point::new(-1, -1),
+        )
+        .unwrap();
+        let full: Vec<Region> = contours
+            .iter()
+            .map(|contour| {
+                let rect = imgproc::bounding_rect(&contour).unwrap();
+                Region {
+                    x: rect.x as f64,
+                    y: rect.y as f64,
+                    width: rect.width as f64,
+                    height: rect.height as f64,
+                    area: imgproc::contour_area(&contour, false).unwrap(),
+                    contours: 1,
+                }
+            })
+            .collect();
+        let min_area = 100.0;
+        let large = |regions: &[Region]| -> Vec<Region> {
+            regions
+                .iter()
+                .filter(|region| region.area > min_area)
+                .copied()
+                .collect()
+        };
+
+        // At full size only the small object is big enough
+        let found = large(&full);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].x < 20.0 && found[0].y < 20.0);
+        assert!(coarse_regions(&thresh, 1, 0, 1).unwrap().is_empty());
+
+        // Halving twice joins the specks, and the small object stays
+        let mut levels = vec![full];
+        levels.extend(coarse_regions(&thresh, 3, 0, 1).unwrap());
+        assert_eq!(levels.len(), 3);
+        let found = large(&combine(levels));
+        assert_eq!(found.len(), 2, "{:?}", found);
+        let sparse = found.iter().find(|region| region.x > 60.0).unwrap();
+        assert!(sparse.area > 2000.0, "{:?}", sparse);
+        assert!(sparse.x <= 80.0 && sparse.x + sparse.width >= 138.0);
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_normalize_brightness_ramp() {
+        use crate::config::DetectorConfig;
+        use crate::detector::FrameHistory;
+        use crate::normalize::{Normalize, Normalizer};
+        use opencv::core::{self, Mat, Scalar};
+        use opencv::prelude::*;
+
+        // A still, textured scene brightening by 4 gray levels per frame,
+        // like a cloud moving off the sun
+        let frame = |step: i32| {
+            let mut mat =
+                Mat::new_rows_cols_with_default(16, 64, core::CV_8UC1, Scalar::all(0.0)).unwrap();
+            for row in 0..16 {
+                for col in 0..64 {
+                    *mat.at_2d_mut::<u8>(row, col).unwrap() = (2 * col + 4 * step) as u8;
+                }
+            }
+            mat
+        };
+        let changed_pixels = |normalize| {
+            let config = DetectorConfig {
+                normalize,
+                ..Default::default()
+            };
+            let mut normalizer = Normalizer::new(&config).unwrap();
+            let mut history = FrameHistory::new(10);
+            let mut changed = 0;
+            for step in 0..20 {
+                let mut gray = frame(step);
+                if let Some(normalizer) = normalizer.as_mut() {
+                    gray = normalizer.apply(&gray).unwrap();
+                }
+                if let Some(mask) = history.diff(gray).unwrap() {
+                    changed += core::count_non_zero(&mask).unwrap();
+                }
+            }
+            changed
+        };
+        let plain = changed_pixels(Normalize::Off);
+        assert!(plain > 0);
+        assert_eq!(changed_pixels(Normalize::Equalize), 0);
+        assert!(changed_pixels(Normalize::Clahe) < plain);
+    }
+
+    #[test]
+    fn test_mask_editor() {
+        use crate::mask_editor::{Brush, MaskEditor, MaskImage};
+
+        let ignored = |mask: &MaskImage| mask.pixels.iter().filter(|&&pixel| pixel == 0).count();
+        let mut editor = MaskEditor::default();
+        editor.radius = 2.0;
+        assert!(editor.mask_for(20, 10).is_clear());
+        assert!(!editor.can_undo());
+
+        // A dot covers the pixels whose centers are within the radius
+        editor.press((10.0, 5.0));
+        editor.release();
+        let dot = editor.mask.clone().unwrap();
+        assert_eq!(ignored(&dot), 12);
+        assert_eq!(dot.pixels[5 * 20 + 10], 0);
+        assert!(!dot.is_clear());
+
+        // A fast drag leaves no gaps along the line
+        editor.press((2.0, 2.0));
+        editor.drag((18.0, 2.0));
+        editor.release();
+        let mask = editor.mask.as_ref().unwrap();
+        assert!((2..18).all(|x| mask.pixels[2 * 20 + x] == 0));
+
+        // Erasing, whole-mask edits and undo
+        editor.brush = Brush::Include;
+        let before = ignored(editor.mask.as_ref().unwrap());
+        editor.edit(|mask| mask.invert());
+        assert_eq!(ignored(editor.mask.as_ref().unwrap()), 200 - before);
+        assert!(editor.undo());
+        assert!(editor.undo());
+        assert_eq!(editor.mask, Some(dot.clone()));
+        assert!(editor.undo());
+        assert!(editor.mask.as_ref().unwrap().is_clear());
+        assert!(!editor.undo());
+
+        // A new processing resolution rescales, keeping the mask binary
+        editor.mask = Some(dot.clone());
+        let doubled = editor.mask_for(40, 20).clone();
+        assert_eq!(ignored(&doubled), 4 * 12);
+        assert!(doubled
+            .pixels
+            .iter()
+            .all(|&pixel| pixel == 0 || pixel == 255));
+        assert_eq!(*editor.mask_for(20, 10), dot);
+
+        let overlay = MaskImage::new(2, 1).overlay(0.5);
+        assert_eq!(overlay, [0; 8]);
+        let mut mask = MaskImage::new(2, 1);
+        mask.fill(0);
+        assert_eq!(mask.overlay(1.0), [255, 0, 0, 255, 255, 0, 0, 255]);
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_ignore_mask() {
+        use crate::mask::IgnoreMask;
+        use opencv::core::{self, Mat, Scalar};
+        use opencv::{imgproc, prelude::*};
+
+        // Left half black (ignored), right half gray (kept), at half the
+        // frame size
+        let mut image =
+            Mat::new_rows_cols_with_default(4, 8, core::CV_8UC1, Scalar::all(128.0)).unwrap();
+        imgproc::rectangle(
+            &mut image,
+            core::Rect::new(0, 0, 4, 4),
+            Scalar::all(0.0),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )
+        .unwrap();
+        let mut mask = IgnoreMask::new("mask.png".into(), &image).unwrap();
+
+        let thresh =
+            Mat::new_rows_cols_with_default(8, 16, core::CV_8UC1, Scalar::all(255.0)).unwrap();
+        let masked = mask.apply(&thresh).unwrap();
+        assert_eq!((masked.rows(), masked.cols()), (8, 16));
+        assert_eq!(core::count_non_zero(&masked).unwrap(), 8 * 8);
+        assert_eq!(*masked.at_2d::<u8>(0, 7).unwrap(), 0);
+        assert_eq!(*masked.at_2d::<u8>(0, 8).unwrap(), 255);
+
+        // A frame size change resizes the mask again
+        let thresh =
+            Mat::new_rows_cols_with_default(4, 8, core::CV_8UC1, Scalar::all(255.0)).unwrap();
+        assert_eq!(
+            core::count_non_zero(&mask.apply(&thresh).unwrap()).unwrap(),
+            16
+        );
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_panic_message() {
+        use crate::crash::panic_message;
+
+        let panic = std::panic::catch_unwind(|| panic!("camera {} vanished", 2)).unwrap_err();
+        assert_eq!(panic_message(&*panic), "camera 2 vanished");
+        let panic = std::panic::catch_unwind(|| panic!("static")).unwrap_err();
+        assert_eq!(panic_message(&*panic), "static");
+        let panic = std::panic::catch_unwind(|| std::panic::panic_any(7)).unwrap_err();
+        assert_eq!(panic_message(&*panic), "unknown panic");
+    }
+
+    #[cfg(feature = "camera")]
+    #[test]
+    fn test_frame_format_conversion() {
+        use crate::detector::{to_bgr, to_grayscale};
+        use opencv::core::{self, Mat, Scalar};
+        use opencv::prelude::*;
+
+        for typ in [core::CV_8UC1, core::CV_8UC2, core::CV_8UC3, core::CV_8UC4] {
+            let frame = Mat::new_rows_cols_with_default(8, 8, typ, Scalar::all(128.0)).unwrap();
+            let gray = to_grayscale(&frame).unwrap();
+            assert_eq!(gray.channels(), 1);
+            assert_eq!((gray.rows(), gray.cols()), (8, 8));
+            assert_eq!(to_bgr(&frame).unwrap().channels(), 3);
+        }
+
+        // 16-bit thermal frames get one clear error naming the format
+        let frame =
+            Mat::new_rows_cols_with_default(8, 8, core::CV_16UC1, Scalar::all(0.0)).unwrap();
+        let err = to_grayscale(&frame).unwrap_err();
+        assert_eq!(crate::error::exit_code_for(&err), 2);
+        assert!(err.to_string().contains("CV_16UC1"));
+    }
 }
@@ -0,0 +1,165 @@
+//! When each frame was captured. Cameras report the driver's buffer
+//! timestamp through `CAP_PROP_POS_MSEC`, which is earlier than the moment
+//! the frame is processed by however long it sat in the buffer; video files
+//! report the position in the file. Backends that report nothing fall back
+//! to the processing time.
+
+// Without the `camera` feature nothing is captured
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use chrono::{DateTime, Local, TimeDelta};
+use std::time::{Duration, Instant};
+
+/// Which clock a frame's time comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimestampSource {
+    /// Driver timestamp on the monotonic clock (V4L2)
+    Monotonic,
+    /// Driver timestamp on the wall clock
+    Realtime,
+    /// Position in the input file
+    File,
+    /// No usable timestamp; the time the frame was processed
+    Processing,
+}
+
+impl TimestampSource {
+    pub fn describe(self) -> &'static str {
+        match self {
+            TimestampSource::Monotonic | TimestampSource::Realtime => "driver timestamps",
+            TimestampSource::File => "file position",
+            TimestampSource::Processing => "processing time",
+        }
+    }
+}
+
+/// Both clocks read at the moment a frame is processed
+#[derive(Clone, Copy, Debug)]
+pub struct Clocks {
+    pub instant: Instant,
+    pub wall: DateTime<Local>,
+    /// `CLOCK_MONOTONIC` in milliseconds, where the platform has one
+    pub monotonic_ms: Option<f64>,
+}
+
+impl Clocks {
+    pub fn now() -> Self {
+        Self {
+            instant: Instant::now(),
+            wall: Local::now(),
+            monotonic_ms: monotonic_ms(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameTime {
+    /// When the frame was captured, on the detector's monotonic clock
+    pub captured: Instant,
+    pub wall: DateTime<Local>,
+    /// How long the frame waited between capture and processing
+    pub latency: Duration,
+    pub source: TimestampSource,
+    /// Position in the input file
+    pub position: Option<Duration>,
+}
+
+impl FrameTime {
+    /// A timestamp older than this isn't the frame's: the driver uses some
+    /// other clock
+    const MAX_LATENCY_MS: f64 = 10_000.0;
+    /// Allowed for the clocks being read a little apart
+    const SKEW_MS: f64 = 50.0;
+
+    /// The processing time, for frames without a timestamp
+    pub fn processed(clocks: &Clocks) -> Self {
+        Self {
+            captured: clocks.instant,
+            wall: clocks.wall,
+            latency: Duration::ZERO,
+            source: TimestampSource::Processing,
+            position: None,
+        }
+    }
+
+    /// A camera frame whose backend reported `reported_ms`, on whichever
+    /// clock it is close to
+    pub fn from_device(reported_ms: f64, clocks: &Clocks) -> Self {
+        if !(reported_ms > 0.0 && reported_ms.is_finite()) {
+            return Self::processed(clocks);
+        }
+        let age = |now_ms: f64| {
+            let age = now_ms - reported_ms;
+            (-Self::SKEW_MS..=Self::MAX_LATENCY_MS)
+                .contains(&age)
+                .then(|| Duration::from_secs_f64(age.max(0.0) / 1000.0))
+        };
+        let wall_ms = clocks.wall.timestamp_micros() as f64 / 1000.0;
+        let (latency, source) = match clocks.monotonic_ms.and_then(age) {
+            Some(latency) => (latency, TimestampSource::Monotonic),
+            None => match age(wall_ms) {
+                Some(latency) => (latency, TimestampSource::Realtime),
+                None => return Self::processed(clocks),
+            },
+        };
+        Self {
+            captured: clocks
+                .instant
+                .checked_sub(latency)
+                .unwrap_or(clocks.instant),
+            wall: clocks.wall - TimeDelta::from_std(latency).unwrap_or_default(),
+            latency,
+            source,
+            position: None,
+        }
+    }
+
+    /// A frame `position_ms` into a video file. Events are still dated by
+    /// the wall clock, the position says where to find them in the file.
+    pub fn in_file(position_ms: f64, clocks: &Clocks) -> Self {
+        Self {
+            position: (position_ms >= 0.0 && position_ms.is_finite())
+                .then(|| Duration::from_secs_f64(position_ms / 1000.0)),
+            source: TimestampSource::File,
+            ..Self::processed(clocks)
+        }
+    }
+
+    /// Time for event lines, e.g. "2024-01-01 12:00:00" or
+    /// "2024-01-01 12:00:00 @ 00:01:23.456" in a file
+    pub fn label(&self) -> String {
+        let wall = self.wall.format("%Y-%m-%d %H:%M:%S").to_string();
+        match self.position {
+            Some(position) => format!("{} @ {}", wall, format_position(position)),
+            None => wall,
+        }
+    }
+}
+
+/// e.g. "00:01:23.456"
+pub fn format_position(position: Duration) -> String {
+    let millis = position.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+#[cfg(unix)]
+fn monotonic_ms() -> Option<f64> {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `now` is a valid timespec for clock_gettime to fill in
+    let result = unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    (result == 0).then(|| now.tv_sec as f64 * 1000.0 + now.tv_nsec as f64 / 1_000_000.0)
+}
+
+#[cfg(not(unix))]
+fn monotonic_ms() -> Option<f64> {
+    None
+}
@@ -0,0 +1,126 @@
+use serde::Deserialize;
+
+/// Object tracking settings, read from the `[tracking]` config section
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TrackingConfig {
+    pub enabled: bool,
+    /// Largest centroid jump (pixels) between frames that still counts as the same object
+    pub max_distance: f64,
+    /// Frames a track may go unseen before it is dropped
+    pub max_missed: u32,
+}
+
+impl Default for TrackingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_distance: 80.0,
+            max_missed: 15,
+        }
+    }
+}
+
+/// One motion area in a single frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct Detection {
+    pub centroid: (f64, f64),
+    pub area: f64,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+struct Track {
+    id: u64,
+    last: Detection,
+    missed: u32,
+}
+
+/// Nearest-neighbour centroid tracker. Contours are matched to existing
+/// tracks within `max_distance`; unmatched contours open new tracks.
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+pub struct CentroidTracker {
+    max_distance: f64,
+    max_missed: u32,
+    next_id: u64,
+    tracks: Vec<Track>,
+}
+
+#[cfg_attr(not(feature = "camera"), allow(dead_code))]
+impl CentroidTracker {
+    pub fn new(config: &TrackingConfig) -> Self {
+        Self {
+            max_distance: config.max_distance,
+            max_missed: config.max_missed,
+            next_id: 1,
+            tracks: Vec::new(),
+        }
+    }
+
+    /// Feed one frame's detections and return the ids of tracks opened by it
+    pub fn update(&mut self, detections: &[Detection]) -> Vec<u64> {
+        // Every track/detection pair inside the gate, closest first
+        let mut pairs = Vec::new();
+        for (t, track) in self.tracks.iter().enumerate() {
+            for (c, detection) in detections.iter().enumerate() {
+                let (x, y) = detection.centroid;
+                let distance = (track.last.centroid.0 - x).hypot(track.last.centroid.1 - y);
+                if distance <= self.max_distance {
+                    pairs.push((distance, t, c));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut track_matched = vec![false; self.tracks.len()];
+        let mut detection_matched = vec![false; detections.len()];
+        for (_, t, c) in pairs {
+            if track_matched[t] || detection_matched[c] {
+                continue;
+            }
+            track_matched[t] = true;
+            detection_matched[c] = true;
+            self.tracks[t].last = detections[c];
+            self.tracks[t].missed = 0;
+        }
+
+        // Age out tracks that were not seen this frame
+        for (track, matched) in self.tracks.iter_mut().zip(&track_matched) {
+            if !matched {
+                track.missed += 1;
+            }
+        }
+        let max_missed = self.max_missed;
+        self.tracks.retain(|track| track.missed <= max_missed);
+
+        let mut new_ids = Vec::new();
+        for (detection, matched) in detections.iter().zip(&detection_matched) {
+            if !matched {
+                let id = self.next_id;
+                self.next_id += 1;
+                self.tracks.push(Track {
+                    id,
+                    last: *detection,
+                    missed: 0,
+                });
+                new_ids.push(id);
+            }
+        }
+        new_ids
+    }
+
+    /// Tracks matched in the latest frame, with where they were seen
+    pub fn visible(&self) -> Vec<(u64, Detection)> {
+        self.tracks
+            .iter()
+            .filter(|track| track.missed == 0)
+            .map(|track| (track.id, track.last))
+            .collect()
+    }
+
+    /// Ids of the tracks currently alive, oldest first
+    pub fn active_ids(&self) -> Vec<u64> {
+        self.tracks.iter().map(|track| track.id).collect()
+    }
+}
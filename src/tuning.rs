@@ -0,0 +1,304 @@
+//! Tuning against the last event (the GUI's "🎚 Tune" window). The GUI's
+//! detector keeps the blurred grayscale frames around the most recent motion
+//! event, and a worker thread runs them through detection again with
+//! trial settings, so the effect of a new sensitivity or minimum area shows
+//! at once instead of at the next event.
+
+// Without the `camera` feature only the GUI and the tests use this
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use crate::regions::MinArea;
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// Frames kept from before the frame that fired the event
+pub const PRE_FRAMES: usize = 60;
+/// Frames recorded after it
+pub const POST_FRAMES: usize = 30;
+
+/// The settings a replay tries out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TuneParams {
+    pub sensitivity: f64,
+    pub min_area: MinArea,
+}
+
+/// The frames around one event, with their capture times
+#[derive(Clone, Debug)]
+pub struct Recording<F> {
+    pub recorded: DateTime<Local>,
+    pub frames: Vec<(Instant, F)>,
+    /// Index of the frame that fired the event
+    pub event_frame: usize,
+}
+
+/// Keeps the last `PRE_FRAMES` frames, and the frames around an event once
+/// one fires
+#[derive(Debug)]
+pub struct EventRecorder<F> {
+    frames: VecDeque<(Instant, F)>,
+    /// The event being recorded: when, and the index of its frame
+    event: Option<(DateTime<Local>, usize)>,
+}
+
+impl<F> EventRecorder<F> {
+    pub fn new() -> Self {
+        Self {
+            frames: VecDeque::with_capacity(PRE_FRAMES + POST_FRAMES + 1),
+            event: None,
+        }
+    }
+
+    /// Add a frame; returns the recording once `POST_FRAMES` have followed
+    /// the event
+    pub fn push(&mut self, at: Instant, frame: F) -> Option<Recording<F>> {
+        self.frames.push_back((at, frame));
+        match self.event {
+            None => {
+                while self.frames.len() > PRE_FRAMES + 1 {
+                    self.frames.pop_front();
+                }
+                None
+            }
+            Some((_, index)) if self.frames.len() <= index + POST_FRAMES => None,
+            Some((recorded, event_frame)) => {
+                self.event = None;
+                Some(Recording {
+                    recorded,
+                    frames: self.frames.drain(..).collect(),
+                    event_frame,
+                })
+            }
+        }
+    }
+
+    /// The last frame pushed fired an event. Events while one is being
+    /// recorded belong to it.
+    pub fn mark_event(&mut self, now: DateTime<Local>) {
+        if self.event.is_none() && !self.frames.is_empty() {
+            self.event = Some((now, self.frames.len() - 1));
+        }
+    }
+}
+
+impl<F> Default for EventRecorder<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the recorded event looks like with `params`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TuneResult {
+    pub params: TuneParams,
+    pub recorded: DateTime<Local>,
+    /// Per frame: seconds from the frame that fired the event, and the
+    /// motion score
+    pub scores: Vec<(f64, f64)>,
+    /// The score above which a frame has motion
+    pub threshold: f64,
+    /// Index of the first frame with motion, if any
+    pub first_motion: Option<usize>,
+    pub motion_frames: usize,
+}
+
+impl TuneResult {
+    pub fn would_trigger(&self) -> bool {
+        self.first_motion.is_some()
+    }
+
+    /// e.g. "Would trigger 0.4 s before the recorded event (12 of 91 frames)"
+    pub fn summary(&self) -> String {
+        let Some(first) = self.first_motion else {
+            return format!(
+                "Would not trigger (no frame above the threshold in {})",
+                self.scores.len()
+            );
+        };
+        let offset = self.scores[first].0;
+        let when = if offset.abs() < 0.05 {
+            "at the recorded event".to_string()
+        } else if offset < 0.0 {
+            format!("{:.1} s before the recorded event", -offset)
+        } else {
+            format!("{:.1} s after the recorded event", offset)
+        };
+        format!(
+            "Would trigger {} ({} of {} frames with motion)",
+            when,
+            self.motion_frames,
+            self.scores.len()
+        )
+    }
+}
+
+/// Scores and threshold of a replay, gathered into a `TuneResult`
+pub fn summarize<F>(
+    recording: &Recording<F>,
+    params: TuneParams,
+    scores: Vec<f64>,
+    threshold: f64,
+) -> TuneResult {
+    let event_at = recording.frames[recording.event_frame].0;
+    let offset = |at: Instant| match at.checked_duration_since(event_at) {
+        Some(after) => after.as_secs_f64(),
+        None => -event_at.duration_since(at).as_secs_f64(),
+    };
+    let first_motion = scores.iter().position(|&score| score > threshold);
+    TuneResult {
+        params,
+        recorded: recording.recorded,
+        motion_frames: scores.iter().filter(|&&score| score > threshold).count(),
+        scores: recording
+            .frames
+            .iter()
+            .zip(scores)
+            .map(|((at, _), score)| (offset(*at), score))
+            .collect(),
+        threshold,
+        first_motion,
+    }
+}
+
+#[cfg(feature = "camera")]
+pub use replay::{ReplaySettings, TuneWorker};
+
+#[cfg(feature = "camera")]
+mod replay {
+    use super::{summarize, Recording, TuneParams, TuneResult};
+    use crate::detector::{clean_mask, contour_regions, FrameHistory};
+    use crate::gui::MotionState;
+    use crate::{mask, regions, scale, sensitivity, smoothing};
+    use anyhow::Result;
+    use crossbeam_channel::{Receiver, Sender};
+    use opencv::core::Mat;
+    use std::time::Duration;
+
+    /// The detector's settings other than the ones being tuned, as they
+    /// were when the event was recorded
+    pub struct ReplaySettings {
+        pub curve: sensitivity::SensitivityCurve,
+        pub mask: Option<mask::IgnoreMask>,
+        pub open_kernel: i32,
+        pub open_iterations: i32,
+        pub pyramid_levels: u32,
+        pub aspect: regions::AspectRange,
+        pub merge_gap: regions::MergeGap,
+        pub diff_lag: usize,
+        pub smoothing: Duration,
+        pub frame_scale: scale::FrameScale,
+    }
+
+    /// Run the recorded frames through detection with `params`. The first
+    /// `diff_lag` frames have nothing to be compared with and score 0.
+    fn replay(
+        recording: &Recording<Mat>,
+        settings: &mut ReplaySettings,
+        params: TuneParams,
+    ) -> Result<TuneResult> {
+        let mut history = FrameHistory::new(settings.diff_lag);
+        history.set_threshold(settings.curve.threshold(params.sensitivity));
+        let mut smoother = smoothing::MotionSmoother::new(settings.smoothing);
+        let (width, height) = settings.frame_scale.capture;
+        let frame_area = width as f64 * height as f64;
+        let min_area = params.min_area.pixels(frame_area);
+        let mut scores = Vec::with_capacity(recording.frames.len());
+        let threshold = regions::MotionScore::new(&[], min_area, frame_area).threshold;
+        for (at, frame) in &recording.frames {
+            let Some(mut thresh) = history.diff(frame.try_clone()?)? else {
+                scores.push(smoother.update(*at, 0.0));
+                continue;
+            };
+            if let Some(mask) = settings.mask.as_mut() {
+                thresh = mask.apply(&thresh)?;
+            }
+            let (_, dilated) = clean_mask(&thresh, settings.open_kernel, settings.open_iterations)?;
+            let found: Vec<regions::Region> = contour_regions(
+                &thresh,
+                &dilated,
+                settings.pyramid_levels,
+                settings.open_kernel,
+                settings.open_iterations,
+            )?
+            .iter()
+            .map(|region| settings.frame_scale.region_to_capture(region))
+            .filter(|region| settings.aspect.contains(region.aspect()))
+            .collect();
+            let merged = regions::merge_regions(found, settings.merge_gap.pixels(width as u32));
+            let score = regions::MotionScore::new(&merged, min_area, frame_area).score;
+            scores.push(smoother.update(*at, score));
+        }
+        Ok(summarize(recording, params, scores, threshold))
+    }
+
+    enum Job {
+        Load(Recording<Mat>, ReplaySettings),
+        Run(TuneParams),
+    }
+
+    /// Replays on its own thread, so dragging a slider never stalls the
+    /// detector or the GUI. Results go to the GUI as `MotionState.tuning`.
+    pub struct TuneWorker {
+        jobs: Sender<Job>,
+        loaded: bool,
+    }
+
+    impl TuneWorker {
+        pub fn spawn(states: Sender<MotionState>) -> Self {
+            let (jobs, receiver) = crossbeam_channel::unbounded();
+            std::thread::spawn(move || work(receiver, states));
+            Self {
+                jobs,
+                loaded: false,
+            }
+        }
+
+        /// A recording has been loaded
+        pub fn is_loaded(&self) -> bool {
+            self.loaded
+        }
+
+        /// Replace the recording, and replay it with `params`
+        pub fn load(
+            &mut self,
+            recording: Recording<Mat>,
+            settings: ReplaySettings,
+            params: TuneParams,
+        ) {
+            self.loaded = true;
+            let _ = self.jobs.send(Job::Load(recording, settings));
+            self.run(params);
+        }
+
+        pub fn run(&self, params: TuneParams) {
+            let _ = self.jobs.send(Job::Run(params));
+        }
+    }
+
+    fn work(jobs: Receiver<Job>, states: Sender<MotionState>) {
+        let mut loaded: Option<(Recording<Mat>, ReplaySettings)> = None;
+        while let Ok(job) = jobs.recv() {
+            // Only the latest settings matter while a slider is dragged
+            let mut params = None;
+            for job in std::iter::once(job).chain(jobs.try_iter()) {
+                match job {
+                    Job::Load(recording, settings) => loaded = Some((recording, settings)),
+                    Job::Run(run) => params = Some(run),
+                }
+            }
+            let (Some(params), Some((recording, settings))) = (params, loaded.as_mut()) else {
+                continue;
+            };
+            let tuning =
+                replay(recording, settings, params).map_err(|e| format!("Replay failed: {:#}", e));
+            let state = MotionState {
+                tuning: Some(tuning),
+                ..Default::default()
+            };
+            if states.send(state).is_err() {
+                return;
+            }
+        }
+    }
+}
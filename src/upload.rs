@@ -0,0 +1,245 @@
+//! Uploads of event snapshots and clips to an S3-compatible bucket
+//! (`--s3-bucket`). A background thread sends them with retries, so a slow
+//! or unreachable endpoint never holds up detection.
+
+// Without the `camera` feature nothing is captured to upload
+#![cfg_attr(not(feature = "camera"), allow(dead_code))]
+
+use crate::alerts::{AlertKind, AlertSender, AppAlert, Severity};
+use anyhow::{Context, Result};
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// `--s3-endpoint`, `--s3-bucket`, `--s3-region`, `--s3-prefix` and the keys
+#[derive(Clone, PartialEq)]
+pub struct S3Options {
+    /// e.g. `https://s3.eu-central-1.amazonaws.com` or a MinIO server
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    /// Prepended to every object key, e.g. `porch/`
+    pub prefix: String,
+    /// `None` for anonymous access
+    pub credentials: Option<(String, String)>,
+}
+
+// Keeps the secret key out of logs and panics
+impl std::fmt::Debug for S3Options {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("S3Options")
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("prefix", &self.prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Where uploads go, so tests can swap in a fake bucket
+pub trait ObjectStore: Send {
+    fn put(&mut self, key: &str, body: &[u8], content_type: &str) -> Result<()>;
+}
+
+enum Body {
+    /// Encoded in memory, never written locally
+    Bytes(Vec<u8>),
+    /// Read when its turn comes, e.g. a finished clip
+    File(PathBuf),
+}
+
+struct Job {
+    key: String,
+    body: Body,
+}
+
+/// Queues uploads for the background thread. Clones share the queue; the
+/// thread exits once every clone is dropped and the queue is empty.
+#[derive(Clone, Debug)]
+pub struct Uploader {
+    sender: Sender<Job>,
+    prefix: String,
+}
+
+impl Uploader {
+    /// Uploads waiting beyond this are dropped, so an outage can't eat memory
+    const QUEUE: usize = 64;
+    pub const ATTEMPTS: u32 = 5;
+
+    /// Send to `store` from a new thread, waiting `first_retry` after the
+    /// first failure and twice as long after each one after that. Uploads
+    /// given up on are also raised on `alerts`, for the GUI.
+    pub fn start(
+        store: Box<dyn ObjectStore>,
+        prefix: String,
+        first_retry: Duration,
+        alerts: Option<AlertSender>,
+    ) -> Self {
+        let (sender, receiver) = crossbeam_channel::bounded(Self::QUEUE);
+        std::thread::spawn(move || run(store, receiver, first_retry, alerts));
+        Self { sender, prefix }
+    }
+
+    /// Queue an image encoded in memory as `<prefix><file_name>`
+    pub fn upload_bytes(&self, file_name: &str, bytes: Vec<u8>) {
+        self.queue(file_name, Body::Bytes(bytes));
+    }
+
+    /// Queue a file that's already on disk, named after it
+    pub fn upload_file(&self, path: &Path) {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.queue(&file_name, Body::File(path.to_path_buf()));
+    }
+
+    fn queue(&self, file_name: &str, body: Body) {
+        let key = format!("{}{}", self.prefix, file_name);
+        match self.sender.try_send(Job { key, body }) {
+            Ok(()) => {}
+            Err(TrySendError::Full(job)) => {
+                eprintln!("Warning: upload queue full, not uploading {}", job.key)
+            }
+            Err(TrySendError::Disconnected(job)) => {
+                eprintln!("Warning: uploader stopped, not uploading {}", job.key)
+            }
+        }
+    }
+}
+
+/// Wait before retry number `retry` (1-based), capped at a minute
+pub fn backoff(first_retry: Duration, retry: u32) -> Duration {
+    first_retry
+        .saturating_mul(1 << (retry - 1).min(16))
+        .min(Duration::from_secs(60))
+}
+
+pub fn content_type(key: &str) -> &'static str {
+    let extension = Path::new(key)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("mp4") => "video/mp4",
+        Some("mkv") => "video/x-matroska",
+        Some("avi") => "video/x-msvideo",
+        Some("wav") => "audio/wav",
+        _ => "application/octet-stream",
+    }
+}
+
+fn run(
+    mut store: Box<dyn ObjectStore>,
+    receiver: Receiver<Job>,
+    first_retry: Duration,
+    alerts: Option<AlertSender>,
+) {
+    let failed = |message: String| {
+        eprintln!("{}", message);
+        if let Some(alerts) = &alerts {
+            alerts.raise(AppAlert::new(
+                AlertKind::UploadFailed,
+                Severity::Error,
+                message,
+            ));
+        }
+    };
+    for job in receiver {
+        let body = match job.body {
+            Body::Bytes(bytes) => bytes,
+            Body::File(path) => match std::fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    failed(format!("Failed to upload {}: {}", path.display(), e));
+                    continue;
+                }
+            },
+        };
+        let content_type = content_type(&job.key);
+        for attempt in 1..=Uploader::ATTEMPTS {
+            match store.put(&job.key, &body, content_type) {
+                Ok(()) => {
+                    println!("  Uploaded {}", job.key);
+                    break;
+                }
+                Err(e) if attempt < Uploader::ATTEMPTS => {
+                    let delay = backoff(first_retry, attempt);
+                    eprintln!(
+                        "Upload of {} failed: {:#}; retrying in {}",
+                        job.key,
+                        e,
+                        crate::humanize::humanize_duration(delay)
+                    );
+                    std::thread::sleep(delay);
+                }
+                Err(e) => failed(format!(
+                    "Giving up on uploading {} after {} attempts: {:#}",
+                    job.key, attempt, e
+                )),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "s3")]
+struct S3Store {
+    bucket: Box<s3::Bucket>,
+}
+
+#[cfg(feature = "s3")]
+impl ObjectStore for S3Store {
+    fn put(&mut self, key: &str, body: &[u8], content_type: &str) -> Result<()> {
+        let response =
+            self.bucket
+                .put_object_with_content_type_blocking(key, body, content_type)?;
+        match response.status_code() {
+            200..=299 => Ok(()),
+            status => anyhow::bail!("HTTP {}", status),
+        }
+    }
+}
+
+#[cfg(feature = "s3")]
+fn open_store(options: &S3Options) -> Result<Box<dyn ObjectStore>> {
+    let credentials = match &options.credentials {
+        Some((access_key, secret_key)) => s3::creds::Credentials::new(
+            Some(access_key.as_str()),
+            Some(secret_key.as_str()),
+            None,
+            None,
+            None,
+        )?,
+        None => s3::creds::Credentials::anonymous()?,
+    };
+    let region = s3::Region::Custom {
+        region: options.region.clone(),
+        endpoint: options.endpoint.clone(),
+    };
+    // Path-style URLs work with MinIO and other self-hosted servers too
+    let bucket = s3::Bucket::new(&options.bucket, region, credentials)?.with_path_style();
+    Ok(Box::new(S3Store { bucket }))
+}
+
+#[cfg(not(feature = "s3"))]
+fn open_store(_options: &S3Options) -> Result<Box<dyn ObjectStore>> {
+    anyhow::bail!("--s3-bucket needs a build with the `s3` feature (cargo build --features s3)")
+}
+
+/// The uploader for `--s3-bucket`; failed uploads are also raised on
+/// `alerts` when given
+pub fn open(options: &S3Options, alerts: Option<AlertSender>) -> Result<Uploader> {
+    let store = open_store(options)
+        .with_context(|| format!("Failed to set up uploads to {}", options.bucket))?;
+    println!(
+        "Uploading snapshots and clips to bucket {} at {}",
+        options.bucket, options.endpoint
+    );
+    Ok(Uploader::start(
+        store,
+        options.prefix.clone(),
+        Duration::from_secs(2),
+        alerts,
+    ))
+}
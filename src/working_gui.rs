@@ -1,4 +1,10 @@
 // Working GUI that connects to actual motion detection
+
+// Shared with the main binary so both GUIs word durations the same way
+#[path = "humanize.rs"]
+#[allow(dead_code)]
+mod humanize;
+
 use chrono::Local;
 use eframe;
 use eframe::egui;
@@ -103,6 +109,8 @@ impl MotionDetectorGui {
 impl eframe::App for MotionDetectorGui {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.update_status();
+        // Keep "Last Motion" counting without new status
+        ctx.request_repaint_after(Duration::from_secs(1));
 
         // Menu bar
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
@@ -268,8 +276,10 @@ impl eframe::App for MotionDetectorGui {
 
                     // Last motion time
                     if let Some(last_time) = self.status.last_motion_time {
-                        let duration = last_time.elapsed();
-                        ui.label(format!("Last Motion: {:.1}s ago", duration.as_secs_f32()));
+                        ui.label(format!(
+                            "Last Motion: {} ago",
+                            humanize::humanize_duration(last_time.elapsed())
+                        ));
                     }
 
                     // Visual indicator